@@ -0,0 +1,181 @@
+//! Raycasts a single mesh of increasing size with each narrowphase acceleration structure: none
+//! (brute force), an octree, and the [`TriangleBvh`] used by the [`MeshBvhPlugin`], for rays that
+//! hit and miss the mesh.
+//!
+//! Every structure tests the same triangles with [`ray_triangle_intersection`], so the results
+//! only differ by how many triangles each one skips, and how long it takes to find them. The mesh
+//! size where the BVH overtakes brute force is what [`MeshBvhCache::min_triangles`] is tuned from.
+
+use bevy::{
+    math::{FloatOrd, Vec3A},
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use bevy_mod_raycast::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// Octree nodes with fewer triangles are not split.
+const MAX_OCTANT_TRIANGLES: usize = 8;
+const MAX_OCTREE_DEPTH: usize = 8;
+
+/// A minimal octree, storing each triangle in the smallest octant that fully contains it.
+struct Octree {
+    min: Vec3A,
+    max: Vec3A,
+    triangles: Vec<usize>,
+    children: Vec<Octree>,
+}
+
+impl Octree {
+    fn new(triangles: &[[Vec3A; 3]]) -> Self {
+        let (min, max) = triangles.iter().flatten().fold(
+            (Vec3A::INFINITY, Vec3A::NEG_INFINITY),
+            |(min, max), vertex| (min.min(*vertex), max.max(*vertex)),
+        );
+        let mut octree = Self::leaf(min, max);
+        octree.triangles = (0..triangles.len()).collect();
+        octree.split(triangles, 0);
+        octree
+    }
+
+    fn leaf(min: Vec3A, max: Vec3A) -> Self {
+        Self {
+            min,
+            max,
+            triangles: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn split(&mut self, triangles: &[[Vec3A; 3]], depth: usize) {
+        if self.triangles.len() <= MAX_OCTANT_TRIANGLES || depth == MAX_OCTREE_DEPTH {
+            return;
+        }
+        let center = (self.min + self.max) / 2.0;
+        self.children = (0..8)
+            .map(|octant| {
+                let upper = Vec3A::new(
+                    (octant & 1) as f32,
+                    (octant >> 1 & 1) as f32,
+                    (octant >> 2 & 1) as f32,
+                );
+                let min = Vec3A::select(upper.cmpgt(Vec3A::ZERO), center, self.min);
+                let max = Vec3A::select(upper.cmpgt(Vec3A::ZERO), self.max, center);
+                Self::leaf(min, max)
+            })
+            .collect();
+        self.triangles.retain(|&triangle| {
+            let child = self.children.iter_mut().find(|child| {
+                triangles[triangle]
+                    .iter()
+                    .all(|vertex| vertex.cmpge(child.min).all() && vertex.cmple(child.max).all())
+            });
+            child.map(|child| child.triangles.push(triangle)).is_none()
+        });
+        for child in &mut self.children {
+            child.split(triangles, depth + 1);
+        }
+    }
+
+    /// The distance at which the ray enters the octant, if it does before `max_distance`.
+    fn entry(&self, origin: Vec3A, inverse_direction: Vec3A, max_distance: f32) -> Option<f32> {
+        let t1 = (self.min - origin) * inverse_direction;
+        let t2 = (self.max - origin) * inverse_direction;
+        let near = t1.min(t2).max_element().max(0.0);
+        let far = t1.max(t2).min_element().min(max_distance);
+        (near <= far).then_some(near)
+    }
+
+    fn cast_ray(&self, ray: &Ray3d, triangles: &[[Vec3A; 3]], nearest: &mut f32) {
+        let origin = Vec3A::from(ray.origin);
+        let inverse_direction = Vec3A::from(*ray.direction).recip();
+        if self.entry(origin, inverse_direction, *nearest).is_none() {
+            return;
+        }
+        for &triangle in &self.triangles {
+            if let Some(hit) = ray_triangle_intersection(ray, &triangles[triangle], Backfaces::Cull)
+            {
+                *nearest = nearest.min(*hit.distance());
+            }
+        }
+        let mut children: Vec<_> = self
+            .children
+            .iter()
+            .filter_map(|child| Some((child.entry(origin, inverse_direction, *nearest)?, child)))
+            .collect();
+        children.sort_unstable_by_key(|(near, _)| FloatOrd(*near));
+        for (near, child) in children {
+            if near <= *nearest {
+                child.cast_ray(ray, triangles, nearest);
+            }
+        }
+    }
+}
+
+/// The triangles of an ico sphere with the given number of subdivisions.
+fn sphere_triangles(subdivisions: usize) -> Vec<[Vec3A; 3]> {
+    let mesh = Sphere::new(0.5).mesh().ico(subdivisions).unwrap();
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        unreachable!("ico spheres have positions");
+    };
+    let Some(Indices::U32(indices)) = mesh.indices() else {
+        unreachable!("ico spheres have u32 indices");
+    };
+    indices
+        .chunks_exact(3)
+        .map(|triangle| [0, 1, 2].map(|i| Vec3A::from(positions[triangle[i] as usize])))
+        .collect()
+}
+
+fn brute_force(ray: &Ray3d, triangles: &[[Vec3A; 3]]) -> f32 {
+    triangles
+        .iter()
+        .filter_map(|triangle| ray_triangle_intersection(ray, triangle, Backfaces::Cull))
+        .fold(f32::INFINITY, |nearest, hit| nearest.min(*hit.distance()))
+}
+
+fn bench_case(c: &mut Criterion, name: &str, ray: Ray3d) {
+    let mut group = c.benchmark_group(name);
+    group.warm_up_time(std::time::Duration::from_millis(500));
+    for subdivisions in [0, 1, 2, 3, 4, 6, 10, 20, 50] {
+        let triangles = sphere_triangles(subdivisions);
+        let parameter = format!("{}_triangles", triangles.len());
+        group.bench_function(BenchmarkId::new("brute_force", &parameter), |b| {
+            b.iter(|| black_box(brute_force(&ray, &triangles)));
+        });
+        let octree = Octree::new(&triangles);
+        group.bench_function(BenchmarkId::new("octree", &parameter), |b| {
+            b.iter(|| {
+                let mut nearest = f32::INFINITY;
+                octree.cast_ray(&ray, &triangles, &mut nearest);
+                black_box(nearest)
+            });
+        });
+        let bvh = TriangleBvh::new(&triangles);
+        group.bench_function(BenchmarkId::new("bvh", &parameter), |b| {
+            b.iter(|| {
+                let mut nearest = f32::INFINITY;
+                bvh.cast_ray(ray, f32::INFINITY, |triangle, _| {
+                    let hit =
+                        ray_triangle_intersection(&ray, &triangles[triangle], Backfaces::Cull);
+                    let distance = hit.map(|hit| *hit.distance());
+                    nearest = nearest.min(distance.unwrap_or(f32::INFINITY));
+                    distance
+                });
+                black_box(nearest)
+            });
+        });
+    }
+}
+
+fn mesh_acceleration(c: &mut Criterion) {
+    bench_case(c, "mesh_acceleration_hit", Ray3d::new(Vec3::Z, Vec3::NEG_Z));
+    // Passes through the bounds of the sphere, but beside the sphere itself.
+    let beside = Ray3d::new(Vec3::new(0.45, 0.45, 1.0), Vec3::NEG_Z);
+    bench_case(c, "mesh_acceleration_miss", beside);
+}
+
+criterion_group!(benches, mesh_acceleration);
+criterion_main!(benches);
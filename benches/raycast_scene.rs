@@ -0,0 +1,79 @@
+//! Raycasts through scenes of many mesh entities with the [`Raycast`] system param, including
+//! culling, for rays that hit the nearest entity, miss every entity, and pass through all of them.
+//!
+//! Results are grouped by case, with one benchmark per narrowphase strategy and scene size, so
+//! strategies can be compared on the same scenes. Run the whole suite with `cargo bench-raycast`.
+
+use bevy::{
+    ecs::system::SystemState,
+    prelude::*,
+    tasks::{ComputeTaskPool, TaskPool},
+};
+use bevy_mod_raycast::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// Spawns a row of `count` spheres along `-Z`, each made of `subdivisions` ico sphere subdivisions,
+/// and returns the number of triangles in each sphere.
+fn scene(count: usize, subdivisions: usize) -> (World, usize) {
+    ComputeTaskPool::get_or_init(TaskPool::default);
+    let mut world = World::new();
+    let mesh = Sphere::new(0.5).mesh().ico(subdivisions).unwrap();
+    let aabb = mesh.compute_aabb().unwrap();
+    let triangles = mesh_triangle_count(&mesh);
+    let mut meshes = Assets::<Mesh>::default();
+    let handle = meshes.add(mesh);
+    world.insert_resource(meshes);
+    for i in 0..count {
+        world.spawn((
+            handle.clone(),
+            aabb,
+            GlobalTransform::from_xyz(0.0, 0.0, -2.0 * (i + 1) as f32),
+            InheritedVisibility::VISIBLE,
+            ViewVisibility::default(),
+        ));
+    }
+    (world, triangles)
+}
+
+/// Benchmarks the `ray` against scenes of increasing size with the given settings.
+fn bench_case(c: &mut Criterion, name: &str, ray: Ray3d, settings: RaycastSettings) {
+    let mut group = c.benchmark_group(name);
+    group.warm_up_time(std::time::Duration::from_millis(500));
+    for (count, subdivisions) in [(10, 2), (100, 2), (1000, 2), (100, 20)] {
+        let (mut world, triangles) = scene(count, subdivisions);
+        let mut state = SystemState::<Raycast>::new(&mut world);
+        let parameter = format!("{count}_entities_{triangles}_triangles");
+        group.bench_function(BenchmarkId::new("brute_force", &parameter), |b| {
+            let mut raycast = state.get_mut(&mut world);
+            b.iter(|| black_box(raycast.cast_ray(ray, &settings).len()));
+        });
+        let mut cache = MeshBvhCache::default();
+        cache.build_async = false;
+        let meshes = world.resource::<Assets<Mesh>>();
+        cache.require(meshes.ids().next().unwrap(), meshes);
+        world.insert_resource(cache);
+        group.bench_function(BenchmarkId::new("bvh", parameter), |b| {
+            let mut raycast = state.get_mut(&mut world);
+            b.iter(|| black_box(raycast.cast_ray(ray, &settings).len()));
+        });
+    }
+}
+
+fn raycast_scene(c: &mut Criterion) {
+    let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+    let along_row = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+    bench_case(c, "raycast_scene_nearest_hit", along_row, settings.clone());
+    // Passes beside every sphere, through none of their AABBs.
+    let beside_row = Ray3d::new(Vec3::X * 2.0, Vec3::NEG_Z);
+    bench_case(c, "raycast_scene_miss", beside_row, settings.clone());
+    // The worst case: every entity is culled in and tested, and every hit is kept.
+    bench_case(
+        c,
+        "raycast_scene_all_hits",
+        along_row,
+        settings.never_early_exit(),
+    );
+}
+
+criterion_group!(benches, raycast_scene);
+criterion_main!(benches);
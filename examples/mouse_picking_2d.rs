@@ -22,10 +22,19 @@ fn setup(
     commands.spawn((
         MaterialMesh2dBundle {
             mesh: meshes.add(Circle::default()).into(),
-            transform: Transform::default().with_scale(Vec3::splat(128.)),
+            transform: Transform::from_xyz(-100., 0., 0.).with_scale(Vec3::splat(128.)),
             material: materials.add(ColorMaterial::from(Color::from(css::PURPLE))),
             ..default()
         },
         RaycastMesh::<()>::default(), // Make this mesh ray cast-able;
     ));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::default()).into(),
+            transform: Transform::from_xyz(100., 0., 0.).with_scale(Vec3::splat(128.)),
+            material: materials.add(ColorMaterial::from(Color::from(css::TEAL))),
+            ..default()
+        },
+        RaycastMesh::<()>::default(),
+    ));
 }
@@ -21,9 +21,6 @@ fn main() {
 const MAX_BOUNCES: usize = 64;
 const LASER_SPEED: f32 = 0.03;
 
-#[derive(Reflect)]
-struct Laser;
-
 fn bouncing_raycast(
     mut raycast: Raycast,
     mut gizmos: Gizmos,
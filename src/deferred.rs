@@ -14,8 +14,19 @@
 //! generic parameter. For example, [`RaycastSource<Foo>`] can cast rays against meshes with
 //! [`RaycastMesh<Foo>`], but not against meshes that instead only have a [`RaycastMesh<Bar>`]
 //! component.
+//!
+//! Several sources can share the ray of another source with [`RaycastMethod::Shared`], such as the
+//! crosshair ray of a split-screen camera, tested once against enemies and once against the
+//! world. The ray is built once by the provider, and each sharing source raycasts it with its own
+//! layers, early exit, and visibility. See [`RaycastSource::from_provider`].
+//!
+//! Scenes such as glTF files spawn their meshes as descendants of the scene root, so a
+//! [`RaycastMesh`] added to the root alone is never hit. Add [`AutoDescend`] next to it, and
+//! every descendant with a mesh becomes a target too, as the hierarchy changes. See
+//! [`AutoDescend`].
 
 use std::{
+    cmp::Reverse,
     fmt::Debug,
     hash::{Hash, Hasher},
     marker::PhantomData,
@@ -23,45 +34,88 @@ use std::{
 
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use bevy_math::{Mat4, Ray3d, Vec2};
+use bevy_hierarchy::{Children, HierarchyQueryExt, Parent};
+use bevy_math::{FloatOrd, Mat4, Ray3d, Vec2};
 use bevy_reflect::{Reflect, TypePath};
-use bevy_render::camera::Camera;
+#[cfg(feature = "serialize")]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+use bevy_render::camera::{Camera, NormalizedRenderTarget};
 use bevy_transform::components::GlobalTransform;
-use bevy_utils::{default, tracing::*};
+use bevy_utils::{default, tracing::*, Duration, HashMap, Instant};
 use bevy_window::{PrimaryWindow, Window};
 
-use crate::{immediate::*, primitives::*};
+#[cfg(feature = "debug")]
+use crate::debug;
+use crate::{budget::*, immediate::*, layers::*, primitives::*};
 
 pub struct DeferredRaycastingPlugin<T>(pub PhantomData<fn() -> T>);
 impl<T: TypePath + Send + Sync> Plugin for DeferredRaycastingPlugin<T> {
     fn build(&self, app: &mut App) {
-        app.init_resource::<RaycastPluginState<T>>().add_systems(
-            First,
-            (
-                build_rays::<T>
-                    .in_set(RaycastSystem::BuildRays::<T>)
-                    .run_if(|state: Res<RaycastPluginState<T>>| state.build_rays),
-                update_raycast::<T>
-                    .in_set(RaycastSystem::UpdateRaycast::<T>)
-                    .run_if(|state: Res<RaycastPluginState<T>>| state.update_raycast),
-                update_target_intersections::<T>
-                    .in_set(RaycastSystem::UpdateIntersections::<T>)
-                    .run_if(|state: Res<RaycastPluginState<T>>| state.update_raycast),
-            )
-                .chain(),
-        );
+        app.init_resource::<RaycastPluginState<T>>()
+            .init_resource::<RaycastDiagnostics<T>>()
+            .add_event::<RaycastDiagnosticChanged<T>>()
+            .add_systems(
+                First,
+                (
+                    update_inherited_raycast_meshes::<T>,
+                    (validate_raycast_sources::<T>, build_rays::<T>)
+                        .chain()
+                        .in_set(RaycastSystem::BuildRays::<T>)
+                        .after(crate::bounds::insert_missing_aabbs)
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.build_rays),
+                    update_raycast::<T>
+                        .in_set(RaycastSystem::UpdateRaycast::<T>)
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.update_raycast),
+                    update_target_intersections::<T>
+                        .in_set(RaycastSystem::UpdateIntersections::<T>)
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.update_raycast),
+                )
+                    .chain(),
+            );
 
         app.register_type::<RaycastMesh<T>>()
-            .register_type::<RaycastSource<T>>();
+            .register_type::<RaycastSource<T>>()
+            .register_type::<AutoDescend>()
+            .register_type::<InheritedRaycastMesh<T>>();
+
+        if !app.is_plugin_added::<crate::bounds::NewMeshBoundsPlugin>() {
+            app.add_plugins(crate::bounds::NewMeshBoundsPlugin);
+        }
+        if !app.is_plugin_added::<crate::reflection::RaycastReflectionPlugin>() {
+            app.add_plugins(crate::reflection::RaycastReflectionPlugin);
+        }
+
+        app.init_resource::<crate::hit_buffer::HitBuffer<T>>()
+            .add_systems(
+                First,
+                (
+                    crate::hit_buffer::update_hit_buffer::<T>
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.update_hit_buffer),
+                    trigger_hit_observers::<T>
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.trigger_hit_observers),
+                )
+                    .after(RaycastSystem::UpdateIntersections::<T>),
+            );
+
+        #[cfg(feature = "2d")]
+        if !app.is_plugin_added::<crate::mesh2d::Mesh2dBoundsPlugin>() {
+            app.add_plugins(crate::mesh2d::Mesh2dBoundsPlugin);
+        }
 
         #[cfg(feature = "debug")]
-        app.add_systems(
-            First,
-            debug::update_debug_cursor::<T>
-                .in_set(RaycastSystem::UpdateDebugCursor::<T>)
-                .run_if(|state: Res<RaycastPluginState<T>>| state.update_debug_cursor)
-                .after(RaycastSystem::UpdateIntersections::<T>),
-        );
+        app.init_resource::<debug::RaycastInspector<T>>()
+            .register_type::<debug::RaycastInspector<T>>()
+            .add_systems(
+                First,
+                (
+                    debug::update_debug_cursor::<T>
+                        .in_set(RaycastSystem::UpdateDebugCursor::<T>)
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.update_debug_cursor),
+                    debug::update_raycast_inspector::<T>
+                        .run_if(|state: Res<RaycastPluginState<T>>| state.update_inspector),
+                )
+                    .after(RaycastSystem::UpdateIntersections::<T>),
+            );
     }
 }
 impl<T> Default for DeferredRaycastingPlugin<T> {
@@ -122,8 +176,20 @@ impl<T> Clone for RaycastSystem<T> {
 pub struct RaycastPluginState<T> {
     pub build_rays: bool,
     pub update_raycast: bool,
+    /// Whether the [`HitBuffer`](crate::hit_buffer::HitBuffer) is updated.
+    pub update_hit_buffer: bool,
+    /// Which sources are raycast each frame.
+    pub scheduling: RaycastScheduling,
+    /// Whether [`OnRaycastHit`] is triggered on hit entities.
+    pub trigger_hit_observers: bool,
     #[cfg(feature = "debug")]
     pub update_debug_cursor: bool,
+    /// The size of the gizmos drawn by the debug cursor.
+    #[cfg(feature = "debug")]
+    pub debug_cursor_size: debug::DebugCursorSize,
+    /// Whether the [`debug::RaycastInspector`] is updated.
+    #[cfg(feature = "debug")]
+    pub update_inspector: bool,
     _marker: PhantomData<fn() -> T>,
 }
 
@@ -132,13 +198,64 @@ impl<T> Default for RaycastPluginState<T> {
         RaycastPluginState {
             build_rays: true,
             update_raycast: true,
+            update_hit_buffer: false,
+            scheduling: RaycastScheduling::default(),
+            trigger_hit_observers: false,
             #[cfg(feature = "debug")]
             update_debug_cursor: false,
+            #[cfg(feature = "debug")]
+            debug_cursor_size: default(),
+            #[cfg(feature = "debug")]
+            update_inspector: false,
             _marker: PhantomData,
         }
     }
 }
 
+impl<T> RaycastPluginState<T> {
+    /// Copy the hits of every source into the [`HitBuffer`](crate::hit_buffer::HitBuffer) every
+    /// frame.
+    pub fn with_hit_buffer(self) -> Self {
+        RaycastPluginState {
+            update_hit_buffer: true,
+            ..self
+        }
+    }
+
+    /// Trigger [`OnRaycastHit`] on every entity hit by a source, every frame.
+    pub fn with_hit_observers(self) -> Self {
+        RaycastPluginState {
+            trigger_hit_observers: true,
+            ..self
+        }
+    }
+
+    /// Set the [`RaycastScheduling`] used to pick which sources are raycast each frame.
+    pub fn with_scheduling(self, scheduling: RaycastScheduling) -> Self {
+        RaycastPluginState { scheduling, ..self }
+    }
+}
+
+/// Picks which [`RaycastSource`]s are raycast each frame by [`update_raycast`]. Sources that are
+/// skipped keep their intersections from the last time they were raycast.
+///
+/// When there are many sources that don't need to be updated every frame, such as the vision of
+/// NPCs, limiting how many are raycast per frame spreads their cost over several frames. Only
+/// sources with a ray count towards the limit. A `sources_per_frame` of zero is treated as one, so
+/// sources are never left without updates.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RaycastScheduling {
+    /// Raycast every source every frame.
+    #[default]
+    EveryFrame,
+    /// Raycast at most `sources_per_frame` sources each frame, cycling through them in turn.
+    RoundRobin { sources_per_frame: usize },
+    /// Raycast at most `sources_per_frame` sources each frame, picking the sources with the
+    /// highest [`RaycastSource::priority`] multiplied by the number of frames since they were
+    /// last raycast, so low priority sources are still updated eventually.
+    Priority { sources_per_frame: usize },
+}
+
 #[cfg(feature = "debug")]
 impl<T> RaycastPluginState<T> {
     pub fn with_debug_cursor(self) -> Self {
@@ -147,17 +264,33 @@ impl<T> RaycastPluginState<T> {
             ..self
         }
     }
+
+    /// Update the [`debug::RaycastInspector`] every frame.
+    pub fn with_inspector(self) -> Self {
+        RaycastPluginState {
+            update_inspector: true,
+            ..self
+        }
+    }
+
+    /// Set the size of the gizmos drawn by the debug cursor; see [`debug::DebugCursorSize`].
+    pub fn with_debug_cursor_size(self, debug_cursor_size: debug::DebugCursorSize) -> Self {
+        RaycastPluginState {
+            debug_cursor_size,
+            ..self
+        }
+    }
 }
 
 /// Marks an entity as pickable, with type T.
 ///
 /// # Requirements
 ///
-/// The marked entity must also have a [Mesh](bevy_render::mesh::Mesh) component.
+/// The marked entity must also have a [Mesh](bevy_render::mesh::Mesh) component, unless it also
+/// has [`AutoDescend`], in which case its descendants with meshes are raycast.
 #[derive(Component, Debug, Reflect)]
 #[reflect(Component)]
 pub struct RaycastMesh<T: TypePath> {
-    #[reflect(ignore)]
     pub intersections: Vec<(Entity, IntersectionData)>,
     #[reflect(ignore)]
     _marker: PhantomData<T>,
@@ -193,6 +326,35 @@ impl<T: TypePath> Clone for RaycastMesh<T> {
     }
 }
 
+/// Makes the descendants with meshes of an entity with a [`RaycastMesh<T>`] raycast targets too,
+/// such as the meshes spawned under the root of a glTF scene.
+///
+/// Each of these descendants is given a [`RaycastMesh<T>`] and an [`InheritedRaycastMesh<T>`]
+/// pointing back to the root, which are kept up to date as children are added, removed, or
+/// reparented. Hits on the descendants are also added to the intersections of the root's
+/// [`RaycastMesh<T>`], so the root can be queried as a whole. Descendants that have their own
+/// [`RaycastMesh<T>`] are left unchanged.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct AutoDescend;
+
+/// Added with a [`RaycastMesh<T>`] to the descendants of an entity with [`AutoDescend`]. Both are
+/// removed when the entity is no longer one of its descendants.
+#[derive(Component, Debug, Reflect)]
+#[reflect(Component)]
+pub struct InheritedRaycastMesh<T: TypePath> {
+    root: Entity,
+    #[reflect(ignore)]
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: TypePath> InheritedRaycastMesh<T> {
+    /// The ancestor with [`AutoDescend`] that this entity inherited its [`RaycastMesh<T>`] from.
+    pub fn root(&self) -> Entity {
+        self.root
+    }
+}
+
 /// The `RaycastSource` component is used to generate rays with the specified `cast_method`. A `ray`
 /// is generated when the RaycastSource is initialized, either by waiting for update_raycast system
 /// to process the ray, or by using a `with_ray` function.`
@@ -203,14 +365,50 @@ pub struct RaycastSource<T: TypePath> {
     pub cast_method: RaycastMethod,
     /// When `true`, raycasting will only hit the nearest entity, skipping any entities that are
     /// further away. This can significantly improve performance in cases where a ray intersects
-    /// many AABBs.
+    /// many AABBs. Targets with a [`RaycastPierce`](crate::markers::RaycastPierce) component never
+    /// block the ray.
     pub should_early_exit: bool,
     /// Determines how raycasting should consider entity visibility.
     pub visibility: RaycastVisibility,
-    #[reflect(ignore)]
+    /// When `true`, [`RaycastSourceStats`] are collected each time this source is raycast. Off by
+    /// default, as timing every raycast has a small cost.
+    pub collect_stats: bool,
+    /// The axis conventions used to build rays from the source's transform with
+    /// [`RaycastMethod::Transform`], and to orient the debug cursor.
+    pub coordinate_system: CoordinateSystem,
+    /// The camera used to build rays with [`RaycastMethod::Cursor`] and
+    /// [`RaycastMethod::Screenspace`]. When `None`, the camera on this source's entity is used.
+    pub camera: Option<Entity>,
+    /// How urgently this source is raycast when [`RaycastScheduling::Priority`] is used.
+    pub priority: f32,
+    /// Whether this source is skipped when the [`RaycastBudget`] of the frame is spent, or always
+    /// raycast. See the [`budget`](crate::budget) module.
+    pub priority_class: RaycastPriorityClass,
+    /// Only entities on at least one of these layers are raycast. See the
+    /// [`layers`](crate::layers) module.
+    pub layers: RaycastLayers,
+    /// Which optional data is computed for the hits of this source. See
+    /// [`RaycastSettings::hit_data`].
+    pub hit_data: HitData,
+    /// When `true`, rays built from the camera with [`RaycastMethod::Cursor`] and
+    /// [`RaycastMethod::Screenspace`] end at the camera's far plane, so only what the camera
+    /// renders is hit. These rays always start on the near plane.
+    pub clip_to_far_plane: bool,
+    /// When `true`, the entity hit first last time this source was raycast is raycast before any
+    /// other, which skips most of the work when the same entity stays hovered. The results are the
+    /// same either way. See [`RaycastSettings::warm_start`].
+    pub warm_start: bool,
     pub ray: Option<Ray3d>,
-    #[reflect(ignore)]
+    /// The distance along the ray to the far plane, when clipping to it.
+    max_distance: f32,
     intersections: Vec<(Entity, IntersectionData)>,
+    stats: RaycastSourceStats,
+    frames_since_raycast: u32,
+    /// The entity this source only raycasts while it is captured. See [`RaycastSource::capture`].
+    captured: Option<Entity>,
+    skipped_by_budget: bool,
+    /// Whether the provider of a [`RaycastMethod::Shared`] source builds its ray from a camera.
+    shared_from_camera: bool,
     #[reflect(ignore)]
     _marker: PhantomData<fn() -> T>,
 }
@@ -221,8 +419,23 @@ impl<T: TypePath> Default for RaycastSource<T> {
             cast_method: RaycastMethod::Screenspace(Vec2::ZERO),
             should_early_exit: true,
             visibility: RaycastVisibility::MustBeVisibleAndInView,
+            collect_stats: false,
+            coordinate_system: CoordinateSystem::default(),
+            camera: None,
+            priority: 1.0,
+            priority_class: RaycastPriorityClass::Normal,
+            layers: RaycastLayers::ALL,
+            hit_data: HitData::DEFAULT,
+            clip_to_far_plane: false,
+            warm_start: false,
             ray: None,
+            max_distance: f32::INFINITY,
             intersections: Vec::new(),
+            stats: RaycastSourceStats::default(),
+            frames_since_raycast: 0,
+            captured: None,
+            skipped_by_budget: false,
+            shared_from_camera: false,
             _marker: PhantomData,
         }
     }
@@ -234,8 +447,23 @@ impl<T: TypePath> Clone for RaycastSource<T> {
             cast_method: self.cast_method.clone(),
             should_early_exit: self.should_early_exit,
             visibility: self.visibility,
+            collect_stats: self.collect_stats,
+            coordinate_system: self.coordinate_system,
+            camera: self.camera,
+            priority: self.priority,
+            priority_class: self.priority_class,
+            layers: self.layers,
+            hit_data: self.hit_data,
+            clip_to_far_plane: self.clip_to_far_plane,
+            warm_start: self.warm_start,
             ray: self.ray,
+            max_distance: self.max_distance,
             intersections: self.intersections.clone(),
+            stats: self.stats,
+            frames_since_raycast: self.frames_since_raycast,
+            captured: self.captured,
+            skipped_by_budget: self.skipped_by_budget,
+            shared_from_camera: self.shared_from_camera,
             _marker: PhantomData,
         }
     }
@@ -261,11 +489,12 @@ impl<T: TypePath> RaycastSource<T> {
             ..self
         }
     }
-    /// Initializes a [RaycastSource] with a valid ray derived from a transform.
+    /// Initializes a [RaycastSource] with a valid ray derived from a transform, pointing along the
+    /// forward axis of its [`CoordinateSystem`].
     pub fn with_ray_transform(self, transform: Mat4) -> Self {
         RaycastSource {
             cast_method: RaycastMethod::Transform,
-            ray: Some(ray_from_transform(transform)),
+            ray: Some(self.coordinate_system.ray_from_transform(transform)),
             ..self
         }
     }
@@ -283,6 +512,58 @@ impl<T: TypePath> RaycastSource<T> {
         Self { visibility, ..self }
     }
 
+    /// Set the `coordinate_system` field of this raycast source.
+    pub fn with_coordinate_system(self, coordinate_system: CoordinateSystem) -> Self {
+        Self {
+            coordinate_system,
+            ..self
+        }
+    }
+
+    /// Set the `collect_stats` field of this raycast source.
+    pub fn with_stats(self, collect_stats: bool) -> Self {
+        Self {
+            collect_stats,
+            ..self
+        }
+    }
+
+    /// Set the `warm_start` field of this raycast source.
+    pub fn with_warm_start(self, warm_start: bool) -> Self {
+        Self { warm_start, ..self }
+    }
+
+    /// Set the `clip_to_far_plane` field of this raycast source.
+    pub fn with_clip_to_far_plane(self, clip_to_far_plane: bool) -> Self {
+        Self {
+            clip_to_far_plane,
+            ..self
+        }
+    }
+
+    /// Set the `priority` field of this raycast source.
+    pub fn with_priority(self, priority: f32) -> Self {
+        Self { priority, ..self }
+    }
+
+    /// Set the `priority_class` field of this raycast source.
+    pub fn with_priority_class(self, priority_class: RaycastPriorityClass) -> Self {
+        Self {
+            priority_class,
+            ..self
+        }
+    }
+
+    /// Set the `layers` field of this raycast source.
+    pub fn with_layers(self, layers: RaycastLayers) -> Self {
+        Self { layers, ..self }
+    }
+
+    /// Set the `hit_data` field of this raycast source.
+    pub fn with_hit_data(self, hit_data: HitData) -> Self {
+        Self { hit_data, ..self }
+    }
+
     /// Instantiates and initializes a [RaycastSource] with a valid screenspace ray.
     pub fn new_screenspace(
         cursor_pos_screen: Vec2,
@@ -326,6 +607,37 @@ impl<T: TypePath> RaycastSource<T> {
         }
     }
 
+    /// Instantiates a [RaycastSource] that casts rays through the cursor from `camera`, which can
+    /// be any camera entity, not only the one this source is added to. Use
+    /// [`RaycastSource::with_screenspace`] to cast through a fixed position in the viewport
+    /// instead.
+    pub fn from_camera(camera: Entity) -> Self {
+        RaycastSource {
+            cast_method: RaycastMethod::Cursor,
+            camera: Some(camera),
+            ..default()
+        }
+    }
+
+    /// Instantiates a [RaycastSource] that raycasts the ray of the [RaycastSource] on `provider`,
+    /// with [`RaycastMethod::Shared`]. Use the `with_*` methods to give it its own layers, early
+    /// exit, or visibility.
+    pub fn from_provider(provider: Entity) -> Self {
+        RaycastSource {
+            cast_method: RaycastMethod::Shared(provider),
+            ..default()
+        }
+    }
+
+    /// Cast rays through `viewport_pos` with [`RaycastMethod::Screenspace`]. The ray is built by
+    /// the [`build_rays`] system.
+    pub fn with_screenspace(self, viewport_pos: Vec2) -> Self {
+        RaycastSource {
+            cast_method: RaycastMethod::Screenspace(viewport_pos),
+            ..self
+        }
+    }
+
     /// Get a reference to the ray cast source's intersections, if one exists.
     pub fn get_intersections(&self) -> Option<&[(Entity, IntersectionData)]> {
         if self.intersections.is_empty() {
@@ -364,10 +676,93 @@ impl<T: TypePath> RaycastSource<T> {
     pub fn is_screenspace(&self) -> bool {
         matches!(self.cast_method, RaycastMethod::Screenspace(_))
     }
+
+    /// Returns `true` if this is using [`RaycastMethod::Transform`].
+    pub fn is_transform(&self) -> bool {
+        matches!(self.cast_method, RaycastMethod::Transform)
+    }
+
+    /// The entity whose ray this source uses, if this is using [`RaycastMethod::Shared`].
+    pub fn ray_provider(&self) -> Option<Entity> {
+        match self.cast_method {
+            RaycastMethod::Shared(provider) => Some(provider),
+            _ => None,
+        }
+    }
+
+    /// Get the statistics collected for this source. These are only updated while
+    /// [`RaycastSource::collect_stats`] is `true`.
+    pub fn stats(&self) -> &RaycastSourceStats {
+        &self.stats
+    }
+
+    /// The number of frames since this source was last raycast, which is more than zero when the
+    /// [`RaycastScheduling`] skipped it.
+    pub fn frames_since_raycast(&self) -> u32 {
+        self.frames_since_raycast
+    }
+
+    /// Returns `true` if this source was skipped in the last frame it had a ray because the
+    /// [`RaycastBudget`] was spent. Its intersections are then those of the last time it was
+    /// raycast.
+    pub fn skipped_by_budget(&self) -> bool {
+        self.skipped_by_budget
+    }
+
+    /// Reset the statistics collected for this source.
+    pub fn reset_stats(&mut self) {
+        self.stats = RaycastSourceStats::default();
+    }
+
+    /// Capture the `target`, so this source only raycasts it, ignoring all other geometry, until
+    /// the capture is [released](Self::release_capture). The intersections of the source then hold
+    /// the hit on the target if it is still hit, or are empty, which
+    /// [`capture_missed`](Self::capture_missed) reports.
+    ///
+    /// Capture the entity under the cursor when a drag starts, so the drag keeps tracking it when
+    /// the cursor slides over other entities.
+    pub fn capture(&mut self, target: Entity) {
+        self.captured = Some(target);
+    }
+
+    /// Release the captured target, so this source raycasts all geometry again.
+    pub fn release_capture(&mut self) {
+        self.captured = None;
+    }
+
+    /// The target this source has captured, if any.
+    pub fn captured(&self) -> Option<Entity> {
+        self.captured
+    }
+
+    /// Returns `true` if this source has captured a target, but missed it in the last raycast.
+    pub fn capture_missed(&self) -> bool {
+        self.captured.is_some() && self.intersections.is_empty()
+    }
+}
+
+/// Counters for profiling a [`RaycastSource`], collected when [`RaycastSource::collect_stats`] is
+/// enabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct RaycastSourceStats {
+    /// The number of times the source has been raycast. Frames where the source had no ray are not
+    /// counted.
+    pub frames_evaluated: u64,
+    /// The number of evaluated frames where the ray hit at least one entity.
+    pub hits: u64,
+    /// The number of evaluated frames where the ray hit nothing.
+    pub misses: u64,
+    /// How long the most recent raycast took.
+    pub last_duration: Duration,
 }
 
 /// Specifies the method used to generate rays.
 #[derive(Clone, Debug, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
 pub enum RaycastMethod {
     /// Use the mouse cursor to build a ray.
     Cursor,
@@ -375,78 +770,296 @@ pub enum RaycastMethod {
     ///
     /// # Component Requirements
     ///
-    /// This requires a [Camera] component on this [RaycastSource]'s entity, to determine where the
-    /// screenspace ray is firing from in the world.
+    /// This requires a [Camera] component on this [RaycastSource]'s entity, or on its
+    /// [`RaycastSource::camera`], to determine where the screenspace ray is firing from in the
+    /// world.
     Screenspace(Vec2),
-    /// Use a transform in world space to define a pick ray. This transform is applied to a vector
-    /// at the origin pointing up to generate a ray.
+    /// Use a transform in world space to define a pick ray. The ray starts at the translation of
+    /// the transform, and points along the forward axis of the source's
+    /// [`RaycastSource::coordinate_system`], which is -Z by default.
     ///
     /// # Component Requirements
     ///
     /// Requires a [GlobalTransform] component associated with this [RaycastSource]'s entity.
     Transform,
+    /// Use the ray built by the [`RaycastSource<T>`] of another entity, the ray provider, with the
+    /// same far plane distance. The ray is built once for all of the sources sharing it, and each
+    /// source raycasts it with its own settings.
+    ///
+    /// # Component Requirements
+    ///
+    /// Requires a [`RaycastSource<T>`] with the same `T` on the provider, which must not be
+    /// shared itself.
+    Shared(Entity),
 }
 
 pub fn build_rays<T: TypePath>(
-    mut pick_source_query: Query<(
-        &mut RaycastSource<T>,
-        Option<&GlobalTransform>,
-        Option<&Camera>,
-    )>,
-    window: Query<&Window, With<PrimaryWindow>>,
+    mut pick_source_query: Query<(Entity, &mut RaycastSource<T>, Option<&GlobalTransform>)>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    windows: Query<&Window>,
 ) {
-    for (mut pick_source, transform, camera) in &mut pick_source_query {
-        pick_source.ray = match &mut pick_source.cast_method {
-            RaycastMethod::Cursor => {
-                query_window(&window, camera, transform).and_then(|(window, camera, transform)| {
-                    window.cursor_position().and_then(|cursor_pos| {
-                        ray_from_screenspace(cursor_pos, camera, transform, window)
-                    })
+    // Missing components are reported once by `validate_raycast_sources`, not every frame here.
+    let primary_window = primary_window.get_single().ok();
+    let mut shared = Vec::new();
+    for (entity, mut pick_source, transform) in &mut pick_source_query {
+        if let Some(provider) = pick_source.ray_provider() {
+            shared.push((entity, provider));
+            continue;
+        }
+        let coordinate_system = pick_source.coordinate_system;
+        let view = cameras
+            .get(pick_source.camera.unwrap_or(entity))
+            .ok()
+            .and_then(|(camera, transform)| {
+                let window = camera_window(camera, primary_window, &windows)?;
+                Some((window, (camera, transform)))
+            });
+        pick_source.ray = match &pick_source.cast_method {
+            RaycastMethod::Cursor => view.and_then(|(window, (camera, transform))| {
+                window.cursor_position().and_then(|cursor_pos| {
+                    ray_from_screenspace(cursor_pos, camera, transform, window)
                 })
-            }
+            }),
             RaycastMethod::Screenspace(cursor_pos_screen) => {
-                query_window(&window, camera, transform).and_then(|(window, camera, transform)| {
+                view.and_then(|(window, (camera, transform))| {
                     ray_from_screenspace(*cursor_pos_screen, camera, transform, window)
                 })
             }
             RaycastMethod::Transform => transform
                 .map(|t| t.compute_matrix())
-                .map(ray_from_transform),
+                .map(|t| coordinate_system.ray_from_transform(t)),
+            RaycastMethod::Shared(_) => unreachable!("shared rays are copied below"),
+        };
+        pick_source.max_distance = match (pick_source.ray, view) {
+            (Some(ray), Some((_, (camera, transform))))
+                if pick_source.clip_to_far_plane && !pick_source.is_transform() =>
+            {
+                let clip_from_world =
+                    camera.clip_from_view() * transform.compute_matrix().inverse();
+                far_plane_distance(ray, &clip_from_world)
+            }
+            _ => f32::INFINITY,
+        };
+    }
+    // Shared sources copy the rays of their providers once they are all built.
+    for (entity, provider) in shared {
+        let provided = pick_source_query
+            .get(provider)
+            .ok()
+            .filter(|(_, provider, _)| provider.ray_provider().is_none())
+            .map(|(_, provider, _)| {
+                let from_camera = !provider.is_transform();
+                (provider.ray, provider.max_distance, from_camera)
+            });
+        let Ok((_, mut pick_source, _)) = pick_source_query.get_mut(entity) else {
+            continue;
         };
+        (
+            pick_source.ray,
+            pick_source.max_distance,
+            pick_source.shared_from_camera,
+        ) = provided.unwrap_or((None, f32::INFINITY, false));
     }
 }
 
-fn query_window<'q, 'a: 'q, 'b>(
-    window: &'q Query<'_, '_, &'a Window, With<PrimaryWindow>>,
-    camera: Option<&'b Camera>,
-    transform: Option<&'b GlobalTransform>,
-) -> Option<(&'q Window, &'b Camera, &'b GlobalTransform)> {
-    let window = match window.get_single() {
-        Ok(window) => window,
-        Err(_) => {
-            error!("No primary window found, cannot cast ray");
-            return None;
-        }
+/// The window the `camera` renders to, which screenspace positions and the cursor are relative to.
+/// Cameras rendering to an image use the primary window.
+fn camera_window<'a>(
+    camera: &Camera,
+    primary_window: Option<Entity>,
+    windows: &'a Query<&Window>,
+) -> Option<&'a Window> {
+    let window = match camera.target.normalize(primary_window)? {
+        NormalizedRenderTarget::Window(window) => window.entity(),
+        _ => primary_window?,
     };
-    let camera = match camera {
-        Some(camera) => camera,
-        None => {
-            error!(
-                "The PickingSource is a CameraScreenSpace but has no associated Camera component"
+    windows.get(window).ok()
+}
+
+/// Why a [`RaycastSource`] can't build rays. See [`RaycastDiagnostics`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum RaycastSourceProblem {
+    /// A [`RaycastMethod::Transform`] source has no [`GlobalTransform`].
+    MissingTransform,
+    /// The camera of a screenspace or cursor source has no [`Camera`] component. The camera is the
+    /// source entity itself, unless it has a [`RaycastSource::camera`].
+    MissingCamera { camera: Entity },
+    /// The camera of a screenspace or cursor source has no [`GlobalTransform`].
+    MissingCameraTransform { camera: Entity },
+    /// The provider of a [`RaycastMethod::Shared`] source has no [`RaycastSource`] of the same
+    /// type, or shares the ray of another source itself.
+    MissingRayProvider { provider: Entity },
+}
+
+impl std::fmt::Display for RaycastSourceProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingTransform => write!(f, "it has no GlobalTransform"),
+            Self::MissingCamera { camera } => {
+                write!(f, "its camera {camera} has no Camera component")
+            }
+            Self::MissingCameraTransform { camera } => {
+                write!(f, "its camera {camera} has no GlobalTransform")
+            }
+            Self::MissingRayProvider { provider } => {
+                write!(
+                    f,
+                    "its ray provider {provider} has no unshared RaycastSource"
+                )
+            }
+        }
+    }
+}
+
+/// The [`RaycastSource<T>`]s that can't build rays, and why, updated every frame before rays are
+/// built. Tools can use this to show a checklist of misconfigured sources.
+///
+/// Each problem is also logged once when it first appears, instead of every frame, and a
+/// [`RaycastDiagnosticChanged<T>`] event is sent whenever a problem appears or is resolved.
+#[derive(Resource)]
+pub struct RaycastDiagnostics<T> {
+    problems: HashMap<Entity, RaycastSourceProblem>,
+    missing_window: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for RaycastDiagnostics<T> {
+    fn default() -> Self {
+        Self {
+            problems: HashMap::new(),
+            missing_window: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> RaycastDiagnostics<T> {
+    /// The problem of the source on `entity`, if it has one.
+    pub fn get(&self, entity: Entity) -> Option<RaycastSourceProblem> {
+        self.problems.get(&entity).copied()
+    }
+
+    /// Iterate over the sources that can't build rays, and their problems.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, RaycastSourceProblem)> + '_ {
+        self.problems
+            .iter()
+            .map(|(entity, problem)| (*entity, *problem))
+    }
+
+    /// Returns `true` if there are screenspace or cursor sources whose camera has no window to build
+    /// their rays with.
+    pub fn missing_window(&self) -> bool {
+        self.missing_window
+    }
+
+    /// Returns `true` if every source can build rays.
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty() && !self.missing_window
+    }
+}
+
+/// Sent when the [`RaycastDiagnostics<T>`] of a source change.
+#[derive(Event, Debug)]
+pub struct RaycastDiagnosticChanged<T> {
+    /// The source entity.
+    pub entity: Entity,
+    /// The new problem of the source, or `None` if its problem was resolved.
+    pub problem: Option<RaycastSourceProblem>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RaycastDiagnosticChanged<T> {
+    pub fn new(entity: Entity, problem: Option<RaycastSourceProblem>) -> Self {
+        Self {
+            entity,
+            problem,
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Manually implemented, because deriving would require `T: Clone`.
+impl<T> Clone for RaycastDiagnosticChanged<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.entity, self.problem)
+    }
+}
+
+/// Updates the [`RaycastDiagnostics<T>`] with the [`RaycastSource<T>`]s that are missing the
+/// components their [`RaycastMethod`] needs to build rays, and whether the window of a camera is
+/// missing.
+pub fn validate_raycast_sources<T: TypePath>(
+    sources: Query<(Entity, &RaycastSource<T>, Has<GlobalTransform>)>,
+    cameras: Query<Has<GlobalTransform>, With<Camera>>,
+    camera_targets: Query<&Camera>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    windows: Query<&Window>,
+    mut diagnostics: ResMut<RaycastDiagnostics<T>>,
+    mut changed: EventWriter<RaycastDiagnosticChanged<T>>,
+) {
+    let primary_window = primary_window.get_single().ok();
+    let mut missing_window = false;
+    let mut problems = HashMap::new();
+    for (entity, source, has_transform) in &sources {
+        if let Some(provider) = source.ray_provider() {
+            let has_provider = sources
+                .get(provider)
+                .is_ok_and(|(_, provider, _)| provider.ray_provider().is_none());
+            if !has_provider {
+                problems.insert(
+                    entity,
+                    RaycastSourceProblem::MissingRayProvider { provider },
+                );
+            }
+        } else if let Some(problem) = source_problem(entity, source, has_transform, &cameras) {
+            problems.insert(entity, problem);
+        } else if !source.is_transform() {
+            let camera = camera_targets.get(source.camera.unwrap_or(entity));
+            missing_window |= camera.map_or(true, |camera| {
+                camera_window(camera, primary_window, &windows).is_none()
+            });
+        }
+    }
+    for (entity, problem) in &problems {
+        if diagnostics.problems.get(entity) != Some(problem) {
+            warn!(
+                "The RaycastSource<{}> on {entity} cannot build rays: {problem}",
+                T::short_type_path()
             );
-            return None;
+            changed.send(RaycastDiagnosticChanged::new(*entity, Some(*problem)));
         }
-    };
-    let camera_transform = match transform {
-        Some(transform) => transform,
-        None => {
-            error!(
-        "The PickingSource is a CameraScreenSpace but has no associated GlobalTransform component"
-    );
-            return None;
+    }
+    for entity in diagnostics.problems.keys() {
+        if !problems.contains_key(entity) {
+            changed.send(RaycastDiagnosticChanged::new(*entity, None));
         }
-    };
-    Some((window, camera, camera_transform))
+    }
+    if missing_window && !diagnostics.missing_window {
+        warn!("No window found for the camera of a screenspace or cursor raycast source, so it will not cast");
+    }
+    // Only mark the resource as changed when the diagnostics did.
+    if diagnostics.problems != problems || diagnostics.missing_window != missing_window {
+        diagnostics.problems = problems;
+        diagnostics.missing_window = missing_window;
+    }
+}
+
+fn source_problem<T: TypePath>(
+    entity: Entity,
+    source: &RaycastSource<T>,
+    has_transform: bool,
+    cameras: &Query<Has<GlobalTransform>, With<Camera>>,
+) -> Option<RaycastSourceProblem> {
+    if source.is_transform() {
+        return (!has_transform).then_some(RaycastSourceProblem::MissingTransform);
+    }
+    let camera = source.camera.unwrap_or(entity);
+    match cameras.get(camera) {
+        Ok(true) => None,
+        Ok(false) => Some(RaycastSourceProblem::MissingCameraTransform { camera }),
+        Err(_) => Some(RaycastSourceProblem::MissingCamera { camera }),
+    }
 }
 
 /// Iterates through all entities with the [RaycastMesh] component, checking for
@@ -455,19 +1068,100 @@ fn query_window<'q, 'a: 'q, 'b>(
 pub fn update_raycast<T: TypePath + Send + Sync + 'static>(
     mut raycast: crate::immediate::Raycast,
     mut pick_source_query: Query<&mut RaycastSource<T>>,
-    targets: Query<&RaycastMesh<T>>,
+    targets: Query<(), With<RaycastMesh<T>>>,
+    state: Option<Res<RaycastPluginState<T>>>,
+    mut next_round_robin: Local<usize>,
 ) {
-    for mut pick_source in &mut pick_source_query {
-        if let Some(ray) = pick_source.ray {
-            pick_source.intersections.clear();
+    let mut sources: Vec<_> = pick_source_query
+        .iter_mut()
+        .filter(|source| source.ray.is_some())
+        .collect();
+    for source in &mut sources {
+        // Only sources that are actually raycast should be marked as changed.
+        source.bypass_change_detection().frames_since_raycast += 1;
+    }
+    // Critical sources are raycast every frame, so they are not scheduled.
+    let (mut critical, mut sources): (Vec<_>, Vec<_>) = sources
+        .into_iter()
+        .partition(|source| source.priority_class == RaycastPriorityClass::Critical);
+    match state.map(|state| state.scheduling).unwrap_or_default() {
+        RaycastScheduling::EveryFrame => {}
+        RaycastScheduling::RoundRobin { sources_per_frame } => {
+            let sources_per_frame = sources_per_frame.max(1);
+            if !sources.is_empty() {
+                let start = *next_round_robin % sources.len();
+                sources.rotate_left(start);
+                *next_round_robin = start + sources_per_frame;
+            }
+            sources.truncate(sources_per_frame);
+        }
+        RaycastScheduling::Priority { sources_per_frame } => {
+            let sources_per_frame = sources_per_frame.max(1);
+            sources.sort_by_key(|source| {
+                Reverse(FloatOrd(
+                    source.priority * source.frames_since_raycast as f32,
+                ))
+            });
+            sources.truncate(sources_per_frame);
+        }
+    }
+    // Cosmetic sources are raycast last, so they don't spend the budget of the others.
+    sources.sort_by_key(|source| source.priority_class);
+    critical.append(&mut sources);
+
+    for mut pick_source in critical {
+        let Some(ray) = pick_source.ray else {
+            continue;
+        };
+        let warm_start = pick_source
+            .warm_start
+            .then(|| pick_source.intersections.first().map(|(entity, _)| *entity))
+            .flatten();
+
+        let start = pick_source.collect_stats.then(Instant::now);
+        let test = |_| pick_source.should_early_exit;
+        let mut settings = RaycastSettings::default()
+            .with_early_exit_test(&test)
+            .with_visibility(pick_source.visibility)
+            .with_max_distance(pick_source.max_distance)
+            .with_priority_class(pick_source.priority_class)
+            .with_layers(pick_source.layers)
+            .with_hit_data(pick_source.hit_data);
+        // Rays cast from a camera only hit the level of detail that camera sees.
+        let from_camera = match pick_source.cast_method {
+            RaycastMethod::Transform => false,
+            RaycastMethod::Shared(_) => pick_source.shared_from_camera,
+            _ => true,
+        };
+        if from_camera {
+            settings = settings.with_lod_viewpoint(ray.origin);
+        }
+        if let Some(entity) = warm_start {
+            settings = settings.with_warm_start(entity);
+        }
+        match pick_source.captured {
+            Some(target) => raycast.cast_ray_on_candidates(ray, &settings, [target]),
+            None => raycast.cast_ray_filtered_by_query(ray, &settings, &targets),
+        };
+        // Sources skipped by the budget keep their intersections, like unscheduled sources.
+        pick_source.skipped_by_budget = raycast.skipped_by_budget();
+        if pick_source.skipped_by_budget {
+            continue;
+        }
+        pick_source.frames_since_raycast = 0;
+        pick_source.intersections.clear();
+        pick_source.intersections.extend_from_slice(&raycast.output);
 
-            let filter = |entity| targets.contains(entity);
-            let test = |_| pick_source.should_early_exit;
-            let settings = RaycastSettings::default()
-                .with_filter(&filter)
-                .with_early_exit_test(&test)
-                .with_visibility(pick_source.visibility);
-            pick_source.intersections = raycast.cast_ray(ray, &settings).to_vec();
+        if let Some(start) = start {
+            let hit = !pick_source.intersections.is_empty();
+            let stats = &mut pick_source.stats;
+            stats.last_duration = start.elapsed();
+            stats.frames_evaluated += 1;
+            if hit {
+                stats.hits += 1;
+            } else {
+                stats.misses += 1;
+            }
         }
     }
 }
@@ -475,6 +1169,7 @@ pub fn update_raycast<T: TypePath + Send + Sync + 'static>(
 pub fn update_target_intersections<T: TypePath + Send + Sync>(
     sources: Query<(Entity, &RaycastSource<T>)>,
     mut meshes: Query<&mut RaycastMesh<T>>,
+    inherited: Query<&InheritedRaycastMesh<T>>,
     mut previously_updated_raycast_meshes: Local<Vec<Entity>>,
 ) {
     // Clear any entities with intersections last frame
@@ -486,72 +1181,525 @@ pub fn update_target_intersections<T: TypePath + Send + Sync>(
 
     for (source_entity, source) in sources.iter() {
         for (mesh_entity, intersection) in source.intersections().iter() {
-            if let Ok(mut mesh) = meshes.get_mut(*mesh_entity) {
-                mesh.intersections
-                    .push((source_entity, intersection.to_owned()));
-                previously_updated_raycast_meshes.push(*mesh_entity);
+            // Hits on inherited meshes are also hits on the root they inherited from.
+            let root = inherited
+                .get(*mesh_entity)
+                .ok()
+                .map(|inherited| inherited.root);
+            for entity in std::iter::once(*mesh_entity).chain(root) {
+                if let Ok(mut mesh) = meshes.get_mut(entity) {
+                    mesh.intersections
+                        .push((source_entity, intersection.to_owned()));
+                    previously_updated_raycast_meshes.push(entity);
+                }
             }
         }
     }
 }
 
-#[cfg(feature = "debug")]
-pub mod debug {
-    #![allow(unused)]
-
-    use bevy_color::palettes::css;
-    use bevy_ecs::system::{Commands, Query};
-    use bevy_gizmos::gizmos::Gizmos;
-    use bevy_math::{Dir3, Quat, Vec3};
-    use bevy_reflect::TypePath;
-    use bevy_utils::tracing::info;
-    use std::marker::PhantomData;
-
-    use crate::prelude::*;
-
-    /// Updates the 3d cursor to be in the pointed world coordinates
-    #[allow(clippy::too_many_arguments)]
-    pub fn update_debug_cursor<T: TypePath + Send + Sync>(
-        mut commands: Commands,
-        mut sources: Query<&RaycastSource<T>>,
-        mut gizmos: Gizmos,
-    ) {
-        for ray in sources.iter().filter_map(|s| s.ray) {
-            let orientation = Quat::from_rotation_arc(Vec3::NEG_Z, *ray.direction);
-            gizmos.ray(ray.origin, *ray.direction, css::BLUE);
-            gizmos.sphere(ray.origin, orientation, 0.1, css::BLUE);
-        }
-
-        for (is_first, intersection) in sources.iter().flat_map(|m| {
-            m.intersections()
-                .iter()
-                .map(|i| i.1.clone())
-                .enumerate()
-                .map(|(i, hit)| (i == 0, hit))
-        }) {
-            let color = match is_first {
-                true => css::GREEN,
-                false => css::PINK,
+/// Gives the descendants with meshes of every [`RaycastMesh<T>`] with [`AutoDescend`] an
+/// [`InheritedRaycastMesh<T>`], and removes it from entities that are no longer descendants. The
+/// targets are only updated when the hierarchy, or these components, changed.
+pub fn update_inherited_raycast_meshes<T: TypePath + Send + Sync>(
+    mut commands: Commands,
+    roots: Query<
+        Entity,
+        (
+            With<RaycastMesh<T>>,
+            With<AutoDescend>,
+            Without<InheritedRaycastMesh<T>>,
+        ),
+    >,
+    changed: Query<
+        (),
+        Or<(
+            Changed<Children>,
+            Changed<Parent>,
+            Added<AutoDescend>,
+            Added<RaycastMesh<T>>,
+        )>,
+    >,
+    (mut removed_descend, mut removed_meshes, mut removed_parents): (
+        RemovedComponents<AutoDescend>,
+        RemovedComponents<RaycastMesh<T>>,
+        RemovedComponents<Parent>,
+    ),
+    children: Query<&Children>,
+    mesh_entities: Query<Has<RaycastMesh<T>>, MeshFilter>,
+    inherited: Query<(Entity, &InheritedRaycastMesh<T>)>,
+) {
+    let removed = removed_descend.read().count()
+        + removed_meshes.read().count()
+        + removed_parents.read().count();
+    if changed.is_empty() && removed == 0 {
+        return;
+    }
+
+    let mut targets = HashMap::new();
+    for root in &roots {
+        for entity in children.iter_descendants(root) {
+            let Ok(has_raycast_mesh) = mesh_entities.get(entity) else {
+                continue;
             };
-            gizmos.ray(intersection.position(), intersection.normal(), color);
-            gizmos.circle(
-                intersection.position(),
-                Dir3::new_unchecked(intersection.normal().normalize()),
-                0.1,
-                color,
-            );
-            gizmos.circle_2d(intersection.position().truncate(), 10.0, color);
+            // Entities with their own raycast mesh are already targets.
+            if !has_raycast_mesh || inherited.contains(entity) {
+                targets.entry(entity).or_insert(root);
+            }
         }
     }
+    for (entity, inherited) in &inherited {
+        match targets.remove(&entity) {
+            Some(root) if root == inherited.root => {}
+            Some(root) => {
+                commands.entity(entity).insert(InheritedRaycastMesh::<T> {
+                    root,
+                    _marker: PhantomData,
+                });
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .remove::<(RaycastMesh<T>, InheritedRaycastMesh<T>)>();
+            }
+        }
+    }
+    for (entity, root) in targets {
+        commands.entity(entity).insert((
+            RaycastMesh::<T>::default(),
+            InheritedRaycastMesh::<T> {
+                root,
+                _marker: PhantomData,
+            },
+        ));
+    }
+}
 
-    /// Used to debug [`RaycastMesh`] intersections.
-    pub fn print_intersections<T: TypePath + Send + Sync>(query: Query<&RaycastMesh<T>>) {
-        for (_, intersection) in query.iter().flat_map(|mesh| mesh.intersections.iter()) {
-            info!(
-                "Distance {:?}, Position {:?}",
-                intersection.distance(),
-                intersection.position()
-            );
+/// Triggered on an entity each frame a [`RaycastSource<T>`] hits it, when
+/// [`RaycastPluginState::with_hit_observers`] is enabled.
+///
+/// Add an observer to the entity to run its own behavior when it is hit, such as a button or a
+/// pressure plate, instead of scanning the hits of every source in a central system:
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_mod_raycast::prelude::*;
+/// # #[derive(Reflect)]
+/// # struct MyRaycastSet;
+/// fn spawn_button(mut commands: Commands) {
+///     commands
+///         .spawn(RaycastMesh::<MyRaycastSet>::default())
+///         .observe(|trigger: Trigger<OnRaycastHit<MyRaycastSet>>| {
+///             info!("Hit at {}", trigger.event().hit.position());
+///         });
+/// }
+/// ```
+#[derive(Event)]
+pub struct OnRaycastHit<T> {
+    /// The [`RaycastSource<T>`] entity that hit the entity.
+    pub source: Entity,
+    /// The hit on the entity.
+    pub hit: IntersectionData,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> OnRaycastHit<T> {
+    /// Creates the event for a `hit` by the `source` entity.
+    pub fn new(source: Entity, hit: IntersectionData) -> Self {
+        Self {
+            source,
+            hit,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for OnRaycastHit<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.source, self.hit)
+    }
+}
+
+/// Triggers [`OnRaycastHit`] on every entity hit by a [`RaycastSource<T>`].
+pub fn trigger_hit_observers<T: TypePath + Send + Sync>(
+    mut commands: Commands,
+    sources: Query<(Entity, &RaycastSource<T>)>,
+) {
+    for (source, pick_source) in &sources {
+        for (entity, hit) in pick_source.intersections() {
+            commands.trigger_targets(OnRaycastHit::<T>::new(source, *hit), *entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn stats_count_hits_and_misses() {
+        let mut world = World::new();
+        for plane in spawn_planes(&mut world, &[-1.0]) {
+            world.entity_mut(plane).insert(RaycastMesh::<()>::default());
         }
+        let source = RaycastSource::<()>::new()
+            .with_visibility(RaycastVisibility::Ignore)
+            .with_stats(true);
+        let source = world
+            .spawn(RaycastSource {
+                ray: Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z)),
+                ..source
+            })
+            .id();
+        world.run_system_once(update_raycast::<()>);
+
+        let mut source_mut = world.get_mut::<RaycastSource<()>>(source).unwrap();
+        source_mut.ray = Some(Ray3d::new(Vec3::ZERO, Vec3::Z));
+        world.run_system_once(update_raycast::<()>);
+
+        let stats = world.get::<RaycastSource<()>>(source).unwrap().stats();
+        assert_eq!(stats.frames_evaluated, 2);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+    }
+
+    #[test]
+    fn captured_sources_only_hit_their_target() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0]);
+        for plane in &planes {
+            world
+                .entity_mut(*plane)
+                .insert(RaycastMesh::<()>::default());
+        }
+        let source = RaycastSource::<()>::new().with_visibility(RaycastVisibility::Ignore);
+        let source = world
+            .spawn(RaycastSource {
+                ray: Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z)),
+                ..source
+            })
+            .id();
+        let capture = |world: &mut World, target| {
+            let mut source_mut = world.get_mut::<RaycastSource<()>>(source).unwrap();
+            source_mut.capture(target);
+            world.run_system_once(update_raycast::<()>);
+            let source = world.get::<RaycastSource<()>>(source).unwrap();
+            let hits: Vec<_> = source.intersections().iter().map(|(e, _)| *e).collect();
+            (hits, source.capture_missed())
+        };
+        // The captured plane is hit through the plane in front of it.
+        assert_eq!(capture(&mut world, planes[1]), (vec![planes[1]], false));
+        let elsewhere = world.spawn_empty().id();
+        assert_eq!(capture(&mut world, elsewhere), (vec![], true));
+    }
+
+    #[test]
+    fn scheduling_limits_sources_per_frame() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        let sources = [1.0, 1.0, 4.0].map(|priority| {
+            let source = RaycastSource::<()>::new().with_priority(priority);
+            world
+                .spawn(RaycastSource {
+                    ray: Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z)),
+                    ..source
+                })
+                .id()
+        });
+        let run = |world: &mut World, scheduling| {
+            world.insert_resource(RaycastPluginState::<()>::default().with_scheduling(scheduling));
+            world.run_system_once(update_raycast::<()>);
+            sources.map(|source| {
+                let source = world.get::<RaycastSource<()>>(source).unwrap();
+                source.frames_since_raycast()
+            })
+        };
+
+        let round_robin = RaycastScheduling::RoundRobin {
+            sources_per_frame: 2,
+        };
+        assert_eq!(run(&mut world, RaycastScheduling::EveryFrame), [0, 0, 0]);
+        let mut skipped = run(&mut world, round_robin);
+        skipped.sort();
+        assert_eq!(skipped, [0, 0, 1]);
+
+        // The high priority source is raycast more often than the others, which are still updated.
+        let priority = RaycastScheduling::Priority {
+            sources_per_frame: 1,
+        };
+        let mut counts = [0; 3];
+        for _ in 0..8 {
+            let frames = run(&mut world, priority);
+            for (count, frames) in counts.iter_mut().zip(frames) {
+                *count += (frames == 0) as u32;
+            }
+        }
+        assert!(counts[2] > counts[0] + counts[1]);
+        assert!(counts.iter().all(|count| *count > 0));
+
+        // A limit of zero still raycasts one source each frame.
+        let starved = RaycastScheduling::RoundRobin {
+            sources_per_frame: 0,
+        };
+        let frames = run(&mut world, starved);
+        assert_eq!(frames.iter().filter(|frames| **frames == 0).count(), 1);
+    }
+
+    #[test]
+    fn budget_skips_cosmetic_sources() {
+        let mut world = World::new();
+        let plane = spawn_planes(&mut world, &[-1.0])[0];
+        world.entity_mut(plane).insert(RaycastMesh::<()>::default());
+        world.insert_resource(RaycastBudget::new(1));
+        let sources = [
+            RaycastPriorityClass::Cosmetic,
+            RaycastPriorityClass::Critical,
+        ]
+        .map(|class| {
+            let source = RaycastSource::<()>::new()
+                .with_visibility(RaycastVisibility::Ignore)
+                .with_priority_class(class);
+            world
+                .spawn(RaycastSource {
+                    ray: Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z)),
+                    ..source
+                })
+                .id()
+        });
+        world.run_system_once(update_raycast::<()>);
+        // The critical source is raycast first, and spends the budget.
+        let [cosmetic, critical] =
+            sources.map(|source| world.get::<RaycastSource<()>>(source).unwrap());
+        assert!(!critical.skipped_by_budget());
+        assert_eq!(critical.intersections().len(), 1);
+        assert!(cosmetic.skipped_by_budget());
+        assert!(cosmetic.intersections().is_empty());
+        assert_eq!(cosmetic.frames_since_raycast(), 1);
+        assert_eq!(world.resource::<RaycastBudget>().skipped(), 1);
+    }
+
+    #[test]
+    fn auto_descend_targets_descendant_meshes() {
+        let mut world = World::new();
+        let plane = spawn_planes(&mut world, &[-1.0])[0];
+        let root = world
+            .spawn((RaycastMesh::<()>::default(), AutoDescend))
+            .add_child(plane)
+            .id();
+        let source = RaycastSource::<()> {
+            ray: Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z)),
+            ..RaycastSource::new().with_visibility(RaycastVisibility::Ignore)
+        };
+        world.spawn(source);
+        let mut schedule = Schedule::default();
+        schedule.add_systems(
+            (
+                update_inherited_raycast_meshes::<()>,
+                update_raycast::<()>,
+                update_target_intersections::<()>,
+            )
+                .chain(),
+        );
+        let mut update = |world: &mut World| schedule.run(world);
+
+        update(&mut world);
+        let inherited = world.get::<InheritedRaycastMesh<()>>(plane).unwrap();
+        assert_eq!(inherited.root(), root);
+        let intersections = |world: &World, entity| {
+            world
+                .get::<RaycastMesh<()>>(entity)
+                .map(|mesh| mesh.intersections().len())
+        };
+        assert_eq!(intersections(&world, plane), Some(1));
+        assert_eq!(intersections(&world, root), Some(1));
+
+        // Once it leaves the hierarchy, the plane is no longer a target.
+        world.entity_mut(plane).remove_parent();
+        update(&mut world);
+        assert!(world.get::<InheritedRaycastMesh<()>>(plane).is_none());
+        assert_eq!(intersections(&world, plane), None);
+        assert_eq!(intersections(&world, root), Some(0));
+    }
+
+    #[test]
+    fn observers_run_on_hit_entities() {
+        #[derive(Resource, Default)]
+        struct Hits(Vec<(Entity, Entity, f32)>);
+
+        let mut world = World::new();
+        world.init_resource::<Hits>();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        for plane in &planes {
+            world
+                .entity_mut(*plane)
+                .insert(RaycastMesh::<()>::default())
+                .observe(
+                    |trigger: Trigger<OnRaycastHit<()>>, mut hits: ResMut<Hits>| {
+                        let event = trigger.event();
+                        hits.0
+                            .push((trigger.entity(), event.source, event.hit.distance()));
+                    },
+                );
+        }
+        let source = RaycastSource::<()> {
+            ray: Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z)),
+            ..RaycastSource::new().with_visibility(RaycastVisibility::Ignore)
+        };
+        let source = world.spawn(source).id();
+        world.run_system_once(update_raycast::<()>);
+        world.run_system_once(trigger_hit_observers::<()>);
+
+        assert_eq!(world.resource::<Hits>().0, vec![(planes[0], source, 1.0)]);
+    }
+
+    #[test]
+    fn shared_sources_raycast_the_provider_ray() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        world
+            .entity_mut(planes[0])
+            .insert((RaycastMesh::<()>::default(), RaycastLayers::layer(1)));
+        world
+            .entity_mut(planes[1])
+            .insert(RaycastMesh::<()>::default());
+        let source =
+            RaycastSource::<()>::new_transform_empty().with_visibility(RaycastVisibility::Ignore);
+        let provider = world.spawn((source, GlobalTransform::IDENTITY)).id();
+        let shared =
+            RaycastSource::<()>::from_provider(provider).with_visibility(RaycastVisibility::Ignore);
+        let sources = [
+            shared.clone().with_layers(RaycastLayers::DEFAULT),
+            shared.clone().with_early_exit(false),
+        ]
+        .map(|source| world.spawn(source).id());
+        // Providers can't share the ray of another source themselves.
+        let chained = world
+            .spawn(RaycastSource::<()>::from_provider(sources[0]))
+            .id();
+        world.run_system_once(build_rays::<()>);
+        world.run_system_once(update_raycast::<()>);
+
+        let hits = |world: &World, source| {
+            let source = world.get::<RaycastSource<()>>(source).unwrap();
+            let hits = source.intersections().iter().map(|(entity, _)| *entity);
+            (source.get_ray(), hits.collect::<Vec<_>>())
+        };
+        let ray = Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z));
+        assert_eq!(hits(&world, provider), (ray, vec![planes[0]]));
+        assert_eq!(hits(&world, sources[0]), (ray, vec![planes[1]]));
+        assert_eq!(hits(&world, sources[1]), (ray, planes.to_vec()));
+        assert_eq!(hits(&world, chained), (None, vec![]));
+
+        world.init_resource::<RaycastDiagnostics<()>>();
+        world.init_resource::<Events<RaycastDiagnosticChanged<()>>>();
+        world.run_system_once(validate_raycast_sources::<()>);
+        let diagnostics = world.resource::<RaycastDiagnostics<()>>();
+        let problem = RaycastSourceProblem::MissingRayProvider {
+            provider: sources[0],
+        };
+        assert_eq!(diagnostics.get(chained), Some(problem));
+        assert_eq!(diagnostics.get(sources[0]), None);
+    }
+
+    #[test]
+    fn sources_report_missing_components() {
+        let mut world = World::new();
+        let camera = world.spawn(Camera::default()).id();
+        let sources = [
+            RaycastSource::<()>::from_camera(camera),
+            RaycastSource::new_cursor(),
+            RaycastSource::new_transform_empty(),
+        ]
+        .map(|source| world.spawn(source).id());
+
+        let changes = |world: &mut World| {
+            world.run_system_once(validate_raycast_sources::<()>);
+            let mut events = world.resource_mut::<Events<RaycastDiagnosticChanged<()>>>();
+            let mut changes: Vec<_> = events.drain().map(|e| (e.entity, e.problem)).collect();
+            changes.sort_by_key(|(entity, _)| *entity);
+            changes
+        };
+        world.init_resource::<RaycastDiagnostics<()>>();
+        world.init_resource::<Events<RaycastDiagnosticChanged<()>>>();
+        use RaycastSourceProblem::*;
+        assert_eq!(
+            changes(&mut world),
+            [
+                (sources[0], Some(MissingCameraTransform { camera })),
+                (sources[1], Some(MissingCamera { camera: sources[1] })),
+                (sources[2], Some(MissingTransform)),
+            ]
+        );
+        // Unchanged problems are only reported once.
+        assert_eq!(changes(&mut world), []);
+        let diagnostics = world.resource::<RaycastDiagnostics<()>>();
+        assert_eq!(diagnostics.get(sources[2]), Some(MissingTransform));
+        assert!(!diagnostics.missing_window());
+
+        world.entity_mut(camera).insert(GlobalTransform::IDENTITY);
+        world
+            .entity_mut(sources[2])
+            .insert(GlobalTransform::IDENTITY);
+        assert_eq!(
+            changes(&mut world),
+            [(sources[0], None), (sources[2], None)]
+        );
+        let diagnostics = world.resource::<RaycastDiagnostics<()>>();
+        assert_eq!(diagnostics.iter().count(), 1);
+        // The camera is complete now, but renders to a primary window that doesn't exist.
+        assert!(diagnostics.missing_window());
+    }
+
+    #[test]
+    fn screenspace_rays_use_the_scale_factor_of_the_camera_window() {
+        use bevy::{
+            render::camera::{camera_system, ManualTextureViews, RenderTarget, Viewport},
+            window::{
+                WindowCreated, WindowRef, WindowResized, WindowResolution, WindowScaleFactorChanged,
+            },
+        };
+
+        let mut world = World::new();
+        world.init_resource::<Events<WindowResized>>();
+        world.init_resource::<Events<WindowCreated>>();
+        world.init_resource::<Events<WindowScaleFactorChanged>>();
+        world.init_resource::<Events<AssetEvent<Image>>>();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<ManualTextureViews>();
+        let window = |scale_factor| Window {
+            resolution: WindowResolution::new(1600.0, 1200.0)
+                .with_scale_factor_override(scale_factor),
+            ..default()
+        };
+        world.spawn((window(1.0), PrimaryWindow));
+        let hidpi_window = world.spawn(window(2.0)).id();
+        // The right half of the hidpi window, in physical pixels.
+        let camera = world
+            .spawn((
+                Camera {
+                    target: RenderTarget::Window(WindowRef::Entity(hidpi_window)),
+                    viewport: Some(Viewport {
+                        physical_position: UVec2::new(800, 0),
+                        physical_size: UVec2::new(800, 1200),
+                        ..default()
+                    }),
+                    ..default()
+                },
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+        world.run_system_once(camera_system::<Projection>);
+
+        // The center of the viewport, in logical pixels of the hidpi window.
+        let center = Vec2::new(600.0, 300.0);
+        let source = world
+            .spawn(RaycastSource::<()>::from_camera(camera).with_screenspace(center))
+            .id();
+        world.run_system_once(build_rays::<()>);
+        let ray = world.get::<RaycastSource<()>>(source).unwrap().ray.unwrap();
+        assert!(ray.direction.abs_diff_eq(Vec3::NEG_Z, 1e-5));
     }
 }
@@ -0,0 +1,129 @@
+//! # Draping
+//!
+//! Roads, decals, and paths drawn over terrain are made by projecting a curve onto the scene, one
+//! sample at a time. [`Raycast::drape_polyline`] casts a ray from each point of a sampled curve in
+//! a given direction, and returns where each one lands.
+//!
+//! Instead of culling the whole world for every sample, the entities that could be hit by any of
+//! the rays are found once, and each ray is then only culled against those. Use
+//! [`resample_polyline`] to add samples along long segments, so the draped curve follows the
+//! surface between the original points.
+
+use bevy_ecs::entity::Entity;
+use bevy_math::{Dir3, Ray3d, Vec3, Vec3A};
+
+use crate::{immediate::world_aabb, prelude::*};
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Casts a ray from each of the `points` along `direction`, and returns the nearest hit of
+    /// each, or `None` where nothing was hit. See the [module docs](self).
+    ///
+    /// Set [`RaycastSettings::max_distance`] to how far the curve can be from the surface, which
+    /// also limits how many entities are considered for each ray.
+    pub fn drape_polyline(
+        &mut self,
+        points: &[Vec3],
+        direction: Dir3,
+        settings: &RaycastSettings,
+    ) -> Vec<Option<(Entity, IntersectionData)>> {
+        self.sweep_candidates(points, *direction, settings);
+        let candidates = std::mem::take(&mut *self.contained);
+        let hits = points
+            .iter()
+            .map(|point| {
+                let ray = Ray3d::new(*point, *direction);
+                self.cast_ray_on_candidates(ray, settings, candidates.iter().copied())
+                    .first()
+                    .copied()
+            })
+            .collect();
+        *self.contained = candidates;
+        hits
+    }
+
+    /// Fills the contained list with the entities whose bounds overlap the box swept by casting
+    /// every point along `direction`, up to the maximum distance of the `settings`.
+    fn sweep_candidates(&mut self, points: &[Vec3], direction: Vec3, settings: &RaycastSettings) {
+        let Some(first) = points.first() else {
+            self.contained.clear();
+            return;
+        };
+        // Axes the rays don't move along stay finite when the maximum distance is infinite.
+        let reach = Vec3::select(
+            direction.cmpeq(Vec3::ZERO),
+            Vec3::ZERO,
+            direction * settings.max_distance,
+        );
+        let (min, max) = points.iter().fold((*first, *first), |(min, max), point| {
+            let end = *point + reach;
+            (min.min(*point).min(end), max.max(*point).max(end))
+        });
+        let (min, max) = (Vec3A::from(min), Vec3A::from(max));
+
+        self.cull_with(settings, None, |bounds| {
+            let aabb = world_aabb(&bounds.aabb, &bounds.transform.model_to_world());
+            let overlaps = aabb.max().cmpge(min).all() && aabb.min().cmple(max).all();
+            overlaps.then_some([0.0; 2])
+        });
+        self.contain_culled();
+    }
+}
+
+/// Adds points along each segment of the polyline, so that consecutive points are at most
+/// `max_spacing` apart. The original points are kept.
+pub fn resample_polyline(points: &[Vec3], max_spacing: f32) -> Vec<Vec3> {
+    let mut resampled = Vec::with_capacity(points.len());
+    resampled.extend(points.first());
+    for pair in points.windows(2) {
+        let [start, end] = [pair[0], pair[1]];
+        let steps = (start.distance(end) / max_spacing).ceil().max(1.0);
+        let steps = if steps.is_finite() { steps as usize } else { 1 };
+        resampled.extend((1..=steps).map(|i| start.lerp(end, i as f32 / steps as f32)));
+    }
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, render::primitives::Aabb};
+
+    use super::*;
+
+    #[test]
+    fn drapes_path_over_steps() {
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut world = World::new();
+        let mut meshes = Assets::<Mesh>::default();
+        let step = meshes.add(Cuboid::new(2.0, 2.0, 2.0));
+        world.insert_resource(meshes);
+        let mut spawn_step = |x: f32, height: f32| {
+            world.spawn((
+                step.clone(),
+                Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+                GlobalTransform::from_xyz(x, height - 1.0, 0.0),
+                InheritedVisibility::VISIBLE,
+                ViewVisibility::default(),
+            ));
+        };
+        spawn_step(0.0, 0.0);
+        spawn_step(2.0, 1.0);
+        // Far outside the path, so it is never a candidate.
+        spawn_step(50.0, 3.0);
+
+        let path = resample_polyline(&[Vec3::new(-0.5, 5.0, 0.0), Vec3::new(2.5, 5.0, 0.0)], 1.0);
+        assert_eq!(path.len(), 4);
+        let heights = world.run_system_once(move |mut raycast: Raycast| {
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .with_max_distance(10.0);
+            raycast.sweep_candidates(&path, Vec3::NEG_Y, &settings);
+            assert_eq!(raycast.contained.len(), 2);
+            raycast
+                .drape_polyline(&path, Dir3::NEG_Y, &settings)
+                .iter()
+                .map(|hit| hit.map(|(_, hit)| hit.position().y))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(heights, vec![Some(0.0), Some(0.0), Some(1.0), Some(1.0)]);
+    }
+}
@@ -0,0 +1,229 @@
+//! # Dynamic Raycast Meshes
+//!
+//! Geometry that is regenerated often, such as per-frame UI shapes or debug volumes, can be made
+//! raycastable without creating a [`Mesh`] asset for it. Add a [`DynamicRaycastMesh`] holding the
+//! vertex and index buffers to an entity with a `GlobalTransform` and visibility components, and
+//! the [`Raycast`] system param will test its triangles directly.
+//!
+//! The bounds of a [`DynamicRaycastMesh`] are computed whenever its geometry is set, and are used
+//! for culling instead of the entity's [`Aabb`], so changes are picked up by the very next raycast.
+
+use bevy_ecs::component::Component;
+use bevy_math::{Mat4, Ray3d, Vec3};
+use bevy_render::{
+    mesh::{Indices, Mesh, VertexAttributeValues},
+    primitives::Aabb,
+};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+use crate::{
+    primitives::IntersectionData,
+    raycast::{ray_mesh_intersection, ray_mesh_intersections, validate_raycast_mesh, Backfaces},
+};
+
+/// Triangles that are raycast directly, without a [`Mesh`] asset. See the [module docs](self).
+///
+/// Every three indices form a triangle, or every three positions if there are no indices.
+/// Geometry with out of bounds indices or incomplete triangles is never hit.
+#[derive(Component, Clone, Debug, Default)]
+pub struct DynamicRaycastMesh {
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    indices: Option<Vec<u32>>,
+    /// The bounds of the positions, or `None` if the geometry is empty or invalid.
+    aabb: Option<Aabb>,
+}
+
+impl DynamicRaycastMesh {
+    /// Creates a raycast mesh from model-space vertex `positions`, and optional triangle `indices`.
+    pub fn new(positions: Vec<[f32; 3]>, indices: Option<Vec<u32>>) -> Self {
+        let mut mesh = Self::default();
+        mesh.set_geometry(positions, indices);
+        mesh
+    }
+
+    /// Copies the triangles of a [`Mesh`]. Returns `None` if the mesh is not supported by
+    /// [`ray_intersection_over_mesh`].
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        validate_raycast_mesh(mesh).ok()?;
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+        let indices = mesh.indices().map(|indices| match indices {
+            Indices::U16(indices) => indices.iter().map(|i| *i as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        });
+        let dynamic_mesh = Self::new(positions.clone(), indices);
+        Some(match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => {
+                dynamic_mesh.with_normals(normals.clone())
+            }
+            _ => dynamic_mesh,
+        })
+    }
+
+    /// Adds vertex normals, which are interpolated to find the normal of hits. The normals are
+    /// ignored unless there is one for each position.
+    pub fn with_normals(mut self, normals: Vec<[f32; 3]>) -> Self {
+        self.set_normals(Some(normals));
+        self
+    }
+
+    /// Replaces the geometry, and updates its bounds. Any vertex normals are removed.
+    pub fn set_geometry(&mut self, positions: Vec<[f32; 3]>, indices: Option<Vec<u32>>) {
+        let complete = match &indices {
+            Some(indices) => {
+                indices.len() % 3 == 0 && indices.iter().all(|i| (*i as usize) < positions.len())
+            }
+            None => positions.len() % 3 == 0,
+        };
+        self.aabb = complete
+            .then(|| Aabb::enclosing(positions.iter().map(|p| Vec3::from(*p))))
+            .flatten();
+        self.positions = positions;
+        self.normals = None;
+        self.indices = indices;
+    }
+
+    /// Replaces the vertex normals. See [`DynamicRaycastMesh::with_normals`].
+    pub fn set_normals(&mut self, normals: Option<Vec<[f32; 3]>>) {
+        self.normals = normals.filter(|normals| normals.len() == self.positions.len());
+    }
+
+    /// The model-space vertex positions.
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    /// The vertex normals, if there are any.
+    pub fn normals(&self) -> Option<&[[f32; 3]]> {
+        self.normals.as_deref()
+    }
+
+    /// The triangle indices, if the geometry is indexed.
+    pub fn indices(&self) -> Option<&[u32]> {
+        self.indices.as_deref()
+    }
+
+    /// The model-space bounds of the geometry, or `None` if it is empty or invalid.
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.aabb
+    }
+
+    /// The number of triangles in the geometry.
+    pub fn triangle_count(&self) -> usize {
+        match &self.indices {
+            Some(indices) => indices.len() / 3,
+            None => self.positions.len() / 3,
+        }
+    }
+
+    /// Checks if the ray intersects the geometry, transformed into world space by
+    /// `mesh_transform`, and returns the nearest intersection if one exists.
+    pub fn ray_intersection(
+        &self,
+        mesh_transform: &Mat4,
+        ray: Ray3d,
+        backface_culling: Backfaces,
+    ) -> Option<IntersectionData> {
+        self.aabb?;
+        ray_mesh_intersection(
+            mesh_transform,
+            &self.positions,
+            self.normals.as_deref(),
+            ray,
+            self.indices.as_ref(),
+            backface_culling,
+        )
+    }
+
+    /// Like [`DynamicRaycastMesh::ray_intersection`], but returns every intersection, sorted
+    /// nearest first.
+    pub fn ray_intersections(
+        &self,
+        mesh_transform: &Mat4,
+        ray: Ray3d,
+        backface_culling: Backfaces,
+    ) -> Vec<IntersectionData> {
+        if self.aabb.is_none() {
+            return Vec::new();
+        }
+        ray_mesh_intersections(
+            mesh_transform,
+            &self.positions,
+            self.normals.as_deref(),
+            ray,
+            self.indices.as_ref(),
+            backface_culling,
+        )
+    }
+}
+
+/// The AABB an entity is culled with, which is the bounds of its [`DynamicRaycastMesh`] if it has
+/// one, since its [`Aabb`] may be out of date.
+pub(crate) fn culling_aabb(
+    (aabb, dynamic_mesh): (Option<&Aabb>, Option<&DynamicRaycastMesh>),
+) -> Option<Aabb> {
+    match dynamic_mesh {
+        Some(dynamic_mesh) => dynamic_mesh.aabb(),
+        None => aabb.copied(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+
+    #[test]
+    fn raycasts_without_mesh_asset() {
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        let quad = DynamicRaycastMesh::new(
+            vec![
+                [-1.0, -1.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 1.0, 0.0],
+            ],
+            Some(vec![0, 1, 2, 0, 2, 3]),
+        );
+        let entity = world
+            .spawn((
+                quad,
+                GlobalTransform::from_xyz(0.0, 0.0, -2.0),
+                InheritedVisibility::VISIBLE,
+                ViewVisibility::default(),
+            ))
+            .id();
+
+        let cast = |world: &mut World| {
+            world.run_system_once(|mut raycast: Raycast| {
+                let ray = Ray3d::new(Vec3::new(0.5, 0.5, 0.0), Vec3::NEG_Z);
+                let settings =
+                    RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, hit)| (*entity, hit.distance(), hit.source_mesh()))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(cast(&mut world), vec![(entity, 2.0, None)]);
+
+        // Shrinking the geometry updates its bounds, so the ray now misses.
+        world
+            .get_mut::<DynamicRaycastMesh>(entity)
+            .unwrap()
+            .set_geometry(
+                vec![[-0.1, -0.1, 0.0], [0.1, -0.1, 0.0], [0.0, 0.1, 0.0]],
+                None,
+            );
+        assert_eq!(cast(&mut world), vec![]);
+    }
+}
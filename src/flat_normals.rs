@@ -0,0 +1,130 @@
+//! # Flat Normals
+//!
+//! Hits on meshes without vertex normals report the normal of the hit triangle, which the raycast
+//! computes with a cross product for every hit. For meshes that are raycast often, the
+//! [`FlatNormalsPlugin`] precomputes these normals once per mesh asset with
+//! [`compute_flat_normals_for_raycast`], and the [`Raycast`] system param reads them from the
+//! [`FlatNormalCache`] instead.
+//!
+//! The cache is updated in [`First`] from the mesh asset events of the previous frame. Meshes
+//! marked in [`VolatileMeshes`] are not cached, since they change faster than the cache could be
+//! reused, and are cached once they become stable.
+
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, AssetId, Assets};
+use bevy_ecs::prelude::*;
+use bevy_render::mesh::Mesh;
+use bevy_utils::HashMap;
+
+use crate::{raycast::compute_flat_normals_for_raycast, volatility::VolatileMeshes};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Maintains the [`FlatNormalCache`] for meshes without vertex normals.
+#[derive(Default)]
+pub struct FlatNormalsPlugin;
+impl Plugin for FlatNormalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlatNormalCache>().add_systems(
+            First,
+            update_flat_normal_cache.after(crate::volatility::update_mesh_volatility),
+        );
+    }
+}
+
+/// Per-triangle normals of meshes without vertex normals, cached by mesh asset id. See the
+/// [module docs](self).
+#[derive(Resource, Default)]
+pub struct FlatNormalCache {
+    normals: HashMap<AssetId<Mesh>, Vec<[f32; 3]>>,
+}
+
+impl FlatNormalCache {
+    /// Get the cached normals of the triangles of the mesh.
+    pub fn get(&self, mesh: impl Into<AssetId<Mesh>>) -> Option<&[[f32; 3]]> {
+        self.normals.get(&mesh.into()).map(Vec::as_slice)
+    }
+
+    /// Compute and cache the normals of the triangles of the mesh, replacing any cached normals.
+    /// Meshes with vertex normals are not cached, since their hits interpolate those instead.
+    pub fn update(&mut self, mesh: impl Into<AssetId<Mesh>>, meshes: &Assets<Mesh>) {
+        let id = mesh.into();
+        match meshes.get(id) {
+            Some(mesh) if mesh.attribute(Mesh::ATTRIBUTE_NORMAL).is_none() => {
+                self.normals
+                    .insert(id, compute_flat_normals_for_raycast(mesh));
+            }
+            _ => self.invalidate(id),
+        }
+    }
+
+    /// Discard the cached normals of the mesh.
+    pub fn invalidate(&mut self, mesh: impl Into<AssetId<Mesh>>) {
+        self.normals.remove(&mesh.into());
+    }
+}
+
+/// Caches the normals of meshes that were added, modified, or became stable, and discards those of
+/// meshes that were removed or are volatile.
+pub fn update_flat_normal_cache(
+    mut cache: ResMut<FlatNormalCache>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    volatile_meshes: Option<Res<VolatileMeshes>>,
+) {
+    let is_volatile = |id: AssetId<Mesh>| {
+        volatile_meshes
+            .as_ref()
+            .is_some_and(|volatile| volatile.is_volatile(id))
+    };
+    if let Some(volatile_meshes) = volatile_meshes.as_ref() {
+        for id in volatile_meshes.iter_volatile() {
+            cache.invalidate(id);
+        }
+        for id in volatile_meshes.iter_stabilized() {
+            cache.update(id, &meshes);
+        }
+    }
+    for event in mesh_events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } if !is_volatile(*id) => {
+                cache.update(*id, &meshes);
+            }
+            AssetEvent::Removed { id } | AssetEvent::Unused { id } => {
+                cache.invalidate(*id);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn raycasts_use_cached_normals() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        let (id, mesh) = world.resource::<Assets<Mesh>>().iter().next().unwrap();
+        assert_eq!(
+            compute_flat_normals_for_raycast(mesh),
+            vec![[0.0, 0.0, 1.0]; 2]
+        );
+
+        // Cache normals that differ from the triangles, to tell them apart from computed ones.
+        let mut cache = FlatNormalCache::default();
+        cache.normals.insert(id, vec![[0.0, 1.0, 0.0]; 2]);
+        world.insert_resource(cache);
+        let normal = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast.cast_ray(ray, &settings)[0].1.normal()
+        });
+        assert_eq!(normal, Vec3::Y);
+    }
+}
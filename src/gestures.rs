@@ -0,0 +1,343 @@
+//! # Cursor Gestures
+//!
+//! Classifies mouse button presses over raycastable entities into clicks, double-clicks, and drags.
+//!
+//! Add the [`CursorGesturePlugin`] alongside the [`CursorRayPlugin`], then read [`CursorGesture`]
+//! events in your systems. Each frame the entity under the [`CursorRay`] is found with the
+//! [`Raycast`] system param, and button presses are tracked against it, using the cursor position
+//! of the [`CursorRayWindow`] the ray was built from. A press that moves further
+//! than [`CursorGestureSettings::drag_threshold`] pixels becomes a drag, otherwise releasing the
+//! button over the same entity emits a click. Releasing the button ends a drag even when the cursor
+//! has left the window.
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_input::{mouse::MouseButton, ButtonInput, InputSystem};
+use bevy_math::Vec2;
+use bevy_time::Time;
+use bevy_utils::Duration;
+use bevy_window::Window;
+
+use crate::prelude::*;
+
+/// Emits [`CursorGesture`] events for entities under the [`CursorRay`].
+///
+/// Requires the [`CursorRayPlugin`].
+#[derive(Default)]
+pub struct CursorGesturePlugin;
+impl Plugin for CursorGesturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorGestureSettings>()
+            .init_resource::<CursorGestureState>()
+            .add_event::<CursorGesture>()
+            .add_systems(PreUpdate, update_cursor_gestures.after(InputSystem));
+    }
+}
+
+/// Configures how the [`CursorGesturePlugin`] classifies presses.
+#[derive(Resource, Clone, Debug)]
+pub struct CursorGestureSettings {
+    /// The mouse button that is tracked.
+    pub button: MouseButton,
+    /// How far, in logical pixels, the cursor must move while pressed before the press becomes a
+    /// drag.
+    pub drag_threshold: f32,
+    /// The maximum time between two clicks on the same entity for them to count as a double-click.
+    pub double_click_time: Duration,
+    /// The visibility setting used for the raycast that finds the entity under the cursor.
+    pub visibility: RaycastVisibility,
+}
+
+impl Default for CursorGestureSettings {
+    fn default() -> Self {
+        Self {
+            button: MouseButton::Left,
+            drag_threshold: 4.0,
+            double_click_time: Duration::from_millis(300),
+            visibility: RaycastVisibility::MustBeVisibleAndInView,
+        }
+    }
+}
+
+/// A gesture performed with the cursor on a raycastable entity.
+#[derive(Event, Clone, Debug)]
+pub enum CursorGesture {
+    /// The button was pressed and released over the same entity without exceeding the drag
+    /// threshold.
+    Click {
+        entity: Entity,
+        hit: IntersectionData,
+    },
+    /// A second click on the same entity within [`CursorGestureSettings::double_click_time`]. This
+    /// is sent after the [`CursorGesture::Click`] for the same release.
+    DoubleClick {
+        entity: Entity,
+        hit: IntersectionData,
+    },
+    /// The cursor moved past the drag threshold while the button was held after pressing on
+    /// `entity`.
+    DragStart { entity: Entity, position: Vec2 },
+    /// The cursor moved while dragging `entity`. `delta` is in logical pixels since the last event.
+    Drag {
+        entity: Entity,
+        position: Vec2,
+        delta: Vec2,
+    },
+    /// The button was released, ending the drag of `entity`. If the cursor left the window,
+    /// `position` is where it was last seen.
+    DragEnd { entity: Entity, position: Vec2 },
+}
+
+/// Tracks the in-progress press used to classify gestures.
+#[derive(Resource, Default, Debug)]
+pub struct CursorGestureState {
+    press: Option<Press>,
+    /// The last clicked entity, and the [`Time::elapsed`] when it was clicked.
+    last_click: Option<(Entity, Duration)>,
+}
+
+#[derive(Debug)]
+struct Press {
+    entity: Entity,
+    origin: Vec2,
+    last_position: Vec2,
+    dragging: bool,
+}
+
+impl CursorGestureState {
+    /// The entity currently being pressed or dragged, if any.
+    pub fn pressed_entity(&self) -> Option<Entity> {
+        self.press.as_ref().map(|press| press.entity)
+    }
+
+    /// Returns `true` if a drag is in progress.
+    pub fn is_dragging(&self) -> bool {
+        self.press.as_ref().is_some_and(|press| press.dragging)
+    }
+}
+
+/// Classifies button presses over the entity under the [`CursorRay`] into [`CursorGesture`]s.
+pub fn update_cursor_gestures(
+    settings: Res<CursorGestureSettings>,
+    mut state: ResMut<CursorGestureState>,
+    (mouse, time): (Res<ButtonInput<MouseButton>>, Res<Time>),
+    (cursor_ray, cursor_ray_window): (Res<CursorRay>, Res<CursorRayWindow>),
+    windows: Query<&Window>,
+    mut raycast: Raycast,
+    mut gestures: EventWriter<CursorGesture>,
+) {
+    let position = cursor_ray_window
+        .and_then(|window| windows.get(window).ok())
+        .and_then(Window::cursor_position);
+
+    let raycast_settings = RaycastSettings::default().with_visibility(settings.visibility);
+    let mut hovered = || {
+        position?;
+        cursor_ray.and_then(|ray| raycast.cast_ray(ray, &raycast_settings).first().cloned())
+    };
+
+    if mouse.just_pressed(settings.button) {
+        state.press = position
+            .zip(hovered())
+            .map(|(position, (entity, _))| Press {
+                entity,
+                origin: position,
+                last_position: position,
+                dragging: false,
+            });
+    }
+
+    let Some(press) = state.press.as_mut() else {
+        return;
+    };
+    if let Some(position) = position {
+        if !press.dragging && position.distance(press.origin) > settings.drag_threshold {
+            press.dragging = true;
+            gestures.send(CursorGesture::DragStart {
+                entity: press.entity,
+                position: press.origin,
+            });
+        }
+        if press.dragging && position != press.last_position {
+            gestures.send(CursorGesture::Drag {
+                entity: press.entity,
+                position,
+                delta: position - press.last_position,
+            });
+        }
+        press.last_position = position;
+    }
+
+    // A release may be missed while the window is unfocused, so any press without the button held
+    // is ended.
+    if mouse.pressed(settings.button) {
+        return;
+    }
+    let Some(press) = state.press.take() else {
+        return;
+    };
+    if press.dragging {
+        gestures.send(CursorGesture::DragEnd {
+            entity: press.entity,
+            position: press.last_position,
+        });
+        return;
+    }
+    let Some((entity, hit)) = hovered().filter(|(entity, _)| *entity == press.entity) else {
+        return;
+    };
+    let now = time.elapsed();
+    let is_double_click = state.last_click.is_some_and(|(last_entity, time)| {
+        last_entity == entity && now - time <= settings.double_click_time
+    });
+    gestures.send(CursorGesture::Click { entity, hit });
+    if is_double_click {
+        gestures.send(CursorGesture::DoubleClick { entity, hit });
+        state.last_click = None;
+    } else {
+        state.last_click = Some((entity, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, window::PrimaryWindow};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    fn gesture_world() -> World {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        world.insert_resource(CursorGestureSettings {
+            visibility: RaycastVisibility::Ignore,
+            ..default()
+        });
+        world.init_resource::<CursorGestureState>();
+        world.init_resource::<Events<CursorGesture>>();
+        world.init_resource::<ButtonInput<MouseButton>>();
+        world.init_resource::<Time>();
+        world.insert_resource(CursorRay(Some(Ray3d::new(Vec3::ZERO, Vec3::NEG_Z))));
+        let window = world.spawn((Window::default(), PrimaryWindow)).id();
+        world.insert_resource(CursorRayWindow(Some(window)));
+        world
+    }
+
+    /// Runs a frame `millis` after the last one, with the button `pressed` and the cursor at
+    /// `cursor`, and returns the gestures that were sent.
+    fn frame(
+        world: &mut World,
+        millis: u64,
+        pressed: bool,
+        cursor: Option<Vec2>,
+    ) -> Vec<CursorGesture> {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(millis));
+        let mut input = world.resource_mut::<ButtonInput<MouseButton>>();
+        input.clear();
+        match pressed {
+            true => input.press(MouseButton::Left),
+            false => input.release(MouseButton::Left),
+        }
+        let mut windows = world.query_filtered::<&mut Window, With<PrimaryWindow>>();
+        windows.single_mut(world).set_cursor_position(cursor);
+        world.run_system_once(update_cursor_gestures);
+        world
+            .resource_mut::<Events<CursorGesture>>()
+            .drain()
+            .collect()
+    }
+
+    fn click(world: &mut World, millis: u64) -> Vec<CursorGesture> {
+        let position = Some(Vec2::new(10.0, 10.0));
+        let mut gestures = frame(world, millis, true, position);
+        gestures.extend(frame(world, 16, false, position));
+        gestures
+    }
+
+    #[test]
+    fn presses_released_in_place_are_clicks() {
+        let mut world = gesture_world();
+        let gestures = click(&mut world, 16);
+        assert!(matches!(gestures[..], [CursorGesture::Click { .. }]));
+
+        // Releasing away from the entity is not a click.
+        world.insert_resource(CursorRay(Some(Ray3d::new(Vec3::X * 5.0, Vec3::NEG_Z))));
+        let position = Some(Vec2::new(10.0, 10.0));
+        frame(&mut world, 1000, true, position);
+        world.insert_resource(CursorRay(None));
+        assert!(frame(&mut world, 16, false, position).is_empty());
+    }
+
+    #[test]
+    fn quick_second_clicks_are_double_clicks() {
+        let mut world = gesture_world();
+        click(&mut world, 16);
+        let gestures = click(&mut world, 100);
+        assert!(matches!(
+            gestures[..],
+            [
+                CursorGesture::Click { .. },
+                CursorGesture::DoubleClick { .. }
+            ]
+        ));
+
+        // A double-click starts over, and slow clicks are not double-clicks.
+        let gestures = click(&mut world, 100);
+        assert!(matches!(gestures[..], [CursorGesture::Click { .. }]));
+        let gestures = click(&mut world, 500);
+        assert!(matches!(gestures[..], [CursorGesture::Click { .. }]));
+    }
+
+    #[test]
+    fn drags_end_when_released_outside_the_window() {
+        let mut world = gesture_world();
+        frame(&mut world, 16, true, Some(Vec2::new(10.0, 10.0)));
+        let gestures = frame(&mut world, 16, true, Some(Vec2::new(30.0, 10.0)));
+        assert!(matches!(
+            gestures[..],
+            [
+                CursorGesture::DragStart { .. },
+                CursorGesture::Drag { delta, .. },
+            ] if delta == Vec2::new(20.0, 0.0)
+        ));
+        assert!(world.resource::<CursorGestureState>().is_dragging());
+
+        assert!(frame(&mut world, 16, true, None).is_empty());
+        let gestures = frame(&mut world, 16, false, None);
+        assert!(matches!(
+            gestures[..],
+            [CursorGesture::DragEnd { position, .. }] if position == Vec2::new(30.0, 10.0)
+        ));
+        assert_eq!(
+            world.resource::<CursorGestureState>().pressed_entity(),
+            None
+        );
+    }
+
+    #[test]
+    fn cursor_is_read_from_the_window_of_the_cursor_ray() {
+        let mut world = gesture_world();
+        let secondary = world.spawn(Window::default()).id();
+        world.insert_resource(CursorRayWindow(Some(secondary)));
+        let press = |world: &mut World, millis: u64, pressed: bool, cursor: Vec2| {
+            let mut window = world.get_mut::<Window>(secondary).unwrap();
+            window.set_cursor_position(Some(cursor));
+            // The primary window keeps a cursor that never moves.
+            frame(world, millis, pressed, Some(Vec2::new(10.0, 10.0)))
+        };
+
+        press(&mut world, 16, true, Vec2::new(50.0, 50.0));
+        let gestures = press(&mut world, 16, true, Vec2::new(70.0, 50.0));
+        assert!(matches!(
+            gestures[..],
+            [
+                CursorGesture::DragStart { position, .. },
+                CursorGesture::Drag { delta, .. },
+            ] if position == Vec2::new(50.0, 50.0) && delta == Vec2::new(20.0, 0.0)
+        ));
+        let gestures = press(&mut world, 16, false, Vec2::new(70.0, 50.0));
+        assert!(matches!(gestures[..], [CursorGesture::DragEnd { .. }]));
+    }
+}
@@ -0,0 +1,135 @@
+//! # Hierarchies
+//!
+//! Characters and props are often built from several mesh entities under one root, such as a
+//! soldier holding a gun. Depending on the use case, a click on any part should select the whole
+//! hierarchy, or the part that was clicked.
+//!
+//! [`Raycast::cast_ray_on_hierarchy`] casts a ray against the root entity and all of its
+//! descendants, and returns a [`HierarchyHit`] with both the descendant that was hit first and the
+//! bounds of the hierarchy as a whole. Descendants without meshes, such as attachment points, are
+//! skipped.
+
+use bevy_ecs::prelude::*;
+use bevy_hierarchy::{Children, HierarchyQueryExt};
+use bevy_math::{Ray3d, Vec3A};
+use bevy_render::primitives::Aabb;
+
+use crate::{
+    dynamic_mesh::culling_aabb,
+    immediate::{model_matrix, world_aabb},
+    prelude::*,
+};
+
+/// The nearest hit on a hierarchy of entities, found with [`Raycast::cast_ray_on_hierarchy`].
+#[derive(Clone, Copy, Debug)]
+pub struct HierarchyHit {
+    /// The root entity of the hierarchy.
+    pub root: Entity,
+    /// The entity in the hierarchy that was hit first, which may be the root.
+    pub entity: Entity,
+    /// The intersection with the hit entity.
+    pub intersection: IntersectionData,
+    /// The world-space AABB enclosing the AABBs of every raycastable entity in the hierarchy.
+    pub bounds: Aabb,
+    /// The `[near, far]` distances along the ray at which it enters and exits the
+    /// [`bounds`](Self::bounds).
+    pub bounds_distances: [f32; 2],
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Casts the `ray` against `root` and all of its descendants, and returns the nearest hit, or
+    /// `None` if no entity in the hierarchy was hit. See the [module docs](self).
+    ///
+    /// The `children` query is passed in, so that the [`Raycast`] system param does not need
+    /// access to the hierarchy in systems that never use it:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// fn raycast_system(mut raycast: Raycast, children: Query<&Children>) {
+    ///     # let soldier = Entity::PLACEHOLDER;
+    ///     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+    ///     if let Some(hit) = raycast.cast_ray_on_hierarchy(ray, soldier, &children, &default()) {
+    ///         info!("clicked {:?} of soldier {:?}", hit.entity, hit.root);
+    ///     }
+    /// }
+    /// ```
+    pub fn cast_ray_on_hierarchy(
+        &mut self,
+        ray: Ray3d,
+        root: Entity,
+        children: &Query<&Children>,
+        settings: &RaycastSettings,
+    ) -> Option<HierarchyHit> {
+        let hierarchy: Vec<Entity> = std::iter::once(root)
+            .chain(children.iter_descendants(root))
+            .filter(|entity| self.culling_query.contains(*entity))
+            .collect();
+        let (entity, intersection) = self
+            .cast_ray_on_candidates(ray, settings, hierarchy.iter().copied())
+            .first()
+            .copied()?;
+        let bounds = self.hierarchy_bounds(&hierarchy, settings)?;
+        let bounds_distances = SlabRay::new(ray).intersects_aabb(&bounds)?;
+        Some(HierarchyHit {
+            root,
+            entity,
+            intersection,
+            bounds,
+            bounds_distances,
+        })
+    }
+
+    /// The world-space AABB enclosing the AABBs of the `entities`.
+    fn hierarchy_bounds(&self, entities: &[Entity], settings: &RaycastSettings) -> Option<Aabb> {
+        let (min, max) = entities
+            .iter()
+            .filter_map(|entity| self.culling_query.get(*entity).ok())
+            .filter_map(|(_, _, bounds, transform, entity, ..)| {
+                let transform = model_matrix(
+                    entity,
+                    transform,
+                    &self.transform_history,
+                    settings.transform_blend,
+                );
+                Some(world_aabb(&culling_aabb(bounds)?, &transform))
+            })
+            .fold(None, |bounds: Option<(Vec3A, Vec3A)>, aabb| {
+                Some(match bounds {
+                    Some((min, max)) => (min.min(aabb.min()), max.max(aabb.max())),
+                    None => (aabb.min(), aabb.max()),
+                })
+            })?;
+        Some(Aabb::from_min_max(min.into(), max.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn hierarchy_hit_reports_descendant_and_bounds() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0, -5.0]);
+        let attachment = world.spawn_empty().id();
+        let root = world.spawn_empty().id();
+        world
+            .entity_mut(root)
+            .push_children(&[attachment, planes[1], planes[2]]);
+
+        let hit = world.run_system_once(move |mut raycast: Raycast, children: Query<&Children>| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast.cast_ray_on_hierarchy(ray, root, &children, &settings)
+        });
+        let hit = hit.unwrap();
+        // The plane outside the hierarchy is in front, but is not part of it.
+        assert_eq!((hit.root, hit.entity), (root, planes[1]));
+        assert_eq!(hit.intersection.distance(), 3.0);
+        assert_eq!(hit.bounds_distances, [3.0, 5.0]);
+    }
+}
@@ -0,0 +1,145 @@
+//! # Flat Hit Buffers
+//!
+//! Hosts that embed Bevy, such as editors with a web front-end or native tools talking through an
+//! FFI layer, can't easily walk the generic [`RaycastSource`] components to read raycast results.
+//!
+//! Enable [`RaycastPluginState::with_hit_buffer`], and each frame the hits of every
+//! [`RaycastSource<T>`] are copied into the [`HitBuffer<T>`] resource as a contiguous array of
+//! [`FlatHit`]s. The layout of a [`FlatHit`] is `#[repr(C)]` and has no padding, so the buffer can
+//! be read directly from wasm memory or through a pointer with [`HitBuffer::as_ptr`].
+
+use std::marker::PhantomData;
+
+use bevy_ecs::prelude::*;
+use bevy_reflect::TypePath;
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// A raycast hit with a fixed, C-compatible layout of 48 bytes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FlatHit {
+    /// The [`Entity::to_bits`] of the [`RaycastSource`] entity.
+    pub source: u64,
+    /// The [`Entity::to_bits`] of the hit entity.
+    pub entity: u64,
+    /// The world space position of the hit.
+    pub position: [f32; 3],
+    /// The world space normal of the hit.
+    pub normal: [f32; 3],
+    /// The distance from the ray origin to the hit.
+    pub distance: f32,
+    /// The index of this hit among the hits of its source, where `0` is the nearest.
+    pub index: u32,
+}
+
+impl FlatHit {
+    /// Flattens the hit of `source` on `entity`, which is the `index`-th nearest hit of the source.
+    pub fn new(source: Entity, entity: Entity, hit: &IntersectionData, index: u32) -> Self {
+        Self {
+            source: source.to_bits(),
+            entity: entity.to_bits(),
+            position: hit.position().to_array(),
+            normal: hit.normal().to_array(),
+            distance: hit.distance(),
+            index,
+        }
+    }
+}
+
+/// The hits of all [`RaycastSource<T>`] entities during the last raycast, updated when
+/// [`RaycastPluginState::with_hit_buffer`] is enabled. See the [module docs](self).
+///
+/// The hits of each source are contiguous and sorted nearest first, but the order of the sources
+/// is unspecified.
+#[derive(Resource)]
+pub struct HitBuffer<T> {
+    hits: Vec<FlatHit>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for HitBuffer<T> {
+    fn default() -> Self {
+        Self {
+            hits: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> HitBuffer<T> {
+    /// The hits in the buffer.
+    pub fn hits(&self) -> &[FlatHit] {
+        &self.hits
+    }
+
+    /// A pointer to the first hit in the buffer. The pointer is valid for [`HitBuffer::len`] hits
+    /// until the buffer is next updated.
+    pub fn as_ptr(&self) -> *const FlatHit {
+        self.hits.as_ptr()
+    }
+
+    /// The number of hits in the buffer.
+    pub fn len(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// Returns `true` if there were no hits.
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+}
+
+/// Copies the hits of every [`RaycastSource<T>`] into the [`HitBuffer<T>`].
+pub fn update_hit_buffer<T: TypePath + Send + Sync>(
+    sources: Query<(Entity, &RaycastSource<T>)>,
+    mut buffer: ResMut<HitBuffer<T>>,
+) {
+    buffer.hits.clear();
+    for (source_entity, source) in &sources {
+        buffer.hits.extend(
+            (0..)
+                .zip(source.intersections())
+                .map(|(i, (entity, hit))| FlatHit::new(source_entity, *entity, hit, i)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+
+    #[derive(TypePath)]
+    struct Picking;
+
+    #[test]
+    fn flattens_source_hits() {
+        assert_eq!(std::mem::size_of::<FlatHit>(), 48);
+
+        let mut world = World::new();
+        world.init_resource::<HitBuffer<Picking>>();
+        let [near, far] = [(); 2].map(|_| world.spawn_empty().id());
+        let hit = |z: f32| IntersectionData::new(Vec3::Z * z, Vec3::Z, Vec3::ZERO, -z, None, None);
+        let mut source = RaycastSource::<Picking>::new();
+        *source.intersections_mut() = vec![(near, hit(-1.0)), (far, hit(-3.0))];
+        let source = world.spawn(source).id();
+        world.run_system_once(update_hit_buffer::<Picking>);
+
+        let buffer = world.resource::<HitBuffer<Picking>>();
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(
+            buffer.hits()[1],
+            FlatHit {
+                source: source.to_bits(),
+                entity: far.to_bits(),
+                position: [0.0, 0.0, -3.0],
+                normal: [0.0, 0.0, 1.0],
+                distance: 3.0,
+                index: 1,
+            }
+        );
+    }
+}
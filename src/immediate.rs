@@ -7,24 +7,46 @@
 //! when you call the `cast_ray` method. See the [`Raycast`] documentation for more details. You
 //! don't even need to add a plugin to your application.
 
-use bevy_asset::{Assets, Handle};
-use bevy_ecs::{prelude::*, system::lifetimeless::Read, system::SystemParam};
-use bevy_math::{FloatOrd, Ray3d};
+use std::{ops::Range, sync::Arc};
+
+use bevy_asset::{AssetId, Assets, Handle};
+use bevy_ecs::{
+    prelude::*,
+    query::{QueryData, QueryFilter, ROQueryItem},
+    system::lifetimeless::Read,
+    system::SystemParam,
+};
+use bevy_math::{Dir3, FloatOrd, Mat4, Ray3d, Vec3, Vec3A};
 use bevy_reflect::Reflect;
-use bevy_render::{prelude::*, primitives::Aabb};
+#[cfg(feature = "serialize")]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+use bevy_render::{
+    prelude::*,
+    primitives::{Aabb, Frustum},
+    view::VisibilityRange,
+};
+use bevy_tasks::{ComputeTaskPool, TaskPool};
 use bevy_transform::components::GlobalTransform;
-use bevy_utils::tracing::*;
+use bevy_utils::{tracing::*, Parallel};
 
 #[cfg(feature = "debug")]
-use {
-    bevy_gizmos::gizmos::Gizmos,
-    bevy_math::{Quat, Vec3},
-};
+use {bevy_gizmos::gizmos::Gizmos, bevy_math::Quat};
 
-use crate::prelude::*;
+use crate::{
+    dynamic_mesh::culling_aabb,
+    prelude::*,
+    raycast::{
+        ray_intersection_over_mesh_with_caches, ray_intersections_over_mesh_with_caches, MeshCaches,
+    },
+};
 
 /// How a raycast should handle visibility
-#[derive(Clone, Copy, Reflect)]
+#[derive(Clone, Copy, Debug, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
 pub enum RaycastVisibility {
     /// Completely ignore visibility checks. Hidden items can still be raycasted against.
     Ignore,
@@ -35,17 +57,351 @@ pub enum RaycastVisibility {
     MustBeVisibleAndInView,
 }
 
-/// Settings for a raycast.
-#[derive(Clone)]
-pub struct RaycastSettings<'a> {
-    /// Determines how raycasting should consider entity visibility.
-    pub visibility: RaycastVisibility,
-    /// A filtering function that is applied to every entity that is raycasted. Only entities that
-    /// return `true` will be considered.
-    pub filter: &'a dyn Fn(Entity) -> bool,
-    /// A function that is run every time a hit is found. Raycasting will continue to check for hits
-    /// along the ray as long as this returns false.
-    pub early_exit_test: &'a dyn Fn(Entity) -> bool,
+/// How [`Raycast::entities_containing_point`] decides if an entity contains a point.
+#[derive(Clone, Copy, Debug, Default, Reflect)]
+pub enum PointContainment {
+    /// The point is inside the entity's AABB.
+    #[default]
+    Aabb,
+    /// The point is inside the entity's AABB and enclosed by its mesh.
+    Mesh,
+}
+
+/// An entity whose AABB is hit by a raycast, and where it stands among the other candidates,
+/// given to [`RaycastSettings::candidate_filter`] and
+/// [`RaycastSettings::candidate_early_exit_test`].
+///
+/// Candidates are listed by where the ray enters their AABB, nearest first, which is the order they
+/// are raycast in. A [`RaycastSettings::warm_start`] entity is raycast first, but keeps its place
+/// in the list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RaycastCandidate {
+    /// The candidate entity.
+    pub entity: Entity,
+    /// The position of the candidate in the list.
+    pub index: usize,
+    /// The number of candidates in the list.
+    pub count: usize,
+    /// The distances along the ray where it enters and exits the AABB of the candidate. The entry
+    /// is negative when the ray starts inside the AABB.
+    pub aabb_distances: [f32; 2],
+}
+
+/// A [`RaycastSettings::candidate_filter`] or [`RaycastSettings::candidate_early_exit_test`].
+pub type RaycastCandidateTest<'a> = &'a dyn Fn(&RaycastCandidate) -> bool;
+
+/// A candidate test owned by an [`OwnedRaycastSettings`].
+pub type OwnedRaycastCandidateTest = Arc<dyn Fn(&RaycastCandidate) -> bool + Send + Sync>;
+
+/// The distance used to sort hits, nearest first.
+///
+/// The two only differ for hits behind the ray origin. The AABB culling of a raycast is ordered by
+/// distance along the ray, which never exceeds either key, so both are safe to use with early exit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum HitSorting {
+    /// Sort by signed distance along the ray direction. Hits behind the origin are negative, and
+    /// sort before hits in front of it.
+    #[default]
+    AlongRay,
+    /// Sort by the euclidean distance between the ray origin and the hit, regardless of which side
+    /// of the origin the hit is on.
+    FromOrigin,
+}
+
+impl HitSorting {
+    /// The sort key of a hit at `position` along the `ray`.
+    pub fn key(&self, ray: Ray3d, position: Vec3) -> f32 {
+        match self {
+            HitSorting::AlongRay => (position - ray.origin).dot(*ray.direction),
+            HitSorting::FromOrigin => position.distance(ray.origin),
+        }
+    }
+}
+
+/// A raycast filter or early exit test owned by an [`OwnedRaycastSettings`].
+pub type OwnedRaycastTest = Arc<dyn Fn(Entity) -> bool + Send + Sync>;
+
+/// Defines [`RaycastSettings`] and [`OwnedRaycastSettings`] from a single list of settings, so a
+/// setting is added in one place: its field, default, and builder in both structs, and the copy
+/// made by [`OwnedRaycastSettings::as_settings`].
+///
+/// The `closures` differ in type between the two structs, and are borrowed from the owned settings
+/// with the given function. Their builders are written by hand. Every other setting is `Copy`, and
+/// is declared with its builder, which takes `$arg` and stores `$value`.
+macro_rules! raycast_settings {
+    (
+        $(#[doc = $doc:literal])*
+        pub struct $name:ident<$lt:lifetime>;
+        $(#[doc = $owned_doc:literal])*
+        pub struct $owned_name:ident;
+        closures {$(
+            $(#[doc = $closure_doc:literal])*
+            $closure:ident: $closure_ty:ty = $closure_default:expr,
+                $owned_closure_ty:ty = $owned_closure_default:expr => $borrow:path;
+        )*}
+        settings {$(
+            $(#[doc = $setting_doc:literal])*
+            $setting:ident: $setting_ty:ty = $setting_default:expr;
+            $(#[doc = $with_doc:literal])*
+            fn $with:ident($arg:ident: $arg_ty:ty) => $value:expr;
+        )*}
+    ) => {
+        $(#[doc = $doc])*
+        #[derive(Clone)]
+        pub struct $name<$lt> {
+            $(
+                $(#[doc = $closure_doc])*
+                pub $closure: $closure_ty,
+            )*
+            $(
+                $(#[doc = $setting_doc])*
+                pub $setting: $setting_ty,
+            )*
+        }
+
+        impl<$lt> $name<$lt> {
+            $(
+                $(#[doc = $with_doc])*
+                pub fn $with(mut self, $arg: $arg_ty) -> Self {
+                    self.$setting = $value;
+                    self
+                }
+            )*
+        }
+
+        impl<$lt> Default for $name<$lt> {
+            fn default() -> Self {
+                Self {
+                    $($closure: $closure_default,)*
+                    $($setting: $setting_default,)*
+                }
+            }
+        }
+
+        $(#[doc = $owned_doc])*
+        #[derive(Clone)]
+        pub struct $owned_name {
+            $(
+                #[doc = concat!("See [`", stringify!($name), "::", stringify!($closure), "`].")]
+                pub $closure: $owned_closure_ty,
+            )*
+            $(
+                #[doc = concat!("See [`", stringify!($name), "::", stringify!($setting), "`].")]
+                pub $setting: $setting_ty,
+            )*
+        }
+
+        impl $owned_name {
+            $(
+                #[doc = concat!("See [`", stringify!($name), "::", stringify!($with), "`].")]
+                pub fn $with(mut self, $arg: $arg_ty) -> Self {
+                    self.$setting = $value;
+                    self
+                }
+            )*
+
+            #[doc = concat!("Borrow these settings as [`", stringify!($name), "`].")]
+            pub fn as_settings(&self) -> $name<'_> {
+                $name {
+                    $($closure: $borrow(&self.$closure),)*
+                    $($setting: self.$setting,)*
+                }
+            }
+        }
+
+        impl Default for $owned_name {
+            fn default() -> Self {
+                Self {
+                    $($closure: $owned_closure_default,)*
+                    $($setting: $setting_default,)*
+                }
+            }
+        }
+    };
+}
+
+fn borrow_test(test: &OwnedRaycastTest) -> &dyn Fn(Entity) -> bool {
+    &**test
+}
+
+fn borrow_candidate_test(
+    test: &Option<OwnedRaycastCandidateTest>,
+) -> Option<RaycastCandidateTest<'_>> {
+    test.as_deref().map(|test| test as RaycastCandidateTest)
+}
+
+raycast_settings! {
+    /// Settings for a raycast.
+    pub struct RaycastSettings<'a>;
+
+    /// Like [`RaycastSettings`], but owns its closures instead of borrowing them, so it can be built
+    /// ahead of time and stored in a [`Resource`] or [`Component`]. Use
+    /// [`OwnedRaycastSettings::as_settings`] to pass them to the raycasting methods:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// #[derive(Resource)]
+    /// struct LaserSettings(OwnedRaycastSettings);
+    ///
+    /// fn raycast_system(mut raycast: Raycast, settings: Res<LaserSettings>) {
+    ///     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+    ///     let hits = raycast.cast_ray(ray, &settings.0.as_settings());
+    /// }
+    /// ```
+    pub struct OwnedRaycastSettings;
+
+    closures {
+        /// A filtering function that is applied to every entity that is raycasted. Only entities that
+        /// return `true` will be considered.
+        filter: &'a dyn Fn(Entity) -> bool = &|_| true,
+            OwnedRaycastTest = Arc::new(|_| true) => borrow_test;
+        /// A function that is run every time a hit is found. Raycasting will continue to check for hits
+        /// along the ray as long as this returns false. Hits on entities with [`RaycastPierce`] never
+        /// stop the raycast.
+        early_exit_test: &'a dyn Fn(Entity) -> bool = &|_| true,
+            OwnedRaycastTest = Arc::new(|_| true) => borrow_test;
+        /// Like [`RaycastSettings::filter`], but given the [`RaycastCandidate`] context of the entity, so it
+        /// can also skip entities by their place among the candidates, such as every candidate after the
+        /// tenth whose AABB starts beyond some distance. It is applied to the entities that pass the
+        /// [`RaycastSettings::filter`], which are the candidates it counts.
+        candidate_filter: Option<RaycastCandidateTest<'a>> = None,
+            Option<OwnedRaycastCandidateTest> = None => borrow_candidate_test;
+        /// Like [`RaycastSettings::early_exit_test`], but given the [`RaycastCandidate`] context of the hit
+        /// entity. A hit only stops the raycast if both tests return `true`. The candidates it counts are
+        /// those that pass both filters.
+        candidate_early_exit_test: Option<RaycastCandidateTest<'a>> = None,
+            Option<OwnedRaycastCandidateTest> = None => borrow_candidate_test;
+    }
+
+    settings {
+        /// Determines how raycasting should consider entity visibility.
+        visibility: RaycastVisibility = RaycastVisibility::MustBeVisibleAndInView;
+        /// Set the [`RaycastVisibility`] setting to apply to the raycast.
+        fn with_visibility(visibility: RaycastVisibility) => visibility;
+
+        /// When set, entities with a [`TransformHistory`] are raycast against their transform blended
+        /// between the previous and current fixed timestep by this factor, instead of their current
+        /// [`GlobalTransform`]. See the [`interpolation`](crate::interpolation) module.
+        transform_blend: Option<f32> = None;
+        /// Raycast against transforms blended between the previous and current fixed timestep; see
+        /// [`RaycastSettings::transform_blend`].
+        fn with_transform_blend(blend: f32) => Some(blend);
+
+        /// Meshes with more triangles than this are not tested triangle by triangle. Instead, the point
+        /// where the ray enters their AABB is reported as an
+        /// [approximate](IntersectionData::is_approximate) hit. This guards against stalls when a very
+        /// dense mesh is raycast by accident. A [`SimplifiedMesh`] is checked against the limit instead
+        /// of the mesh it simplifies, so adding one restores exact hits.
+        max_triangles_per_cast: Option<usize> = None;
+        /// Set the [`RaycastSettings::max_triangles_per_cast`] limit.
+        fn with_max_triangles_per_cast(max_triangles: usize) => Some(max_triangles);
+
+        /// Hits further than this distance, as measured by the [`RaycastSettings::sorting`], are
+        /// ignored, and entities whose AABB starts beyond it are culled.
+        max_distance: f32 = f32::INFINITY;
+        /// Set the [`RaycastSettings::max_distance`] of the raycast.
+        fn with_max_distance(max_distance: f32) => max_distance;
+
+        /// How hits are sorted and compared against [`RaycastSettings::max_distance`]. See
+        /// [`HitSorting`].
+        sorting: HitSorting = HitSorting::AlongRay;
+        /// Set how hits are sorted; see [`HitSorting`].
+        fn with_sorting(sorting: HitSorting) => sorting;
+
+        /// When `true`, the raycast becomes a line cast, also returning hits behind the ray origin with
+        /// negative distances. With [`HitSorting::AlongRay`] these hits sort first and are not limited by
+        /// [`RaycastSettings::max_distance`]; use [`HitSorting::FromOrigin`] to limit both sides.
+        include_behind_origin: bool = false;
+        /// Also return hits behind the ray origin; see [`RaycastSettings::include_behind_origin`].
+        fn with_include_behind_origin(include_behind_origin: bool) => include_behind_origin;
+
+        /// When `true`, every hit on an entity is returned, such as where the ray enters and exits a closed
+        /// mesh, instead of only the nearest one. This can be used to measure the thickness of a mesh, or to
+        /// find where the ray leaves a volume. All hits on the entity that blocks the ray are kept, even
+        /// those beyond the nearest blocking hit. Exit points are on backfaces, so they are only found on
+        /// entities with [`NoBackfaceCulling`].
+        all_hits_per_entity: bool = false;
+        /// Return every hit on each entity; see [`RaycastSettings::all_hits_per_entity`].
+        fn with_all_hits_per_entity(all_hits_per_entity: bool) => all_hits_per_entity;
+
+        /// When set, entities with a [`VisibilityRange`] are only raycast when their level of detail is the
+        /// one mostly shown to a camera at this position, so overlapping LOD meshes are not hit more than
+        /// once. Set this to the position of the camera the ray is cast from. Entities are tested against
+        /// the middle of the crossfade margins of their range, so a single LOD is hit when the ranges of
+        /// adjacent LODs share their margins.
+        lod_viewpoint: Option<Vec3> = None;
+        /// Only raycast the level of detail shown at this viewpoint; see
+        /// [`RaycastSettings::lod_viewpoint`].
+        fn with_lod_viewpoint(lod_viewpoint: Vec3) => Some(lod_viewpoint);
+
+        /// The maximum number of hits returned by a raycast. When more entities are hit, only the nearest
+        /// `max_hits` are kept, and the rest are discarded. `None`, the default, returns every hit.
+        ///
+        /// Hits beyond the nearest `max_hits` are discarded as entities are raycast, so this bounds the
+        /// memory used by raycasts through dense or pathological scenes, such as a ray grazing
+        /// thousands of overlapping entities with early exit disabled. At most twice `max_hits` hits are
+        /// kept at a time, along with the hits of the entity being raycast, or of every candidate with a
+        /// [`parallel_narrowphase_threshold`](RaycastSettings::parallel_narrowphase_threshold).
+        max_hits: Option<usize> = None;
+        /// Only keep the nearest `max_hits` hits; see [`RaycastSettings::max_hits`].
+        fn with_max_hits(max_hits: usize) => Some(max_hits);
+
+        /// When `true`, hits report the normal of the hit triangle instead of interpolating the vertex
+        /// normals of the mesh. Use this when imported meshes have broken normals, or use
+        /// [`GeometricNormals`] to do so for specific entities.
+        geometric_normals: bool = false;
+        /// Report the normal of the hit triangle; see [`RaycastSettings::geometric_normals`].
+        fn with_geometric_normals(geometric_normals: bool) => geometric_normals;
+
+        /// When set, this entity is raycast before any other, so its hit, if it has one, bounds the
+        /// search from the start. Entities whose AABB starts beyond that hit are skipped without testing
+        /// their meshes. Set this to the entity hit by the same ray last frame: when it is still hit, most
+        /// of the work of finding the nearest hit is skipped.
+        ///
+        /// The entity is always raycast again with its current transform and mesh, and every entity whose
+        /// AABB starts before its hit is still tested, so the results are the same as without a warm
+        /// start, even when entities moved. Entities are skipped, not stopped at, when their AABB starts
+        /// beyond the nearest hit, and candidate tests see each entity at its place in the list sorted
+        /// by AABB distance, so only the order in which entities are raycast, and callbacks are called,
+        /// differs.
+        warm_start: Option<Entity> = None;
+        /// Raycast `warm_start` before any other entity. See [`RaycastSettings::warm_start`].
+        fn with_warm_start(warm_start: Entity) => Some(warm_start);
+
+        /// When set, and the AABBs of at least this many entities are hit by the ray, the geometry of
+        /// these entities is raycast in parallel on the [`ComputeTaskPool`]. Rays that pass through many
+        /// large meshes finish sooner, at the cost of raycasting entities behind the nearest hit that a
+        /// serial raycast would skip. The results are the same either way.
+        ///
+        /// Parallel raycasts have some overhead, so leave this unset unless rays often hit dozens of
+        /// meshes.
+        parallel_narrowphase_threshold: Option<usize> = None;
+        /// Raycast the geometry of entities in parallel when the AABBs of at least this many entities
+        /// are hit. See [`RaycastSettings::parallel_narrowphase_threshold`].
+        fn with_parallel_narrowphase_threshold(parallel_narrowphase_threshold: usize) =>
+            Some(parallel_narrowphase_threshold);
+
+        /// Which optional data is computed for each hit. Skipping data that isn't needed makes
+        /// raycasts slightly cheaper. The world-space vertices of the hit triangle are skipped by
+        /// default, see [`Raycast::hit_triangle`].
+        hit_data: HitData = HitData::DEFAULT;
+        /// Choose which optional data is computed for each hit. See [`HitData`].
+        fn with_hit_data(hit_data: HitData) => hit_data;
+
+        /// How important this raycast is. [`RaycastPriorityClass::Cosmetic`] raycasts are skipped when the
+        /// [`RaycastBudget`] of the frame is spent. See the [`budget`](crate::budget) module.
+        priority_class: RaycastPriorityClass = RaycastPriorityClass::Normal;
+        /// Set the [`RaycastPriorityClass`] of the raycast.
+        fn with_priority_class(priority_class: RaycastPriorityClass) => priority_class;
+
+        /// Only entities on at least one of these layers are raycast. Entities without a [`RaycastLayers`]
+        /// component are on [`RaycastLayers::DEFAULT`]. See the [`layers`](crate::layers) module.
+        layers: RaycastLayers = RaycastLayers::ALL;
+        /// Set the `layers` field of the settings.
+        fn with_layers(layers: RaycastLayers) => layers;
+    }
 }
 
 impl<'a> RaycastSettings<'a> {
@@ -61,9 +417,21 @@ impl<'a> RaycastSettings<'a> {
         self
     }
 
-    /// Set the [`RaycastVisibility`] setting to apply to the raycast.
-    pub fn with_visibility(mut self, visibility: RaycastVisibility) -> Self {
-        self.visibility = visibility;
+    /// Set the [`RaycastSettings::candidate_filter`] to apply to the raycast.
+    pub fn with_candidate_filter(
+        mut self,
+        candidate_filter: &'a impl Fn(&RaycastCandidate) -> bool,
+    ) -> Self {
+        self.candidate_filter = Some(candidate_filter);
+        self
+    }
+
+    /// Set the [`RaycastSettings::candidate_early_exit_test`] to apply to the raycast.
+    pub fn with_candidate_early_exit_test(
+        mut self,
+        candidate_early_exit_test: &'a impl Fn(&RaycastCandidate) -> bool,
+    ) -> Self {
+        self.candidate_early_exit_test = Some(candidate_early_exit_test);
         self
     }
 
@@ -78,20 +446,133 @@ impl<'a> RaycastSettings<'a> {
     }
 }
 
-impl<'a> Default for RaycastSettings<'a> {
-    fn default() -> Self {
-        Self {
-            visibility: RaycastVisibility::MustBeVisibleAndInView,
-            filter: &|_| true,
-            early_exit_test: &|_| true,
-        }
+impl OwnedRaycastSettings {
+    /// Set the filter to apply to the raycast.
+    pub fn with_filter(mut self, filter: impl Fn(Entity) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Arc::new(filter);
+        self
+    }
+
+    /// Set the early exit test to apply to the raycast.
+    pub fn with_early_exit_test(
+        mut self,
+        early_exit_test: impl Fn(Entity) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.early_exit_test = Arc::new(early_exit_test);
+        self
+    }
+
+    /// See [`RaycastSettings::with_candidate_filter`].
+    pub fn with_candidate_filter(
+        mut self,
+        candidate_filter: impl Fn(&RaycastCandidate) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.candidate_filter = Some(Arc::new(candidate_filter));
+        self
+    }
+
+    /// See [`RaycastSettings::with_candidate_early_exit_test`].
+    pub fn with_candidate_early_exit_test(
+        mut self,
+        candidate_early_exit_test: impl Fn(&RaycastCandidate) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.candidate_early_exit_test = Some(Arc::new(candidate_early_exit_test));
+        self
+    }
+
+    /// This raycast should exit as soon as the nearest hit is found.
+    pub fn always_early_exit(self) -> Self {
+        self.with_early_exit_test(|_| true)
+    }
+
+    /// This raycast should check all entities whose AABB intersects the ray and return all hits.
+    pub fn never_early_exit(self) -> Self {
+        self.with_early_exit_test(|_| false)
     }
 }
 
+impl<'a> From<&'a OwnedRaycastSettings> for RaycastSettings<'a> {
+    fn from(settings: &'a OwnedRaycastSettings) -> Self {
+        settings.as_settings()
+    }
+}
+
+/// The component holding the mesh of a 3D mesh entity. Queries only name it through this alias and
+/// read its mesh with [`MeshHandleComponent`], so when the renderer changes how meshes are attached
+/// to entities, only this definition and the [`MeshHandleComponent`] implementation change.
+pub(crate) type Mesh3dComponent = Handle<Mesh>;
+/// The component holding the mesh of a 2D mesh entity, like [`Mesh3dComponent`].
+#[cfg(feature = "2d")]
+pub(crate) type Mesh2dComponent = bevy_sprite::Mesh2dHandle;
+
 #[cfg(feature = "2d")]
-type MeshFilter = Or<(With<Handle<Mesh>>, With<bevy_sprite::Mesh2dHandle>)>;
+pub(crate) type MeshFilter = Or<(
+    With<Mesh3dComponent>,
+    With<Mesh2dComponent>,
+    With<DynamicRaycastMesh>,
+    With<RaycastAabbTarget>,
+)>;
 #[cfg(not(feature = "2d"))]
-type MeshFilter = With<Handle<Mesh>>;
+pub(crate) type MeshFilter = Or<(
+    With<Mesh3dComponent>,
+    With<DynamicRaycastMesh>,
+    With<RaycastAabbTarget>,
+)>;
+
+/// The components used to cull an entity before testing its mesh.
+type CullingData = (
+    Read<InheritedVisibility>,
+    Read<ViewVisibility>,
+    AnyOf<(Read<Aabb>, Read<DynamicRaycastMesh>)>,
+    Read<GlobalTransform>,
+    Entity,
+    Option<Read<BoundingSphere>>,
+    Option<Read<VisibilityRange>>,
+    Has<RaycastAabbTarget>,
+);
+
+/// The 2D mesh of an entity, checked by [`Raycast::explain`].
+#[cfg(feature = "2d")]
+pub(crate) type Mesh2dData = Option<Read<Mesh2dComponent>>;
+#[cfg(not(feature = "2d"))]
+pub(crate) type Mesh2dData = ();
+
+/// The components checked by [`Raycast::explain`], which are all optional so missing ones can be
+/// reported.
+pub(crate) type ExplainData = (
+    Option<Read<Mesh3dComponent>>,
+    Mesh2dData,
+    Option<Read<SimplifiedMesh>>,
+    Option<Read<DynamicRaycastMesh>>,
+    Has<RaycastAabbTarget>,
+    Option<Read<Aabb>>,
+    Option<Read<InheritedVisibility>>,
+    Option<Read<ViewVisibility>>,
+    Option<Read<GlobalTransform>>,
+);
+
+/// A hit drawn by [`Raycast::debug_cast_ray_with_labels`], to be labeled with text. Formatting it
+/// with [`Display`](std::fmt::Display) gives its entity, index, and distance, such as
+/// `3v1 #0 (2.500)`.
+#[cfg(feature = "debug")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugHitLabel {
+    /// The hit entity.
+    pub entity: Entity,
+    /// The index of the hit along the ray, where `0` is the nearest.
+    pub index: usize,
+    /// The world space position of the hit, where the label should be placed.
+    pub position: Vec3,
+    /// The distance from the ray origin to the hit.
+    pub distance: f32,
+}
+
+#[cfg(feature = "debug")]
+impl std::fmt::Display for DebugHitLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} #{} ({:.3})", self.entity, self.index, self.distance)
+    }
+}
 
 /// Add this raycasting [`SystemParam`] to your system to raycast into the world with an
 /// immediate-mode API. Call `cast_ray` to immediately perform a raycast and get a result. Under the
@@ -145,42 +626,81 @@ pub struct Raycast<'w, 's> {
     #[doc(hidden)]
     pub meshes: Res<'w, Assets<Mesh>>,
     #[doc(hidden)]
-    pub hits: Local<'s, Vec<(FloatOrd, (Entity, IntersectionData))>>,
-    #[doc(hidden)]
     pub output: Local<'s, Vec<(Entity, IntersectionData)>>,
     #[doc(hidden)]
-    pub culled_list: Local<'s, Vec<(FloatOrd, Entity)>>,
+    pub culled_list: Local<'s, Vec<([f32; 2], Entity)>>,
+    #[doc(hidden)]
+    pub culled_queue: Local<'s, Parallel<Vec<([f32; 2], Entity)>>>,
     #[doc(hidden)]
-    pub culling_query: Query<
+    pub errors: Local<'s, Vec<(Entity, RaycastError)>>,
+    #[doc(hidden)]
+    pub error_queue: Local<'s, Parallel<Vec<(Entity, RaycastError)>>>,
+    #[doc(hidden)]
+    pub contained: Local<'s, Vec<Entity>>,
+    #[doc(hidden)]
+    pub culling_query: Query<'w, 's, CullingData, MeshFilter>,
+    #[doc(hidden)]
+    pub mesh_query: Query<
         'w,
         's,
         (
-            Read<InheritedVisibility>,
-            Read<ViewVisibility>,
-            Read<Aabb>,
+            Read<Mesh3dComponent>,
+            Option<Read<SimplifiedMesh>>,
+            Option<Read<NoBackfaceCulling>>,
             Read<GlobalTransform>,
-            Entity,
         ),
-        MeshFilter,
+        Without<RaycastSkinnedMesh>,
     >,
     #[doc(hidden)]
-    pub mesh_query: Query<
+    pub dynamic_mesh_query: Query<
         'w,
         's,
         (
-            Read<Handle<Mesh>>,
-            Option<Read<SimplifiedMesh>>,
+            Read<DynamicRaycastMesh>,
             Option<Read<NoBackfaceCulling>>,
             Read<GlobalTransform>,
         ),
     >,
+    #[doc(hidden)]
+    pub aabb_target_query: Query<
+        'w,
+        's,
+        (
+            Read<Aabb>,
+            Option<Read<NoBackfaceCulling>>,
+            Read<GlobalTransform>,
+        ),
+        With<RaycastAabbTarget>,
+    >,
+    #[doc(hidden)]
+    pub pierce_query: Query<'w, 's, (), With<RaycastPierce>>,
+    #[doc(hidden)]
+    pub layers_query: Query<'w, 's, Read<RaycastLayers>>,
+    #[doc(hidden)]
+    pub geometric_normals_query: Query<'w, 's, (), With<GeometricNormals>>,
+    #[doc(hidden)]
+    pub flat_normals: Option<Res<'w, FlatNormalCache>>,
+    #[doc(hidden)]
+    pub mesh_bvhs: Option<Res<'w, MeshBvhCache>>,
+    #[doc(hidden)]
+    pub transform_cache: Option<Res<'w, TransformCache>>,
+    #[doc(hidden)]
+    pub strategy_query: Query<'w, 's, Read<RaycastStrategy>>,
+    #[doc(hidden)]
+    pub transform_history: Query<'w, 's, Read<TransformHistory>>,
+    #[doc(hidden)]
+    pub explain_query: Query<'w, 's, ExplainData>,
+    #[doc(hidden)]
+    pub budget: Option<Res<'w, RaycastBudget>>,
+    #[doc(hidden)]
+    pub skipped_by_budget: Local<'s, bool>,
     #[cfg(feature = "2d")]
     #[doc(hidden)]
     pub mesh2d_query: Query<
         'w,
         's,
         (
-            Read<bevy_sprite::Mesh2dHandle>,
+            Read<Mesh2dComponent>,
             Option<Read<SimplifiedMesh>>,
             Read<GlobalTransform>,
         ),
@@ -195,6 +715,32 @@ impl<'w, 's> Raycast<'w, 's> {
         ray: Ray3d,
         settings: &RaycastSettings,
         gizmos: &mut Gizmos,
+    ) -> &[(Entity, IntersectionData)] {
+        self.debug_cast_ray_with_labels(ray, settings, gizmos, |_| {})
+    }
+
+    #[cfg(feature = "debug")]
+    /// Like [`Raycast::debug_cast_ray`], but also calls `label` with a [`DebugHitLabel`] for each
+    /// hit, nearest first. Gizmos can't draw text, so use this to place text at the hits, which
+    /// makes it possible to tell apart many hits along the same ray:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// fn raycast_system(mut raycast: Raycast, mut gizmos: Gizmos, names: Query<&Name>) {
+    ///     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+    ///     raycast.debug_cast_ray_with_labels(ray, &default(), &mut gizmos, |label| {
+    ///         let name = names.get(label.entity).map(Name::as_str).unwrap_or_default();
+    ///         info!("{name} {label}");
+    ///     });
+    /// }
+    /// ```
+    pub fn debug_cast_ray_with_labels(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+        gizmos: &mut Gizmos,
+        mut label: impl FnMut(DebugHitLabel),
     ) -> &[(Entity, IntersectionData)] {
         use bevy_color::palettes::css;
         use bevy_math::Dir3;
@@ -205,13 +751,8 @@ impl<'w, 's> Raycast<'w, 's> {
 
         let hits = self.cast_ray(ray, settings);
 
-        for (is_first, intersection) in hits
-            .iter()
-            .map(|i| i.1.clone())
-            .enumerate()
-            .map(|(i, hit)| (i == 0, hit))
-        {
-            let color = match is_first {
+        for (index, (entity, intersection)) in hits.iter().enumerate() {
+            let color = match index == 0 {
                 true => css::GREEN,
                 false => css::PINK,
             };
@@ -222,6 +763,12 @@ impl<'w, 's> Raycast<'w, 's> {
                 0.1,
                 color,
             );
+            label(DebugHitLabel {
+                entity: *entity,
+                index,
+                position: intersection.position(),
+                distance: intersection.distance(),
+            });
         }
 
         if let Some(hit) = hits.first() {
@@ -231,103 +778,2076 @@ impl<'w, 's> Raycast<'w, 's> {
         hits
     }
 
+    /// Like [`Raycast::cast_ray`], but only considers entities that match `query`, in addition to
+    /// the [`RaycastSettings::filter`]. This replaces the common pattern of building a filter
+    /// closure that calls [`Query::contains`]:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// # #[derive(Component)]
+    /// # struct Enemy;
+    /// fn raycast_system(mut raycast: Raycast, enemies: Query<(), With<Enemy>>) {
+    ///     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+    ///     let hits = raycast.cast_ray_filtered_by_query(ray, &default(), &enemies);
+    /// }
+    /// ```
+    ///
+    /// The query must be passed in, because a [`SystemParam`] can't create queries for arbitrary
+    /// filters on demand.
+    pub fn cast_ray_filtered_by_query<D: QueryData, F: QueryFilter>(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+        query: &Query<D, F>,
+    ) -> &[(Entity, IntersectionData)] {
+        let filter = |entity| query.contains(entity) && (settings.filter)(entity);
+        let settings = RaycastSettings {
+            filter: &filter,
+            ..settings.clone()
+        };
+        self.cast_ray(ray, &settings)
+    }
+
+    /// Casts a ray from `start` toward `end`, returning a sorted list of intersections, nearest
+    /// first. Hits beyond `end` are never returned, and the [`RaycastSettings::max_distance`] is
+    /// measured from `start`. Returns no hits if `start` and `end` are the same point. Hits before
+    /// `start` are never returned either, regardless of
+    /// [`RaycastSettings::include_behind_origin`].
+    ///
+    /// This is convenient for line of sight checks, where the ray should stop at the target.
+    pub fn cast_segment(
+        &mut self,
+        start: Vec3,
+        end: Vec3,
+        settings: &RaycastSettings,
+    ) -> &[(Entity, IntersectionData)] {
+        let Ok(direction) = Dir3::new(end - start) else {
+            self.output.clear();
+            return self.output.as_ref();
+        };
+        let settings = RaycastSettings {
+            max_distance: settings.max_distance.min(start.distance(end)),
+            include_behind_origin: false,
+            ..settings.clone()
+        };
+        self.cast_ray(
+            Ray3d {
+                origin: start,
+                direction,
+            },
+            &settings,
+        )
+    }
+
+    /// Like [`Raycast::cast_ray`], but only returns hits on the portion of the ray inside the camera
+    /// `frustum`, using [`clip_ray_to_frustum`]. The clipped end of the ray also limits the
+    /// [`RaycastSettings::max_distance`], which lets more entities be culled by their AABB.
+    ///
+    /// The ray is cast from where it enters the frustum, so entities outside of the frustum can't
+    /// block it, or use up the [`RaycastSettings::max_hits`]. Hit distances are still measured from
+    /// the origin of the `ray`, and [`RaycastSettings::include_behind_origin`] is ignored.
+    pub fn cast_ray_clipped_to_frustum(
+        &mut self,
+        ray: Ray3d,
+        frustum: &Frustum,
+        settings: &RaycastSettings,
+    ) -> &[(Entity, IntersectionData)] {
+        let Some([start, end]) = clip_ray_to_frustum(ray, frustum) else {
+            self.output.clear();
+            return self.output.as_ref();
+        };
+        let settings = RaycastSettings {
+            max_distance: settings.max_distance.min(end) - start,
+            include_behind_origin: false,
+            ..settings.clone()
+        };
+        let clipped_ray = Ray3d {
+            origin: ray.get_point(start),
+            direction: ray.direction,
+        };
+        self.cast_ray(clipped_ray, &settings);
+        for (_, hit) in self.output.iter_mut() {
+            *hit = hit.with_distance(hit.distance() + start);
+        }
+        self.output.as_ref()
+    }
+
+    /// Like [`Raycast::cast_ray`], but also returns the entities in the path of the ray that could
+    /// not be raycast, and why. [`Raycast::cast_ray`] silently skips these entities.
+    ///
+    /// Only entities that pass the visibility and filter settings, and whose AABB is hit by the
+    /// ray, are checked. Entities that are culled by an earlier blocking hit may not be reported.
+    pub fn cast_ray_with_errors(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+    ) -> (&[(Entity, IntersectionData)], &[(Entity, RaycastError)]) {
+        self.cast_ray(ray, settings);
+        (self.output.as_ref(), self.errors.as_ref())
+    }
+
+    /// Returns `true` if the last raycast was skipped, and returned no hits, because the
+    /// [`RaycastBudget`] of the frame was spent. See the [`budget`](crate::budget) module.
+    pub fn skipped_by_budget(&self) -> bool {
+        *self.skipped_by_budget
+    }
+
+    /// Spends a raycast of the `class` from the [`RaycastBudget`], if there is one, returning
+    /// `false` if the raycast must be skipped instead.
+    pub(crate) fn spend_budget(&mut self, class: RaycastPriorityClass) -> bool {
+        let spent = self
+            .budget
+            .as_ref()
+            .map_or(true, |budget| budget.try_spend(class));
+        *self.skipped_by_budget = !spent;
+        spent
+    }
+
     /// Casts the `ray` into the world and returns a sorted list of intersections, nearest first.
     pub fn cast_ray(
         &mut self,
         ray: Ray3d,
         settings: &RaycastSettings,
     ) -> &[(Entity, IntersectionData)] {
+        self.cast_ray_over(ray, settings, None)
+    }
+
+    /// Like [`Raycast::cast_ray`], but only the `candidates` are considered, instead of every
+    /// entity in the world. This avoids testing the AABB of every entity when the caller has
+    /// already narrowed down which entities could be hit, such as with a query:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// # #[derive(Component)]
+    /// # struct Enemy;
+    /// fn raycast_system(mut raycast: Raycast, enemies: Query<Entity, With<Enemy>>) {
+    ///     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+    ///     let hits = raycast.cast_ray_on_candidates(ray, &default(), &enemies);
+    /// }
+    /// ```
+    ///
+    /// Candidates are culled one by one on the calling thread, so this is only faster than
+    /// [`Raycast::cast_ray_filtered_by_query`] when the candidates are a small part of the world.
+    /// Candidates that are not raycastable are ignored.
+    pub fn cast_ray_on_candidates(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+        candidates: impl IntoIterator<Item = Entity>,
+    ) -> &[(Entity, IntersectionData)] {
+        self.cast_ray_over(ray, settings, Some(&mut candidates.into_iter()))
+    }
+
+    /// Like [`Raycast::cast_ray`], but returns a lazy iterator over the hits, nearest first. Entities
+    /// are only raycast as the iterator needs them, so callers that stop at the first hit they
+    /// are interested in don't pay for raycasting the entities behind it:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// # #[derive(Component)]
+    /// # struct Glass;
+    /// fn raycast_system(mut raycast: Raycast, glass: Query<(), With<Glass>>) {
+    ///     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+    ///     let settings = RaycastSettings::default().never_early_exit();
+    ///     let first_opaque_hit = raycast
+    ///         .cast_ray_iter(ray, &settings)
+    ///         .find(|(entity, _)| !glass.contains(*entity));
+    /// }
+    /// ```
+    ///
+    /// The hits are the same as those of [`Raycast::cast_ray`], in the same order: the iterator
+    /// ends after the nearest blocking hit as set by the early exit tests, or after
+    /// [`max_hits`](RaycastSettings::max_hits) hits. Use [`RaycastSettings::never_early_exit`] to
+    /// iterate over every hit. The [`warm_start`](RaycastSettings::warm_start) setting is ignored,
+    /// since entities are raycast in order as the iterator reaches them. All entities are still
+    /// culled against the ray, and filtered, up front. Entities that could not be raycast are
+    /// collected in [`RaycastHits::errors`] as the iterator reaches them.
+    pub fn cast_ray_iter<'a>(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings<'a>,
+    ) -> RaycastHits<'_, 'w, 's, 'a> {
+        let settings = RaycastSettings {
+            warm_start: None,
+            ..settings.clone()
+        };
+        match self.spend_budget(settings.priority_class) {
+            true => {
+                self.cull(ray, &settings, None);
+                self.filter_culled(&settings);
+            }
+            false => {
+                self.culled_list.clear();
+                self.errors.clear();
+            }
+        }
+        let culled = std::mem::take(&mut *self.culled_list);
+        let errors = std::mem::take(&mut *self.errors);
+        RaycastHits {
+            raycast: self,
+            ray,
+            settings,
+            candidate_count: culled.len(),
+            culled: culled.into_iter(),
+            pending: Vec::new(),
+            blocking: None,
+            returned: 0,
+            errors,
+        }
+    }
+
+    /// Casts the ray against the `candidates`, or every entity if there are none.
+    fn cast_ray_over(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+        candidates: Option<&mut dyn Iterator<Item = Entity>>,
+    ) -> &[(Entity, IntersectionData)] {
+        self.output.clear();
+        if !self.spend_budget(settings.priority_class) {
+            self.errors.clear();
+            return self.output.as_ref();
+        }
+        self.cull(ray, settings, candidates);
+
+        let mut nearest_blocking_hit = FloatOrd(f32::INFINITY);
+        // The blocking entity, and its index in the culled list.
+        let mut blocking = None;
+        let mut hits = std::mem::take(&mut *self.output);
+        let mut errors = std::mem::take(&mut *self.errors);
+        let raycast_guard = debug_span!("raycast");
+        self.filter_culled(settings);
+        let candidate_count = self.culled_list.len();
+        let narrowphase_settings = NarrowphaseSettings::from(settings);
+        let parallel_results = settings
+            .parallel_narrowphase_threshold
+            .filter(|threshold| self.culled_list.len() >= *threshold)
+            .map(|_| {
+                let max_hits = settings.max_hits.map(|max| (max, settings.sorting));
+                self.parallel_narrowphase(ray, narrowphase_settings, max_hits)
+            });
+        // The sort key is cheap, so it is recomputed instead of stored next to each hit.
+        let key = |(_, hit): &(Entity, IntersectionData)| {
+            FloatOrd(settings.sorting.key(ray, hit.position()))
+        };
+        // Discards the blocked hits, and keeps the nearest `max_hits` of the rest. A hit that is
+        // blocked stays blocked, and an entity's hits are only kept beyond the nearest blocking hit
+        // while it is the blocking entity, which it can only become while it is raycast. So this
+        // can run between entities, and discards the same hits as it would at the end.
+        let trim = |hits: &mut Vec<(Entity, IntersectionData)>,
+                    nearest_blocking_hit: FloatOrd,
+                    blocking_entity: Option<Entity>| {
+            hits.retain(|hit| {
+                let dist = key(hit);
+                let blocked = dist > nearest_blocking_hit
+                    && !(settings.all_hits_per_entity && blocking_entity == Some(hit.0));
+                !blocked && dist.0 <= settings.max_distance
+            });
+            if let Some(max_hits) = settings.max_hits {
+                keep_nearest(hits, max_hits, key);
+            }
+        };
+        // The warm start entity is raycast first, but keeps its index in the culled list, so
+        // candidate tests see the same candidates either way.
+        let warm_start = settings.warm_start.and_then(|warm_start| {
+            self.culled_list
+                .iter()
+                .position(|(_, entity)| *entity == warm_start)
+        });
+        let order = warm_start
+            .into_iter()
+            .chain((0..candidate_count).filter(|i| Some(*i) != warm_start));
+        for i in order {
+            let (aabb_distances, entity) = &self.culled_list[i];
+            // Is it even possible the entity could be closer than the current best?
+            if FloatOrd(aabb_distances[0]) > nearest_blocking_hit {
+                continue;
+            }
+            // Triangles behind the nearest blocking hit can be skipped, unless every hit on the
+            // blocking entity is kept.
+            let entity_settings = match settings.all_hits_per_entity {
+                true => narrowphase_settings,
+                false => NarrowphaseSettings {
+                    max_distance: settings.max_distance.min(nearest_blocking_hit.0),
+                    ..narrowphase_settings
+                },
+            };
+            let record_hit = |intersection: IntersectionData| {
+                let distance = FloatOrd(settings.sorting.key(ray, intersection.position()));
+                let is_blocking = !self.pierce_query.contains(*entity)
+                    && (settings.early_exit_test)(*entity)
+                    && settings.candidate_early_exit_test.map_or(true, |test| {
+                        test(&RaycastCandidate {
+                            entity: *entity,
+                            index: i,
+                            count: candidate_count,
+                            aabb_distances: *aabb_distances,
+                        })
+                    });
+                // Ties go to the entity that is first in the culled list, so they don't depend on
+                // the warm start.
+                let is_nearer = distance < nearest_blocking_hit
+                    || distance == nearest_blocking_hit
+                        && blocking.is_some_and(|(index, _)| i < index);
+                if is_blocking && is_nearer {
+                    // The reason we don't just return here is because right now we are going
+                    // through the AABBs in order, but that doesn't mean that an AABB that starts
+                    // further away cant end up with a closer hit than an AABB that starts closer.
+                    // We need to keep checking AABBs that could possibly contain a nearer hit.
+                    nearest_blocking_hit = distance.min(nearest_blocking_hit);
+                    blocking = Some((i, *entity));
+                }
+                hits.push((*entity, intersection));
+            };
+            // Parallel results are reduced in the same order as a serial raycast, so entities
+            // behind the nearest blocking hit are dropped and the results are the same.
+            let result = match &parallel_results {
+                Some(results) => {
+                    let (entity_hits, result) = &results[i];
+                    entity_hits.iter().copied().for_each(record_hit);
+                    *result
+                }
+                None => {
+                    let _raycast_guard = raycast_guard.enter();
+                    self.narrowphase(ray, entity_settings, *entity, *aabb_distances, record_hit)
+                }
+            };
+            if let Err(error) = result {
+                errors.push((*entity, error));
+            }
+            if settings.max_hits.is_some_and(|max| hits.len() > 2 * max) {
+                trim(
+                    &mut hits,
+                    nearest_blocking_hit,
+                    blocking.map(|(_, entity)| entity),
+                );
+            }
+        }
+        *self.errors = errors;
+
+        // Hits are filtered and sorted in place, and returned without copying them.
+        trim(
+            &mut hits,
+            nearest_blocking_hit,
+            blocking.map(|(_, entity)| entity),
+        );
+        hits.sort_by_key(key);
+        *self.output = hits;
+        self.output.as_ref()
+    }
+
+    /// Removes the entities rejected by the [`RaycastSettings::candidate_filter`] from the culled
+    /// list.
+    fn filter_culled(&mut self, settings: &RaycastSettings) {
+        if let Some(candidate_filter) = settings.candidate_filter {
+            let count = self.culled_list.len();
+            let mut index = 0;
+            self.culled_list.retain(|(aabb_distances, entity)| {
+                index += 1;
+                candidate_filter(&RaycastCandidate {
+                    entity: *entity,
+                    index: index - 1,
+                    count,
+                    aabb_distances: *aabb_distances,
+                })
+            });
+        }
+    }
+
+    /// Fills the culled list with the `candidates`, or every entity if there are none, whose AABB
+    /// is hit by the ray, sorted by where the ray enters their AABB. Entities that can't be raycast
+    /// are added to the errors.
+    fn cull(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+        candidates: Option<&mut dyn Iterator<Item = Entity>>,
+    ) {
+        let transform_blend = settings.transform_blend;
+        let max_distance = settings.max_distance;
+        let include_behind_origin = settings.include_behind_origin;
+        let in_range =
+            |[near, far]: [f32; 2]| (include_behind_origin || far >= 0.0) && near <= max_distance;
+        self.cull_with(settings, candidates, |bounds| {
+            // The sphere is placed with the current transform, so it can't reject entities that
+            // are raycast with a blended transform.
+            if transform_blend.is_none()
+                && bounds
+                    .sphere
+                    .is_some_and(|sphere| !sphere.intersects_ray(ray).is_some_and(in_range))
+            {
+                return None;
+            }
+            bounds
+                .transform
+                .slab_ray(ray)
+                .intersects_aabb(&bounds.aabb)
+                .filter(|d| in_range(*d))
+        });
+    }
+
+    /// Fills the culled list with the `candidates`, or every entity if there are none, that pass
+    /// the visibility, layer, level of detail, and filter settings, and whose bounds pass the
+    /// `test`. Entities are listed with the distances returned by the `test`, sorted by the first.
+    /// Tests that don't order entities along a ray return zero distances. Entities whose transform
+    /// can't be inverted are added to the errors instead.
+    ///
+    /// This is the broadphase of every query of the [`Raycast`] system param, which only differ by
+    /// their bounds test.
+    pub(crate) fn cull_with(
+        &mut self,
+        settings: &RaycastSettings,
+        candidates: Option<&mut dyn Iterator<Item = Entity>>,
+        test: impl Fn(&CullingBounds) -> Option<[f32; 2]> + Sync,
+    ) {
         let ray_cull = info_span!("ray culling");
-        let ray_cull_guard = ray_cull.enter();
+        let _ray_cull_guard = ray_cull.enter();
 
-        self.hits.clear();
         self.culled_list.clear();
-        self.output.clear();
+        self.errors.clear();
 
-        // Check all entities to see if the ray intersects the AABB, use this to build a short list
-        // of entities that are in the path of the ray.
-        let (aabb_hits_tx, aabb_hits_rx) = crossbeam_channel::unbounded::<(FloatOrd, Entity)>();
+        // Check all entities to see if their bounds pass the test, use this to build a short list
+        // of entities that could be hit. The per-thread queues keep their capacity between casts,
+        // so a typical cast does not allocate.
+        let culled_queue = &*self.culled_queue;
+        let error_queue = &*self.error_queue;
         let visibility_setting = settings.visibility;
-        self.culling_query.par_iter().for_each(
-            |(inherited_visibility, view_visibility, aabb, transform, entity)| {
-                let should_raycast = match visibility_setting {
-                    RaycastVisibility::Ignore => true,
-                    RaycastVisibility::MustBeVisible => inherited_visibility.get(),
-                    RaycastVisibility::MustBeVisibleAndInView => view_visibility.get(),
-                };
-                if should_raycast {
-                    if let Some([near, _]) = intersects_aabb(ray, aabb, &transform.compute_matrix())
-                        .filter(|[_, far]| *far >= 0.0)
-                    {
-                        aabb_hits_tx.send((FloatOrd(near), entity)).ok();
+        let transform_blend = settings.transform_blend;
+        let lod_viewpoint = settings.lod_viewpoint;
+        let layers = settings.layers;
+        let transform_history = &self.transform_history;
+        let transform_cache = self.transform_cache.as_deref();
+        let layers_query = &self.layers_query;
+        let cull = |item: ROQueryItem<CullingData>| {
+            let (
+                inherited_visibility,
+                view_visibility,
+                bounds,
+                transform,
+                entity,
+                sphere,
+                range,
+                aabb_target,
+            ) = item;
+            if !is_visible(
+                visibility_setting,
+                inherited_visibility,
+                view_visibility,
+                aabb_target,
+            ) || !is_in_layers(layers, layers_query, entity)
+            {
+                return None;
+            }
+            if let (Some(viewpoint), Some(range)) = (lod_viewpoint, range) {
+                if !is_lod_shown(range, viewpoint.distance(transform.translation())) {
+                    return None;
+                }
+            }
+            // Dynamic meshes are raycast in their current pose, which the sphere of their
+            // asset doesn't enclose.
+            let sphere = sphere.filter(|_| bounds.1.is_none());
+            let aabb = culling_aabb(bounds)?;
+            let transform = match culling_transform(
+                transform_cache,
+                entity,
+                transform,
+                transform_history,
+                transform_blend,
+            ) {
+                Ok(transform) => transform,
+                Err(error) => return Some(Err(error)),
+            };
+            let bounds = CullingBounds {
+                aabb,
+                sphere,
+                transform,
+            };
+            test(&bounds).map(Ok)
+        };
+        match candidates {
+            Some(candidates) => {
+                for item in candidates.filter_map(|entity| self.culling_query.get(entity).ok()) {
+                    let entity = item.4;
+                    match cull(item) {
+                        Some(Ok(distances)) => self.culled_list.push((distances, entity)),
+                        Some(Err(error)) => self.errors.push((entity, error)),
+                        None => {}
                     }
                 }
-            },
-        );
-        *self.culled_list = aabb_hits_rx.try_iter().collect();
-        self.culled_list.sort_by_key(|(aabb_near, _)| *aabb_near);
-        drop(ray_cull_guard);
-
-        let mut nearest_blocking_hit = FloatOrd(f32::INFINITY);
-        let raycast_guard = debug_span!("raycast");
-        self.culled_list
-            .iter()
-            .filter(|(_, entity)| (settings.filter)(*entity))
-            .for_each(|(aabb_near, entity)| {
-                let mut raycast_mesh =
-                    |mesh_handle: &Handle<Mesh>,
-                     simplified_mesh: Option<&SimplifiedMesh>,
-                     no_backface_culling: Option<&NoBackfaceCulling>,
-                     transform: &GlobalTransform| {
-                        // Is it even possible the mesh could be closer than the current best?
-                        if *aabb_near > nearest_blocking_hit {
-                            return;
+            }
+            None => {
+                self.culling_query.par_iter().for_each(|item| {
+                    let entity = item.4;
+                    match cull(item) {
+                        Some(Ok(distances)) => {
+                            culled_queue.scope(|queue| queue.push((distances, entity)));
                         }
+                        Some(Err(error)) => error_queue.scope(|queue| queue.push((entity, error))),
+                        None => {}
+                    }
+                });
+                self.culled_queue.drain_into(&mut self.culled_list);
+                self.error_queue.drain_into(&mut self.errors);
+            }
+        }
+        self.culled_list
+            .retain(|(_, entity)| (settings.filter)(*entity));
+        self.errors.retain(|(entity, _)| (settings.filter)(*entity));
+        self.culled_list
+            .sort_by_key(|([near, _], _)| FloatOrd(*near));
+    }
 
-                        // Does the mesh handle resolve?
-                        let mesh_handle = simplified_mesh.map(|m| &m.mesh).unwrap_or(mesh_handle);
-                        let Some(mesh) = self.meshes.get(mesh_handle) else {
-                            return;
-                        };
-
-                        let _raycast_guard = raycast_guard.enter();
-                        let backfaces = match no_backface_culling {
-                            Some(_) => Backfaces::Include,
-                            None => Backfaces::Cull,
-                        };
-                        let transform = transform.compute_matrix();
-                        let intersection =
-                            ray_intersection_over_mesh(mesh, &transform, ray, backfaces);
-                        if let Some(intersection) = intersection {
-                            let distance = FloatOrd(intersection.distance());
-                            if (settings.early_exit_test)(*entity)
-                                && distance < nearest_blocking_hit
-                            {
-                                // The reason we don't just return here is because right now we are
-                                // going through the AABBs in order, but that doesn't mean that an
-                                // AABB that starts further away cant end up with a closer hit than
-                                // an AABB that starts closer. We need to keep checking AABBs that
-                                // could possibly contain a nearer hit.
-                                nearest_blocking_hit = distance.min(nearest_blocking_hit);
-                            }
-                            self.hits.push((distance, (*entity, intersection)));
-                        };
-                    };
+    /// Moves the entities of the culled list into the contained list, followed by the entities
+    /// that could not be culled. Raycasts on these candidates report the same errors as a raycast
+    /// on every entity.
+    pub(crate) fn contain_culled(&mut self) {
+        self.contained.clear();
+        let culled = self.culled_list.drain(..).map(|(_, entity)| entity);
+        self.contained.extend(culled);
+        let errors = self.errors.iter().map(|(entity, _)| *entity);
+        self.contained.extend(errors);
+    }
 
-                if let Ok((mesh, simp_mesh, culling, transform)) = self.mesh_query.get(*entity) {
-                    raycast_mesh(mesh, simp_mesh, culling, transform);
+    /// Raycasts the geometry of every entity in the culled list on the [`ComputeTaskPool`], and
+    /// returns the hits and result of each, in the order of the culled list. When `max_hits` is
+    /// set, only the nearest hits of each entity by its sorting are returned.
+    fn parallel_narrowphase(
+        &self,
+        ray: Ray3d,
+        settings: NarrowphaseSettings,
+        max_hits: Option<(usize, HitSorting)>,
+    ) -> Vec<(Vec<IntersectionData>, Result<(), RaycastError>)> {
+        let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        let chunk_size = self
+            .culled_list
+            .len()
+            .div_ceil(task_pool.thread_num())
+            .max(1);
+        task_pool
+            .scope(|scope| {
+                for chunk in self.culled_list.chunks(chunk_size) {
+                    scope.spawn(async move {
+                        let _raycast_guard = debug_span!("raycast").entered();
+                        chunk
+                            .iter()
+                            .map(|(aabb_distances, entity)| {
+                                let mut hits = Vec::new();
+                                let result = self.narrowphase(
+                                    ray,
+                                    settings,
+                                    *entity,
+                                    *aabb_distances,
+                                    |hit| hits.push(hit),
+                                );
+                                if let Some((max_hits, sorting)) = max_hits {
+                                    keep_nearest(&mut hits, max_hits, |hit| {
+                                        FloatOrd(sorting.key(ray, hit.position()))
+                                    });
+                                }
+                                (hits, result)
+                            })
+                            .collect::<Vec<_>>()
+                    });
                 }
+            })
+            .into_iter()
+            .flatten()
+            .collect()
+    }
 
+    /// Raycasts the geometry of the `entity`, whose AABB the ray enters and exits at the
+    /// `aabb_distances`, and visits each hit. Returns an error if the geometry could not be raycast.
+    fn narrowphase(
+        &self,
+        ray: Ray3d,
+        settings: NarrowphaseSettings,
+        entity: Entity,
+        aabb_distances: [f32; 2],
+        mut visit: impl FnMut(IntersectionData),
+    ) -> Result<(), RaycastError> {
+        let mut raycast_geometry =
+            |geometry: RaycastGeometry,
+             source_mesh: Option<(AssetId<Mesh>, bool)>,
+             backfaces: Backfaces,
+             transform: &GlobalTransform| {
+                let transform = model_matrix(
+                    entity,
+                    transform,
+                    &self.transform_history,
+                    settings.transform_blend,
+                );
+                let exceeds_max_triangles = settings
+                    .max_triangles_per_cast
+                    .is_some_and(|max| geometry.triangle_count() > max);
+                // Line casts start the mesh raycast where the line enters the AABB, and shift
+                // distances back to be relative to the original origin.
+                let start = if settings.include_behind_origin {
+                    aabb_distances[0].min(0.0)
+                } else {
+                    0.0
+                };
+                // Geometric normals are computed from the hit triangle, even if it isn't requested.
+                let geometric_normals =
+                    settings.geometric_normals || self.geometric_normals_query.contains(entity);
+                let hit_data = settings
+                    .hit_data
+                    .with_triangle(settings.hit_data.triangle || geometric_normals);
+                let mut record_hit = |intersection: IntersectionData| {
+                    let mut intersection = intersection.with_aabb_distances(aabb_distances);
+                    if let Some((mesh_id, proxy)) = source_mesh {
+                        intersection = intersection.with_source_mesh(mesh_id, proxy);
+                    }
+                    if geometric_normals {
+                        intersection = with_geometric_normal(intersection, &transform);
+                    }
+                    if !settings.hit_data.triangle {
+                        intersection = intersection.with_triangle(None);
+                    }
+                    visit(intersection);
+                };
+                if exceeds_max_triangles {
+                    record_hit(approximate_aabb_intersection(
+                        ray,
+                        aabb_distances[0].max(start),
+                    ));
+                    return;
+                }
+                let mesh_ray = Ray3d {
+                    origin: ray.get_point(start),
+                    direction: ray.direction,
+                };
+                // Hits are filtered by their sort key afterwards, so the limit is padded to keep hits
+                // that are only beyond it by rounding error.
+                let max_distance = settings.max_distance - start;
+                let max_distance = max_distance + max_distance.abs() * 1e-5;
+                let shift = |hit: IntersectionData| match start < 0.0 {
+                    true => hit.with_distance(hit.distance() + start),
+                    false => hit,
+                };
+                if settings.all_hits_per_entity {
+                    geometry
+                        .ray_intersections(&transform, mesh_ray, backfaces, hit_data, max_distance)
+                        .into_iter()
+                        .for_each(|hit| record_hit(shift(hit)));
+                } else if let Some(hit) = geometry.ray_intersection(
+                    &transform,
+                    mesh_ray,
+                    backfaces,
+                    hit_data,
+                    max_distance,
+                ) {
+                    record_hit(shift(hit));
+                }
+            };
+
+        let mut result = Ok(());
+        let mut raycast_mesh = |mesh_handle: &Handle<Mesh>,
+                                simplified_mesh: Option<&SimplifiedMesh>,
+                                backfaces: Backfaces,
+                                transform: &GlobalTransform| {
+            // Does the mesh handle resolve?
+            let mesh_handle = simplified_mesh.map(|m| &m.mesh).unwrap_or(mesh_handle);
+            let Some(mesh) = self.meshes.get(mesh_handle) else {
+                result = Err(RaycastError::MissingMesh);
+                return;
+            };
+            if let Err(error) = validate_raycast_mesh(mesh) {
+                result = Err(error);
+                return;
+            }
+            let source_mesh = (mesh_handle.id(), simplified_mesh.is_some());
+            let use_bvh = !self
+                .strategy_query
+                .get(entity)
+                .is_ok_and(|strategy| *strategy == RaycastStrategy::BruteForce);
+            let bvhs = self.mesh_bvhs.as_deref().filter(|_| use_bvh);
+            if let Some(bvhs) = bvhs {
+                bvhs.record_cast(mesh_handle, mesh_triangle_count(mesh));
+            }
+            let caches = MeshCaches {
+                flat_normals: self
+                    .flat_normals
+                    .as_ref()
+                    .and_then(|cache| cache.get(mesh_handle)),
+                bvh: bvhs.and_then(|cache| cache.get(mesh_handle)),
+                world_to_mesh: cached_transform(
+                    self.transform_cache.as_deref(),
+                    entity,
+                    transform,
+                    settings.transform_blend,
+                )
+                .and_then(|cached| cached.world_to_model().copied()),
+            };
+            let triangles = simplified_mesh.and_then(|s| s.triangle_range(mesh));
+            raycast_geometry(
+                RaycastGeometry::Mesh(mesh, caches, triangles),
+                Some(source_mesh),
+                backfaces,
+                transform,
+            );
+        };
+
+        if let Ok((aabb, culling, transform)) = self.aabb_target_query.get(entity) {
+            let geometry = RaycastGeometry::Aabb(aabb);
+            raycast_geometry(geometry, None, backfaces(culling), transform);
+            return Ok(());
+        }
+
+        if let Ok((mesh, simp_mesh, culling, transform)) = self.mesh_query.get(entity) {
+            raycast_mesh(mesh.mesh_handle(), simp_mesh, backfaces(culling), transform);
+        }
+
+        #[cfg(feature = "2d")]
+        if let Ok((mesh, simp_mesh, transform)) = self.mesh2d_query.get(entity) {
+            raycast_mesh(mesh.mesh_handle(), simp_mesh, Backfaces::Include, transform);
+        }
+
+        if let Ok((dynamic_mesh, culling, transform)) = self.dynamic_mesh_query.get(entity) {
+            let geometry = RaycastGeometry::Dynamic(dynamic_mesh);
+            raycast_geometry(geometry, None, backfaces(culling), transform);
+        }
+        result
+    }
+
+    /// Looks up the world-space vertices of the triangle of a `hit` on the `entity`, for raycasts
+    /// that didn't capture them with [`HitData::triangle`]. Returns the captured
+    /// [`IntersectionData::triangle`] if there is one, and `None` if the hit was not on a mesh or
+    /// dynamic mesh, or the mesh is no longer loaded.
+    ///
+    /// The vertices are read from the mesh that was hit, with the current transform of the
+    /// entity, so they don't account for a [`RaycastSettings::transform_blend`]. This is only done
+    /// for the hits it is called on, which is cheaper than capturing the triangle of every hit
+    /// when only the nearest one needs it.
+    pub fn hit_triangle(&self, entity: Entity, hit: &IntersectionData) -> Option<[Vec3A; 3]> {
+        if hit.triangle().is_some() {
+            return hit.triangle();
+        }
+        let Some(mesh_id) = hit.source_mesh() else {
+            let (dynamic_mesh, _, transform) = self.dynamic_mesh_query.get(entity).ok()?;
+            return hit.triangle_from(dynamic_mesh.positions(), transform);
+        };
+        let transform = self.mesh_query.get(entity).map(|(.., transform)| transform);
+        #[cfg(feature = "2d")]
+        let transform = transform.or_else(|_| {
+            self.mesh2d_query
+                .get(entity)
+                .map(|(.., transform)| transform)
+        });
+        let transform = transform.ok()?;
+        match self
+            .meshes
+            .get(mesh_id)?
+            .attribute(Mesh::ATTRIBUTE_POSITION)?
+        {
+            bevy_render::mesh::VertexAttributeValues::Float32x3(positions) => {
+                hit.triangle_from(positions, transform)
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds the raycastable entities that contain the world-space `point`, using the same
+    /// visibility, filtering, and transform settings as [`Raycast::cast_ray`]. The early exit test
+    /// and distance limits of the settings are ignored.
+    ///
+    /// With [`PointContainment::Aabb`] only the entity's AABB is checked. With
+    /// [`PointContainment::Mesh`] the mesh must also enclose the point, which is determined by
+    /// casting a ray from the point and checking if the nearest triangle hit faces away from it.
+    /// This is only meaningful for closed meshes.
+    pub fn entities_containing_point(
+        &mut self,
+        point: Vec3,
+        containment: PointContainment,
+        settings: &RaycastSettings,
+    ) -> &[Entity] {
+        let transform_blend = settings.transform_blend;
+        self.cull_with(settings, None, |bounds| {
+            let world_to_model = bounds.transform.world_to_model();
+            let model_point = world_to_model.transform_point3a(Vec3A::from(point));
+            let inside = model_point.cmpge(bounds.aabb.min()).all()
+                && model_point.cmple(bounds.aabb.max()).all();
+            inside.then_some([0.0; 2])
+        });
+        self.contained.clear();
+        let culled = self.culled_list.iter().map(|(_, entity)| *entity);
+        self.contained.extend(culled);
+
+        if let PointContainment::Mesh = containment {
+            // Any direction works for closed meshes; this one is unlikely to be axis aligned with
+            // mesh edges.
+            let ray = Ray3d::new(point, Vec3::new(0.267, 0.802, 0.534));
+            // The winding of the hit triangle tells whether the point is inside.
+            let containment_hit_data = HitData::MINIMAL.with_triangle(true);
+            let mesh_geometry =
+                |mesh_handle: &Handle<Mesh>, simplified_mesh: Option<&SimplifiedMesh>| {
+                    let mesh_handle = simplified_mesh.map(|m| &m.mesh).unwrap_or(mesh_handle);
+                    self.meshes
+                        .get(mesh_handle)
+                        .filter(|mesh| validate_raycast_mesh(mesh).is_ok())
+                        .map(|mesh| {
+                            let triangles = simplified_mesh.and_then(|s| s.triangle_range(mesh));
+                            RaycastGeometry::Mesh(mesh, MeshCaches::default(), triangles)
+                        })
+                };
+            let encloses_point =
+                |entity: Entity, geometry: Option<RaycastGeometry>, transform: &GlobalTransform| {
+                    let Some(geometry) = geometry else {
+                        return false;
+                    };
+                    let transform =
+                        model_matrix(entity, transform, &self.transform_history, transform_blend);
+                    geometry
+                        .ray_intersection(
+                            &transform,
+                            ray,
+                            Backfaces::Include,
+                            containment_hit_data,
+                            f32::INFINITY,
+                        )
+                        .and_then(|hit| hit.triangle())
+                        .is_some_and(|[a, b, c]| {
+                            (b - a).cross(c - a).dot(Vec3A::from(*ray.direction)) > 0.0
+                        })
+                };
+            let mut contained = std::mem::take(&mut *self.contained);
+            contained.retain(|entity| {
+                if let Ok((mesh, simp_mesh, _, transform)) = self.mesh_query.get(*entity) {
+                    return encloses_point(
+                        *entity,
+                        mesh_geometry(mesh.mesh_handle(), simp_mesh),
+                        transform,
+                    );
+                }
                 #[cfg(feature = "2d")]
                 if let Ok((mesh, simp_mesh, transform)) = self.mesh2d_query.get(*entity) {
-                    raycast_mesh(&mesh.0, simp_mesh, Some(&NoBackfaceCulling), transform);
+                    return encloses_point(
+                        *entity,
+                        mesh_geometry(mesh.mesh_handle(), simp_mesh),
+                        transform,
+                    );
+                }
+                if let Ok((dynamic_mesh, _, transform)) = self.dynamic_mesh_query.get(*entity) {
+                    let geometry = RaycastGeometry::Dynamic(dynamic_mesh);
+                    return encloses_point(*entity, Some(geometry), transform);
                 }
+                false
             });
+            *self.contained = contained;
+        }
 
-        self.hits.retain(|(dist, _)| *dist <= nearest_blocking_hit);
-        self.hits.sort_by_key(|(k, _)| *k);
-        let hits = self.hits.iter().map(|(_, (e, i))| (*e, i.to_owned()));
-        *self.output = hits.collect();
-        self.output.as_ref()
+        self.contained.as_ref()
+    }
+}
+
+/// Keeps the `max_hits` hits with the smallest `key`, in no particular order.
+fn keep_nearest<T>(hits: &mut Vec<T>, max_hits: usize, key: impl FnMut(&T) -> FloatOrd) {
+    if max_hits >= hits.len() {
+        return;
+    }
+    // Partition the nearest hits to the front, so the rest can be dropped without sorting.
+    if max_hits > 0 {
+        hits.select_nth_unstable_by_key(max_hits - 1, key);
+    }
+    hits.truncate(max_hits);
+}
+
+/// The settings used to raycast the geometry of a single entity. Unlike [`RaycastSettings`], these
+/// can be shared between threads.
+#[derive(Clone, Copy)]
+struct NarrowphaseSettings {
+    transform_blend: Option<f32>,
+    max_triangles_per_cast: Option<usize>,
+    include_behind_origin: bool,
+    all_hits_per_entity: bool,
+    geometric_normals: bool,
+    hit_data: HitData,
+    /// Hits beyond this distance may be skipped.
+    max_distance: f32,
+}
+
+impl From<&RaycastSettings<'_>> for NarrowphaseSettings {
+    fn from(settings: &RaycastSettings) -> Self {
+        Self {
+            transform_blend: settings.transform_blend,
+            max_triangles_per_cast: settings.max_triangles_per_cast,
+            include_behind_origin: settings.include_behind_origin,
+            all_hits_per_entity: settings.all_hits_per_entity,
+            geometric_normals: settings.geometric_normals,
+            hit_data: settings.hit_data,
+            max_distance: settings.max_distance,
+        }
+    }
+}
+
+/// A lazy iterator over the hits of a ray, nearest first. See [`Raycast::cast_ray_iter`].
+pub struct RaycastHits<'r, 'w, 's, 'a> {
+    raycast: &'r Raycast<'w, 's>,
+    ray: Ray3d,
+    settings: RaycastSettings<'a>,
+    /// The number of entities in the culled list, as seen by the candidate tests.
+    candidate_count: usize,
+    /// The entities whose AABB is hit by the ray and have not been raycast yet, sorted by where
+    /// the ray enters their AABB.
+    culled: std::vec::IntoIter<([f32; 2], Entity)>,
+    /// Hits on entities that were raycast, which may be behind hits on entities that weren't yet,
+    /// with their sort key and whether they stop the raycast.
+    pending: Vec<(FloatOrd, bool, (Entity, IntersectionData))>,
+    /// The sort key and entity of the nearest blocking hit, once it was returned.
+    blocking: Option<(FloatOrd, Entity)>,
+    /// The number of hits returned so far.
+    returned: usize,
+    errors: Vec<(Entity, RaycastError)>,
+}
+
+impl RaycastHits<'_, '_, '_, '_> {
+    /// The entities that could not be raycast so far, and why, like
+    /// [`Raycast::cast_ray_with_errors`]. Entities are only raycast as the iterator reaches them,
+    /// so errors on entities behind the last hit returned may not be listed yet.
+    pub fn errors(&self) -> &[(Entity, RaycastError)] {
+        &self.errors
+    }
+}
+
+impl Iterator for RaycastHits<'_, '_, '_, '_> {
+    type Item = (Entity, IntersectionData);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self
+            .settings
+            .max_hits
+            .is_some_and(|max_hits| self.returned >= max_hits)
+        {
+            return None;
+        }
+        loop {
+            let nearest_pending = (0..self.pending.len()).min_by_key(|i| self.pending[*i].0);
+            // Entities whose AABB starts beyond the nearest blocking hit are never raycast.
+            let next_aabb = self
+                .culled
+                .as_slice()
+                .first()
+                .map(|([near, _], _)| *near)
+                .filter(|near| {
+                    self.blocking
+                        .map_or(true, |(key, _)| FloatOrd(*near) <= key)
+                });
+            // Entities whose AABB starts beyond a pending hit can't have a nearer hit.
+            if let Some(i) = nearest_pending {
+                if next_aabb.map_or(true, |near| self.pending[i].0 .0 <= near) {
+                    let (key, is_blocking, hit) = self.pending.swap_remove(i);
+                    // Like `Raycast::cast_ray`, hits beyond the blocking hit are discarded, except
+                    // those on the blocking entity when every hit per entity is kept.
+                    let all_hits_per_entity = self.settings.all_hits_per_entity;
+                    let blocked = self
+                        .blocking
+                        .is_some_and(|(blocking_key, blocking_entity)| {
+                            key > blocking_key && !(all_hits_per_entity && hit.0 == blocking_entity)
+                        });
+                    if blocked {
+                        continue;
+                    }
+                    if is_blocking && self.blocking.is_none() {
+                        self.blocking = Some((key, hit.0));
+                    }
+                    self.returned += 1;
+                    return Some(hit);
+                }
+            }
+            next_aabb?;
+            let index = self.candidate_count - self.culled.len();
+            let (aabb_distances, entity) = self.culled.next()?;
+            let (settings, pending) = (&self.settings, &mut self.pending);
+            let is_blocking = !self.raycast.pierce_query.contains(entity)
+                && (settings.early_exit_test)(entity)
+                && settings.candidate_early_exit_test.map_or(true, |test| {
+                    test(&RaycastCandidate {
+                        entity,
+                        index,
+                        count: self.candidate_count,
+                        aabb_distances,
+                    })
+                });
+            let result = self.raycast.narrowphase(
+                self.ray,
+                settings.into(),
+                entity,
+                aabb_distances,
+                |hit| {
+                    let key = settings.sorting.key(self.ray, hit.position());
+                    if key <= settings.max_distance {
+                        pending.push((FloatOrd(key), is_blocking, (entity, hit)));
+                    }
+                },
+            );
+            if let Err(error) = result {
+                self.errors.push((entity, error));
+            }
+        }
+    }
+}
+
+/// Whether an entity passes the [`RaycastVisibility`] setting. Bevy only computes the
+/// [`ViewVisibility`] of entities it renders, so an `aabb_target` counts as in view whenever it is
+/// visible.
+pub(crate) fn is_visible(
+    visibility_setting: RaycastVisibility,
+    inherited_visibility: &InheritedVisibility,
+    view_visibility: &ViewVisibility,
+    aabb_target: bool,
+) -> bool {
+    match visibility_setting {
+        RaycastVisibility::Ignore => true,
+        RaycastVisibility::MustBeVisible => inherited_visibility.get(),
+        RaycastVisibility::MustBeVisibleAndInView => {
+            view_visibility.get() || (aabb_target && inherited_visibility.get())
+        }
+    }
+}
+
+/// Whether an entity is on any of the `layers` tested by a raycast. Entities without a
+/// [`RaycastLayers`] are on [`RaycastLayers::DEFAULT`].
+pub(crate) fn is_in_layers(
+    layers: RaycastLayers,
+    layers_query: &Query<Read<RaycastLayers>>,
+    entity: Entity,
+) -> bool {
+    layers == RaycastLayers::ALL
+        || layers.intersects(layers_query.get(entity).unwrap_or(&RaycastLayers::DEFAULT))
+}
+
+/// Whether the level of detail with this visibility `range` is the one mostly shown to a camera
+/// `distance` away, which is the case between the middles of its crossfade margins.
+fn is_lod_shown(range: &VisibilityRange, distance: f32) -> bool {
+    let start = (range.start_margin.start + range.start_margin.end) / 2.0;
+    let end = (range.end_margin.start + range.end_margin.end) / 2.0;
+    (start..end).contains(&distance)
+}
+
+/// The model-to-world matrix of `entity`, accounting for [`RaycastSettings::transform_blend`].
+pub(crate) fn model_matrix(
+    entity: Entity,
+    transform: &GlobalTransform,
+    transform_history: &Query<Read<TransformHistory>>,
+    transform_blend: Option<f32>,
+) -> Mat4 {
+    transform_blend
+        .and_then(|blend| Some(transform_history.get(entity).ok()?.blend(blend)))
+        .unwrap_or_else(|| transform.compute_matrix())
+}
+
+/// The [`CachedTransform`] of the entity, if it is cached for its current `transform`. Blended
+/// transforms are never cached.
+pub(crate) fn cached_transform<'a>(
+    transform_cache: Option<&'a TransformCache>,
+    entity: Entity,
+    transform: &GlobalTransform,
+    transform_blend: Option<f32>,
+) -> Option<&'a CachedTransform> {
+    match transform_blend {
+        Some(_) => None,
+        None => transform_cache?.get(entity, transform),
+    }
+}
+
+/// The transform of an entity, checked to be invertible, as used by [`Raycast::cull_with`].
+pub(crate) fn culling_transform<'a>(
+    transform_cache: Option<&'a TransformCache>,
+    entity: Entity,
+    transform: &GlobalTransform,
+    transform_history: &Query<Read<TransformHistory>>,
+    transform_blend: Option<f32>,
+) -> Result<CullingTransform<'a>, RaycastError> {
+    match cached_transform(transform_cache, entity, transform, transform_blend) {
+        Some(cached) => match cached.world_to_model() {
+            Some(world_to_model) => Ok(CullingTransform::Cached {
+                model_to_world: cached.model_to_world(),
+                world_to_model,
+            }),
+            None => Err(RaycastError::DegenerateTransform),
+        },
+        None => {
+            let transform = model_matrix(entity, transform, transform_history, transform_blend);
+            match is_degenerate_transform(&transform) {
+                true => Err(RaycastError::DegenerateTransform),
+                false => Ok(CullingTransform::Computed(transform)),
+            }
+        }
+    }
+}
+
+/// The bounds of an entity, given to the bounds test of [`Raycast::cull_with`].
+pub(crate) struct CullingBounds<'a> {
+    /// The model-space AABB of the entity.
+    pub aabb: Aabb,
+    pub sphere: Option<&'a BoundingSphere>,
+    pub transform: CullingTransform<'a>,
+}
+
+/// The invertible transform of an entity being culled, from the [`TransformCache`] if it is cached.
+#[derive(Clone, Copy)]
+pub(crate) enum CullingTransform<'a> {
+    Cached {
+        model_to_world: &'a Mat4,
+        world_to_model: &'a Mat4,
+    },
+    /// The model matrix, which is inverted as needed.
+    Computed(Mat4),
+}
+
+impl CullingTransform<'_> {
+    pub fn model_to_world(&self) -> Mat4 {
+        match self {
+            CullingTransform::Cached { model_to_world, .. } => **model_to_world,
+            CullingTransform::Computed(transform) => *transform,
+        }
+    }
+
+    pub fn world_to_model(&self) -> Mat4 {
+        match self {
+            CullingTransform::Cached { world_to_model, .. } => **world_to_model,
+            CullingTransform::Computed(transform) => transform.inverse(),
+        }
+    }
+
+    /// Prepares the `ray` for tests against the model-space AABB of the entity.
+    pub fn slab_ray(&self, ray: Ray3d) -> SlabRay {
+        match self {
+            CullingTransform::Cached { world_to_model, .. } => {
+                SlabRay::with_world_to_model(ray, world_to_model)
+            }
+            CullingTransform::Computed(transform) => SlabRay::in_model_space(ray, transform),
+        }
+    }
+}
+
+/// The world-space AABB enclosing the model-space `aabb` placed with `model_to_world`.
+pub(crate) fn world_aabb(aabb: &Aabb, model_to_world: &Mat4) -> Aabb {
+    let half_extents = [
+        model_to_world.x_axis,
+        model_to_world.y_axis,
+        model_to_world.z_axis,
+    ]
+    .iter()
+    .zip(aabb.half_extents.to_array())
+    .map(|(axis, extent)| Vec3A::from(axis.truncate().abs()) * extent)
+    .fold(Vec3A::ZERO, |sum, extent| sum + extent);
+    Aabb {
+        center: model_to_world.transform_point3a(aabb.center),
+        half_extents,
+    }
+}
+
+/// Replaces the normal of the hit with the normal of the hit triangle, which is in world space.
+/// Mirroring transforms reverse the winding of the triangle, so the normal is flipped to keep
+/// pointing out of the mesh, like interpolated normals do.
+fn with_geometric_normal(intersection: IntersectionData, transform: &Mat4) -> IntersectionData {
+    match intersection.triangle() {
+        Some([a, b, c]) => {
+            let normal =
+                (b - a).cross(c - a).normalize_or_zero() * transform.determinant().signum();
+            intersection.with_normal(normal)
+        }
+        None => intersection,
+    }
+}
+
+/// How to treat backfaces of an entity, depending on whether it has [`NoBackfaceCulling`].
+pub(crate) fn backfaces(no_backface_culling: Option<&NoBackfaceCulling>) -> Backfaces {
+    match no_backface_culling {
+        Some(_) => Backfaces::Include,
+        None => Backfaces::Cull,
+    }
+}
+
+/// The triangles of an entity that are tested by the narrowphase of a raycast.
+enum RaycastGeometry<'a> {
+    /// A mesh asset, the data cached for it, and the range of its triangles to test, if it is only
+    /// partly tested.
+    Mesh(&'a Mesh, MeshCaches<'a>, Option<Range<usize>>),
+    Dynamic(&'a DynamicRaycastMesh),
+    /// The faces of the AABB of a [`RaycastAabbTarget`].
+    Aabb(&'a Aabb),
+}
+
+impl RaycastGeometry<'_> {
+    fn triangle_count(&self) -> usize {
+        match self {
+            RaycastGeometry::Mesh(mesh, _, triangles) => triangles
+                .as_ref()
+                .map_or_else(|| mesh_triangle_count(mesh), |triangles| triangles.len()),
+            RaycastGeometry::Dynamic(mesh) => mesh.triangle_count(),
+            RaycastGeometry::Aabb(_) => 0,
+        }
+    }
+
+    fn ray_intersection(
+        &self,
+        mesh_transform: &Mat4,
+        ray: Ray3d,
+        backfaces: Backfaces,
+        hit_data: HitData,
+        max_distance: f32,
+    ) -> Option<IntersectionData> {
+        match self {
+            RaycastGeometry::Mesh(mesh, caches, triangles) => {
+                ray_intersection_over_mesh_with_caches(
+                    mesh,
+                    *caches,
+                    triangles.clone(),
+                    mesh_transform,
+                    ray,
+                    backfaces,
+                    hit_data,
+                    max_distance,
+                )
+            }
+            RaycastGeometry::Dynamic(mesh) => mesh.ray_intersection(mesh_transform, ray, backfaces),
+            RaycastGeometry::Aabb(aabb) => {
+                aabb_face_intersections(aabb, mesh_transform, ray, backfaces)
+                    .first()
+                    .copied()
+            }
+        }
+    }
+
+    fn ray_intersections(
+        &self,
+        mesh_transform: &Mat4,
+        ray: Ray3d,
+        backfaces: Backfaces,
+        hit_data: HitData,
+        max_distance: f32,
+    ) -> Vec<IntersectionData> {
+        match self {
+            RaycastGeometry::Mesh(mesh, caches, triangles) => {
+                ray_intersections_over_mesh_with_caches(
+                    mesh,
+                    *caches,
+                    triangles.clone(),
+                    mesh_transform,
+                    ray,
+                    backfaces,
+                    hit_data,
+                    max_distance,
+                )
+            }
+            RaycastGeometry::Dynamic(mesh) => {
+                mesh.ray_intersections(mesh_transform, ray, backfaces)
+            }
+            RaycastGeometry::Aabb(aabb) => {
+                aabb_face_intersections(aabb, mesh_transform, ray, backfaces)
+            }
+        }
+    }
+}
+
+/// The hits where the ray enters and exits the AABB, nearest first, skipping hits behind the ray
+/// origin. The exit is a backface, so it is only hit when backfaces are included.
+fn aabb_face_intersections(
+    aabb: &Aabb,
+    transform: &Mat4,
+    ray: Ray3d,
+    backfaces: Backfaces,
+) -> Vec<IntersectionData> {
+    let Some([entry, exit]) = ray_aabb_intersections(ray, aabb, transform) else {
+        return Vec::new();
+    };
+    let faces = match backfaces {
+        Backfaces::Cull => vec![entry],
+        Backfaces::Include => vec![entry, exit],
+    };
+    faces
+        .into_iter()
+        .filter(|hit| hit.distance() >= 0.0)
+        .collect()
+}
+
+/// A stand-in hit where the ray enters an entity's AABB, used when the mesh is too dense to test.
+fn approximate_aabb_intersection(ray: Ray3d, distance: f32) -> IntersectionData {
+    IntersectionData::new(
+        ray.get_point(distance),
+        -*ray.direction,
+        Vec3::ZERO,
+        distance,
+        None,
+        None,
+    )
+    .with_approximate(true)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::{
+        ecs::system::RunSystemOnce,
+        prelude::*,
+        render::{primitives::Aabb, render_asset::RenderAssetUsages},
+    };
+
+    use super::*;
+
+    /// Spawns unit planes facing `+Z` at each of the `z` positions, and returns their entities.
+    pub(crate) fn spawn_planes(world: &mut World, positions: &[f32]) -> Vec<Entity> {
+        // Culling runs in parallel on the compute task pool.
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut meshes = Assets::<Mesh>::default();
+        let plane = meshes.add(
+            Mesh::new(
+                bevy::render::mesh::PrimitiveTopology::TriangleList,
+                RenderAssetUsages::default(),
+            )
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_POSITION,
+                vec![
+                    [-1.0, -1.0, 0.0],
+                    [1.0, -1.0, 0.0],
+                    [1.0, 1.0, 0.0],
+                    [-1.0, 1.0, 0.0],
+                ],
+            )
+            .with_inserted_indices(bevy::render::mesh::Indices::U32(vec![0, 1, 2, 0, 2, 3])),
+        );
+        world.insert_resource(meshes);
+        positions
+            .iter()
+            .map(|z| {
+                world
+                    .spawn((
+                        plane.clone(),
+                        Aabb::from_min_max(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0)),
+                        GlobalTransform::from_translation(Vec3::Z * *z),
+                        InheritedVisibility::VISIBLE,
+                        ViewVisibility::default(),
+                        NoBackfaceCulling,
+                    ))
+                    .id()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sort_keys() {
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+        let behind = Vec3::new(1.0, 0.0, 2.0);
+        assert_eq!(HitSorting::AlongRay.key(ray, behind), -2.0);
+        assert_eq!(HitSorting::FromOrigin.key(ray, behind), 5.0_f32.sqrt());
+        let ahead = Vec3::new(0.0, 0.0, -3.0);
+        assert_eq!(HitSorting::AlongRay.key(ray, ahead), 3.0);
+        assert_eq!(HitSorting::FromOrigin.key(ray, ahead), 3.0);
+    }
+
+    #[test]
+    fn hits_sorted_nearest_first() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-3.0, -1.0, -2.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            raycast
+                .cast_ray(ray, &settings)
+                .iter()
+                .map(|(entity, hit)| (*entity, hit.distance()))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(
+            hits,
+            vec![(planes[1], 1.0), (planes[2], 2.0), (planes[0], 3.0)]
+        );
+    }
+
+    #[test]
+    fn max_hits_keeps_nearest() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-5.0, -3.0, -1.0, -4.0, -2.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            [2, 0].map(|max_hits| {
+                let settings = settings.clone().with_max_hits(max_hits);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            })
+        });
+        assert_eq!(hits, [vec![planes[2], planes[4]], vec![]]);
+    }
+
+    #[test]
+    fn max_hits_bounds_the_hits_kept_while_raycasting() {
+        let mut world = World::new();
+        let depths: Vec<f32> = (1..=20).map(|i| -i as f32).collect();
+        let planes = spawn_planes(&mut world, &depths);
+        let (hits, capacity) = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit()
+                .with_max_hits(3);
+            let hits = [None, Some(0)].map(|threshold| {
+                let settings = RaycastSettings {
+                    parallel_narrowphase_threshold: threshold,
+                    ..settings.clone()
+                };
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            });
+            (hits, raycast.output.capacity())
+        });
+        assert_eq!(hits[0], planes[..3]);
+        assert_eq!(hits[1], planes[..3]);
+        assert!(capacity < planes.len(), "kept {capacity} hits");
+    }
+
+    #[test]
+    fn candidate_tests_see_candidate_context() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-4.0, -1.0, -3.0, -2.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            let mut cast = |settings: &RaycastSettings| {
+                raycast
+                    .cast_ray(ray, settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            };
+            // Only the first three candidates, or those entered within 2.5, are raycast.
+            let candidate_filter = |candidate: &RaycastCandidate| {
+                assert_eq!(candidate.count, 4);
+                candidate.index < 3 && candidate.aabb_distances[0] < 2.5
+            };
+            let filtered = settings
+                .clone()
+                .never_early_exit()
+                .with_candidate_filter(&candidate_filter);
+            // The nearest candidate doesn't block the ray.
+            let early_exit = |candidate: &RaycastCandidate| candidate.index > 0;
+            let early_exit = settings.with_candidate_early_exit_test(&early_exit);
+            [cast(&filtered), cast(&early_exit)]
+        });
+        assert_eq!(
+            hits,
+            [vec![planes[1], planes[3]], vec![planes[1], planes[3]]]
+        );
+    }
+
+    #[test]
+    fn warm_start_keeps_nearest_hit() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0, -5.0]);
+        let warm_starts = planes.clone();
+        let hits = world.run_system_once(move |mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            // The warm start entity was the nearest hit, but another entity moved in front of it.
+            warm_starts
+                .iter()
+                .map(|warm_start| {
+                    let settings = settings.clone().with_warm_start(*warm_start);
+                    raycast
+                        .cast_ray(ray, &settings)
+                        .iter()
+                        .map(|(entity, _)| *entity)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(hits, vec![vec![planes[0]]; 3]);
+    }
+
+    #[test]
+    fn warm_start_keeps_candidate_indices() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0, -5.0]);
+        let warm_start = planes[2];
+        let hits = world.run_system_once(move |mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let skip_nearest = |candidate: &RaycastCandidate| candidate.index != 0;
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .with_candidate_filter(&skip_nearest);
+            [settings.clone(), settings.with_warm_start(warm_start)].map(|settings| {
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            })
+        });
+        assert_eq!(hits, [vec![planes[1]], vec![planes[1]]]);
+    }
+
+    #[test]
+    fn parallel_narrowphase_matches_serial() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0, -5.0, -7.0]);
+        let pierced = planes[0];
+        let hits = world.run_system_once(move |mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let early_exit = |entity: Entity| entity != pierced;
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .with_early_exit_test(&early_exit);
+            [
+                settings.clone(),
+                settings.with_parallel_narrowphase_threshold(2),
+            ]
+            .map(|settings| {
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, hit)| (*entity, hit.distance()))
+                    .collect::<Vec<_>>()
+            })
+        });
+        assert_eq!(hits[0], vec![(planes[0], 1.0), (planes[1], 3.0)]);
+        assert_eq!(hits[1], hits[0]);
+    }
+
+    #[test]
+    fn hit_iterator_returns_hits_nearest_first() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-5.0, -3.0, -1.0, -4.0, -2.0]);
+        let skip = planes[2];
+        let (all, first) = world.run_system_once(move |mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let all: Vec<_> = raycast.cast_ray_iter(ray, &settings).collect();
+            let first = raycast
+                .cast_ray_iter(ray, &settings)
+                .find(|(entity, _)| *entity != skip);
+            (all, first)
+        });
+        let distances: Vec<_> = all.iter().map(|(_, hit)| hit.distance()).collect();
+        assert_eq!(distances, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(first.map(|(entity, _)| entity), Some(planes[4]));
+    }
+
+    #[test]
+    fn hit_iterator_matches_cast_ray() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-5.0, -3.0, -1.0, -4.0, -2.0, 1.5]);
+        world.entity_mut(planes[2]).insert(RaycastPierce);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            let behind = settings
+                .clone()
+                .never_early_exit()
+                .with_include_behind_origin(true)
+                .with_sorting(HitSorting::FromOrigin);
+            [
+                settings.clone(),
+                settings.clone().never_early_exit().with_max_hits(3),
+                behind.clone(),
+                behind.with_max_distance(1.5),
+            ]
+            .map(|settings| {
+                let distances = |hits: &mut dyn Iterator<Item = (Entity, IntersectionData)>| {
+                    hits.map(|(_, hit)| hit.distance()).collect::<Vec<_>>()
+                };
+                let iter = distances(&mut raycast.cast_ray_iter(ray, &settings));
+                let cast = distances(&mut raycast.cast_ray(ray, &settings).iter().copied());
+                assert_eq!(iter, cast);
+                iter
+            })
+        });
+        // The nearest plane is pierced, so the raycast stops at the next one.
+        assert_eq!(hits[0], vec![1.0, 2.0]);
+        assert_eq!(hits[1], vec![1.0, 2.0, 3.0]);
+        assert_eq!(hits[2], vec![1.0, -1.5, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(hits[3], vec![1.0, -1.5]);
+    }
+
+    #[test]
+    fn hit_iterator_collects_errors() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        world
+            .entity_mut(planes[1])
+            .insert(Handle::<Mesh>::default());
+        let (hits, errors) = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let mut hits = raycast.cast_ray_iter(ray, &settings);
+            let entities = hits.by_ref().map(|(entity, _)| entity).collect::<Vec<_>>();
+            (entities, hits.errors().to_vec())
+        });
+        assert_eq!(hits, vec![planes[0]]);
+        assert_eq!(errors, vec![(planes[1], RaycastError::MissingMesh)]);
+    }
+
+    #[test]
+    fn geometric_normals_replace_broken_normals() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let (_, mesh) = meshes.iter_mut().next().unwrap();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, -1.0]; 4]);
+        assert_eq!(find_mismatched_normals(mesh, FRAC_PI_2), vec![0, 1]);
+        world.entity_mut(planes[0]).insert(GeometricNormals);
+
+        let normals = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            [false, true].map(|geometric_normals| {
+                let settings = settings.clone().with_geometric_normals(geometric_normals);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(_, hit)| hit.normal())
+                    .collect::<Vec<_>>()
+            })
+        });
+        assert_eq!(normals[0], [Vec3::Z, Vec3::NEG_Z]);
+        assert_eq!(normals[1], [Vec3::Z, Vec3::Z]);
+    }
+
+    #[test]
+    fn aabb_targets_are_hit_on_their_faces() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[]);
+        let target = world
+            .spawn((
+                RaycastAabbTarget,
+                Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+                GlobalTransform::from_scale(Vec3::new(1.0, 1.0, 2.0)),
+                InheritedVisibility::VISIBLE,
+                ViewVisibility::default(),
+            ))
+            .id();
+        let cast = |world: &mut World, origin: Vec3| {
+            world.run_system_once(move |mut raycast: Raycast| {
+                let settings = RaycastSettings::default()
+                    .with_visibility(RaycastVisibility::Ignore)
+                    .with_all_hits_per_entity(true);
+                raycast
+                    .cast_ray(Ray3d::new(origin, Vec3::NEG_Z), &settings)
+                    .iter()
+                    .map(|(entity, hit)| (*entity, hit.distance(), hit.normal()))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(cast(&mut world, Vec3::Z * 5.0), [(target, 3.0, Vec3::Z)]);
+        assert_eq!(cast(&mut world, Vec3::ZERO), []);
+
+        world.entity_mut(target).insert(NoBackfaceCulling);
+        assert_eq!(
+            cast(&mut world, Vec3::Z * 5.0),
+            [(target, 3.0, Vec3::Z), (target, 7.0, Vec3::NEG_Z)]
+        );
+        assert_eq!(cast(&mut world, Vec3::ZERO), [(target, 2.0, Vec3::NEG_Z)]);
+    }
+
+    #[test]
+    fn aabb_targets_are_hit_with_default_settings() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[]);
+        let target = world
+            .spawn((
+                RaycastAabbTarget,
+                Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+                GlobalTransform::from_xyz(0.0, 0.0, -5.0),
+                InheritedVisibility::VISIBLE,
+                ViewVisibility::default(),
+            ))
+            .id();
+        let cast = |world: &mut World| {
+            world.run_system_once(|mut raycast: Raycast| {
+                let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+                raycast
+                    .cast_ray(ray, &default())
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(cast(&mut world), [target]);
+
+        world.entity_mut(target).insert(InheritedVisibility::HIDDEN);
+        assert_eq!(cast(&mut world), []);
+    }
+
+    #[test]
+    fn line_cast_includes_hits_behind_origin() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[1.0, -1.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let ray_hits = raycast.cast_ray(ray, &settings).len();
+            let settings = settings.with_include_behind_origin(true);
+            let line_hits = raycast
+                .cast_ray(ray, &settings)
+                .iter()
+                .map(|(entity, hit)| (*entity, hit.distance()))
+                .collect::<Vec<_>>();
+            (ray_hits, line_hits)
+        });
+        assert_eq!(hits, (1, vec![(planes[0], -1.0), (planes[1], 1.0)]));
+    }
+
+    #[test]
+    fn candidates_limit_the_raycast() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0, -3.0]);
+        let candidates = [planes[2], planes[1]];
+        let hits = world.run_system_once(move |mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            raycast
+                .cast_ray_on_candidates(ray, &settings, candidates)
+                .iter()
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(hits, vec![planes[1], planes[2]]);
+    }
+
+    #[test]
+    fn all_hits_per_entity_include_exit_points() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-10.0]);
+        let cube = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::new(2.0, 2.0, 2.0));
+        let cube = world
+            .spawn((
+                cube,
+                Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+                GlobalTransform::from_xyz(0.0, 0.0, -5.0),
+                InheritedVisibility::VISIBLE,
+                ViewVisibility::default(),
+                NoBackfaceCulling,
+            ))
+            .id();
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::new(0.1, 0.2, 0.0), Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            let mut distances = |settings: &RaycastSettings| {
+                raycast
+                    .cast_ray(ray, settings)
+                    .iter()
+                    .map(|(entity, hit)| (*entity, hit.distance()))
+                    .collect::<Vec<_>>()
+            };
+            let nearest = distances(&settings);
+            let settings = settings.with_all_hits_per_entity(true);
+            let blocked = distances(&settings);
+            let all = distances(&settings.never_early_exit());
+            (nearest, blocked, all)
+        });
+        assert_eq!(hits.0, vec![(cube, 4.0)]);
+        assert_eq!(hits.1, vec![(cube, 4.0), (cube, 6.0)]);
+        assert_eq!(hits.2, vec![(cube, 4.0), (cube, 6.0), (planes[0], 10.0)]);
+    }
+
+    #[test]
+    fn lod_viewpoint_skips_hidden_levels_of_detail() {
+        let mut world = World::new();
+        let lods = spawn_planes(&mut world, &[-2.0, -2.0]);
+        world
+            .entity_mut(lods[0])
+            .insert(VisibilityRange::abrupt(0.0, 5.0));
+        world.entity_mut(lods[1]).insert(VisibilityRange {
+            start_margin: 4.0..6.0,
+            end_margin: 100.0..100.0,
+        });
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            [0.0, 4.5, 10.0].map(|z| {
+                let ray = Ray3d::new(Vec3::new(0.5, -0.5, z), Vec3::NEG_Z);
+                let settings = settings.clone().with_lod_viewpoint(ray.origin);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            })
+        });
+        // The second viewpoint is 6.5 from the planes, past the middle of the crossfade.
+        assert_eq!(hits, [vec![lods[0]], vec![lods[1]], vec![lods[1]]]);
+    }
+
+    #[test]
+    fn hits_report_source_mesh() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        let mesh = world.get::<Handle<Mesh>>(planes[0]).unwrap().clone();
+        world
+            .entity_mut(planes[1])
+            .insert(SimplifiedMesh::new(mesh.clone()));
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            raycast
+                .cast_ray(ray, &settings)
+                .iter()
+                .map(|(_, hit)| (hit.source_mesh(), hit.is_proxy()))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(
+            hits,
+            vec![(Some(mesh.id()), false), (Some(mesh.id()), true)]
+        );
+    }
+
+    #[test]
+    fn looked_up_triangles_match_captured_triangles() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        world.entity_mut(planes[0]).insert(GlobalTransform::from(
+            Transform::from_xyz(0.1, 0.0, -1.0)
+                .with_rotation(Quat::from_rotation_z(0.3))
+                .with_scale(Vec3::new(2.0, 3.0, 1.0)),
+        ));
+        let mesh = world.get::<Handle<Mesh>>(planes[1]).unwrap().clone();
+        let meshes = world.resource::<Assets<Mesh>>();
+        let dynamic_mesh = DynamicRaycastMesh::from_mesh(meshes.get(&mesh).unwrap()).unwrap();
+        world
+            .entity_mut(planes[1])
+            .remove::<Handle<Mesh>>()
+            .insert(dynamic_mesh);
+
+        let triangles = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::new(0.2, 0.1, 0.0), Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let captured = raycast
+                .cast_ray(ray, &settings.clone().with_hit_data(HitData::ALL))
+                .to_vec();
+            let looked_up = raycast.cast_ray(ray, &settings).to_vec();
+            captured
+                .iter()
+                .zip(&looked_up)
+                .map(|((_, captured), (entity, hit))| {
+                    assert_eq!(hit.triangle(), None);
+                    (captured.triangle(), raycast.hit_triangle(*entity, hit))
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(triangles.len(), 2);
+        for (captured, looked_up) in triangles {
+            let (captured, looked_up) = (captured.unwrap(), looked_up.unwrap());
+            for (a, b) in captured.iter().zip(looked_up) {
+                assert!(a.abs_diff_eq(b, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn simplified_index_ranges_limit_raycast_triangles() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0]);
+        let mesh = world.get::<Handle<Mesh>>(planes[0]).unwrap().clone();
+        // Only the second of the two triangles of the plane, which covers its upper left half.
+        let simplified = SimplifiedMesh::new(mesh.clone()).with_index_range(3..6);
+        let meshes = world.resource::<Assets<Mesh>>();
+        assert_eq!(
+            simplified.triangle_range(meshes.get(&mesh).unwrap()),
+            Some(1..2)
+        );
+        world.entity_mut(planes[0]).insert(simplified);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            [Vec2::new(-0.5, 0.5), Vec2::new(0.5, -0.5)].map(|xy| {
+                let ray = Ray3d::new(xy.extend(0.0), Vec3::NEG_Z);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .first()
+                    .and_then(|(_, hit)| hit.triangle_index())
+            })
+        });
+        assert_eq!(hits, [Some(1), None]);
+    }
+
+    #[test]
+    fn errors_explain_skipped_entities() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0, -3.0, -4.0]);
+        let lines = world.resource_mut::<Assets<Mesh>>().add(Mesh::new(
+            bevy::render::mesh::PrimitiveTopology::LineList,
+            RenderAssetUsages::default(),
+        ));
+        world.entity_mut(planes[0]).insert(lines);
+        world
+            .entity_mut(planes[1])
+            .insert(Handle::<Mesh>::default());
+        world.entity_mut(planes[2]).insert(GlobalTransform::from(
+            Transform::from_xyz(0.0, 0.0, -3.0).with_scale(Vec3::ZERO),
+        ));
+
+        let (hits, mut errors) = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let (hits, errors) = raycast.cast_ray_with_errors(ray, &settings);
+            let hits: Vec<_> = hits.iter().map(|(entity, _)| *entity).collect();
+            (hits, errors.to_vec())
+        });
+        errors.sort_by_key(|(entity, _)| *entity);
+        assert_eq!(hits, vec![planes[3]]);
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    planes[0],
+                    RaycastError::UnsupportedTopology(
+                        bevy::render::mesh::PrimitiveTopology::LineList
+                    )
+                ),
+                (planes[1], RaycastError::MissingMesh),
+                (planes[2], RaycastError::DegenerateTransform),
+            ]
+        );
+    }
+
+    #[test]
+    fn hit_data_is_only_computed_when_requested() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            [HitData::ALL, HitData::MINIMAL].map(|hit_data| {
+                let settings = settings.clone().with_hit_data(hit_data);
+                raycast.cast_ray(ray, &settings)[0].1
+            })
+        });
+        assert!(hits[0].triangle().is_some());
+        assert_eq!(hits[1].triangle(), None);
+        assert!(!HitData::default().triangle);
+        // Data that every hit has is still set.
+        assert_eq!(hits[1].triangle_index(), hits[0].triangle_index());
+        assert_eq!(hits[1].position(), hits[0].position());
+    }
+
+    #[test]
+    fn degenerate_transforms_are_skipped() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0, -3.0, -4.0]);
+        // Scaled to zero, close enough to zero that the inverse overflows, and scaled to zero on
+        // one axis only.
+        let scales = [Vec3::ZERO, Vec3::splat(1e-14), Vec3::new(1.0, 1.0, 0.0)];
+        for (plane, scale) in planes.iter().zip(scales) {
+            let transform = world
+                .get::<GlobalTransform>(*plane)
+                .unwrap()
+                .compute_transform();
+            world
+                .entity_mut(*plane)
+                .insert(GlobalTransform::from(transform.with_scale(scale)));
+        }
+
+        let (hits, mut errors) = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let (hits, errors) = raycast.cast_ray_with_errors(ray, &settings);
+            let hits: Vec<_> = hits.iter().map(|(e, hit)| (*e, hit.distance())).collect();
+            (hits, errors.to_vec())
+        });
+        errors.sort_by_key(|(entity, _)| *entity);
+        assert_eq!(hits, vec![(planes[3], 4.0)]);
+        assert_eq!(
+            errors,
+            planes[..3]
+                .iter()
+                .map(|plane| (*plane, RaycastError::DegenerateTransform))
+                .collect::<Vec<_>>()
+        );
+
+        let contained = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast
+                .entities_containing_point(
+                    Vec3::new(0.0, 0.0, -2.0),
+                    PointContainment::Aabb,
+                    &settings,
+                )
+                .to_vec()
+        });
+        assert!(contained.is_empty());
+    }
+
+    #[test]
+    fn segment_stops_at_end() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-3.0, -1.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .never_early_exit();
+            let segment = raycast
+                .cast_segment(Vec3::ZERO, Vec3::new(0.0, 0.0, -2.0), &settings)
+                .iter()
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<_>>();
+            let degenerate = raycast
+                .cast_segment(Vec3::ZERO, Vec3::ZERO, &settings)
+                .len();
+            (segment, degenerate)
+        });
+        assert_eq!(hits, (vec![planes[1]], 0));
+    }
+
+    #[test]
+    fn frustum_clipped_rays_ignore_blockers_outside_the_frustum() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -5.0, -7.0, -12.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            // Bevy swaps the near and far planes to reverse the depth.
+            let orthographic = Mat4::orthographic_rh(-2.0, 2.0, -2.0, 2.0, 10.0, 4.0);
+            let frustum = Frustum::from_clip_from_world(&orthographic);
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            [
+                settings.clone(),
+                settings.never_early_exit().with_max_hits(1),
+            ]
+            .map(|settings| {
+                raycast
+                    .cast_ray_clipped_to_frustum(ray, &frustum, &settings)
+                    .iter()
+                    .map(|(entity, hit)| (*entity, hit.distance()))
+                    .collect::<Vec<_>>()
+            })
+        });
+        for hits in hits {
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].0, planes[1]);
+            assert!((hits[0].1 - 5.0).abs() < 1e-4);
+        }
     }
 }
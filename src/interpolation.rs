@@ -0,0 +1,126 @@
+//! # Fixed Timestep Raycasting
+//!
+//! Gameplay running in [`FixedUpdate`] can step zero, one, or several times per rendered frame, and
+//! games commonly interpolate what is drawn between the two most recent fixed steps. A raycast made
+//! against the latest [`GlobalTransform`]s can then disagree with what the player sees on screen.
+//!
+//! To line the two up, add the [`TransformHistoryPlugin`], insert a [`TransformHistory`] on the
+//! entities that move in the fixed timestep, and set
+//! [`RaycastSettings::with_transform_blend`] to the same blend factor used for rendering, usually
+//! `Time<Fixed>::overstep_fraction()`:
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! fn fixed_raycast(mut raycast: Raycast, time: Res<Time<Fixed>>) {
+//!     let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+//!     let settings = RaycastSettings::default().with_transform_blend(time.overstep_fraction());
+//!     let hits = raycast.cast_ray(ray, &settings);
+//! }
+//! ```
+//!
+//! Entities without a [`TransformHistory`] always use their current [`GlobalTransform`].
+//!
+//! [`GlobalTransform`]s are only propagated in [`PostUpdate`], so they don't change between the
+//! fixed timesteps of a frame. The history records the local [`Transform`] at the end of each
+//! fixed timestep instead, and places it under the [`GlobalTransform`] of the entity's parent.
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_hierarchy::Parent;
+use bevy_math::Mat4;
+use bevy_reflect::Reflect;
+use bevy_transform::components::{GlobalTransform, Transform};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Records the [`Transform`] of every entity with a [`TransformHistory`] at the end of each fixed
+/// timestep.
+#[derive(Default)]
+pub struct TransformHistoryPlugin;
+impl Plugin for TransformHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TransformHistory>()
+            .add_systems(FixedLast, update_transform_history);
+    }
+}
+
+/// The local [`Transform`] of this entity at the end of the previous and current fixed timestep.
+///
+/// Maintained by the [`TransformHistoryPlugin`].
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct TransformHistory {
+    pub previous: Transform,
+    pub current: Transform,
+    /// The [`GlobalTransform`] of the entity's parent when the current transform was recorded, or
+    /// the identity if it has no parent.
+    pub parent: GlobalTransform,
+}
+
+impl TransformHistory {
+    /// Blends between the previous and current transform, and returns the world-space result. A
+    /// `blend` of `0.0` is the previous transform, `1.0` is the current transform, and values
+    /// greater than `1.0` extrapolate.
+    pub fn blend(&self, blend: f32) -> Mat4 {
+        let local = Mat4::from_scale_rotation_translation(
+            self.previous.scale.lerp(self.current.scale, blend),
+            self.previous.rotation.slerp(self.current.rotation, blend),
+            self.previous
+                .translation
+                .lerp(self.current.translation, blend),
+        );
+        self.parent.compute_matrix() * local
+    }
+}
+
+/// Shifts the current transform of each [`TransformHistory`] into the previous transform, and
+/// records the latest [`Transform`].
+pub fn update_transform_history(
+    mut query: Query<(&Transform, Option<&Parent>, &mut TransformHistory)>,
+    parents: Query<&GlobalTransform>,
+) {
+    for (transform, parent, mut history) in &mut query {
+        if history.is_added() {
+            history.previous = *transform;
+        } else {
+            history.previous = history.current;
+        }
+        history.current = *transform;
+        history.parent = parent
+            .and_then(|parent| parents.get(parent.get()).ok())
+            .copied()
+            .unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn history_records_every_fixed_step_of_a_frame() {
+        let mut world = World::new();
+        let parent = world.spawn(GlobalTransform::from_xyz(0.0, 10.0, 0.0)).id();
+        let entity = world
+            .spawn((Transform::default(), TransformHistory::default()))
+            .set_parent(parent)
+            .id();
+
+        // Two fixed steps run in the same frame, so transforms are not propagated between them.
+        let mut fixed_last = Schedule::default();
+        fixed_last.add_systems(update_transform_history);
+        for x in [1.0, 2.0] {
+            world.get_mut::<Transform>(entity).unwrap().translation.x = x;
+            fixed_last.run(&mut world);
+        }
+        let history = world.get::<TransformHistory>(entity).unwrap();
+        assert_eq!(history.previous.translation.x, 1.0);
+        assert_eq!(history.current.translation.x, 2.0);
+        let blended = history.blend(0.5).transform_point3(Vec3::ZERO);
+        assert_eq!(blended, Vec3::new(1.5, 10.0, 0.0));
+    }
+}
@@ -0,0 +1,282 @@
+//! # Raycast Jobs
+//!
+//! Systems that need many rays, but not their hits right away, such as AI line of sight checks or
+//! audio occlusion, can queue them as jobs in the [`RaycastJobs`] resource instead of raycasting
+//! them in the frame they are needed. The [`RaycastJobPlugin`] raycasts queued jobs in
+//! [`PostUpdate`], at most [`RaycastJobs::max_jobs_per_frame`] of them each frame, and keeps their
+//! hits until they are taken with [`RaycastJobs::take`].
+//!
+//! Submitting a job returns a [`RaycastJobToken`], which is used to take its hits, and to cancel it
+//! with [`RaycastJobToken::cancel`] when its hits are no longer needed. Jobs are also cancelled when
+//! every copy of their token is dropped, since their hits could never be taken, and jobs submitted
+//! with [`RaycastJobs::submit_for`] are cancelled when their owner entity despawns. Cancelled jobs
+//! are never raycast, and their hits are discarded if they were already done.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! #[derive(Component)]
+//! struct LineOfSight(Option<RaycastJobToken>);
+//!
+//! fn check_line_of_sight(
+//!     mut jobs: ResMut<RaycastJobs>,
+//!     mut guards: Query<(Entity, &GlobalTransform, &mut LineOfSight)>,
+//! ) {
+//!     for (guard, transform, mut sight) in &mut guards {
+//!         if let Some(hits) = sight.0.as_ref().and_then(|token| jobs.take(token)) {
+//!             // React to what the guard sees.
+//!         }
+//!         if sight.0.as_ref().map_or(true, |token| !jobs.is_pending(token)) {
+//!             let ray = Ray3d::new(transform.translation(), *transform.forward());
+//!             sight.0 = Some(jobs.submit_for(guard, ray, default()));
+//!         }
+//!     }
+//! }
+//!
+//! App::new()
+//!     .add_plugins(RaycastJobPlugin)
+//!     .insert_resource(RaycastJobs::new(100))
+//!     .add_systems(Update, check_line_of_sight);
+//! ```
+//!
+//! Jobs are raycast with the [`Raycast`] system param, so they spend the [`RaycastBudget`] like any
+//! other raycast. [`RaycastPriorityClass::Cosmetic`] jobs skipped by the budget stay queued, and are
+//! raycast first in the next frame.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_math::Ray3d;
+use bevy_transform::TransformSystem;
+use bevy_utils::HashMap;
+
+use crate::prelude::*;
+
+/// Adds the [`RaycastJobs`] resource, and raycasts its queued jobs in [`PostUpdate`], after
+/// transforms are propagated. See the [module docs](self).
+#[derive(Default)]
+pub struct RaycastJobPlugin;
+impl Plugin for RaycastJobPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RaycastJobs>().add_systems(
+            PostUpdate,
+            run_raycast_jobs.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// A handle to a job queued in the [`RaycastJobs`], used to take its hits or cancel it. Clones of
+/// the token refer to the same job, so it can be cancelled from anywhere a copy is kept. The job
+/// is cancelled once every copy is dropped.
+#[derive(Clone, Debug)]
+pub struct RaycastJobToken {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RaycastJobToken {
+    /// Cancels the job. It won't be raycast, and its hits are discarded if it is already done.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the job was cancelled with [`RaycastJobToken::cancel`].
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// A job and what it is tied to, kept by the [`RaycastJobs`] while it is queued or done.
+struct RaycastJob {
+    token: RaycastJobToken,
+    owner: Option<Entity>,
+}
+
+impl RaycastJob {
+    /// Returns `true` if the hits of the job are no longer needed.
+    fn is_cancelled(&self, entities: &Query<()>) -> bool {
+        // The queue holds the last copy of the token, so nobody can take the hits.
+        self.token.is_cancelled()
+            || Arc::strong_count(&self.token.cancelled) == 1
+            || self.owner.is_some_and(|owner| !entities.contains(owner))
+    }
+}
+
+/// Queued raycasts, and the hits of those that are done. See the [module docs](self).
+#[derive(Resource, Default)]
+pub struct RaycastJobs {
+    /// The number of jobs raycast each frame. `None`, the default, raycasts every queued job.
+    pub max_jobs_per_frame: Option<usize>,
+    next_id: u64,
+    queue: VecDeque<(RaycastJob, Ray3d, OwnedRaycastSettings)>,
+    done: HashMap<u64, (RaycastJob, Vec<(Entity, IntersectionData)>)>,
+}
+
+impl RaycastJobs {
+    /// Raycasts at most `max_jobs_per_frame` jobs each frame.
+    pub fn new(max_jobs_per_frame: usize) -> Self {
+        Self {
+            max_jobs_per_frame: Some(max_jobs_per_frame),
+            ..Default::default()
+        }
+    }
+
+    /// Queues a raycast of the `ray`, returning the token used to take its hits.
+    pub fn submit(&mut self, ray: Ray3d, settings: OwnedRaycastSettings) -> RaycastJobToken {
+        self.queue(None, ray, settings)
+    }
+
+    /// Like [`RaycastJobs::submit`], but the job is cancelled when the `owner` entity despawns.
+    pub fn submit_for(
+        &mut self,
+        owner: Entity,
+        ray: Ray3d,
+        settings: OwnedRaycastSettings,
+    ) -> RaycastJobToken {
+        self.queue(Some(owner), ray, settings)
+    }
+
+    fn queue(
+        &mut self,
+        owner: Option<Entity>,
+        ray: Ray3d,
+        settings: OwnedRaycastSettings,
+    ) -> RaycastJobToken {
+        let token = RaycastJobToken {
+            id: self.next_id,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        self.next_id += 1;
+        let job = RaycastJob {
+            token: token.clone(),
+            owner,
+        };
+        self.queue.push_back((job, ray, settings));
+        token
+    }
+
+    /// Returns `true` if the job is queued and was not cancelled.
+    pub fn is_pending(&self, token: &RaycastJobToken) -> bool {
+        !token.is_cancelled() && self.queue.iter().any(|(job, ..)| job.token.id == token.id)
+    }
+
+    /// Takes the hits of the job, sorted nearest first, if it is done and was not cancelled.
+    pub fn take(&mut self, token: &RaycastJobToken) -> Option<Vec<(Entity, IntersectionData)>> {
+        if token.is_cancelled() {
+            return None;
+        }
+        self.done.remove(&token.id).map(|(_, hits)| hits)
+    }
+
+    /// The number of jobs waiting to be raycast, including cancelled jobs that were not discarded
+    /// yet.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Cancels every queued job, such as when the results of the frame are no longer needed. Done
+    /// jobs can still be taken.
+    pub fn cancel_queued(&mut self) {
+        for (job, ..) in self.queue.drain(..) {
+            job.token.cancel();
+        }
+    }
+}
+
+/// Discards cancelled jobs, and raycasts the queued jobs, up to the
+/// [`RaycastJobs::max_jobs_per_frame`].
+pub fn run_raycast_jobs(mut jobs: ResMut<RaycastJobs>, entities: Query<()>, mut raycast: Raycast) {
+    let jobs = &mut *jobs;
+    jobs.queue.retain(|(job, ..)| !job.is_cancelled(&entities));
+    jobs.done.retain(|_, (job, _)| !job.is_cancelled(&entities));
+
+    let count = jobs
+        .max_jobs_per_frame
+        .map_or(jobs.queue.len(), |max| max.min(jobs.queue.len()));
+    let mut skipped = Vec::new();
+    for (job, ray, settings) in jobs.queue.drain(..count) {
+        let hits = raycast.cast_ray(ray, &settings.as_settings()).to_vec();
+        if raycast.skipped_by_budget() {
+            skipped.push((job, ray, settings));
+            continue;
+        }
+        jobs.done.insert(job.token.id, (job, hits));
+    }
+    for job in skipped.into_iter().rev() {
+        jobs.queue.push_front(job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn cancelled_jobs_are_not_raycast() {
+        let mut world = World::new();
+        let plane = spawn_planes(&mut world, &[-1.0])[0];
+        let owner = world.spawn_empty().id();
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+        let settings = OwnedRaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+        let mut jobs = RaycastJobs::default();
+        let kept = jobs.submit(ray, settings.clone());
+        let cancelled = jobs.submit(ray, settings.clone());
+        let owned = jobs.submit_for(owner, ray, settings.clone());
+        drop(jobs.submit(ray, settings));
+        cancelled.cancel();
+        world.despawn(owner);
+        world.insert_resource(jobs);
+        world.run_system_once(run_raycast_jobs);
+
+        let mut jobs = world.resource_mut::<RaycastJobs>();
+        assert_eq!(jobs.queued(), 0);
+        assert_eq!(jobs.done.len(), 1);
+        let hits = jobs.take(&kept).unwrap();
+        assert_eq!(
+            hits.iter().map(|(e, _)| *e).collect::<Vec<_>>(),
+            vec![plane]
+        );
+        assert!(jobs.take(&kept).is_none());
+        assert!(jobs.take(&cancelled).is_none());
+        assert!(jobs.take(&owned).is_none());
+    }
+
+    #[test]
+    fn jobs_are_spread_over_frames() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+        let settings = OwnedRaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+        let mut jobs = RaycastJobs::new(1);
+        let tokens = [(); 3].map(|_| jobs.submit(ray, settings.clone()));
+        world.insert_resource(jobs);
+
+        world.run_system_once(run_raycast_jobs);
+        let mut jobs = world.resource_mut::<RaycastJobs>();
+        assert!(jobs.take(&tokens[0]).is_some());
+        assert!(jobs.is_pending(&tokens[1]));
+        tokens[1].cancel();
+        assert!(!jobs.is_pending(&tokens[1]));
+
+        world.run_system_once(run_raycast_jobs);
+        let mut jobs = world.resource_mut::<RaycastJobs>();
+        assert!(jobs.take(&tokens[1]).is_none());
+        assert!(jobs.take(&tokens[2]).is_some());
+        // Jobs whose results are no longer needed can be cancelled together.
+        jobs.submit(ray, OwnedRaycastSettings::default());
+        let pending = jobs.submit(ray, OwnedRaycastSettings::default());
+        jobs.cancel_queued();
+        assert!(pending.is_cancelled());
+        assert_eq!(jobs.queued(), 0);
+    }
+}
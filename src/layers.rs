@@ -0,0 +1,135 @@
+//! # Raycast Layers
+//!
+//! Games often need rays that only hit some kinds of entities, such as bullets that pass through
+//! triggers, or a cursor that ignores the player's own character. Put entities on layers with a
+//! [`RaycastLayers`] component, and set the layers a ray tests with
+//! [`RaycastSettings::layers`], or [`RaycastSource::layers`] for deferred sources. Rays only test
+//! entities that share at least one layer with them.
+//!
+//! Entities without a [`RaycastLayers`] are on [`RaycastLayers::DEFAULT`], the first layer. Rays
+//! test [`RaycastLayers::ALL`] by default, so layers only change what is hit once they are set.
+//! Unlike the type parameter of [`RaycastMesh`] and [`RaycastSource`], layers can be changed at
+//! runtime, and don't need a plugin per group.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! const WORLD: usize = 0;
+//! const TRIGGERS: usize = 1;
+//!
+//! fn spawn_trigger(mut commands: Commands) {
+//!     commands.spawn((SpatialBundle::default(), RaycastLayers::layer(TRIGGERS)));
+//! }
+//!
+//! fn fire_bullet(mut raycast: Raycast) {
+//!     let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+//!     // Bullets pass through triggers.
+//!     let settings = RaycastSettings::default().with_layers(RaycastLayers::layer(WORLD));
+//!     let hits = raycast.cast_ray(ray, &settings);
+//! }
+//! ```
+
+use bevy_ecs::{component::Component, reflect::ReflectComponent};
+use bevy_reflect::{std_traits::ReflectDefault, Reflect};
+#[cfg(feature = "serialize")]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// A set of up to 32 layers, as a bitmask. On an entity, the layers it is on; in raycast settings,
+/// the layers that are tested. See the [module docs](self).
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component, Default)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct RaycastLayers(pub u32);
+
+impl RaycastLayers {
+    /// Every layer.
+    pub const ALL: Self = Self(u32::MAX);
+    /// No layers. Entities on no layers are never hit, and rays testing no layers hit nothing.
+    pub const NONE: Self = Self(0);
+    /// The first layer, which entities without a [`RaycastLayers`] are on.
+    pub const DEFAULT: Self = Self::layer(0);
+
+    /// Only the `layer`, which must be less than 32.
+    pub const fn layer(layer: usize) -> Self {
+        Self::NONE.with(layer)
+    }
+
+    /// Adds the `layer`, which must be less than 32.
+    pub const fn with(self, layer: usize) -> Self {
+        assert!(layer < 32, "raycast layers must be less than 32");
+        Self(self.0 | 1 << layer)
+    }
+
+    /// Removes the `layer`, which must be less than 32.
+    pub const fn without(self, layer: usize) -> Self {
+        assert!(layer < 32, "raycast layers must be less than 32");
+        Self(self.0 & !(1 << layer))
+    }
+
+    /// Returns `true` if the `layer` is in the set.
+    pub const fn contains(&self, layer: usize) -> bool {
+        layer < 32 && self.0 & 1 << layer != 0
+    }
+
+    /// Returns `true` if the sets share at least one layer.
+    pub const fn intersects(&self, other: &Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for RaycastLayers {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl FromIterator<usize> for RaycastLayers {
+    fn from_iter<T: IntoIterator<Item = usize>>(layers: T) -> Self {
+        layers.into_iter().fold(Self::NONE, Self::with)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn rays_only_hit_their_layers() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0, -3.0]);
+        world.entity_mut(planes[1]).insert(RaycastLayers::layer(1));
+        world
+            .entity_mut(planes[2])
+            .insert([1, 2].into_iter().collect::<RaycastLayers>());
+        let cast = |world: &mut World, layers| {
+            world.run_system_once(move |mut raycast: Raycast| {
+                let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+                let settings = RaycastSettings::default()
+                    .with_visibility(RaycastVisibility::Ignore)
+                    .with_layers(layers)
+                    .never_early_exit();
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(cast(&mut world, RaycastLayers::ALL), planes);
+        assert_eq!(cast(&mut world, RaycastLayers::DEFAULT), vec![planes[0]]);
+        assert_eq!(cast(&mut world, RaycastLayers::layer(1)), planes[1..]);
+        assert_eq!(cast(&mut world, RaycastLayers::layer(2)), vec![planes[2]]);
+        assert_eq!(cast(&mut world, RaycastLayers::NONE), vec![]);
+    }
+}
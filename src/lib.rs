@@ -18,10 +18,19 @@
 //! - An [immediate-mode API](immediate), which allows you to raycast into the scene on-demand in
 //!   any system. Intersections are returned immediately as a sorted `Vec`.
 //! - A [deferred API](deferred), where raycasts are performed once every frame based on entities
-//!    tagged with specific components. Intersections can be queried from the ECS.
+//!   tagged with specific components. Intersections can be queried from the ECS. This API is
+//!   behind the default `deferred` feature, which can be disabled if you only use the immediate
+//!   API.
 //!
 //! The plugin also provides the [`CursorRayPlugin`] for automatically generating a world space 3D
-//! ray corresponding to the mouse cursor. This is useful for mouse picking.
+//! ray corresponding to the mouse cursor. This is useful for mouse picking. It also provides the
+//! [`CenterRay`] through the center of the view, for crosshairs in games with a locked cursor, and a
+//! [`CursorRayCamera`] on each camera under the cursor, for split-screen views. On touchscreens,
+//! the [`TouchRayPlugin`] builds the [`TouchRays`] of every active touch instead. The
+//! [`CursorHitPlugin`] goes one step further, and keeps the nearest hit under the cursor in the
+//! [`CursorHit`] resource.
+//! Building on this, the [`CursorGesturePlugin`] turns button presses over entities into click,
+//! double-click, and drag events.
 //!
 //! ## Choosing an API
 //!
@@ -46,20 +55,62 @@
 //!
 //! ## Limitations
 //!
-//! This plugin runs entirely on the CPU, with minimal acceleration structures, and without support
-//! for skinned meshes. However, there is a good chance that this simply won't be an issue for your
-//! application. The provided `stress_test` example is a worst-case scenario that can help you judge
-//! if the plugin will meet your performance needs. Using a laptop with an i7-11800H, I am able to
-//! reach 110-530 fps in the stress test, raycasting against 1,000 monkey meshes.
+//! This plugin runs entirely on the CPU, with minimal acceleration structures, and skinned meshes
+//! are only supported by skinning them on the CPU with the [`SkinnedRaycastPlugin`]. However,
+//! there is a good chance that this simply won't be an issue for your application. The provided
+//! `stress_test` example is a worst-case scenario that can help you judge if the plugin will meet
+//! your performance needs. Using a laptop with an i7-11800H, I am able to
+//! reach 110-530 fps in the stress test, raycasting against 1,000 monkey meshes. Raycasts against
+//! high-poly meshes can be sped up with the [`MeshBvhPlugin`].
 
 #![allow(clippy::type_complexity)]
 
+pub mod bounds;
+pub mod budget;
+pub mod bundle;
+pub mod bvh;
 pub mod cursor;
+#[cfg(feature = "debug")]
+pub mod debug;
+#[cfg(feature = "deferred")]
 pub mod deferred;
+pub mod draping;
+pub mod dynamic_mesh;
+pub mod flat_normals;
+pub mod gestures;
+pub mod hierarchy;
+#[cfg(feature = "deferred")]
+pub mod hit_buffer;
 pub mod immediate;
+pub mod interpolation;
+pub mod jobs;
+pub mod layers;
+#[cfg(feature = "lights")]
+pub mod lights;
 pub mod markers;
+#[cfg(feature = "2d")]
+pub mod mesh2d;
+pub mod mesh_bvh;
+pub mod perception;
 pub mod primitives;
+pub mod quantization;
 pub mod raycast;
+pub mod raycastability;
+pub mod reflection;
+pub mod sampling;
+pub mod selection;
+pub mod shape_cast;
+pub mod skinning;
+pub mod smoothing;
+pub mod snapping;
+pub mod spread;
+pub mod static_geometry;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod topology;
+pub mod transform_cache;
+pub mod volatility;
+pub mod volumes;
 
 use bevy_utils::default;
 
@@ -67,10 +118,29 @@ use bevy_utils::default;
 use prelude::*;
 
 pub mod prelude {
-    pub use crate::{cursor::*, deferred::*, immediate::*, markers::*, primitives::*, raycast::*};
+    pub use crate::{
+        bounds::*, budget::*, bundle::*, bvh::*, cursor::*, draping::*, dynamic_mesh::*,
+        flat_normals::*, gestures::*, hierarchy::*, immediate::*, interpolation::*, jobs::*,
+        layers::*, markers::*, mesh_bvh::*, perception::*, primitives::*, quantization::*,
+        raycast::*, raycastability::*, reflection::*, sampling::*, selection::*, skinning::*,
+        smoothing::*, snapping::*, spread::*, static_geometry::*, topology::*, transform_cache::*,
+        volatility::*, volumes::*,
+    };
+
+    #[cfg(feature = "deferred")]
+    pub use crate::{deferred::*, hit_buffer::*};
 
     #[cfg(feature = "debug")]
     pub use crate::debug::*;
+
+    #[cfg(feature = "2d")]
+    pub use crate::mesh2d::*;
+
+    #[cfg(feature = "lights")]
+    pub use crate::lights::*;
+
+    #[cfg(feature = "text")]
+    pub use crate::text::*;
 }
 
 /// Used for examples to reduce picking latency. Not relevant code for the examples.
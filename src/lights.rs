@@ -0,0 +1,220 @@
+//! # Light Visibility
+//!
+//! Helpers for gameplay checks against lights, such as stealth games testing whether a character is
+//! standing in the light. Requires the `lights` feature.
+//!
+//! [`light_ray_to_point`] builds a ray from a light toward a point, respecting the range of point
+//! and spot lights and the cone of spot lights. [`Raycast::is_point_lit`] casts that ray to find
+//! out if anything blocks the light.
+//!
+//! Occlusion is tested against every raycastable entity. Use the [`RaycastSettings::filter`] to
+//! exclude the entity being tested, so it doesn't shadow points on its own surface.
+
+use bevy_math::{Ray3d, Vec3};
+use bevy_pbr::{DirectionalLight, PointLight, SpotLight};
+use bevy_transform::components::GlobalTransform;
+
+use crate::prelude::*;
+
+/// A reference to any of the light types that can be cast from.
+#[derive(Clone, Copy, Debug)]
+pub enum LightRef<'a> {
+    /// Directional lights have no position, so rays are started
+    /// [`DIRECTIONAL_LIGHT_DISTANCE`] away from the point, opposite the light direction.
+    Directional(&'a DirectionalLight),
+    Point(&'a PointLight),
+    Spot(&'a SpotLight),
+}
+
+impl<'a> From<&'a DirectionalLight> for LightRef<'a> {
+    fn from(light: &'a DirectionalLight) -> Self {
+        Self::Directional(light)
+    }
+}
+
+impl<'a> From<&'a PointLight> for LightRef<'a> {
+    fn from(light: &'a PointLight) -> Self {
+        Self::Point(light)
+    }
+}
+
+impl<'a> From<&'a SpotLight> for LightRef<'a> {
+    fn from(light: &'a SpotLight) -> Self {
+        Self::Spot(light)
+    }
+}
+
+/// How far from the target point rays from a [`DirectionalLight`] start.
+pub const DIRECTIONAL_LIGHT_DISTANCE: f32 = 1000.0;
+
+/// A ray from a light toward a point.
+#[derive(Clone, Copy, Debug)]
+pub struct LightRay {
+    pub ray: Ray3d,
+    /// The distance along the ray from the light to the point.
+    pub distance: f32,
+}
+
+/// Builds a ray from the light toward `point`. Returns `None` if the point is outside the range
+/// of a point or spot light, or outside the outer cone of a spot light.
+pub fn light_ray_to_point<'a>(
+    light: impl Into<LightRef<'a>>,
+    light_transform: &GlobalTransform,
+    point: Vec3,
+) -> Option<LightRay> {
+    let light = light.into();
+    let (origin, range) = match light {
+        LightRef::Directional(_) => (
+            point - *light_transform.forward() * DIRECTIONAL_LIGHT_DISTANCE,
+            f32::INFINITY,
+        ),
+        LightRef::Point(light) => (light_transform.translation(), light.range),
+        LightRef::Spot(light) => (light_transform.translation(), light.range),
+    };
+    let to_point = point - origin;
+    let distance = to_point.length();
+    if distance > range || distance <= f32::EPSILON {
+        return None;
+    }
+    if let LightRef::Spot(light) = light {
+        let angle = light_transform.forward().angle_between(to_point);
+        if angle > light.outer_angle {
+            return None;
+        }
+    }
+    Some(LightRay {
+        ray: Ray3d::new(origin, to_point),
+        distance,
+    })
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Returns `true` if `point` is in range of the light, and nothing blocks the ray from the light
+    /// to the point. Hits within `tolerance` of the point are ignored, so that points on a surface
+    /// are not shadowed by that surface.
+    pub fn is_point_lit<'a>(
+        &mut self,
+        light: impl Into<LightRef<'a>>,
+        light_transform: &GlobalTransform,
+        point: Vec3,
+        tolerance: f32,
+        settings: &RaycastSettings,
+    ) -> bool {
+        let Some(LightRay { ray, distance }) = light_ray_to_point(light, light_transform, point)
+        else {
+            return false;
+        };
+        let settings = RaycastSettings {
+            max_distance: settings.max_distance.min(distance - tolerance),
+            include_behind_origin: false,
+            ..settings.clone()
+        }
+        .always_early_exit();
+        self.cast_ray(ray, &settings).is_empty()
+    }
+
+    /// The fraction of `points` that are lit by the light, using [`Raycast::is_point_lit`]. Useful
+    /// for estimating how exposed a character is by sampling points across its body. Returns `0.0`
+    /// if `points` is empty.
+    pub fn lit_fraction<'a>(
+        &mut self,
+        light: impl Into<LightRef<'a>>,
+        light_transform: &GlobalTransform,
+        points: impl IntoIterator<Item = Vec3>,
+        tolerance: f32,
+        settings: &RaycastSettings,
+    ) -> f32 {
+        let light = light.into();
+        let (mut lit, mut total) = (0, 0);
+        for point in points {
+            total += 1;
+            if self.is_point_lit(light, light_transform, point, tolerance, settings) {
+                lit += 1;
+            }
+        }
+        if total == 0 {
+            0.0
+        } else {
+            lit as f32 / total as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn spot_lights_respect_range_and_cone() {
+        let light = SpotLight {
+            range: 10.0,
+            outer_angle: 0.5,
+            ..default()
+        };
+        // The identity transform faces `-Z`.
+        let transform = GlobalTransform::IDENTITY;
+        let at_angle = |angle: f32| Vec3::new(angle.tan() * 5.0, 0.0, -5.0);
+        let ray = light_ray_to_point(&light, &transform, Vec3::new(0.0, 0.0, -5.0)).unwrap();
+        assert_eq!(ray.distance, 5.0);
+        assert_eq!(*ray.ray.direction, Vec3::NEG_Z);
+
+        assert!(light_ray_to_point(&light, &transform, at_angle(0.45)).is_some());
+        assert!(light_ray_to_point(&light, &transform, at_angle(0.55)).is_none());
+        assert!(light_ray_to_point(&light, &transform, Vec3::new(0.0, 0.0, -9.9)).is_some());
+        assert!(light_ray_to_point(&light, &transform, Vec3::new(0.0, 0.0, -10.1)).is_none());
+        // Points behind a spot light are outside of its cone.
+        assert!(light_ray_to_point(&light, &transform, Vec3::new(0.0, 0.0, 5.0)).is_none());
+    }
+
+    #[test]
+    fn point_and_directional_light_rays() {
+        let point_light = PointLight {
+            range: 10.0,
+            ..default()
+        };
+        let transform = GlobalTransform::from_xyz(1.0, 0.0, 0.0);
+        let ray = light_ray_to_point(&point_light, &transform, Vec3::new(1.0, 0.0, 9.9)).unwrap();
+        assert_eq!(ray.ray.origin, Vec3::new(1.0, 0.0, 0.0));
+        assert!((ray.distance - 9.9).abs() < 1e-5);
+        assert!(light_ray_to_point(&point_light, &transform, Vec3::new(1.0, 0.0, 10.1)).is_none());
+
+        // Directional lights have no range, and shine from far away along their direction.
+        let directional = DirectionalLight::default();
+        let transform =
+            GlobalTransform::from(Transform::default().looking_to(Vec3::NEG_Y, Vec3::Z));
+        let point = Vec3::new(2.0, 0.0, -3.0);
+        let ray = light_ray_to_point(&directional, &transform, point).unwrap();
+        assert!(ray.ray.direction.abs_diff_eq(Vec3::NEG_Y, 1e-6));
+        assert!((ray.distance - DIRECTIONAL_LIGHT_DISTANCE).abs() < 1e-2);
+        assert!(ray.ray.get_point(ray.distance).abs_diff_eq(point, 1e-3));
+    }
+
+    #[test]
+    fn occluded_points_are_not_lit() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[-1.0]);
+        let lit = world.run_system_once(|mut raycast: Raycast| {
+            let light = PointLight {
+                range: 20.0,
+                ..default()
+            };
+            let transform = GlobalTransform::IDENTITY;
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            [
+                // Behind the plane.
+                Vec3::new(0.0, 0.0, -2.0),
+                // Beside the plane, so the ray passes its edge.
+                Vec3::new(3.0, 0.0, -2.0),
+                // On the plane, which doesn't shadow itself within the tolerance.
+                Vec3::new(0.0, 0.0, -1.0),
+                // Out of range.
+                Vec3::new(0.0, 0.0, 25.0),
+            ]
+            .map(|point| raycast.is_point_lit(&light, &transform, point, 0.01, &settings))
+        });
+        assert_eq!(lit, [false, true, true, false]);
+    }
+}
@@ -1,10 +1,78 @@
+use std::ops::Range;
+
 use bevy_asset::Handle;
 use bevy_ecs::component::Component;
+use bevy_render::mesh::Mesh;
 
-#[derive(Component)]
+use crate::raycast::mesh_triangle_count;
+
+/// Raycasts against this entity test this mesh instead of the entity's own, such as a version of
+/// it with fewer triangles.
+///
+/// Many exported models pack their levels of detail into one mesh, as ranges of its index buffer.
+/// Set an [`index_range`](Self::index_range) to only raycast one of them, using the entity's own
+/// mesh as the simplified mesh, so no vertex data is duplicated.
+#[derive(Component, Clone, Debug, Default)]
 pub struct SimplifiedMesh {
-    pub mesh: Handle<bevy_render::mesh::Mesh>,
+    pub mesh: Handle<Mesh>,
+    /// Only the triangles in this range of the mesh's indices are raycast, or of its vertices if
+    /// it has no indices. Triangles only partly in the range are skipped.
+    pub index_range: Option<Range<u32>>,
+}
+
+impl SimplifiedMesh {
+    /// Raycast the whole `mesh` instead of the entity's mesh.
+    pub fn new(mesh: Handle<Mesh>) -> Self {
+        Self {
+            mesh,
+            index_range: None,
+        }
+    }
+
+    /// Only raycast the triangles in this range of the mesh's indices.
+    pub fn with_index_range(self, index_range: Range<u32>) -> Self {
+        Self {
+            index_range: Some(index_range),
+            ..self
+        }
+    }
+
+    /// The triangles in the [`index_range`](Self::index_range) of the `mesh`, numbered as in
+    /// [`IntersectionData::triangle_index`](crate::primitives::IntersectionData::triangle_index),
+    /// or `None` if the whole mesh is raycast.
+    pub fn triangle_range(&self, mesh: &Mesh) -> Option<Range<usize>> {
+        let range = self.index_range.as_ref()?;
+        let count = mesh_triangle_count(mesh);
+        Some((range.start as usize).div_ceil(3).min(count)..(range.end as usize / 3).min(count))
+    }
 }
 
 #[derive(Component)]
 pub struct NoBackfaceCulling;
+
+/// Hits on this entity never block the ray, even when the raycast would otherwise exit early after
+/// the nearest hit. Useful for trigger volumes that should always be reported alongside whatever is
+/// behind them.
+#[derive(Component)]
+pub struct RaycastPierce;
+
+/// Hits on this entity report the normal of the hit triangle, instead of interpolating the vertex
+/// normals of its mesh. Use [`find_mismatched_normals`](crate::raycast::find_mismatched_normals)
+/// to find meshes that need this.
+#[derive(Component)]
+pub struct GeometricNormals;
+
+/// Makes an entity raycastable using only its [`Aabb`](bevy_render::primitives::Aabb), without a
+/// mesh. Hits are where the ray enters the AABB, with the normal of the face it entered through.
+/// Useful for cheap interactables such as pickup items and trigger boxes, which don't need triangle
+/// precision.
+///
+/// The entity needs an `Aabb`, a [`GlobalTransform`](bevy_transform::components::GlobalTransform),
+/// and visibility components. If it also has a mesh, the mesh is ignored. Rays starting inside the
+/// AABB only hit it where they leave it when the entity also has [`NoBackfaceCulling`].
+///
+/// Bevy only computes the [`ViewVisibility`](bevy_render::view::ViewVisibility) of entities it
+/// renders, so AABB targets count as in view whenever their
+/// [`InheritedVisibility`](bevy_render::view::InheritedVisibility) is visible.
+#[derive(Component)]
+pub struct RaycastAabbTarget;
@@ -0,0 +1,249 @@
+//! # 2D Meshes
+//!
+//! ## Raycasting in 2D
+//!
+//! In 2D, picking is a question of which entities are under a point, and which of them is on top.
+//! The [`Raycast2d`] system param answers this with [`Raycast2d::cast_point`], using the
+//! [`RaycastSettings2d`], which leave out the settings that only make sense in 3D. Only 2D meshes
+//! are hit, from both sides, and hits are sorted by their Z coordinate, topmost first, regardless
+//! of how far they are from a camera.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! fn pick_system(mut raycast: Raycast2d) {
+//!     let cursor = Vec2::new(10.0, 20.0);
+//!     if let Some((entity, hit)) = raycast.cast_point(cursor, &default()).first() {
+//!         info!("{entity:?} is on top at z = {}", hit.position().z);
+//!     }
+//! }
+//! ```
+//!
+//! ## Bounds
+//!
+//! Raycasts cull entities by their [`Aabb`] before testing their triangles, and entities without
+//! one are never hit. `bevy_sprite` only computes the AABB of a 2D mesh once, and only when its
+//! render plugins are added, so 2D meshes that are spawned headless or whose mesh changes later can
+//! be missed or culled incorrectly.
+//!
+//! The [`Mesh2dBoundsPlugin`] keeps the AABB of every 2D mesh up to date. It is added automatically
+//! by the [`DeferredRaycastingPlugin`]; add it yourself when only using the [`Raycast`] system
+//! param. The AABBs of flat meshes have no depth, which the culling handles without special care.
+
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, Assets};
+use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_math::{Ray3d, Vec2, Vec3};
+use bevy_render::{mesh::Mesh, primitives::Aabb, view::VisibilitySystems};
+use bevy_utils::HashSet;
+
+use crate::{
+    bounds::MeshHandleComponent,
+    immediate::{Mesh2dComponent, Raycast, RaycastSettings, RaycastVisibility},
+    primitives::IntersectionData,
+};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Computes the [`Aabb`] of 2D meshes that are missing one, and updates it when the mesh changes.
+#[derive(Default)]
+pub struct Mesh2dBoundsPlugin;
+impl Plugin for Mesh2dBoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            update_mesh2d_aabbs.in_set(VisibilitySystems::CalculateBounds),
+        );
+    }
+}
+
+/// Settings for picking 2D meshes with [`Raycast2d::cast_point`].
+#[derive(Clone)]
+pub struct RaycastSettings2d<'a> {
+    /// When `true`, only entities that are visible and in view of a camera are hit.
+    pub visible_only: bool,
+    /// A filtering function that is applied to every entity. Only entities that return `true` are
+    /// hit.
+    pub filter: &'a dyn Fn(Entity) -> bool,
+    /// When `true`, only the topmost hit is returned. Otherwise, every entity under the point is
+    /// returned, topmost first.
+    pub topmost_only: bool,
+}
+
+impl<'a> RaycastSettings2d<'a> {
+    /// Set the `visible_only` field of the settings.
+    pub fn with_visible_only(mut self, visible_only: bool) -> Self {
+        self.visible_only = visible_only;
+        self
+    }
+
+    /// Set the filter function of the settings.
+    pub fn with_filter(mut self, filter: &'a impl Fn(Entity) -> bool) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Set the `topmost_only` field of the settings.
+    pub fn with_topmost_only(mut self, topmost_only: bool) -> Self {
+        self.topmost_only = topmost_only;
+        self
+    }
+}
+
+impl<'a> Default for RaycastSettings2d<'a> {
+    fn default() -> Self {
+        Self {
+            visible_only: true,
+            filter: &|_| true,
+            topmost_only: true,
+        }
+    }
+}
+
+/// A system param for picking 2D meshes. See the [module docs](self).
+#[derive(SystemParam)]
+pub struct Raycast2d<'w, 's> {
+    raycast: Raycast<'w, 's>,
+    mesh2ds: Query<'w, 's, (), With<Mesh2dComponent>>,
+}
+
+impl<'w, 's> Raycast2d<'w, 's> {
+    /// Finds the 2D meshes under the world-space `point`, topmost first.
+    ///
+    /// This casts a line through the point along the Z axis, so hits have a negative
+    /// [`distance`](IntersectionData::distance) when they are above `z = 0`. Use the Z coordinate
+    /// of their [`position`](IntersectionData::position) instead.
+    pub fn cast_point(
+        &mut self,
+        point: Vec2,
+        settings: &RaycastSettings2d,
+    ) -> &[(Entity, IntersectionData)] {
+        let ray = Ray3d::new(point.extend(0.0), Vec3::NEG_Z);
+        let topmost_only = settings.topmost_only;
+        let early_exit_test = |_| topmost_only;
+        let visibility = match settings.visible_only {
+            true => RaycastVisibility::MustBeVisibleAndInView,
+            false => RaycastVisibility::Ignore,
+        };
+        let settings = RaycastSettings::default()
+            .with_visibility(visibility)
+            .with_filter(&settings.filter)
+            .with_early_exit_test(&early_exit_test)
+            .with_include_behind_origin(true);
+        self.raycast
+            .cast_ray_filtered_by_query(ray, &settings, &self.mesh2ds)
+    }
+}
+
+/// Inserts an [`Aabb`] on 2D mesh entities without one, or whose mesh handle or mesh asset has
+/// changed.
+pub fn update_mesh2d_aabbs(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    mesh2ds: Query<(Entity, Ref<Mesh2dComponent>, Has<Aabb>)>,
+) {
+    let modified: HashSet<_> = mesh_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, handle, has_aabb) in &mesh2ds {
+        if has_aabb && !handle.is_changed() && !modified.contains(&handle.mesh_id()) {
+            continue;
+        }
+        if let Some(aabb) = meshes
+            .get(handle.mesh_handle())
+            .and_then(Mesh::compute_aabb)
+        {
+            commands.entity(entity).try_insert(aabb);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, sprite::Mesh2dHandle};
+
+    use super::*;
+
+    #[test]
+    fn picks_circle_and_quad() {
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut world = World::new();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        let mut meshes = Assets::<Mesh>::default();
+        let mut spawn_2d = |mesh: Mesh, x: f32| {
+            world
+                .spawn((
+                    Mesh2dHandle(meshes.add(mesh)),
+                    GlobalTransform::from_xyz(x, 0.0, 0.0),
+                    InheritedVisibility::VISIBLE,
+                    ViewVisibility::default(),
+                ))
+                .id()
+        };
+        let circle = spawn_2d(Circle::default().into(), -3.0);
+        let quad = spawn_2d(Rectangle::default().into(), 3.0);
+        world.insert_resource(meshes);
+        world.run_system_once(update_mesh2d_aabbs);
+        assert!(world.get::<Aabb>(circle).is_some());
+
+        let picked = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            // The last position is inside the circle's AABB, but outside the circle.
+            [
+                (-3.0, 0.0),
+                (3.0, 0.0),
+                (3.45, 0.45),
+                (0.0, 0.0),
+                (-2.55, 0.45),
+            ]
+            .map(|(x, y)| {
+                let ray = Ray3d::new(Vec3::new(x, y, 10.0), Vec3::NEG_Z);
+                raycast.cast_ray(ray, &settings).first().map(|(e, _)| *e)
+            })
+        });
+        assert_eq!(picked, [Some(circle), Some(quad), Some(quad), None, None]);
+    }
+
+    #[test]
+    fn topmost_mesh_is_picked_first() {
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut world = World::new();
+        let mut meshes = Assets::<Mesh>::default();
+        let mesh = Mesh2dHandle(meshes.add(Rectangle::default()));
+        let aabb = meshes.get(&mesh.0).unwrap().compute_aabb().unwrap();
+        world.insert_resource(meshes);
+        let visibility = (InheritedVisibility::VISIBLE, ViewVisibility::default());
+        let [bottom, top, below] = [0.0, 1.0, -1.0].map(|z| {
+            let transform = GlobalTransform::from_xyz(0.0, 0.0, z);
+            world
+                .spawn((mesh.clone(), aabb, transform, visibility))
+                .id()
+        });
+        world.spawn((
+            mesh.0.clone(),
+            aabb,
+            GlobalTransform::from_xyz(0.0, 0.0, 2.0),
+            visibility,
+        ));
+
+        let picked = world.run_system_once(|mut raycast: Raycast2d| {
+            let settings = RaycastSettings2d::default().with_visible_only(false);
+            [true, false].map(|topmost_only| {
+                let settings = settings.clone().with_topmost_only(topmost_only);
+                raycast
+                    .cast_point(Vec2::ZERO, &settings)
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .collect::<Vec<_>>()
+            })
+        });
+        // The 3D mesh on top is ignored.
+        assert_eq!(picked, [vec![top], vec![top, bottom, below]]);
+    }
+}
@@ -0,0 +1,545 @@
+//! # Mesh BVHs
+//!
+//! Raycasting a mesh tests the ray against every one of its triangles, which is slow for
+//! high-poly meshes. The [`MeshBvhPlugin`] builds a [`TriangleBvh`] for each mesh asset with at
+//! least [`MeshBvhCache::min_triangles`] triangles, and the [`Raycast`] system param uses it to
+//! only test the triangles near the ray. Testing every triangle of a small mesh is faster than
+//! walking a tree, so small meshes are left alone.
+//!
+//! Small meshes that are raycast often still get a BVH: a mesh with at least
+//! [`MeshBvhCache::frequent_min_triangles`] triangles that is raycast
+//! [`MeshBvhCache::frequent_casts`] times in a frame gets one for as long as it exists, since rays
+//! that miss a mesh gain from a BVH at far fewer triangles than rays that hit it.
+//!
+//! Add a [`RaycastStrategy`] to an entity to override this choice for it. With
+//! [`RaycastStrategy::Bvh`], its mesh gets a BVH whatever its size, and with
+//! [`RaycastStrategy::BruteForce`], its raycasts never use a BVH.
+//!
+//! The cache is updated in [`First`] from the mesh asset events of the previous frame. Meshes
+//! marked in [`VolatileMeshes`] are not cached, since they change faster than a BVH could be
+//! reused, and get a BVH once they become stable.
+//!
+//! Building the BVH of a large mesh can take longer than a frame, so BVHs are built on the
+//! [`AsyncComputeTaskPool`], and cached when they are done. Until then, raycasts test every
+//! triangle of the mesh. Set [`MeshBvhCache::build_async`] to `false` to build them in the frame
+//! the mesh changed instead, or call [`MeshBvhCache::wait_for_builds`] to wait for them. A build that
+//! panics is dropped, and the mesh keeps being raycast without a BVH.
+
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+};
+
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, AssetId, Assets};
+use bevy_ecs::prelude::*;
+use bevy_math::Vec3A;
+use bevy_render::mesh::Mesh;
+use bevy_tasks::{AsyncComputeTaskPool, TaskPool};
+use bevy_utils::{HashMap, HashSet};
+
+#[cfg(feature = "2d")]
+use crate::immediate::Mesh2dComponent;
+use crate::{
+    bounds::MeshHandleComponent,
+    bvh::{mesh_triangles, TriangleBvh},
+    immediate::Mesh3dComponent,
+    markers::SimplifiedMesh,
+    raycast::mesh_triangle_count,
+    volatility::VolatileMeshes,
+};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Maintains the [`MeshBvhCache`]. See the [module docs](self).
+#[derive(Default)]
+pub struct MeshBvhPlugin;
+impl Plugin for MeshBvhPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeshBvhCache>().add_systems(
+            First,
+            update_mesh_bvh_cache.after(crate::volatility::update_mesh_volatility),
+        );
+    }
+}
+
+/// Overrides whether raycasts against an entity's mesh use a BVH. See the
+/// [module docs](self).
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RaycastStrategy {
+    /// Test every triangle of the mesh, even if it has a BVH.
+    BruteForce,
+    /// Build a BVH for the mesh, even if it has fewer than [`MeshBvhCache::min_triangles`]
+    /// triangles, and use it.
+    Bvh,
+}
+
+/// BVHs of mesh assets, cached by mesh asset id. See the [module docs](self).
+#[derive(Resource)]
+pub struct MeshBvhCache {
+    /// Meshes with fewer triangles don't get a BVH, unless an entity using them has
+    /// [`RaycastStrategy::Bvh`]. Changes apply to meshes as they are next added or modified.
+    ///
+    /// Defaults to `256`, which is where the BVH overtakes testing every triangle for rays that
+    /// hit the mesh in the `mesh_acceleration` benchmark. Rays that miss the mesh gain from a BVH
+    /// at even fewer triangles.
+    pub min_triangles: usize,
+    /// Meshes with fewer triangles than [`MeshBvhCache::min_triangles`], but at least this many,
+    /// get a BVH once they are raycast [`MeshBvhCache::frequent_casts`] times in a frame.
+    ///
+    /// Defaults to `64`, which is about where the BVH overtakes testing every triangle for rays
+    /// that miss the mesh in the `mesh_acceleration` benchmark.
+    pub frequent_min_triangles: usize,
+    /// The number of raycasts against a mesh in a frame that make it frequently raycast. See
+    /// [`MeshBvhCache::frequent_min_triangles`]. Defaults to `16`.
+    pub frequent_casts: u32,
+    /// Build BVHs on the [`AsyncComputeTaskPool`] instead of blocking the frame. Meshes being
+    /// built are raycast without a BVH. Defaults to `true`.
+    pub build_async: bool,
+    bvhs: HashMap<AssetId<Mesh>, TriangleBvh>,
+    /// Meshes that get a BVH whatever their size, set by [`MeshBvhCache::require`].
+    required: HashSet<AssetId<Mesh>>,
+    /// Meshes that get a BVH whatever their size, because an entity using them has
+    /// [`RaycastStrategy::Bvh`]. Recounted every frame, so they are released when the strategy is
+    /// removed or the entity is despawned.
+    required_by_entities: HashSet<AssetId<Mesh>>,
+    /// Meshes that get a BVH whatever their size, because they were raycast often.
+    frequent: HashSet<AssetId<Mesh>>,
+    /// Raycasts this frame against meshes that could become frequently raycast, counted by
+    /// [`MeshBvhCache::record_cast`].
+    casts: Mutex<HashMap<AssetId<Mesh>, u32>>,
+    /// The latest build started for each mesh being built. Builds that finish after a newer one
+    /// was started are discarded.
+    building: HashMap<AssetId<Mesh>, u64>,
+    next_build: u64,
+    finished_tx: Sender<FinishedBuild>,
+    finished_rx: Mutex<Receiver<FinishedBuild>>,
+}
+
+/// The mesh and build number of a build that is done, with its BVH, or `None` if it panicked.
+type FinishedBuild = (AssetId<Mesh>, u64, Option<TriangleBvh>);
+
+impl Default for MeshBvhCache {
+    fn default() -> Self {
+        let (finished_tx, finished_rx) = channel();
+        Self {
+            min_triangles: 256,
+            frequent_min_triangles: 64,
+            frequent_casts: 16,
+            build_async: true,
+            bvhs: HashMap::default(),
+            required: HashSet::default(),
+            required_by_entities: HashSet::default(),
+            frequent: HashSet::default(),
+            casts: Mutex::default(),
+            building: HashMap::default(),
+            next_build: 0,
+            finished_tx,
+            finished_rx: Mutex::new(finished_rx),
+        }
+    }
+}
+
+impl MeshBvhCache {
+    /// Get the cached BVH of the mesh.
+    pub fn get(&self, mesh: impl Into<AssetId<Mesh>>) -> Option<&TriangleBvh> {
+        self.bvhs.get(&mesh.into())
+    }
+
+    /// Returns `true` if a BVH of the mesh is being built on the [`AsyncComputeTaskPool`].
+    pub fn is_building(&self, mesh: impl Into<AssetId<Mesh>>) -> bool {
+        self.building.contains_key(&mesh.into())
+    }
+
+    /// Build and cache the BVH of the mesh, replacing any cached BVH, if the mesh has at least
+    /// [`MeshBvhCache::min_triangles`] triangles or is required by [`MeshBvhCache::require`] or a
+    /// [`RaycastStrategy`].
+    pub fn update(&mut self, mesh: impl Into<AssetId<Mesh>>, meshes: &Assets<Mesh>) {
+        let id = mesh.into();
+        self.invalidate(id);
+        if let Some(triangles) = self.triangles_to_build(id, meshes) {
+            self.bvhs.insert(id, TriangleBvh::new(&triangles));
+        }
+    }
+
+    /// Like [`MeshBvhCache::update`], but builds the BVH on the [`AsyncComputeTaskPool`] if
+    /// [`MeshBvhCache::build_async`] is set. The cached BVH is discarded right away, since it no
+    /// longer matches the mesh, and the new one is cached once a later
+    /// [`update_mesh_bvh_cache`] finds it done.
+    pub fn schedule(&mut self, mesh: impl Into<AssetId<Mesh>>, meshes: &Assets<Mesh>) {
+        let id = mesh.into();
+        if !self.build_async {
+            return self.update(id, meshes);
+        }
+        self.invalidate(id);
+        let Some(triangles) = self.triangles_to_build(id, meshes) else {
+            return;
+        };
+        let build = self.next_build;
+        self.next_build += 1;
+        self.building.insert(id, build);
+        let finished = self.finished_tx.clone();
+        AsyncComputeTaskPool::get_or_init(TaskPool::default)
+            .spawn(async move {
+                // Every build reports back, even if it panicked, so it stops being waited for.
+                let bvh = catch_unwind(AssertUnwindSafe(|| TriangleBvh::new(&triangles))).ok();
+                // The cache may have been dropped, in which case the BVH is no longer needed.
+                finished.send((id, build, bvh)).ok();
+            })
+            .detach();
+    }
+
+    /// Give the mesh a BVH whatever its size, scheduling a build if it has none yet.
+    pub fn require(&mut self, mesh: impl Into<AssetId<Mesh>>, meshes: &Assets<Mesh>) {
+        let id = mesh.into();
+        if self.required.insert(id) || self.is_missing(id) {
+            self.schedule(id, meshes);
+        }
+    }
+
+    /// Returns `true` if the mesh got a BVH because it was raycast often. See the
+    /// [module docs](self).
+    pub fn is_frequently_cast(&self, mesh: impl Into<AssetId<Mesh>>) -> bool {
+        self.frequent.contains(&mesh.into())
+    }
+
+    /// Counts a raycast against the mesh, which gets a BVH once it is raycast
+    /// [`MeshBvhCache::frequent_casts`] times in a frame. Only meshes without a BVH, and with at
+    /// least [`MeshBvhCache::frequent_min_triangles`] triangles, are counted. Called by the
+    /// [`Raycast`] system param for every mesh it tests.
+    pub fn record_cast(&self, mesh: impl Into<AssetId<Mesh>>, triangles: usize) {
+        let id = mesh.into();
+        if triangles < self.frequent_min_triangles || !self.is_missing(id) {
+            return;
+        }
+        *self.casts.lock().unwrap().entry(id).or_default() += 1;
+    }
+
+    /// Gives the meshes that were raycast [`MeshBvhCache::frequent_casts`] times since the last
+    /// call a BVH, and resets the counts.
+    fn promote_frequent(
+        &mut self,
+        meshes: &Assets<Mesh>,
+        is_volatile: impl Fn(AssetId<Mesh>) -> bool,
+    ) {
+        let casts = std::mem::take(self.casts.get_mut().unwrap());
+        for (id, casts) in casts {
+            if casts >= self.frequent_casts && !is_volatile(id) && self.frequent.insert(id) {
+                self.schedule(id, meshes);
+            }
+        }
+    }
+
+    /// Undo [`MeshBvhCache::require`], discarding the BVH of the mesh if it has fewer than
+    /// [`MeshBvhCache::min_triangles`] triangles and no entity requires it.
+    pub fn release(&mut self, mesh: impl Into<AssetId<Mesh>>, meshes: &Assets<Mesh>) {
+        let id = mesh.into();
+        if self.required.remove(&id) {
+            self.discard_if_unneeded(id, meshes);
+        }
+    }
+
+    /// Replaces the meshes required by entities, scheduling builds for the newly required meshes
+    /// and discarding the BVHs of the meshes that are no longer needed.
+    fn set_required_by_entities(
+        &mut self,
+        required: HashSet<AssetId<Mesh>>,
+        meshes: &Assets<Mesh>,
+    ) {
+        let previous = std::mem::replace(&mut self.required_by_entities, required.clone());
+        for id in previous.difference(&required) {
+            self.discard_if_unneeded(*id, meshes);
+        }
+        for id in required {
+            if !previous.contains(&id) || self.is_missing(id) {
+                self.schedule(id, meshes);
+            }
+        }
+    }
+
+    fn is_required(&self, id: AssetId<Mesh>) -> bool {
+        self.required.contains(&id)
+            || self.required_by_entities.contains(&id)
+            || self.frequent.contains(&id)
+    }
+
+    /// Returns `true` if the mesh has no BVH and none is being built.
+    fn is_missing(&self, id: AssetId<Mesh>) -> bool {
+        !self.bvhs.contains_key(&id) && !self.is_building(id)
+    }
+
+    /// Discards the BVH of the mesh if it is not required and too small to get one.
+    fn discard_if_unneeded(&mut self, id: AssetId<Mesh>, meshes: &Assets<Mesh>) {
+        let too_small = meshes
+            .get(id)
+            .map_or(true, |mesh| mesh_triangle_count(mesh) < self.min_triangles);
+        if !self.is_required(id) && too_small {
+            self.invalidate(id);
+        }
+    }
+
+    /// Discard the cached BVH of the mesh, and any BVH of it being built.
+    pub fn invalidate(&mut self, mesh: impl Into<AssetId<Mesh>>) {
+        let id = mesh.into();
+        self.bvhs.remove(&id);
+        self.building.remove(&id);
+    }
+
+    /// The mesh-space triangles of the mesh, if it should have a BVH.
+    fn triangles_to_build(
+        &self,
+        id: AssetId<Mesh>,
+        meshes: &Assets<Mesh>,
+    ) -> Option<Vec<[Vec3A; 3]>> {
+        meshes
+            .get(id)
+            .filter(|mesh| self.is_required(id) || mesh_triangle_count(mesh) >= self.min_triangles)
+            .and_then(mesh_triangles)
+    }
+
+    /// Blocks until every BVH being built on the [`AsyncComputeTaskPool`] is done, and caches
+    /// them. This is useful when raycasts must use the BVHs right away, such as in benchmarks.
+    ///
+    /// On wasm, builds run on the browser's event loop, which can't make progress while this
+    /// blocks, so this only caches the builds that are already done.
+    pub fn wait_for_builds(&mut self) {
+        self.receive_finished();
+        #[cfg(not(target_arch = "wasm32"))]
+        while !self.building.is_empty() {
+            // Every build sends its result, even when it panics, and the cache holds a sender, so
+            // this only fails if a build was aborted without unwinding.
+            let Ok(finished) = self.finished_rx.get_mut().unwrap().recv() else {
+                return;
+            };
+            self.cache_finished(finished);
+        }
+    }
+
+    /// Caches the BVHs that finished building, unless a newer build of their mesh was started.
+    fn receive_finished(&mut self) {
+        let finished: Vec<_> = self.finished_rx.get_mut().unwrap().try_iter().collect();
+        for finished in finished {
+            self.cache_finished(finished);
+        }
+    }
+
+    /// Caches the BVH of a finished build, or forgets the build if it panicked, so the mesh is
+    /// raycast without a BVH until it changes or is required again.
+    fn cache_finished(&mut self, (id, build, bvh): FinishedBuild) {
+        if self.building.get(&id) == Some(&build) {
+            self.building.remove(&id);
+            if let Some(bvh) = bvh {
+                self.bvhs.insert(id, bvh);
+            }
+        }
+    }
+}
+
+/// Caches the BVHs that finished building, schedules builds for meshes that were added or
+/// modified, that entities started requiring with [`RaycastStrategy::Bvh`], or that were raycast
+/// often in the previous frame, or that became stable, and discards the BVHs of meshes that were
+/// removed, are volatile, or are no longer required by any entity.
+pub fn update_mesh_bvh_cache(
+    mut cache: ResMut<MeshBvhCache>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    volatile_meshes: Option<Res<VolatileMeshes>>,
+    required: Query<(&Mesh3dComponent, Option<&SimplifiedMesh>, &RaycastStrategy)>,
+    #[cfg(feature = "2d")] required_2d: Query<(
+        &Mesh2dComponent,
+        Option<&SimplifiedMesh>,
+        &RaycastStrategy,
+    )>,
+) {
+    cache.receive_finished();
+    let is_volatile = |id: AssetId<Mesh>| {
+        volatile_meshes
+            .as_ref()
+            .is_some_and(|volatile| volatile.is_volatile(id))
+    };
+    if let Some(volatile_meshes) = volatile_meshes.as_ref() {
+        for id in volatile_meshes.iter_volatile() {
+            cache.invalidate(id);
+        }
+        for id in volatile_meshes.iter_stabilized() {
+            cache.schedule(id, &meshes);
+        }
+    }
+    for event in mesh_events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } if !is_volatile(*id) => {
+                cache.schedule(*id, &meshes);
+            }
+            AssetEvent::Removed { id } => {
+                cache.required.remove(id);
+                cache.frequent.remove(id);
+                cache.invalidate(*id);
+            }
+            AssetEvent::Unused { id } => cache.invalidate(*id),
+            _ => (),
+        }
+    }
+    let required = required.iter().map(|(mesh, simplified, strategy)| {
+        // Raycasts use the simplified mesh instead of the entity's mesh.
+        let id = simplified.map_or(mesh.mesh_id(), |simplified| simplified.mesh.id());
+        (id, *strategy)
+    });
+    #[cfg(feature = "2d")]
+    let required = required.chain(required_2d.iter().map(|(mesh, simplified, strategy)| {
+        let id = simplified.map_or(mesh.mesh_id(), |simplified| simplified.mesh.id());
+        (id, *strategy)
+    }));
+    let required = required
+        .filter(|(id, strategy)| *strategy == RaycastStrategy::Bvh && !is_volatile(*id))
+        .map(|(id, _)| id)
+        .collect();
+    cache.set_required_by_entities(required, &meshes);
+    cache.promote_frequent(&meshes, is_volatile);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, math::Vec3A, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn raycasts_use_mesh_bvhs() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.insert_resource(MeshBvhCache {
+            build_async: false,
+            ..default()
+        });
+        let cast = |world: &mut World| {
+            world.run_system_once(|mut raycast: Raycast| {
+                let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+                let settings =
+                    RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .first()
+                    .map(|(entity, _)| *entity)
+            })
+        };
+
+        // The planes are too small for a BVH, until one of them requires it.
+        world.run_system_once(update_mesh_bvh_cache);
+        let id = world.get::<Handle<Mesh>>(planes[0]).unwrap().id();
+        assert!(world.resource::<MeshBvhCache>().get(id).is_none());
+        world.entity_mut(planes[0]).insert(RaycastStrategy::Bvh);
+        world.run_system_once(update_mesh_bvh_cache);
+        assert!(world.resource::<MeshBvhCache>().get(id).is_some());
+        assert_eq!(cast(&mut world), Some(planes[0]));
+
+        // A BVH with bounds away from the ray finds nothing on either plane, unless it is
+        // overridden.
+        let misplaced = TriangleBvh::new(&[[Vec3A::splat(100.0); 3]; 2]);
+        world
+            .resource_mut::<MeshBvhCache>()
+            .bvhs
+            .insert(id, misplaced);
+        assert_eq!(cast(&mut world), None);
+        world
+            .entity_mut(planes[1])
+            .insert(RaycastStrategy::BruteForce);
+        assert_eq!(cast(&mut world), Some(planes[1]));
+    }
+
+    #[test]
+    fn bvhs_are_released_with_the_last_entity_requiring_them() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0]);
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.insert_resource(MeshBvhCache {
+            build_async: false,
+            ..default()
+        });
+        let id = world.get::<Handle<Mesh>>(planes[0]).unwrap().id();
+        let has_bvh = |world: &mut World| {
+            world.run_system_once(update_mesh_bvh_cache);
+            world.resource::<MeshBvhCache>().get(id).is_some()
+        };
+
+        for plane in &planes {
+            world.entity_mut(*plane).insert(RaycastStrategy::Bvh);
+        }
+        assert!(has_bvh(&mut world));
+        world.entity_mut(planes[0]).remove::<RaycastStrategy>();
+        assert!(has_bvh(&mut world));
+        world.entity_mut(planes[1]).despawn();
+        assert!(!has_bvh(&mut world));
+    }
+
+    #[test]
+    fn frequently_raycast_meshes_get_bvhs() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0]);
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.insert_resource(MeshBvhCache {
+            frequent_min_triangles: 2,
+            frequent_casts: 3,
+            build_async: false,
+            ..default()
+        });
+        let id = world.get::<Handle<Mesh>>(planes[0]).unwrap().id();
+        let cast_and_update = |world: &mut World, casts: usize| {
+            for _ in 0..casts {
+                world.run_system_once(|mut raycast: Raycast| {
+                    let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+                    let settings =
+                        RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+                    raycast.cast_ray(ray, &settings);
+                });
+            }
+            world.run_system_once(update_mesh_bvh_cache);
+            world.resource::<MeshBvhCache>().get(id).is_some()
+        };
+
+        // Casts are counted per frame.
+        assert!(!cast_and_update(&mut world, 2));
+        assert!(!cast_and_update(&mut world, 2));
+        assert!(cast_and_update(&mut world, 3));
+        assert!(world.resource::<MeshBvhCache>().is_frequently_cast(id));
+        // The BVH is kept once the mesh is no longer raycast.
+        assert!(cast_and_update(&mut world, 0));
+    }
+
+    #[test]
+    fn bvhs_are_built_asynchronously() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0]);
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.insert_resource(MeshBvhCache::default());
+        world.entity_mut(planes[0]).insert(RaycastStrategy::Bvh);
+        let id = world.get::<Handle<Mesh>>(planes[0]).unwrap().id();
+
+        world.run_system_once(update_mesh_bvh_cache);
+        let mut cache = world.resource_mut::<MeshBvhCache>();
+        assert!(cache.is_building(id));
+        cache.wait_for_builds();
+        assert!(!cache.is_building(id));
+        assert_eq!(cache.get(id).map(TriangleBvh::triangle_count), Some(2));
+
+        // Builds that were superseded are discarded.
+        cache.building.insert(id, u64::MAX);
+        cache.bvhs.remove(&id);
+        let stale = TriangleBvh::new(&[[Vec3A::ZERO; 3]]);
+        cache.finished_tx.send((id, 0, Some(stale))).unwrap();
+        cache.receive_finished();
+        assert!(cache.get(id).is_none());
+        assert!(cache.is_building(id));
+
+        // Builds that panicked are no longer waited for.
+        cache.finished_tx.send((id, u64::MAX, None)).unwrap();
+        cache.wait_for_builds();
+        assert!(cache.get(id).is_none());
+        assert!(!cache.is_building(id));
+    }
+}
@@ -0,0 +1,175 @@
+//! # Perception
+//!
+//! Helpers for AI perception and cover systems, which need to know how much of an entity can be
+//! seen from a point rather than whether a single ray reaches it.
+//!
+//! [`Raycast::visible_fraction`] casts a segment from the viewpoint to each of a set of sample
+//! points on the target, and returns the fraction of them that are not blocked. Samples can be
+//! taken from the corners of the target's AABB, which is cheap, or spread across the surface of its
+//! mesh with a [`MeshSurfaceSampler`], which is more accurate for irregular shapes.
+
+use bevy_asset::Handle;
+use bevy_ecs::entity::Entity;
+use bevy_math::{BVec3A, Vec3, Vec3A};
+use bevy_reflect::Reflect;
+use bevy_render::mesh::Mesh;
+use bevy_transform::components::GlobalTransform;
+
+use crate::{dynamic_mesh::culling_aabb, immediate::model_matrix, prelude::*};
+
+/// Where [`Raycast::visible_fraction`] places sample points on the target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum VisibilitySamples {
+    /// The eight corners of the target's AABB. The target itself never blocks these samples, since
+    /// the corners are generally not on its surface.
+    AabbCorners,
+    /// This many points spread evenly over the surface of the target's mesh, using
+    /// [`MeshSurfaceSampler::sample_evenly`]. Parts of the target can hide other parts of it, so
+    /// samples on the far side of the target are not visible.
+    Surface(usize),
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Estimates the fraction of the `target` that is visible from `viewpoint`, from `0.0` when
+    /// every sample is blocked to `1.0` when none are. Returns `0.0` if the target is not
+    /// raycastable, or no samples could be placed on it.
+    ///
+    /// Samples are tested with [`Raycast::cast_segment`], using the visibility and filter of the
+    /// `settings`. When the viewpoint is another entity, use the filter to exclude it. Blocking hits
+    /// within `tolerance` of a sample are ignored, so samples are not hidden by the surface they lie
+    /// on.
+    pub fn visible_fraction(
+        &mut self,
+        viewpoint: Vec3,
+        target: Entity,
+        samples: VisibilitySamples,
+        tolerance: f32,
+        settings: &RaycastSettings,
+    ) -> f32 {
+        let points = match samples {
+            VisibilitySamples::AabbCorners => self.aabb_corners(target, settings),
+            VisibilitySamples::Surface(count) => self.surface_samples(target, count, settings),
+        };
+        if points.is_empty() {
+            return 0.0;
+        }
+
+        let target_can_block = matches!(samples, VisibilitySamples::Surface(_));
+        let filter = |entity| (target_can_block || entity != target) && (settings.filter)(entity);
+        let settings = RaycastSettings {
+            filter: &filter,
+            ..settings.clone()
+        }
+        .always_early_exit();
+
+        let visible = points
+            .iter()
+            .filter(|point| {
+                let length = viewpoint.distance(**point);
+                self.cast_segment(viewpoint, **point, &settings)
+                    .first()
+                    .map_or(true, |(_, hit)| hit.distance() >= length - tolerance)
+            })
+            .count();
+        visible as f32 / points.len() as f32
+    }
+
+    fn aabb_corners(&self, target: Entity, settings: &RaycastSettings) -> Vec<Vec3> {
+        let Ok((_, _, bounds, transform, ..)) = self.culling_query.get(target) else {
+            return Vec::new();
+        };
+        let Some(aabb) = culling_aabb(bounds) else {
+            return Vec::new();
+        };
+        let transform = model_matrix(
+            target,
+            transform,
+            &self.transform_history,
+            settings.transform_blend,
+        );
+        let (min, max) = (aabb.min(), aabb.max());
+        (0..8)
+            .map(|i| {
+                let corner =
+                    Vec3A::select(BVec3A::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), max, min);
+                transform.transform_point3a(corner).into()
+            })
+            .collect()
+    }
+
+    fn surface_samples(
+        &self,
+        target: Entity,
+        count: usize,
+        settings: &RaycastSettings,
+    ) -> Vec<Vec3> {
+        let mesh_and_transform = |handle: &Handle<Mesh>, transform: &GlobalTransform| {
+            let mesh = self.meshes.get(handle)?;
+            let transform = model_matrix(
+                target,
+                transform,
+                &self.transform_history,
+                settings.transform_blend,
+            );
+            MeshSurfaceSampler::new(mesh, &transform)
+        };
+        let sampler = match self.mesh_query.get(target) {
+            Ok((handle, _, _, transform)) => mesh_and_transform(handle.mesh_handle(), transform),
+            #[cfg(feature = "2d")]
+            Err(_) => self
+                .mesh2d_query
+                .get(target)
+                .ok()
+                .and_then(|(handle, _, transform)| {
+                    mesh_and_transform(handle.mesh_handle(), transform)
+                }),
+            #[cfg(not(feature = "2d"))]
+            Err(_) => None,
+        };
+        sampler.map_or_else(Vec::new, |sampler| {
+            sampler
+                .sample_evenly(count)
+                .map(|sample| sample.position)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn half_covered_plane() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-2.0, -1.0]);
+        let (target, cover) = (planes[0], planes[1]);
+        // Move the cover so it only hides the left half of the target.
+        let cover_transform = GlobalTransform::from_xyz(-1.0, 0.0, -1.0);
+        world.entity_mut(cover).insert(cover_transform);
+
+        let fractions = world.run_system_once(move |mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            let corners = raycast.visible_fraction(
+                Vec3::ZERO,
+                target,
+                VisibilitySamples::AabbCorners,
+                0.01,
+                &settings,
+            );
+            let surface = raycast.visible_fraction(
+                Vec3::ZERO,
+                target,
+                VisibilitySamples::Surface(64),
+                0.01,
+                &settings,
+            );
+            (corners, surface)
+        });
+        assert_eq!(fractions.0, 0.5);
+        assert!((0.4..=0.6).contains(&fractions.1), "{}", fractions.1);
+    }
+}
@@ -1,95 +1,461 @@
-use bevy_math::{Vec3, Vec3A};
+use bevy_asset::AssetId;
+use bevy_math::{Vec3, Vec3A, Vec4};
 use bevy_reflect::Reflect;
+#[cfg(feature = "serialize")]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+use bevy_render::mesh::Mesh;
+use bevy_transform::components::GlobalTransform;
 
 pub use rays::*;
 
-#[derive(Debug, Clone, Reflect)]
+/// A hit found by a raycast, in world space.
+///
+/// The position, normal, and distance are set for every hit. Other data is optional, and is only
+/// set when the hit came from a source that has it:
+///
+/// | Data | Set for |
+/// |------|---------|
+/// | [`barycentric_coord`](Self::barycentric_coord), [`triangle_index`](Self::triangle_index), [`triangle_indices`](Self::triangle_indices) | Mesh hits |
+/// | [`triangle`](Self::triangle) | Mesh hits, when requested by [`HitData::triangle`]; see [`triangle_from`](Self::triangle_from) |
+/// | [`tangent`](Self::tangent) | Hits on meshes with tangents, when requested by [`HitData::tangent`] |
+/// | [`aabb_distances`](Self::aabb_distances) | Hits from [`Raycast`](crate::immediate::Raycast) |
+/// | [`source_mesh`](Self::source_mesh), [`is_proxy`](Self::is_proxy) | Hits on mesh assets |
+///
+/// Fields are private and only accessible through getters. New data is always added with new
+/// getters, never by changing existing ones, so code reading hits keeps working as data is added. Hits can be built with [`IntersectionData::new`],
+/// or with [`IntersectionData::from_hit`] followed by the `with_*` methods.
+#[derive(Debug, Clone, Copy, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
 pub struct IntersectionData {
-    position: Vec3,
-    normal: Vec3,
+    position: Vec3A,
+    normal: Vec3A,
     barycentric_coord: Vec3,
     distance: f32,
     triangle: Option<[Vec3A; 3]>,
     triangle_index: Option<usize>,
+    triangle_indices: Option<[u32; 3]>,
+    aabb_distances: Option<[f32; 2]>,
+    tangent: Option<Vec4>,
+    approximate: bool,
+    source_mesh: Option<AssetId<Mesh>>,
+    proxy: bool,
 }
 
 impl From<rays::PrimitiveIntersection> for IntersectionData {
     fn from(data: rays::PrimitiveIntersection) -> Self {
         Self {
-            position: data.position(),
-            normal: data.normal(),
+            position: data.position().into(),
+            normal: data.normal().into(),
             distance: data.distance(),
             barycentric_coord: Vec3::ZERO,
             triangle: None,
             triangle_index: None,
+            triangle_indices: None,
+            aabb_distances: None,
+            tangent: None,
+            approximate: false,
+            source_mesh: None,
+            proxy: false,
         }
     }
 }
 
 impl IntersectionData {
+    /// The `position` and `normal` accept both [`Vec3`] and [`Vec3A`], and are stored as [`Vec3A`].
     pub fn new(
-        position: Vec3,
-        normal: Vec3,
+        position: impl Into<Vec3A>,
+        normal: impl Into<Vec3A>,
         barycentric: Vec3,
         distance: f32,
         triangle: Option<[Vec3A; 3]>,
         triangle_index: Option<usize>,
     ) -> Self {
         Self {
-            position,
-            normal,
+            position: position.into(),
+            normal: normal.into(),
             barycentric_coord: barycentric,
             distance,
             triangle,
             triangle_index,
+            triangle_indices: None,
+            aabb_distances: None,
+            tangent: None,
+            approximate: false,
+            source_mesh: None,
+            proxy: false,
+        }
+    }
+
+    /// A hit with only a position, normal, and distance, to be filled in with the `with_*` methods.
+    pub fn from_hit(position: impl Into<Vec3A>, normal: impl Into<Vec3A>, distance: f32) -> Self {
+        Self::new(position, normal, Vec3::ZERO, distance, None, None)
+    }
+
+    /// Set the barycentric coordinates of the hit on its triangle.
+    pub fn with_barycentric_coord(self, barycentric_coord: Vec3) -> Self {
+        Self {
+            barycentric_coord,
+            ..self
+        }
+    }
+
+    /// Set the world-space vertices of the hit triangle, or clear them with `None`.
+    pub fn with_triangle(self, triangle: Option<[Vec3A; 3]>) -> Self {
+        Self { triangle, ..self }
+    }
+
+    /// Set the index of the hit triangle in its mesh.
+    pub fn with_triangle_index(self, triangle_index: usize) -> Self {
+        Self {
+            triangle_index: Some(triangle_index),
+            ..self
+        }
+    }
+
+    /// Set the indices of the vertices of the hit triangle in its mesh's vertex buffer.
+    pub fn with_triangle_indices(self, triangle_indices: [u32; 3]) -> Self {
+        Self {
+            triangle_indices: Some(triangle_indices),
+            ..self
+        }
+    }
+
+    /// Set the `[near, far]` distances along the ray at which it enters and exits the hit entity's
+    /// world-space AABB.
+    pub fn with_aabb_distances(self, aabb_distances: [f32; 2]) -> Self {
+        Self {
+            aabb_distances: Some(aabb_distances),
+            ..self
+        }
+    }
+
+    /// Set the interpolated tangent at the intersection, with the bitangent sign in `w`.
+    pub fn with_tangent(self, tangent: Vec4) -> Self {
+        Self {
+            tangent: Some(tangent),
+            ..self
+        }
+    }
+
+    /// Replace the normal of this intersection.
+    pub fn with_normal(self, normal: impl Into<Vec3A>) -> Self {
+        Self {
+            normal: normal.into(),
+            ..self
+        }
+    }
+
+    /// Replace the distance of this intersection, used when the ray it was found with started
+    /// somewhere other than the reported origin.
+    pub(crate) fn with_distance(self, distance: f32) -> Self {
+        Self { distance, ..self }
+    }
+
+    /// Set the mesh that was raycast to find this intersection, and whether it was a
+    /// [`SimplifiedMesh`](crate::markers::SimplifiedMesh) standing in for the entity's mesh.
+    pub fn with_source_mesh(self, source_mesh: AssetId<Mesh>, proxy: bool) -> Self {
+        Self {
+            source_mesh: Some(source_mesh),
+            proxy,
+            ..self
+        }
+    }
+
+    /// Flag this intersection as approximate; see [`IntersectionData::is_approximate`].
+    pub fn with_approximate(self, approximate: bool) -> Self {
+        Self {
+            approximate,
+            ..self
         }
     }
 
     /// Get the intersection data's position.
     #[must_use]
     pub fn position(&self) -> Vec3 {
+        self.position.into()
+    }
+
+    /// Get the intersection data's position as a [`Vec3A`], which is how it is stored, for SIMD
+    /// math without converting from [`Vec3`].
+    #[must_use]
+    pub fn position_vec3a(&self) -> Vec3A {
         self.position
     }
 
+    /// Get the intersection data's position, moved off the surface along the
+    /// [`normal`](Self::normal) by `epsilon`.
+    ///
+    /// Hit positions are only accurate to a fraction of the size of the coordinates they were
+    /// computed from, so `epsilon` is scaled up by the largest coordinate of the position and of
+    /// the hit triangle, if larger than one. Use this to place objects on a surface without
+    /// z-fighting, or as the origin of a bounced ray, which would otherwise hit the same face again:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// fn bounce(ray: Ray3d, hit: &IntersectionData) -> Ray3d {
+    ///     let normal = hit.normal().normalize();
+    ///     let reflected = *ray.direction - 2.0 * ray.direction.dot(normal) * normal;
+    ///     Ray3d::new(hit.position_with_offset(1e-4), reflected)
+    /// }
+    /// ```
+    #[must_use]
+    pub fn position_with_offset(&self, epsilon: f32) -> Vec3 {
+        let triangle_scale = self.triangle.map_or(0.0, |triangle| {
+            triangle
+                .map(|vertex| vertex.abs().max_element())
+                .into_iter()
+                .fold(0.0, f32::max)
+        });
+        let scale = self
+            .position
+            .abs()
+            .max_element()
+            .max(triangle_scale)
+            .max(1.0);
+        (self.position + self.normal.normalize_or_zero() * epsilon * scale).into()
+    }
+
     /// Get the intersection data's normal.
     #[must_use]
     pub fn normal(&self) -> Vec3 {
+        self.normal.into()
+    }
+
+    /// Get the intersection data's normal as a [`Vec3A`], which is how it is stored, for SIMD math
+    /// without converting from [`Vec3`].
+    #[must_use]
+    pub fn normal_vec3a(&self) -> Vec3A {
         self.normal
     }
 
-    /// Get the intersection data's barycentric coord.
+    /// Get the barycentric coordinates of the hit on its triangle. The `x` and `y` components
+    /// are the weights of the second and third vertex of the [`triangle`](Self::triangle), and `z`
+    /// is the weight of the first. Use [`interpolate`](Self::interpolate) to blend per-vertex
+    /// values with them.
     #[must_use]
     pub fn barycentric_coord(&self) -> Vec3 {
         self.barycentric_coord
     }
 
-    /// Get the intersection data's distance.
+    /// Blends the `values` of the three vertices of the hit triangle, in the order of its vertices,
+    /// by the [`barycentric_coord`](Self::barycentric_coord) of the hit. This works for any
+    /// vertex attribute, such as UVs or colors:
+    ///
+    /// ```
+    /// # use bevy_mod_raycast::prelude::*;
+    /// # use bevy::prelude::*;
+    /// fn hit_uv(hit: &IntersectionData, triangle_uvs: [Vec2; 3]) -> Vec2 {
+    ///     hit.interpolate(triangle_uvs)
+    /// }
+    /// ```
+    ///
+    /// See [`interpolate_vertex_attribute`](crate::raycast::interpolate_vertex_attribute) to look
+    /// up the values in a mesh.
+    #[must_use]
+    pub fn interpolate<T>(&self, values: [T; 3]) -> T
+    where
+        T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+    {
+        let [a, b, c] = values;
+        let [u, v, w] = self.barycentric_coord.to_array();
+        b * u + c * v + a * w
+    }
+
+    /// Get the intersection data's distance. This is negative for hits behind the ray origin, which
+    /// are only returned by line casts; see
+    /// [`RaycastSettings::include_behind_origin`](crate::immediate::RaycastSettings).
     #[must_use]
     pub fn distance(&self) -> f32 {
         self.distance
     }
 
-    /// Get the intersection data's triangle.
+    /// Get the world-space vertices of the hit triangle. These are only captured when requested by
+    /// [`HitData::triangle`], which is off by default, since most hits never read them. Use
+    /// [`triangle_from`](Self::triangle_from) or
+    /// [`Raycast::hit_triangle`](crate::immediate::Raycast::hit_triangle) to look them up for the
+    /// few hits that need them instead.
     #[must_use]
     pub fn triangle(&self) -> Option<[Vec3A; 3]> {
         self.triangle
     }
 
-    /// Get the intersection data's triangle index.
+    /// Looks up the world-space vertices of the hit triangle, from the vertex `positions` of the
+    /// mesh that was hit and its `transform`. Returns the captured [`triangle`](Self::triangle)
+    /// if there is one, and `None` if the hit has no [`triangle_indices`](Self::triangle_indices),
+    /// or they are out of range of the `positions`.
+    ///
+    /// The positions must be those of the [`source_mesh`](Self::source_mesh), which differs from
+    /// the entity's mesh for [proxy](Self::is_proxy) hits.
+    #[must_use]
+    pub fn triangle_from(
+        &self,
+        positions: &[[f32; 3]],
+        transform: &GlobalTransform,
+    ) -> Option<[Vec3A; 3]> {
+        if self.triangle.is_some() {
+            return self.triangle;
+        }
+        let [a, b, c] = self
+            .triangle_indices?
+            .map(|index| positions.get(index as usize).copied());
+        Some([a?, b?, c?].map(|position| transform.affine().transform_point3a(position.into())))
+    }
+
+    /// Get the index of the hit triangle in the mesh, counting triangles in index buffer order, or
+    /// vertex order for meshes without indices.
     #[must_use]
     pub fn triangle_index(&self) -> Option<usize> {
         self.triangle_index
     }
+
+    /// Get the indices of the vertices of the hit triangle in the mesh's vertex buffer, read from
+    /// its index buffer, in the same order as the [`triangle`](Self::triangle). Use these to look
+    /// up any vertex attribute of the hit triangle's vertices.
+    #[must_use]
+    pub fn triangle_indices(&self) -> Option<[u32; 3]> {
+        self.triangle_indices
+    }
+
+    /// Get the tangent at the intersection, interpolated from the mesh's
+    /// [`ATTRIBUTE_TANGENT`](bevy_render::mesh::Mesh::ATTRIBUTE_TANGENT), if it has one. The `w`
+    /// component holds the sign of the bitangent, as in the vertex attribute.
+    #[must_use]
+    pub fn tangent(&self) -> Option<Vec4> {
+        self.tangent
+    }
+
+    /// Get the bitangent at the intersection, if the mesh has tangents. Together with the
+    /// [`normal`](Self::normal) and [`tangent`](Self::tangent), this forms the TBN frame used for
+    /// normal mapping at the hit.
+    #[must_use]
+    pub fn bitangent(&self) -> Option<Vec3> {
+        self.tangent
+            .map(|t| self.normal().normalize_or_zero().cross(t.truncate()) * t.w)
+    }
+
+    /// Returns `true` if this hit was not tested against the mesh's triangles, and is only the point
+    /// where the ray entered the entity's AABB. This happens when a mesh exceeds
+    /// [`RaycastSettings::max_triangles_per_cast`](crate::immediate::RaycastSettings). Approximate
+    /// hits have no triangle data, and their normal faces back along the ray.
+    #[must_use]
+    pub fn is_approximate(&self) -> bool {
+        self.approximate
+    }
+
+    /// Get the `[near, far]` distances along the ray at which it entered and exited the hit
+    /// entity's AABB, if the hit was produced by a raycast that culled using AABBs.
+    ///
+    /// The near distance is negative if the ray origin is inside the AABB. The difference between
+    /// the hit [`distance`](Self::distance) and the far distance is an estimate of how deep into
+    /// the entity's bounds the hit occurred.
+    #[must_use]
+    pub fn aabb_distances(&self) -> Option<[f32; 2]> {
+        self.aabb_distances
+    }
+
+    /// Get the mesh that was raycast to find this intersection, if the hit came from a mesh
+    /// raycast. The [`triangle_index`](Self::triangle_index) and interpolated attributes refer to
+    /// this mesh, which is not the entity's mesh when [`is_proxy`](Self::is_proxy) is `true`.
+    #[must_use]
+    pub fn source_mesh(&self) -> Option<AssetId<Mesh>> {
+        self.source_mesh
+    }
+
+    /// Returns `true` if the hit was found on a [`SimplifiedMesh`](crate::markers::SimplifiedMesh)
+    /// instead of the entity's own mesh. Looking up vertex attributes of the entity's mesh with
+    /// the hit triangle will give wrong results for proxy hits.
+    #[must_use]
+    pub fn is_proxy(&self) -> bool {
+        self.proxy
+    }
+}
+
+/// Which optional [`IntersectionData`] is computed for each hit, set with
+/// [`RaycastSettings::hit_data`](crate::immediate::RaycastSettings::hit_data). Data that is not
+/// requested is skipped, and its getter returns `None`.
+///
+/// New kinds of hit data will be added as new flags, so this can't be built with a struct literal.
+/// Start from [`HitData::ALL`] or [`HitData::MINIMAL`] and use the `with_*` methods instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+#[non_exhaustive]
+pub struct HitData {
+    /// Compute [`IntersectionData::triangle`], the world-space vertices of the hit triangle.
+    pub triangle: bool,
+    /// Compute [`IntersectionData::tangent`], interpolated from the tangents of the mesh.
+    pub tangent: bool,
+}
+
+impl HitData {
+    /// Compute all optional data.
+    pub const ALL: Self = Self {
+        triangle: true,
+        tangent: true,
+    };
+
+    /// Compute all optional data except the [`triangle`](IntersectionData::triangle), which can be
+    /// looked up later from the [`triangle_indices`](IntersectionData::triangle_indices) of the
+    /// hits that need it. This is the default.
+    pub const DEFAULT: Self = Self {
+        triangle: false,
+        tangent: true,
+    };
+
+    /// Only compute the data that every hit has.
+    pub const MINIMAL: Self = Self {
+        triangle: false,
+        tangent: false,
+    };
+
+    /// Set whether to compute the [`triangle`](IntersectionData::triangle) of hits.
+    pub fn with_triangle(mut self, triangle: bool) -> Self {
+        self.triangle = triangle;
+        self
+    }
+
+    /// Set whether to compute the [`tangent`](IntersectionData::tangent) of hits.
+    pub fn with_tangent(mut self, tangent: bool) -> Self {
+        self.tangent = tangent;
+        self
+    }
+}
+
+impl Default for HitData {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
 }
 
 /// Encapsulates Ray3D, preventing use of struct literal syntax. This allows us to guarantee that
 /// the `Ray3d` direction is normalized, because it can only be instantiated with the constructor.
 pub mod rays {
-    use bevy_math::{prelude::*, Ray3d, Vec3A};
-    use bevy_render::{camera::Camera, primitives::Aabb};
+    use bevy_math::{prelude::*, Mat3A, Ray3d, Vec3A};
+    use bevy_reflect::Reflect;
+    #[cfg(feature = "serialize")]
+    use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+    use bevy_render::{
+        camera::Camera,
+        primitives::{Aabb, Frustum},
+    };
     use bevy_transform::components::GlobalTransform;
     use bevy_window::Window;
 
+    /// A hit on a primitive shape, such as a plane or an AABB.
+    #[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+    #[cfg_attr(
+        feature = "serialize",
+        derive(serde::Serialize, serde::Deserialize),
+        reflect(Serialize, Deserialize)
+    )]
     pub struct PrimitiveIntersection {
         position: Vec3,
         normal: Vec3,
@@ -124,8 +490,85 @@ pub mod rays {
         }
     }
 
+    /// The axis conventions of a world, used to build rays from transforms and transforms from
+    /// rays. Bevy is Y-up with cameras looking along -Z, which is the default, but apps with a Z-up
+    /// world can use [`CoordinateSystem::Z_UP`], or any other pair of axes.
+    ///
+    /// The handedness of the coordinate system is set by which way `forward` points relative to
+    /// `up`, e.g. a Z-up app looking along -Y instead of +Y.
+    #[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+    #[cfg_attr(
+        feature = "serialize",
+        derive(serde::Serialize, serde::Deserialize),
+        reflect(Serialize, Deserialize)
+    )]
+    pub struct CoordinateSystem {
+        up: Vec3,
+        forward: Vec3,
+    }
+
+    impl Default for CoordinateSystem {
+        fn default() -> Self {
+            Self::Y_UP
+        }
+    }
+
+    impl CoordinateSystem {
+        /// Bevy's coordinate system, with +Y up and forward along -Z.
+        pub const Y_UP: Self = Self {
+            up: Vec3::Y,
+            forward: Vec3::NEG_Z,
+        };
+
+        /// A Z-up coordinate system, with forward along +Y, as used by many CAD and GIS tools.
+        pub const Z_UP: Self = Self {
+            up: Vec3::Z,
+            forward: Vec3::Y,
+        };
+
+        /// Creates a coordinate system from local `up` and `forward` axes, which are normalized.
+        /// Returns `None` if either is zero, or if they are parallel.
+        pub fn new(up: Vec3, forward: Vec3) -> Option<Self> {
+            let up = up.try_normalize()?;
+            let forward = forward.try_normalize()?;
+            (up.cross(forward).length_squared() > f32::EPSILON).then_some(Self { up, forward })
+        }
+
+        /// The up axis.
+        pub fn up(&self) -> Vec3 {
+            self.up
+        }
+
+        /// The axis transforms look along.
+        pub fn forward(&self) -> Vec3 {
+            self.forward
+        }
+
+        /// Create a transform at the origin of the ray, whose up axis is aligned with the
+        /// direction of the ray. See [`to_aligned_transform`].
+        pub fn to_transform(&self, ray: Ray3d) -> Mat4 {
+            to_aligned_transform(ray, self.up)
+        }
+
+        /// Create a ray that starts at the translation of the `transform`, and points along its
+        /// forward axis.
+        pub fn ray_from_transform(&self, transform: Mat4) -> Ray3d {
+            let pick_position = transform.project_point3(self.forward);
+            let (_, _, source_origin) = transform.to_scale_rotation_translation();
+            let ray_direction = pick_position - source_origin;
+            Ray3d::new(source_origin, ray_direction)
+        }
+
+        /// The rotation from the forward axis to `direction`, used to orient gizmos along rays.
+        pub fn rotation_to(&self, direction: Vec3) -> Quat {
+            Quat::from_rotation_arc(self.forward, direction)
+        }
+    }
+
+    /// Create a transform at the origin of the ray, whose Y axis is aligned with the direction of
+    /// the ray. See [`CoordinateSystem::to_transform`] for other up axes.
     pub fn to_transform(ray: Ray3d) -> Mat4 {
-        to_aligned_transform(ray, [0., 1., 0.].into())
+        CoordinateSystem::Y_UP.to_transform(ray)
     }
 
     /// Create a transform whose origin is at the origin of the ray and
@@ -138,12 +581,10 @@ pub mod rays {
         Mat4::from_rotation_translation(new_rotation, position)
     }
 
+    /// Create a ray that starts at the translation of the `transform`, and points along its -Z
+    /// axis. See [`CoordinateSystem::ray_from_transform`] for other forward axes.
     pub fn ray_from_transform(transform: Mat4) -> Ray3d {
-        let pick_position_ndc = Vec3::from([0.0, 0.0, -1.0]);
-        let pick_position = transform.project_point3(pick_position_ndc);
-        let (_, _, source_origin) = transform.to_scale_rotation_translation();
-        let ray_direction = pick_position - source_origin;
-        Ray3d::new(source_origin, ray_direction)
+        CoordinateSystem::Y_UP.ray_from_transform(transform)
     }
 
     pub fn ray_from_screenspace(
@@ -156,49 +597,411 @@ pub mod rays {
         if let Some(viewport) = &camera.viewport {
             viewport_pos -= viewport.physical_position.as_vec2() / window.scale_factor();
         }
-        camera
-            .viewport_to_world(camera_transform, viewport_pos)
-            .map(Ray3d::from)
+        camera.viewport_to_world(camera_transform, viewport_pos)
+    }
+
+    /// Builds the rays through the four corners of a screenspace `rect`, such as a selection box
+    /// dragged with the cursor, in the order `[top_left, top_right, bottom_right, bottom_left]`.
+    /// The rect is in logical pixels of the `window`, like the cursor position. The rays can be
+    /// used to build a selection volume, or to draw a preview of one.
+    ///
+    /// Returns `None` if any of the rays can't be built; see [`ray_from_screenspace`].
+    pub fn rays_from_screenspace_rect(
+        rect: Rect,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        window: &Window,
+    ) -> Option<[Ray3d; 4]> {
+        let corners = [
+            rect.min,
+            Vec2::new(rect.max.x, rect.min.y),
+            rect.max,
+            Vec2::new(rect.min.x, rect.max.y),
+        ];
+        let [a, b, c, d] =
+            corners.map(|corner| ray_from_screenspace(corner, camera, camera_transform, window));
+        Some([a?, b?, c?, d?])
+    }
+
+    /// Builds a ray through the camera's view at the normalized device coordinates `ndc`, without
+    /// needing a window. Works with perspective and orthographic cameras. The ray starts on the near
+    /// plane. See [`segment_from_ndc`] for custom depth ranges.
+    pub fn ray_from_ndc(
+        ndc: Vec2,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+    ) -> Option<Ray3d> {
+        let world_from_clip = camera_transform.compute_matrix() * camera.clip_from_view().inverse();
+        // Bevy uses reversed depth, so the far plane of a perspective projection is at infinity.
+        let [start, end] = segment_from_ndc(ndc, [1.0, f32::EPSILON], &world_from_clip)?;
+        Dir3::new(end - start).ok().map(|direction| Ray3d {
+            origin: start,
+            direction,
+        })
+    }
+
+    /// Converts the normalized device coordinates `ndc` and a range of NDC depths into a
+    /// world-space `[start, end]` segment, using the inverse of the camera's view projection
+    /// matrix, `world_from_clip`. Depths follow Bevy's reversed depth convention, where `1.0` is the
+    /// near plane and `0.0` is the far plane, so values read back from a depth buffer can be used
+    /// directly. Returns `None` if either end is not finite, such as a depth of `0.0` with an
+    /// infinite perspective projection.
+    ///
+    /// The segment can be cast with
+    /// [`Raycast::cast_segment`](crate::immediate::Raycast::cast_segment).
+    pub fn segment_from_ndc(
+        ndc: Vec2,
+        [near_depth, far_depth]: [f32; 2],
+        world_from_clip: &Mat4,
+    ) -> Option<[Vec3; 2]> {
+        let start = world_from_clip.project_point3(ndc.extend(near_depth));
+        let end = world_from_clip.project_point3(ndc.extend(far_depth));
+        (start.is_finite() && end.is_finite()).then_some([start, end])
+    }
+
+    /// The distance along the ray to the far plane of a camera, whose view projection matrix is
+    /// `clip_from_world`, or [`f32::INFINITY`] if the ray never reaches it, such as with an
+    /// infinite perspective projection.
+    ///
+    /// Rays built by [`ray_from_screenspace`] already start on the near plane, so using this as
+    /// the [`RaycastSettings::max_distance`](crate::immediate::RaycastSettings::max_distance)
+    /// only hits what the camera renders.
+    pub fn far_plane_distance(ray: Ray3d, clip_from_world: &Mat4) -> f32 {
+        // Bevy uses reversed depth, so the far plane is where the clip space depth is zero.
+        let depth = clip_from_world.row(2);
+        let start = depth.dot(ray.origin.extend(1.0));
+        let rate = depth.dot(ray.direction.extend(0.0));
+        let distance = -start / rate;
+        if distance.is_finite() && distance >= 0.0 {
+            distance
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    /// Clips the ray to the inside of a camera's [`Frustum`], returning the `[start, end]` distances
+    /// along the ray of the visible portion, or `None` if no part of the ray in front of its origin
+    /// is inside the frustum.
+    pub fn clip_ray_to_frustum(ray: Ray3d, frustum: &Frustum) -> Option<[f32; 2]> {
+        let origin = Vec3A::from(ray.origin);
+        let direction = Vec3A::from(*ray.direction);
+        let (mut start, mut end) = (0.0_f32, f32::INFINITY);
+        for half_space in &frustum.half_spaces {
+            // The signed distance from the plane is `offset + rate * t`, and is positive inside.
+            let offset = half_space.normal().dot(origin) + half_space.d();
+            let rate = half_space.normal().dot(direction);
+            if rate.abs() < f32::EPSILON {
+                if offset < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = -offset / rate;
+            if rate > 0.0 {
+                start = start.max(t);
+            } else {
+                end = end.min(t);
+            }
+        }
+        (start <= end).then_some([start, end])
     }
 
     /// Checks if the ray intersects with an AABB of a mesh, returning `[near, far]` if it does.
+    ///
+    /// When testing one ray against many AABBs in the same space, build a [`SlabRay`] once
+    /// instead.
     pub fn intersects_aabb(ray: Ray3d, aabb: &Aabb, model_to_world: &Mat4) -> Option<[f32; 2]> {
-        // Transform the ray to model space
-        let world_to_model = model_to_world.inverse();
-        let ray_dir: Vec3A = world_to_model.transform_vector3(*ray.direction).into();
-        let ray_origin: Vec3A = world_to_model.transform_point3(ray.origin).into();
         // Check if the ray intersects the mesh's AABB. It's useful to work in model space
         // because we can do an AABB intersection test, instead of an OBB intersection test.
+        SlabRay::in_model_space(ray, model_to_world).intersects_aabb(aabb)
+    }
 
-        let t_0: Vec3A = (aabb.min() - ray_origin) / ray_dir;
-        let t_1: Vec3A = (aabb.max() - ray_origin) / ray_dir;
-        let t_min: Vec3A = t_0.min(t_1);
-        let t_max: Vec3A = t_0.max(t_1);
+    /// A ray prepared for slab tests against AABBs, with its reciprocal direction precomputed, so
+    /// each test only multiplies.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct SlabRay {
+        origin: Vec3A,
+        direction: Vec3A,
+        inverse_direction: Vec3A,
+    }
 
-        let mut hit_near = t_min.x;
-        let mut hit_far = t_max.x;
+    impl SlabRay {
+        /// Prepares `ray` for tests against AABBs in the same space.
+        pub fn new(ray: Ray3d) -> Self {
+            Self::from_origin_direction(ray.origin.into(), (*ray.direction).into())
+        }
 
-        if hit_near > t_max.y || t_min.y > hit_far {
-            return None;
+        /// Prepares `ray` for tests against AABBs in the model space of `model_to_world`.
+        ///
+        /// The transform must be affine, like those of [`GlobalTransform`], so only its 3x3 part
+        /// is inverted, which is much cheaper than [`Mat4::inverse`].
+        pub fn in_model_space(ray: Ray3d, model_to_world: &Mat4) -> Self {
+            let world_to_model = Mat3A::from_mat4(*model_to_world).inverse();
+            let translation = Vec3A::from(model_to_world.w_axis);
+            Self::from_origin_direction(
+                world_to_model * (Vec3A::from(ray.origin) - translation),
+                world_to_model * Vec3A::from(*ray.direction),
+            )
         }
 
-        if t_min.y > hit_near {
-            hit_near = t_min.y;
+        /// Prepares `ray` for tests against AABBs in model space, when the inverse of the model
+        /// transform, `world_to_model`, is already known.
+        pub fn with_world_to_model(ray: Ray3d, world_to_model: &Mat4) -> Self {
+            Self::from_origin_direction(
+                world_to_model.transform_point3a(ray.origin.into()),
+                world_to_model.transform_vector3a((*ray.direction).into()),
+            )
         }
-        if t_max.y < hit_far {
-            hit_far = t_max.y;
+
+        /// Prepares a ray from its `origin` and `direction`, which doesn't need to be normalized.
+        /// Distances are measured in multiples of the length of the `direction`.
+        pub fn from_origin_direction(origin: Vec3A, direction: Vec3A) -> Self {
+            Self {
+                origin,
+                direction,
+                inverse_direction: direction.recip(),
+            }
         }
 
-        if (hit_near > t_max.z) || (t_min.z > hit_far) {
-            return None;
+        /// The origin of the ray.
+        pub fn origin(&self) -> Vec3A {
+            self.origin
         }
 
-        if t_min.z > hit_near {
-            hit_near = t_min.z;
+        /// The direction of the ray, which is not normalized in model space, so distances along it
+        /// are the same as along the world-space ray.
+        pub fn direction(&self) -> Vec3A {
+            self.direction
         }
-        if t_max.z < hit_far {
-            hit_far = t_max.z;
+
+        /// Checks if the ray intersects the `aabb`, returning `[near, far]` if it does.
+        pub fn intersects_aabb(&self, aabb: &Aabb) -> Option<[f32; 2]> {
+            let (min, max) = (aabb.min(), aabb.max());
+            let mut hit_near = f32::NEG_INFINITY;
+            let mut hit_far = f32::INFINITY;
+            for axis in 0..3 {
+                let origin = self.origin[axis];
+                if self.direction[axis] == 0.0 {
+                    // The ray is parallel to this slab, so it either always or never overlaps it.
+                    // Multiplying by the infinite reciprocal instead would give NaN when the origin
+                    // lies exactly on a face, which is common for flat 2D meshes whose AABB has no
+                    // depth.
+                    if origin < min[axis] || origin > max[axis] {
+                        return None;
+                    }
+                    continue;
+                }
+                let inverse_direction = self.inverse_direction[axis];
+                let t_0 = (min[axis] - origin) * inverse_direction;
+                let t_1 = (max[axis] - origin) * inverse_direction;
+                hit_near = hit_near.max(t_0.min(t_1));
+                hit_far = hit_far.min(t_0.max(t_1));
+                if hit_near > hit_far {
+                    return None;
+                }
+            }
+            Some([hit_near, hit_far])
+        }
+    }
+
+    /// Intersects the ray with the faces of an AABB, returning the hits where it enters and exits
+    /// the AABB, in that order. The normals face out of the AABB, and point back along the ray at
+    /// the entry. Like [`intersects_aabb`], distances are negative behind the ray origin.
+    pub fn ray_aabb_intersections(
+        ray: Ray3d,
+        aabb: &Aabb,
+        model_to_world: &Mat4,
+    ) -> Option<[super::IntersectionData; 2]> {
+        let model_ray = SlabRay::in_model_space(ray, model_to_world);
+        let (ray_origin, ray_dir) = (model_ray.origin(), model_ray.direction());
+        let [near, far] = model_ray.intersects_aabb(aabb)?;
+        // The entry and exit faces are those of the slabs whose bounds the ray crosses last and
+        // first. Slabs the ray is parallel to are never crossed.
+        let (mut entry_normal, mut exit_normal) = (Vec3::ZERO, Vec3::ZERO);
+        let (min, max) = (aabb.min(), aabb.max());
+        let (mut entry_t, mut exit_t) = (f32::NEG_INFINITY, f32::INFINITY);
+        for axis in 0..3 {
+            let direction = ray_dir[axis];
+            if direction == 0.0 {
+                continue;
+            }
+            let t_min = (min[axis] - ray_origin[axis]) / direction;
+            let t_max = (max[axis] - ray_origin[axis]) / direction;
+            let mut normal = Vec3::ZERO;
+            normal[axis] = direction.signum();
+            if t_min.min(t_max) > entry_t {
+                entry_t = t_min.min(t_max);
+                entry_normal = -normal;
+            }
+            if t_min.max(t_max) < exit_t {
+                exit_t = t_min.max(t_max);
+                exit_normal = normal;
+            }
         }
-        Some([hit_near, hit_far])
+        let normal_matrix = Mat3A::from_mat4(*model_to_world).inverse().transpose();
+        let hit = |distance: f32, normal: Vec3| {
+            super::IntersectionData::new(
+                ray.get_point(distance),
+                Vec3::from(normal_matrix * Vec3A::from(normal)).normalize_or_zero(),
+                Vec3::ZERO,
+                distance,
+                None,
+                None,
+            )
+        };
+        Some([hit(near, entry_normal), hit(far, exit_normal)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::{Mat4, Quat, Ray3d, Vec2, Vec3};
+    use bevy_render::primitives::{Aabb, Frustum};
+
+    use super::*;
+
+    #[test]
+    fn ndc_rays() {
+        let perspective = Mat4::perspective_infinite_reverse_rh(1.0, 1.0, 0.1).inverse();
+        let [start, end] = segment_from_ndc(Vec2::ZERO, [1.0, 0.5], &perspective).unwrap();
+        assert!(start.abs_diff_eq(Vec3::new(0.0, 0.0, -0.1), 1e-6));
+        assert!(end.abs_diff_eq(Vec3::new(0.0, 0.0, -0.2), 1e-6));
+        assert_eq!(segment_from_ndc(Vec2::ZERO, [1.0, 0.0], &perspective), None);
+
+        // Orthographic rays are parallel, so the offset is kept along the whole segment.
+        let orthographic = Mat4::orthographic_rh(-2.0, 2.0, -2.0, 2.0, 10.0, 0.0).inverse();
+        let [start, end] = segment_from_ndc(Vec2::splat(0.5), [1.0, 0.0], &orthographic).unwrap();
+        assert!(start.abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+        assert!(end.abs_diff_eq(Vec3::new(1.0, 1.0, -10.0), 1e-6));
+    }
+
+    #[test]
+    fn offset_position_scales_with_coordinates() {
+        let hit = |position: Vec3| {
+            let triangle =
+                [Vec3A::ZERO, Vec3A::X, Vec3A::Y].map(|v| v * 0.5 + Vec3A::from(position));
+            IntersectionData::new(
+                position,
+                Vec3::Z * 2.0,
+                Vec3::ZERO,
+                1.0,
+                Some(triangle),
+                None,
+            )
+        };
+        let near = hit(Vec3::new(0.25, 0.25, 0.0));
+        assert_eq!(near.position_with_offset(0.01), Vec3::new(0.25, 0.25, 0.01));
+
+        // Far from the origin, the same epsilon would be lost to rounding.
+        let far = hit(Vec3::new(1000.0, 0.0, 0.0));
+        let offset = far.position_with_offset(1e-5);
+        assert!(offset.abs_diff_eq(Vec3::new(1000.0, 0.0, 1000.5 * 1e-5), 1e-9));
+        assert_ne!(far.position_with_offset(1e-5), far.position());
+    }
+
+    #[test]
+    fn slab_rays_match_inverted_transforms() {
+        let aabb = Aabb::from_min_max(Vec3::new(-1.0, -2.0, -0.5), Vec3::new(1.0, 2.0, 0.5));
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::new(2.0, 0.5, 3.0),
+            Quat::from_rotation_y(0.7) * Quat::from_rotation_x(-0.3),
+            Vec3::new(0.5, 1.0, -4.0),
+        );
+        let ray = Ray3d::new(Vec3::new(-3.0, 1.5, 5.0), Vec3::new(0.4, -0.1, -1.0));
+
+        // The ray transformed by the full inverse, as culling did before.
+        let world_to_model = transform.inverse();
+        let expected = SlabRay::from_origin_direction(
+            world_to_model.transform_point3a(ray.origin.into()),
+            world_to_model.transform_vector3a((*ray.direction).into()),
+        );
+        let slab_ray = SlabRay::in_model_space(ray, &transform);
+        assert!(slab_ray.origin().abs_diff_eq(expected.origin(), 1e-5));
+        assert!(slab_ray.direction().abs_diff_eq(expected.direction(), 1e-5));
+
+        let [near, far] = slab_ray.intersects_aabb(&aabb).unwrap();
+        let [expected_near, expected_far] = expected.intersects_aabb(&aabb).unwrap();
+        assert!((near - expected_near).abs() < 1e-4 && (far - expected_far).abs() < 1e-4);
+        assert_eq!(
+            intersects_aabb(ray, &aabb, &transform),
+            slab_ray.intersects_aabb(&aabb)
+        );
+    }
+
+    #[test]
+    fn flat_aabb_intersection() {
+        // A 2D quad has an AABB with no depth.
+        let aabb = Aabb::from_min_max(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        let transform = Mat4::IDENTITY;
+
+        let down = Ray3d::new(Vec3::new(0.5, 0.5, 10.0), Vec3::NEG_Z);
+        assert_eq!(intersects_aabb(down, &aabb, &transform), Some([10.0, 10.0]));
+
+        // Parallel to the x and y slabs, with the origin exactly on the edge of the quad.
+        let on_edge = Ray3d::new(Vec3::new(1.0, -1.0, 10.0), Vec3::NEG_Z);
+        assert_eq!(
+            intersects_aabb(on_edge, &aabb, &transform),
+            Some([10.0, 10.0])
+        );
+
+        let outside = Ray3d::new(Vec3::new(1.5, 0.0, 10.0), Vec3::NEG_Z);
+        assert_eq!(intersects_aabb(outside, &aabb, &transform), None);
+
+        // Parallel to the quad, in its plane.
+        let in_plane = Ray3d::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::X);
+        assert_eq!(
+            intersects_aabb(in_plane, &aabb, &transform),
+            Some([4.0, 6.0])
+        );
+    }
+
+    #[test]
+    fn far_plane_clipping() {
+        // Bevy swaps the near and far planes to reverse the depth.
+        let orthographic = Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, 10.0, 0.0);
+        let view_from_world = Mat4::from_translation(Vec3::new(0.0, 0.0, -2.0));
+        let ray = Ray3d::new(Vec3::new(0.0, 0.0, 2.0), Vec3::NEG_Z);
+        let distance = far_plane_distance(ray, &(orthographic * view_from_world));
+        assert!((distance - 10.0).abs() < 1e-4);
+
+        let perspective = Mat4::perspective_infinite_reverse_rh(1.0, 1.0, 0.1);
+        assert_eq!(far_plane_distance(ray, &perspective), f32::INFINITY);
+    }
+
+    #[test]
+    fn z_up_transforms() {
+        let z_up = CoordinateSystem::Z_UP;
+        let transform = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        let ray = z_up.ray_from_transform(transform);
+        assert_eq!(ray.origin, Vec3::new(1.0, 2.0, 3.0));
+        assert!(ray.direction.abs_diff_eq(Vec3::Y, 1e-6));
+        assert!(ray_from_transform(transform)
+            .direction
+            .abs_diff_eq(Vec3::NEG_Z, 1e-6));
+
+        let aligned = z_up.to_transform(Ray3d::new(Vec3::ZERO, Vec3::X));
+        assert!(aligned
+            .transform_vector3(Vec3::Z)
+            .abs_diff_eq(Vec3::X, 1e-6));
+        assert_eq!(CoordinateSystem::new(Vec3::Z, Vec3::NEG_Z * 2.0), None);
+    }
+
+    #[test]
+    fn frustum_clipping() {
+        // Bevy swaps the near and far planes to reverse the depth.
+        let orthographic = Mat4::orthographic_rh(-2.0, 2.0, -2.0, 2.0, 10.0, 4.0);
+        let frustum = Frustum::from_clip_from_world(&orthographic);
+        let clip = |origin: Vec3, direction: Vec3| {
+            clip_ray_to_frustum(Ray3d::new(origin, direction), &frustum)
+        };
+        let [start, end] = clip(Vec3::ZERO, Vec3::NEG_Z).unwrap();
+        assert!((start - 4.0).abs() < 1e-4 && (end - 10.0).abs() < 1e-4);
+
+        // Rays starting inside the frustum are visible from their origin.
+        let [start, end] = clip(Vec3::new(0.0, 0.0, -5.0), Vec3::NEG_Z).unwrap();
+        assert!(start == 0.0 && (end - 5.0).abs() < 1e-4);
+
+        assert_eq!(clip(Vec3::ZERO, Vec3::Z), None);
+        assert_eq!(clip(Vec3::new(3.0, 0.0, 0.0), Vec3::NEG_Z), None);
     }
 }
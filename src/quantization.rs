@@ -0,0 +1,173 @@
+//! # Quantized Hits
+//!
+//! Servers that validate hits and replicate the results to clients can send hundreds of hits per
+//! tick, and a full [`IntersectionData`] is much larger than what clients need to show them.
+//!
+//! A [`HitQuantizer`] packs the entity, position, normal, and distance of a hit into a 20 byte
+//! [`QuantizedHit`]. Positions are stored as 16 bit offsets inside fixed world bounds, normals are
+//! octahedral encoded into two 16 bit values, and distances are stored as 16 bit fractions of a
+//! maximum distance. Both ends of the connection must use the same quantizer.
+
+use bevy_ecs::entity::Entity;
+use bevy_math::{Vec2, Vec3, Vec3Swizzles};
+
+use crate::primitives::IntersectionData;
+
+/// Converts hits to and from [`QuantizedHit`]s. See the [module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HitQuantizer {
+    /// The minimum corner of the bounds positions are stored in.
+    pub min: Vec3,
+    /// The maximum corner of the bounds positions are stored in.
+    pub max: Vec3,
+    /// The largest distance that can be stored.
+    pub max_distance: f32,
+}
+
+impl HitQuantizer {
+    /// Creates a quantizer for hits inside the bounds from `min` to `max`, at distances up to
+    /// `max_distance`. Positions outside the bounds and larger distances are clamped.
+    pub fn new(min: Vec3, max: Vec3, max_distance: f32) -> Self {
+        Self {
+            min,
+            max,
+            max_distance,
+        }
+    }
+
+    /// The largest error of a quantized position along each axis.
+    pub fn position_precision(&self) -> Vec3 {
+        (self.max - self.min) / u16::MAX as f32 / 2.0
+    }
+
+    /// Quantizes the `hit` on `entity`.
+    pub fn quantize(&self, entity: Entity, hit: &IntersectionData) -> QuantizedHit {
+        let position = (hit.position() - self.min) / (self.max - self.min);
+        let normal = octahedral_encode(hit.normal()) * 0.5 + 0.5;
+        QuantizedHit {
+            entity: entity.to_bits(),
+            position: position.to_array().map(quantize_unorm),
+            normal: normal.to_array().map(quantize_unorm),
+            distance: quantize_unorm(hit.distance() / self.max_distance),
+        }
+    }
+
+    /// Restores a quantized hit. The hit has no triangle data, and its barycentric coordinates
+    /// are zero. Returns `None` if the entity bits are invalid.
+    pub fn dequantize(&self, hit: &QuantizedHit) -> Option<(Entity, IntersectionData)> {
+        let entity = Entity::try_from_bits(hit.entity).ok()?;
+        let position =
+            self.min + Vec3::from(hit.position.map(dequantize_unorm)) * (self.max - self.min);
+        let normal = octahedral_decode(Vec2::from(hit.normal.map(dequantize_unorm)) * 2.0 - 1.0);
+        let distance = dequantize_unorm(hit.distance) * self.max_distance;
+        let hit = IntersectionData::new(position, normal, Vec3::ZERO, distance, None, None);
+        Some((entity, hit))
+    }
+}
+
+/// A hit packed by a [`HitQuantizer`] into 20 bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct QuantizedHit {
+    /// The [`Entity::to_bits`] of the hit entity.
+    pub entity: u64,
+    /// The position as fractions of the quantizer's bounds.
+    pub position: [u16; 3],
+    /// The octahedral encoded normal.
+    pub normal: [u16; 2],
+    /// The distance as a fraction of the quantizer's maximum distance.
+    pub distance: u16,
+}
+
+impl QuantizedHit {
+    /// The size of the hit in bytes.
+    pub const SIZE: usize = 20;
+
+    /// Packs the hit into little-endian bytes.
+    pub fn to_le_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..8].copy_from_slice(&self.entity.to_le_bytes());
+        let values = self
+            .position
+            .into_iter()
+            .chain(self.normal)
+            .chain([self.distance]);
+        for (chunk, value) in bytes[8..].chunks_exact_mut(2).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Unpacks a hit from the bytes written by [`QuantizedHit::to_le_bytes`].
+    pub fn from_le_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        let mut entity = [0; 8];
+        entity.copy_from_slice(&bytes[..8]);
+        let value = |i: usize| u16::from_le_bytes([bytes[8 + 2 * i], bytes[9 + 2 * i]]);
+        Self {
+            entity: u64::from_le_bytes(entity),
+            position: [value(0), value(1), value(2)],
+            normal: [value(3), value(4)],
+            distance: value(5),
+        }
+    }
+}
+
+fn quantize_unorm(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+fn dequantize_unorm(value: u16) -> f32 {
+    value as f32 / u16::MAX as f32
+}
+
+/// Maps a direction to a point in the `[-1, 1]` square, by projecting it onto an octahedron and
+/// unfolding the lower half.
+fn octahedral_encode(normal: Vec3) -> Vec2 {
+    let sum = normal.abs().element_sum();
+    if sum == 0.0 {
+        return Vec2::ZERO;
+    }
+    let n = normal / sum;
+    if n.z >= 0.0 {
+        n.xy()
+    } else {
+        (1.0 - n.yx().abs()) * n.xy().signum()
+    }
+}
+
+fn octahedral_decode(encoded: Vec2) -> Vec3 {
+    let mut n = encoded.extend(1.0 - encoded.x.abs() - encoded.y.abs());
+    let fold = (-n.z).max(0.0);
+    n.x += if n.x >= 0.0 { -fold } else { fold };
+    n.y += if n.y >= 0.0 { -fold } else { fold };
+    n.normalize_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantized_hits_round_trip() {
+        let quantizer = HitQuantizer::new(Vec3::splat(-100.0), Vec3::splat(100.0), 500.0);
+        let entity = Entity::from_raw(42);
+        let normal = Vec3::new(0.3, -0.5, -0.8).normalize();
+        let hit = IntersectionData::new(
+            Vec3::new(12.34, -56.78, 90.12),
+            normal,
+            Vec3::ZERO,
+            123.4,
+            None,
+            None,
+        );
+        let quantized = quantizer.quantize(entity, &hit);
+        let bytes = quantized.to_le_bytes();
+        assert_eq!(QuantizedHit::from_le_bytes(bytes), quantized);
+
+        let (restored_entity, restored) = quantizer.dequantize(&quantized).unwrap();
+        assert_eq!(restored_entity, entity);
+        let error = (restored.position() - hit.position()).abs();
+        assert!(error.cmple(quantizer.position_precision() * 1.01).all());
+        assert!(restored.normal().dot(normal) > 0.9999);
+        assert!((restored.distance() - hit.distance()).abs() < 500.0 / u16::MAX as f32);
+    }
+}
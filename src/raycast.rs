@@ -1,11 +1,26 @@
-use bevy_math::{Mat4, Ray3d, Vec3, Vec3A};
+use std::ops::Range;
+
+use bevy_math::{FloatOrd, Mat4, Ray3d, Vec2, Vec3, Vec3A, Vec4};
 use bevy_render::{
-    mesh::{Indices, Mesh, VertexAttributeValues},
+    mesh::{Indices, Mesh, MeshVertexAttribute, VertexAttributeValues},
     render_resource::PrimitiveTopology,
 };
 use bevy_utils::tracing::{error, warn};
 
-use crate::primitives::*;
+use crate::{bvh::TriangleBvh, primitives::*};
+
+/// Data precomputed from a mesh asset that speeds up raycasting it. Data that no longer matches the
+/// mesh, such as after it changed its triangle count, is ignored.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct MeshCaches<'a> {
+    /// The normals of the triangles, used if the mesh has no vertex normals.
+    pub flat_normals: Option<&'a [[f32; 3]]>,
+    /// A BVH over the triangles in mesh space, so only the triangles near the ray are tested.
+    pub bvh: Option<&'a TriangleBvh>,
+    /// The inverse of the mesh transform, from the
+    /// [`TransformCache`](crate::transform_cache::TransformCache).
+    pub world_to_mesh: Option<Mat4>,
+}
 
 /// Cast a ray on a mesh, and returns the intersection
 pub fn ray_intersection_over_mesh(
@@ -14,11 +29,128 @@ pub fn ray_intersection_over_mesh(
     ray: Ray3d,
     backface_culling: Backfaces,
 ) -> Option<IntersectionData> {
+    ray_intersection_over_mesh_with_caches(
+        mesh,
+        MeshCaches::default(),
+        None,
+        mesh_transform,
+        ray,
+        backface_culling,
+        HitData::ALL,
+        f32::INFINITY,
+    )
+}
+
+/// Like [`ray_intersection_over_mesh`], but uses the precomputed `caches` of the mesh, only tests
+/// the `triangles` in this range if one is given, only the requested `hit_data` is computed, and
+/// hits beyond `max_distance` are skipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn ray_intersection_over_mesh_with_caches(
+    mesh: &Mesh,
+    caches: MeshCaches,
+    triangles: Option<Range<usize>>,
+    mesh_transform: &Mat4,
+    ray: Ray3d,
+    backface_culling: Backfaces,
+    hit_data: HitData,
+    max_distance: f32,
+) -> Option<IntersectionData> {
+    let mut nearest = None;
+    let visit = |hit| nearest = Some(hit);
+    visit_ray_intersections_over_mesh(
+        mesh,
+        caches,
+        triangles,
+        mesh_transform,
+        ray,
+        backface_culling,
+        true,
+        hit_data,
+        max_distance,
+        visit,
+    );
+    // Tangents are only needed at the nearest hit, so they are interpolated after the search
+    // instead of for every candidate triangle.
+    match hit_data.tangent {
+        true => nearest.map(|intersection| with_mesh_tangent(mesh, mesh_transform, intersection)),
+        false => nearest,
+    }
+}
+
+/// Like [`ray_intersection_over_mesh`], but returns every intersection with the mesh, such as
+/// where the ray enters and exits a closed mesh, sorted nearest first.
+pub fn ray_intersections_over_mesh(
+    mesh: &Mesh,
+    mesh_transform: &Mat4,
+    ray: Ray3d,
+    backface_culling: Backfaces,
+) -> Vec<IntersectionData> {
+    ray_intersections_over_mesh_with_caches(
+        mesh,
+        MeshCaches::default(),
+        None,
+        mesh_transform,
+        ray,
+        backface_culling,
+        HitData::ALL,
+        f32::INFINITY,
+    )
+}
+
+/// Like [`ray_intersections_over_mesh`], but uses the precomputed `caches` of the mesh, only tests
+/// the `triangles` in this range if one is given, only the requested `hit_data` is computed, and
+/// hits beyond `max_distance` are skipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn ray_intersections_over_mesh_with_caches(
+    mesh: &Mesh,
+    caches: MeshCaches,
+    triangles: Option<Range<usize>>,
+    mesh_transform: &Mat4,
+    ray: Ray3d,
+    backface_culling: Backfaces,
+    hit_data: HitData,
+    max_distance: f32,
+) -> Vec<IntersectionData> {
+    let mut intersections = Vec::new();
+    let visit = |hit| match hit_data.tangent {
+        true => intersections.push(with_mesh_tangent(mesh, mesh_transform, hit)),
+        false => intersections.push(hit),
+    };
+    visit_ray_intersections_over_mesh(
+        mesh,
+        caches,
+        triangles,
+        mesh_transform,
+        ray,
+        backface_culling,
+        false,
+        hit_data,
+        max_distance,
+        visit,
+    );
+    intersections.sort_by_key(|hit| FloatOrd(hit.distance()));
+    intersections
+}
+
+/// Resolves the vertex buffers of the mesh, and calls [`visit_ray_mesh_intersections`].
+#[allow(clippy::too_many_arguments)]
+fn visit_ray_intersections_over_mesh(
+    mesh: &Mesh,
+    caches: MeshCaches,
+    triangles: Option<Range<usize>>,
+    mesh_transform: &Mat4,
+    ray: Ray3d,
+    backface_culling: Backfaces,
+    nearest_only: bool,
+    hit_data: HitData,
+    max_distance: f32,
+    visit: impl FnMut(IntersectionData),
+) {
     if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
         error!(
             "Invalid intersection check: `TriangleList` is the only supported `PrimitiveTopology`"
         );
-        return None;
+        return;
     }
     // Get the vertex positions from the mesh reference resolved from the mesh handle
     let vertex_positions: &Vec<[f32; 3]> = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
@@ -37,36 +169,267 @@ pub fn ray_intersection_over_mesh(
         } else {
             None
         };
+    // Stale data of a mesh that has since changed its triangle count is ignored.
+    let triangle_count = mesh_triangle_count(mesh);
+    let flat_normals = caches
+        .flat_normals
+        .filter(|normals| vertex_normals.is_none() && normals.len() == triangle_count);
+    let bvh = caches
+        .bvh
+        .filter(|bvh| bvh.triangle_count() == triangle_count);
 
-    if let Some(indices) = &mesh.indices() {
-        // Iterate over the list of pick rays that belong to the same group as this mesh
-        match indices {
-            Indices::U16(vertex_indices) => ray_mesh_intersection(
-                mesh_transform,
-                vertex_positions,
-                vertex_normals,
-                ray,
-                Some(vertex_indices),
-                backface_culling,
-            ),
-            Indices::U32(vertex_indices) => ray_mesh_intersection(
-                mesh_transform,
-                vertex_positions,
-                vertex_normals,
-                ray,
-                Some(vertex_indices),
-                backface_culling,
-            ),
-        }
-    } else {
-        ray_mesh_intersection(
+    match mesh.indices() {
+        Some(Indices::U16(vertex_indices)) => visit_ray_mesh_intersections(
+            mesh_transform,
+            caches.world_to_mesh,
+            vertex_positions,
+            vertex_normals,
+            flat_normals,
+            bvh,
+            ray,
+            Some(vertex_indices),
+            triangles,
+            backface_culling,
+            nearest_only,
+            hit_data,
+            max_distance,
+            visit,
+        ),
+        Some(Indices::U32(vertex_indices)) => visit_ray_mesh_intersections(
+            mesh_transform,
+            caches.world_to_mesh,
+            vertex_positions,
+            vertex_normals,
+            flat_normals,
+            bvh,
+            ray,
+            Some(vertex_indices),
+            triangles,
+            backface_culling,
+            nearest_only,
+            hit_data,
+            max_distance,
+            visit,
+        ),
+        None => visit_ray_mesh_intersections(
             mesh_transform,
+            caches.world_to_mesh,
             vertex_positions,
             vertex_normals,
+            flat_normals,
+            bvh,
             ray,
             None::<&Vec<u32>>,
+            triangles,
             backface_culling,
-        )
+            nearest_only,
+            hit_data,
+            max_distance,
+            visit,
+        ),
+    }
+}
+
+/// Adds the interpolated tangent of the mesh at the hit, if the mesh has tangents.
+fn with_mesh_tangent(
+    mesh: &Mesh,
+    mesh_transform: &Mat4,
+    intersection: IntersectionData,
+) -> IntersectionData {
+    let tangent = match mesh.attribute(Mesh::ATTRIBUTE_TANGENT) {
+        Some(VertexAttributeValues::Float32x4(tangents)) => intersection
+            .triangle_index()
+            .and_then(|triangle| triangle_vertex_indices(mesh, triangle))
+            .map(|[i0, i1, i2]| {
+                let [u, v, w] = intersection.barycentric_coord().to_array();
+                let tangent = Vec4::from(tangents[i1]) * u
+                    + Vec4::from(tangents[i2]) * v
+                    + Vec4::from(tangents[i0]) * w;
+                let direction = mesh_transform
+                    .transform_vector3(tangent.truncate())
+                    .normalize_or_zero();
+                // Mirroring flips the handedness of the tangent frame.
+                let handedness = tangent.w.signum() * mesh_transform.determinant().signum();
+                direction.extend(handedness)
+            }),
+        _ => None,
+    };
+    match tangent {
+        Some(tangent) => intersection.with_tangent(tangent),
+        None => intersection,
+    }
+}
+
+/// Interpolates a float vertex `attribute` of the `mesh`, such as
+/// [`Mesh::ATTRIBUTE_UV_0`] or [`Mesh::ATTRIBUTE_COLOR`], at the `hit`. Components the attribute
+/// doesn't have are zero, so UVs are in `x` and `y`.
+///
+/// The `hit` must be on this mesh, which is its
+/// [`source_mesh`](IntersectionData::source_mesh). Returns `None` if the hit has no triangle index,
+/// or the mesh has no such attribute.
+pub fn interpolate_vertex_attribute(
+    mesh: &Mesh,
+    hit: &IntersectionData,
+    attribute: MeshVertexAttribute,
+) -> Option<Vec4> {
+    let vertices = triangle_vertex_indices(mesh, hit.triangle_index()?)?;
+    let values = mesh.attribute(attribute)?;
+    let value = |i: usize| -> Option<Vec4> {
+        Some(match values {
+            VertexAttributeValues::Float32(values) => Vec4::new(*values.get(i)?, 0.0, 0.0, 0.0),
+            VertexAttributeValues::Float32x2(values) => {
+                Vec2::from(*values.get(i)?).extend(0.0).extend(0.0)
+            }
+            VertexAttributeValues::Float32x3(values) => Vec3::from(*values.get(i)?).extend(0.0),
+            VertexAttributeValues::Float32x4(values) => Vec4::from(*values.get(i)?),
+            _ => return None,
+        })
+    };
+    let [a, b, c] = vertices;
+    Some(hit.interpolate([value(a)?, value(b)?, value(c)?]))
+}
+
+/// The indices of the three vertices of `triangle` in the vertex buffer of the mesh.
+pub(crate) fn triangle_vertex_indices(mesh: &Mesh, triangle: usize) -> Option<[usize; 3]> {
+    let first = triangle * 3;
+    match mesh.indices() {
+        Some(Indices::U16(indices)) => indices
+            .get(first..first + 3)
+            .map(|t| [t[0].into_usize(), t[1].into_usize(), t[2].into_usize()]),
+        Some(Indices::U32(indices)) => indices
+            .get(first..first + 3)
+            .map(|t| [t[0].into_usize(), t[1].into_usize(), t[2].into_usize()]),
+        None => Some([first, first + 1, first + 2]),
+    }
+}
+
+/// Why an entity could not be raycast. See
+/// [`Raycast::cast_ray_with_errors`](crate::immediate::Raycast::cast_ray_with_errors).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RaycastError {
+    /// The entity's mesh handle does not resolve to a loaded mesh.
+    MissingMesh,
+    /// The mesh has no `Float32x3` vertex positions.
+    MissingPositions,
+    /// The mesh is not a [`PrimitiveTopology::TriangleList`].
+    UnsupportedTopology(PrimitiveTopology),
+    /// The entity's transform cannot be inverted, such as when it has a scale of zero.
+    DegenerateTransform,
+}
+
+impl std::fmt::Display for RaycastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RaycastError::MissingMesh => write!(f, "the mesh is not loaded"),
+            RaycastError::MissingPositions => {
+                write!(f, "the mesh does not have `Float32x3` vertex positions")
+            }
+            RaycastError::UnsupportedTopology(topology) => write!(
+                f,
+                "the mesh has a `{topology:?}` topology, only `TriangleList` is supported"
+            ),
+            RaycastError::DegenerateTransform => write!(f, "the transform is not invertible"),
+        }
+    }
+}
+
+impl std::error::Error for RaycastError {}
+
+/// Checks that [`ray_intersection_over_mesh`] supports the mesh.
+pub fn validate_raycast_mesh(mesh: &Mesh) -> Result<(), RaycastError> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Err(RaycastError::UnsupportedTopology(mesh.primitive_topology()));
+    }
+    match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(_)) => Ok(()),
+        _ => Err(RaycastError::MissingPositions),
+    }
+}
+
+/// Finds the triangles of a mesh whose vertex normals are missing, zero, or point more than
+/// `max_angle` radians away from the normal of the triangle, and returns their indices.
+///
+/// Vertex normals of smooth surfaces always differ somewhat from the normals of their triangles,
+/// so a `max_angle` of [`FRAC_PI_2`](std::f32::consts::FRAC_PI_2) only flags normals that point
+/// into the surface. Check meshes when they are loaded, and add
+/// [`GeometricNormals`](crate::markers::GeometricNormals) to entities using meshes with broken
+/// normals. Meshes without normals always use the normals of their triangles, and are not flagged.
+pub fn find_mismatched_normals(mesh: &Mesh, max_angle: f32) -> Vec<usize> {
+    let (
+        Some(VertexAttributeValues::Float32x3(positions)),
+        Some(VertexAttributeValues::Float32x3(normals)),
+    ) = (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+    )
+    else {
+        return Vec::new();
+    };
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Vec::new();
+    }
+    let min_cos = max_angle.cos();
+    (0..mesh_triangle_count(mesh))
+        .filter(|triangle| {
+            let Some(vertices) = triangle_vertex_indices(mesh, *triangle) else {
+                return false;
+            };
+            let (Some(a), Some(b), Some(c)) = (
+                positions.get(vertices[0]),
+                positions.get(vertices[1]),
+                positions.get(vertices[2]),
+            ) else {
+                return false;
+            };
+            let [a, b, c] = [a, b, c].map(|p| Vec3::from(*p));
+            // Degenerate triangles can't be hit, so their normals don't matter.
+            let Some(face) = (b - a).cross(c - a).try_normalize() else {
+                return false;
+            };
+            vertices.iter().any(|i| {
+                normals
+                    .get(*i)
+                    .and_then(|normal| Vec3::from(*normal).try_normalize())
+                    .map_or(true, |normal| normal.dot(face) < min_cos)
+            })
+        })
+        .collect()
+}
+
+/// Computes the normal of each triangle of a mesh in mesh space, in the order of
+/// [`IntersectionData::triangle_index`]. These are the normals of hits on meshes without vertex
+/// normals, which raycasts otherwise compute for every hit. See the
+/// [`FlatNormalCache`](crate::flat_normals::FlatNormalCache).
+///
+/// Returns an empty `Vec` for meshes rejected by [`validate_raycast_mesh`]. Degenerate triangles
+/// have a zero normal.
+pub fn compute_flat_normals_for_raycast(mesh: &Mesh) -> Vec<[f32; 3]> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return Vec::new();
+    };
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Vec::new();
+    }
+    (0..mesh_triangle_count(mesh))
+        .map(|triangle| {
+            let vertices = triangle_vertex_indices(mesh, triangle).and_then(|[a, b, c]| {
+                Some([positions.get(a)?, positions.get(b)?, positions.get(c)?])
+            });
+            let Some([a, b, c]) = vertices.map(|v| v.map(|p| Vec3::from(*p))) else {
+                return [0.0; 3];
+            };
+            (b - a).cross(c - a).normalize_or_zero().to_array()
+        })
+        .collect()
+}
+
+/// The number of triangles in a [`PrimitiveTopology::TriangleList`] mesh.
+pub fn mesh_triangle_count(mesh: &Mesh) -> usize {
+    match mesh.indices() {
+        Some(indices) => indices.len() / 3,
+        None => mesh.count_vertices() / 3,
     }
 }
 
@@ -84,7 +447,16 @@ impl IntoUsize for u32 {
     }
 }
 
-/// Checks if a ray intersects a mesh, and returns the nearest intersection if one exists.
+/// Returns `true` if the `transform` can't be inverted without losing all precision, such as when
+/// an entity is animated to a scale of zero. Rays transformed by its inverse would be NaN, so
+/// entities with degenerate transforms are skipped instead of raycast.
+pub fn is_degenerate_transform(transform: &Mat4) -> bool {
+    // Subnormal determinants overflow to infinity when inverted.
+    !transform.determinant().is_normal()
+}
+
+/// Checks if a ray intersects a mesh, and returns the nearest intersection if one exists. Meshes
+/// with a [degenerate transform](is_degenerate_transform) are never hit.
 pub fn ray_mesh_intersection(
     mesh_transform: &Mat4,
     vertex_positions: &[[f32; 3]],
@@ -92,119 +464,245 @@ pub fn ray_mesh_intersection(
     ray: Ray3d,
     indices: Option<&Vec<impl IntoUsize>>,
     backface_culling: Backfaces,
+) -> Option<IntersectionData> {
+    ray_mesh_intersection_within(
+        mesh_transform,
+        vertex_positions,
+        vertex_normals,
+        ray,
+        indices,
+        backface_culling,
+        f32::INFINITY,
+    )
+}
+
+/// Like [`ray_mesh_intersection`], but skips triangles beyond the world-space `max_distance`. When
+/// casting against many meshes, pass the distance of the nearest hit found so far, so triangles
+/// behind it are rejected before they are intersected.
+pub fn ray_mesh_intersection_within(
+    mesh_transform: &Mat4,
+    vertex_positions: &[[f32; 3]],
+    vertex_normals: Option<&[[f32; 3]]>,
+    ray: Ray3d,
+    indices: Option<&Vec<impl IntoUsize>>,
+    backface_culling: Backfaces,
+    max_distance: f32,
 ) -> Option<IntersectionData> {
     // The ray cast can hit the same mesh many times, so we need to track which hit is
     // closest to the camera, and record that.
-    let mut min_pick_distance = f32::MAX;
     let mut pick_intersection = None;
+    visit_ray_mesh_intersections(
+        mesh_transform,
+        None,
+        vertex_positions,
+        vertex_normals,
+        None,
+        None,
+        ray,
+        indices,
+        None,
+        backface_culling,
+        true,
+        HitData::ALL,
+        max_distance,
+        |intersection| pick_intersection = Some(intersection),
+    );
+    pick_intersection
+}
+
+/// Like [`ray_mesh_intersection`], but returns every intersection with the mesh, such as where the
+/// ray enters and exits a closed mesh, sorted nearest first.
+pub fn ray_mesh_intersections(
+    mesh_transform: &Mat4,
+    vertex_positions: &[[f32; 3]],
+    vertex_normals: Option<&[[f32; 3]]>,
+    ray: Ray3d,
+    indices: Option<&Vec<impl IntoUsize>>,
+    backface_culling: Backfaces,
+) -> Vec<IntersectionData> {
+    ray_mesh_intersections_within(
+        mesh_transform,
+        vertex_positions,
+        vertex_normals,
+        ray,
+        indices,
+        backface_culling,
+        f32::INFINITY,
+    )
+}
+
+/// Like [`ray_mesh_intersections`], but skips triangles beyond the world-space `max_distance`.
+pub fn ray_mesh_intersections_within(
+    mesh_transform: &Mat4,
+    vertex_positions: &[[f32; 3]],
+    vertex_normals: Option<&[[f32; 3]]>,
+    ray: Ray3d,
+    indices: Option<&Vec<impl IntoUsize>>,
+    backface_culling: Backfaces,
+    max_distance: f32,
+) -> Vec<IntersectionData> {
+    let mut intersections = Vec::new();
+    visit_ray_mesh_intersections(
+        mesh_transform,
+        None,
+        vertex_positions,
+        vertex_normals,
+        None,
+        None,
+        ray,
+        indices,
+        None,
+        backface_culling,
+        false,
+        HitData::ALL,
+        max_distance,
+        |intersection| intersections.push(intersection),
+    );
+    intersections.sort_by_key(|hit| FloatOrd(hit.distance()));
+    intersections
+}
 
-    let world_to_mesh = mesh_transform.inverse();
+/// Calls `visit` with the world-space intersection of each triangle hit by the ray. When
+/// `nearest_only` is set, triangles further than the last visited hit are skipped, so the last hit
+/// visited is the nearest. Triangles beyond the world-space `max_distance` are never visited.
+/// Triangle vertices are only transformed into world space when requested by the `hit_data`.
+///
+/// Only the `triangles` in this range are tested, if one is given. With a `bvh` over the mesh-space
+/// triangles, only the triangles in the leaves the ray passes through are tested. The inverse of
+/// the mesh transform is only computed if `world_to_mesh` is not given.
+#[allow(clippy::too_many_arguments)]
+fn visit_ray_mesh_intersections(
+    mesh_transform: &Mat4,
+    world_to_mesh: Option<Mat4>,
+    vertex_positions: &[[f32; 3]],
+    vertex_normals: Option<&[[f32; 3]]>,
+    flat_normals: Option<&[[f32; 3]]>,
+    bvh: Option<&TriangleBvh>,
+    ray: Ray3d,
+    indices: Option<&Vec<impl IntoUsize>>,
+    triangles: Option<Range<usize>>,
+    backface_culling: Backfaces,
+    nearest_only: bool,
+    hit_data: HitData,
+    max_distance: f32,
+    mut visit: impl FnMut(IntersectionData),
+) {
+    // Cached inverses are only kept for transforms that are not degenerate.
+    let world_to_mesh = match world_to_mesh {
+        Some(world_to_mesh) => world_to_mesh,
+        None if is_degenerate_transform(mesh_transform) => return,
+        None => mesh_transform.inverse(),
+    };
+    // Normals are transformed by the inverse transpose, so they stay perpendicular to the surface
+    // under non-uniform scale, and keep pointing out of mirrored meshes. Triangles are tested in
+    // mesh space, where mirroring does not change their winding, so backface culling needs no
+    // special care.
+    let normal_matrix = world_to_mesh.transpose();
 
     let mesh_space_ray = Ray3d::new(
         world_to_mesh.transform_point3(ray.origin),
         world_to_mesh.transform_vector3(*ray.direction),
     );
+    let mesh_origin = Vec3A::from(mesh_space_ray.origin);
+    let mesh_direction = Vec3A::from(*mesh_space_ray.direction);
+    // Distances along the normalized mesh space ray are scaled by the transform in world space.
+    let world_units_per_mesh_unit = mesh_transform.transform_vector3a(mesh_direction).length();
+    let max_mesh_distance = match max_distance / world_units_per_mesh_unit {
+        distance if distance.is_nan() => f32::MAX,
+        distance => distance.min(f32::MAX),
+    };
+    let mut min_pick_distance = max_mesh_distance;
 
-    if let Some(indices) = indices {
-        // Make sure this chunk has 3 vertices to avoid a panic.
-        if indices.len() % 3 != 0 {
-            warn!("Index list not a multiple of 3");
+    let mut test_triangle = |triangle_index: usize, index: [usize; 3]| {
+        let tri_vertex_positions = index.map(|i| Vec3A::from(vertex_positions[i]));
+        // A triangle whose vertices are all beyond the nearest hit so far can't be nearer.
+        let nearest_vertex = tri_vertex_positions
+            .map(|vertex| (vertex - mesh_origin).dot(mesh_direction))
+            .into_iter()
+            .fold(f32::INFINITY, f32::min);
+        if nearest_vertex > min_pick_distance {
             return None;
         }
-        // Now that we're in the vector of vertex indices, we want to look at the vertex
-        // positions for each triangle, so we'll take indices in chunks of three, where each
-        // chunk of three indices are references to the three vertices of a triangle.
-        for index in indices.chunks(3) {
-            let triangle_index = Some(index[0].into_usize());
-            let tri_vertex_positions = [
-                Vec3A::from(vertex_positions[index[0].into_usize()]),
-                Vec3A::from(vertex_positions[index[1].into_usize()]),
-                Vec3A::from(vertex_positions[index[2].into_usize()]),
-            ];
-            let tri_normals = vertex_normals.map(|normals| {
-                [
-                    Vec3A::from(normals[index[0].into_usize()]),
-                    Vec3A::from(normals[index[1].into_usize()]),
-                    Vec3A::from(normals[index[2].into_usize()]),
-                ]
-            });
-            let intersection = triangle_intersection(
-                tri_vertex_positions,
-                tri_normals,
-                min_pick_distance,
-                &mesh_space_ray,
-                backface_culling,
-            );
-            if let Some(i) = intersection {
-                pick_intersection = Some(IntersectionData::new(
-                    mesh_transform.transform_point3(i.position()),
-                    mesh_transform.transform_vector3(i.normal()),
+        let tri_normals = vertex_normals.map(|normals| index.map(|i| Vec3A::from(normals[i])));
+        let flat_normal = flat_normals.map(|normals| Vec3A::from(normals[triangle_index]));
+        let intersection = triangle_intersection(
+            tri_vertex_positions,
+            tri_normals,
+            flat_normal,
+            min_pick_distance,
+            &mesh_space_ray,
+            backface_culling,
+        );
+        if let Some(i) = intersection {
+            visit(
+                IntersectionData::new(
+                    mesh_transform.transform_point3a(i.position_vec3a()),
+                    normal_matrix
+                        .transform_vector3a(i.normal_vec3a())
+                        .normalize_or_zero(),
                     i.barycentric_coord(),
                     mesh_transform
                         .transform_vector3(mesh_space_ray.direction * i.distance())
                         .length(),
-                    i.triangle().map(|tri| {
+                    i.triangle().filter(|_| hit_data.triangle).map(|tri| {
                         [
                             mesh_transform.transform_point3a(tri[0]),
                             mesh_transform.transform_point3a(tri[1]),
                             mesh_transform.transform_point3a(tri[2]),
                         ]
                     }),
-                    triangle_index,
-                ));
+                    Some(triangle_index),
+                )
+                .with_triangle_indices(index.map(|i| i as u32)),
+            );
+            if nearest_only {
                 min_pick_distance = i.distance();
             }
         }
-    } else {
-        for i in (0..vertex_positions.len()).step_by(3) {
-            let triangle_index = Some(i);
-            let tri_vertex_positions = [
-                Vec3A::from(vertex_positions[i]),
-                Vec3A::from(vertex_positions[i + 1]),
-                Vec3A::from(vertex_positions[i + 2]),
-            ];
-            let tri_normals = vertex_normals.map(|normals| {
-                [
-                    Vec3A::from(normals[i]),
-                    Vec3A::from(normals[i + 1]),
-                    Vec3A::from(normals[i + 2]),
-                ]
-            });
-            let intersection = triangle_intersection(
-                tri_vertex_positions,
-                tri_normals,
-                min_pick_distance,
-                &mesh_space_ray,
-                backface_culling,
-            );
-            if let Some(i) = intersection {
-                pick_intersection = Some(IntersectionData::new(
-                    mesh_transform.transform_point3(i.position()),
-                    mesh_transform.transform_vector3(i.normal()),
-                    i.barycentric_coord(),
-                    mesh_transform
-                        .transform_vector3(mesh_space_ray.direction * i.distance())
-                        .length(),
-                    i.triangle().map(|tri| {
-                        [
-                            mesh_transform.transform_point3a(tri[0]),
-                            mesh_transform.transform_point3a(tri[1]),
-                            mesh_transform.transform_point3a(tri[2]),
-                        ]
-                    }),
-                    triangle_index,
-                ));
-                min_pick_distance = i.distance();
+        intersection.map(|i| i.distance())
+    };
+
+    // Make sure each triangle has 3 vertices to avoid a panic.
+    if indices.is_some_and(|indices| indices.len() % 3 != 0) {
+        warn!("Index list not a multiple of 3");
+        return;
+    }
+    let triangle_count = indices.map_or(vertex_positions.len(), |indices| indices.len()) / 3;
+    let triangles = triangles.map_or(0..triangle_count, |range| {
+        range.start..range.end.min(triangle_count)
+    });
+    // Each triangle is three consecutive indices, which are references to its vertices, or three
+    // consecutive vertices for meshes without indices.
+    let vertex_indices = |triangle_index: usize| {
+        let first = triangle_index * 3;
+        let index = [first, first + 1, first + 2];
+        match indices {
+            Some(indices) => index.map(|i| indices[i].into_usize()),
+            None => index,
+        }
+    };
+
+    if let Some(bvh) = bvh {
+        bvh.cast_ray(mesh_space_ray, max_mesh_distance, |triangle_index, _| {
+            if !triangles.contains(&triangle_index) {
+                return None;
             }
+            let hit = test_triangle(triangle_index, vertex_indices(triangle_index));
+            // Only nearer hits may prune the tree, since all hits are wanted otherwise.
+            hit.filter(|_| nearest_only)
+        });
+    } else {
+        for triangle_index in triangles {
+            test_triangle(triangle_index, vertex_indices(triangle_index));
         }
     }
-    pick_intersection
 }
 
 #[inline(always)]
 fn triangle_intersection(
     tri_vertices: [Vec3A; 3],
     tri_normals: Option<[Vec3A; 3]>,
+    flat_normal: Option<Vec3A>,
     max_distance: f32,
     ray: &Ray3d,
     backface_culling: Backfaces,
@@ -222,6 +720,8 @@ fn triangle_intersection(
     let barycentric = Vec3::new(u, v, w);
     let normal = if let Some(normals) = tri_normals {
         normals[1] * u + normals[2] * v + normals[0] * w
+    } else if let Some(normal) = flat_normal {
+        normal
     } else {
         (tri_vertices[1] - tri_vertices[0])
             .cross(tri_vertices[2] - tri_vertices[0])
@@ -229,7 +729,7 @@ fn triangle_intersection(
     };
     Some(IntersectionData::new(
         position,
-        normal.into(),
+        normal,
         barycentric,
         distance,
         Some(tri_vertices),
@@ -237,6 +737,171 @@ fn triangle_intersection(
     ))
 }
 
+/// Sweeps a sphere with `radius` from the origin of the ray along its direction, and returns its
+/// first contact with the triangle within `max_distance`, if any.
+///
+/// The distance of the hit is how far the center of the sphere traveled, its position is the
+/// contact point on the triangle, and its normal points from the contact point to the center of
+/// the sphere. A sphere that starts out touching the triangle hits it at a distance of zero.
+/// Triangles are hit from both sides.
+pub fn sphere_triangle_sweep(
+    ray: Ray3d,
+    radius: f32,
+    triangle: [Vec3A; 3],
+    max_distance: f32,
+) -> Option<IntersectionData> {
+    let origin = Vec3A::from(ray.origin);
+    let direction = Vec3A::from(*ray.direction);
+    let [a, b, c] = triangle;
+    let face_normal = (b - a).cross(c - a).try_normalize();
+    let hit = |distance: f32, contact: Vec3A| {
+        let center = origin + direction * distance;
+        let normal = (center - contact)
+            .try_normalize()
+            .or(face_normal)
+            .unwrap_or(-direction);
+        let barycentric = barycentric_coords(contact, triangle).unwrap_or(Vec3::ZERO);
+        IntersectionData::new(contact, normal, barycentric, distance, Some(triangle), None)
+    };
+
+    // The sphere touches the face before any edge or vertex if it meets the plane of the triangle
+    // inside of it.
+    if let Some(normal) = face_normal {
+        let height = (origin - a).dot(normal);
+        let normal = normal * height.signum();
+        let height = height.abs();
+        let approach = -direction.dot(normal);
+        let distance = match height <= radius {
+            true => Some(0.0),
+            false => (approach > 0.0).then(|| (height - radius) / approach),
+        };
+        if let Some(distance) = distance.filter(|distance| *distance <= max_distance) {
+            let contact = origin + direction * distance - normal * height.min(radius);
+            let inside = barycentric_coords(contact, triangle)
+                .is_some_and(|barycentric| barycentric.min_element() >= 0.0);
+            if inside {
+                return Some(hit(distance, contact));
+            }
+        }
+    }
+
+    // Otherwise, it first touches an edge, or a vertex.
+    let mut nearest: Option<(f32, Vec3A)> = None;
+    let mut consider = |distance: f32, contact: Vec3A| {
+        if distance <= max_distance && nearest.map_or(true, |(nearest, _)| distance < nearest) {
+            nearest = Some((distance, contact));
+        }
+    };
+    let radius_squared = radius * radius;
+    for (start, end) in [(a, b), (b, c), (c, a)] {
+        // The sphere touches the edge where its center is `radius` away from the edge's line.
+        let edge = end - start;
+        let offset = origin - start;
+        let edge_squared = edge.length_squared();
+        let edge_direction = edge.dot(direction);
+        let edge_offset = edge.dot(offset);
+        let qa = edge_squared - edge_direction * edge_direction;
+        if qa <= f32::EPSILON * edge_squared {
+            // Moving along the edge, so it is first touched at one of its vertices.
+            continue;
+        }
+        let qb = edge_squared * offset.dot(direction) - edge_offset * edge_direction;
+        let qc = edge_squared * (offset.length_squared() - radius_squared) - edge_offset.powi(2);
+        let discriminant = qb * qb - qa * qc;
+        if discriminant < 0.0 {
+            continue;
+        }
+        let distance = match qc <= 0.0 {
+            true => 0.0,
+            false => (-qb - discriminant.sqrt()) / qa,
+        };
+        let along = (edge_offset + edge_direction * distance) / edge_squared;
+        if distance >= 0.0 && (0.0..=1.0).contains(&along) {
+            consider(distance, start + edge * along);
+        }
+    }
+    for vertex in triangle {
+        let offset = origin - vertex;
+        let qb = offset.dot(direction);
+        let qc = offset.length_squared() - radius_squared;
+        if qc <= 0.0 {
+            consider(0.0, vertex);
+        } else if qb < 0.0 && qb * qb >= qc {
+            consider(-qb - (qb * qb - qc).sqrt(), vertex);
+        }
+    }
+    nearest.map(|(distance, contact)| hit(distance, contact))
+}
+
+/// Sweeps a sphere against every triangle of the mesh, as with [`sphere_triangle_sweep`], and
+/// returns the first contact. Unlike a ray, a sphere is distorted by a non-uniform scale, so the
+/// sweep is done in world space.
+pub fn sphere_mesh_sweep(
+    mesh: &Mesh,
+    mesh_transform: &Mat4,
+    ray: Ray3d,
+    radius: f32,
+    max_distance: f32,
+) -> Option<IntersectionData> {
+    sphere_mesh_sweep_over(
+        mesh,
+        0..mesh_triangle_count(mesh),
+        mesh_transform,
+        ray,
+        radius,
+        max_distance,
+    )
+}
+
+/// Like [`sphere_mesh_sweep`], but only sweeps against the `triangles` of the mesh.
+pub(crate) fn sphere_mesh_sweep_over(
+    mesh: &Mesh,
+    triangles: Range<usize>,
+    mesh_transform: &Mat4,
+    ray: Ray3d,
+    radius: f32,
+    mut max_distance: f32,
+) -> Option<IntersectionData> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let mut nearest = None;
+    for triangle_index in triangles {
+        let Some(indices) = triangle_vertex_indices(mesh, triangle_index) else {
+            continue;
+        };
+        let [Some(a), Some(b), Some(c)] = indices.map(|index| positions.get(index)) else {
+            continue;
+        };
+        let triangle = [a, b, c].map(|p| mesh_transform.transform_point3a(Vec3A::from(*p)));
+        if let Some(hit) = sphere_triangle_sweep(ray, radius, triangle, max_distance) {
+            max_distance = hit.distance();
+            nearest = Some(
+                hit.with_triangle_index(triangle_index)
+                    .with_triangle_indices(indices.map(|i| i as u32)),
+            );
+        }
+    }
+    nearest
+}
+
+/// The barycentric coordinates of the point projected onto the plane of the triangle, in the
+/// order used by [`IntersectionData::barycentric_coord`].
+fn barycentric_coords(point: Vec3A, [a, b, c]: [Vec3A; 3]) -> Option<Vec3> {
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d00, d01, d11) = (ab.dot(ab), ab.dot(ac), ac.dot(ac));
+    let (d20, d21) = (ap.dot(ab), ap.dot(ac));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() <= f32::EPSILON * d00 * d11 {
+        return None;
+    }
+    let u = (d11 * d20 - d01 * d21) / denominator;
+    let v = (d00 * d21 - d01 * d20) / denominator;
+    Some(Vec3::new(u, v, 1.0 - u - v))
+}
+
 #[derive(Copy, Clone, Default)]
 pub enum Backfaces {
     #[default]
@@ -256,19 +921,24 @@ pub fn ray_triangle_intersection(
     let vector_v0_to_v2: Vec3A = triangle[2] - triangle[0];
     let p_vec: Vec3A = (Vec3A::from(*ray.direction)).cross(vector_v0_to_v2);
     let determinant: f32 = vector_v0_to_v1.dot(p_vec);
+    // The determinant scales with the product of the edge lengths, so the parallel test is relative
+    // to the size of the triangle. Otherwise, tiny triangles would never be hit, and huge triangles
+    // would be hit by rays that are almost parallel to them.
+    let epsilon =
+        f32::EPSILON * (vector_v0_to_v1.length_squared() * vector_v0_to_v2.length_squared()).sqrt();
 
     match backface_culling {
         Backfaces::Cull => {
             // if the determinant is negative the triangle is back facing
             // if the determinant is close to 0, the ray misses the triangle
             // This test checks both cases
-            if determinant < f32::EPSILON {
+            if determinant < epsilon || determinant <= 0.0 {
                 return None;
             }
         }
         Backfaces::Include => {
             // ray and triangle are parallel if det is close to 0
-            if determinant.abs() < f32::EPSILON {
+            if determinant.abs() < epsilon || determinant == 0.0 {
                 return None;
             }
         }
@@ -334,6 +1004,68 @@ mod tests {
         assert!(result.unwrap().distance - 1.0 <= f32::EPSILON);
     }
 
+    #[test]
+    fn hits_interpolate_vertex_attributes() {
+        let mesh = Mesh::new(PrimitiveTopology::TriangleList, Default::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vec![V0, V1, V2])
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_UV_0,
+                vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+            );
+        let ray = Ray3d::new(Vec3::new(0.0, 0.5, 0.5), Vec3::X);
+        let hit =
+            ray_intersection_over_mesh(&mesh, &Mat4::IDENTITY, ray, Backfaces::Include).unwrap();
+        let [v0, v1, v2] = hit.triangle().unwrap();
+        assert!(hit
+            .interpolate([v0, v1, v2])
+            .abs_diff_eq(hit.position_vec3a(), 1e-5));
+
+        let uv = interpolate_vertex_attribute(&mesh, &hit, Mesh::ATTRIBUTE_UV_0).unwrap();
+        assert!(uv.abs_diff_eq(Vec4::new(0.5, 0.0, 0.0, 0.0), 1e-5));
+        assert_eq!(
+            interpolate_vertex_attribute(&mesh, &hit, Mesh::ATTRIBUTE_COLOR),
+            None
+        );
+    }
+
+    #[test]
+    fn hits_have_vertex_indices() {
+        let ray = Ray3d::new(Vec3::new(0.0, 0.5, 0.5), Vec3::X);
+        let hit = ray_mesh_intersection(
+            &Mat4::IDENTITY,
+            &[[0.0; 3], V2, V0, V1],
+            None,
+            ray,
+            Some(&vec![0u32, 0, 0, 2, 3, 1]),
+            Backfaces::Include,
+        )
+        .unwrap();
+        assert_eq!(hit.triangle_index(), Some(1));
+        assert_eq!(hit.triangle_indices(), Some([2, 3, 1]));
+    }
+
+    #[test]
+    fn mesh_hits_beyond_max_distance_are_skipped() {
+        // Scaling the mesh moves the triangle to a distance of two in world space.
+        let transform = Mat4::from_scale(Vec3::splat(2.0));
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+        let cast = |max_distance| {
+            ray_mesh_intersection_within(
+                &transform,
+                &[V0, V1, V2],
+                None,
+                ray,
+                None::<&Vec<u32>>,
+                Backfaces::Include,
+                max_distance,
+            )
+            .map(|hit| hit.distance())
+        };
+        assert_eq!(cast(f32::INFINITY), Some(2.0));
+        assert_eq!(cast(2.5), Some(2.0));
+        assert_eq!(cast(1.5), None);
+    }
+
     #[test]
     fn raycast_triangle_mt_culling() {
         let triangle = [V2.into(), V1.into(), V0.into()];
@@ -341,4 +1073,65 @@ mod tests {
         let result = ray_triangle_intersection(&ray, &triangle, Backfaces::Cull);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn mirrored_meshes_have_outward_normals() {
+        let mut sphere = Mesh::from(bevy_math::primitives::Sphere::new(1.0))
+            .with_generated_tangents()
+            .unwrap();
+        sphere.duplicate_vertices();
+        sphere.compute_flat_normals();
+        let transform = Mat4::from_scale(Vec3::new(-4.0, 1.0, 1.0));
+
+        // A ray from outside still hits the front faces, and the normal of the stretched and
+        // mirrored sphere faces back towards the ray.
+        let ray = Ray3d::new(Vec3::new(2.0, 0.0, 5.0), Vec3::NEG_Z);
+        let hit = ray_intersection_over_mesh(&sphere, &transform, ray, Backfaces::Cull).unwrap();
+        let p = hit.position();
+        let ellipsoid_normal = Vec3::new(p.x / 16.0, p.y, p.z).normalize();
+        assert!(hit.normal().dot(ellipsoid_normal) > 0.99);
+        assert!(hit.normal().dot(*ray.direction) < 0.0);
+
+        let [a, b, c] = hit.triangle().unwrap().map(Vec3::from);
+        let face_normal = (b - a).cross(c - a).normalize();
+        assert!(hit.normal().abs_diff_eq(-face_normal, 1e-4));
+        let bitangent = hit.bitangent().unwrap();
+        let unmirrored = ray_intersection_over_mesh(
+            &sphere,
+            &Mat4::from_scale(Vec3::new(4.0, 1.0, 1.0)),
+            Ray3d::new(Vec3::new(-2.0, 0.0, 5.0), Vec3::NEG_Z),
+            Backfaces::Cull,
+        )
+        .unwrap();
+        let mirror = Vec3::new(-1.0, 1.0, 1.0);
+        assert!(bitangent.abs_diff_eq(unmirrored.bitangent().unwrap() * mirror, 1e-4));
+    }
+
+    #[test]
+    fn raycast_triangle_mt_scale() {
+        for scale in [1e-5, 1e-3, 1.0, 1e3, 1e5] {
+            let triangle = [V0, V1, V2].map(|v| Vec3A::from(v) * scale);
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+            let result = ray_triangle_intersection(&ray, &triangle, Backfaces::Cull);
+            let distance = result
+                .expect("triangle should be hit at any scale")
+                .distance;
+            assert!((distance / scale - 1.0).abs() <= 1e-5);
+        }
+    }
+
+    #[test]
+    fn raycast_triangle_mt_degenerate() {
+        // A triangle with no area, and a huge triangle the ray is almost parallel to.
+        let sliver = [V0.into(), V1.into(), Vec3A::from(V0).lerp(V1.into(), 0.5)];
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::X);
+        assert!(ray_triangle_intersection(&ray, &sliver, Backfaces::Include).is_none());
+
+        let huge = [V0, V1, V2].map(|v| Vec3A::from(v) * 1e5);
+        let grazing = Ray3d::new(
+            Vec3::new(1e5 - 0.01, -0.5e5, 0.0),
+            Vec3::new(1e-7, 1.0, 0.0),
+        );
+        assert!(ray_triangle_intersection(&grazing, &huge, Backfaces::Include).is_none());
+    }
 }
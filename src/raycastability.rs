@@ -0,0 +1,263 @@
+//! # Raycastability
+//!
+//! An entity is only hit by raycasts if it has the right components, its mesh is loaded and
+//! supported, and it passes the visibility and filter settings of the raycast. When an entity
+//! isn't hit, [`Raycast::explain`] checks each of these requirements and reports which ones
+//! failed:
+//!
+//! ```
+//! # use bevy_mod_raycast::prelude::*;
+//! # use bevy::prelude::*;
+//! # #[derive(Component)]
+//! # struct Pickable;
+//! fn explain_pickables(raycast: Raycast, pickables: Query<Entity, With<Pickable>>) {
+//!     for entity in &pickables {
+//!         let report = raycast.explain(entity, &default());
+//!         if !report.is_raycastable() {
+//!             warn!("{entity:?} can't be picked: {report}");
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+
+use bevy_ecs::entity::Entity;
+
+use crate::{
+    dynamic_mesh::culling_aabb,
+    immediate::{is_in_layers, is_visible, model_matrix},
+    prelude::*,
+    raycast::{is_degenerate_transform, validate_raycast_mesh},
+};
+
+/// Which requirements for being raycast an entity meets, returned by [`Raycast::explain`].
+/// Formatting it with [`Display`](fmt::Display) lists the requirements that failed.
+///
+/// The report does not depend on any ray, so an entity that meets every requirement may still be
+/// missed by rays that don't pass through its bounds, or that are limited by other settings, such
+/// as [`RaycastSettings::max_distance`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RaycastabilityReport {
+    /// The entity exists. All other requirements fail if it doesn't.
+    pub exists: bool,
+    /// The entity has geometry to raycast: a mesh, a [`DynamicRaycastMesh`], or a
+    /// [`RaycastAabbTarget`].
+    pub has_geometry: bool,
+    /// The entity has bounds to be culled with: an [`Aabb`](bevy_render::primitives::Aabb), or a
+    /// non-empty [`DynamicRaycastMesh`].
+    pub has_aabb: bool,
+    /// The entity has a [`GlobalTransform`](bevy_transform::components::GlobalTransform).
+    pub has_transform: bool,
+    /// The entity has both [`InheritedVisibility`](bevy_render::view::InheritedVisibility) and
+    /// [`ViewVisibility`](bevy_render::view::ViewVisibility).
+    pub has_visibility: bool,
+    /// The entity is visible as required by [`RaycastSettings::visibility`].
+    pub is_visible: bool,
+    /// The entity is on one of the [`RaycastSettings::layers`].
+    pub in_layers: bool,
+    /// The entity is accepted by [`RaycastSettings::filter`].
+    pub passes_filter: bool,
+    /// Why the geometry of the entity can't be raycast, such as when its mesh is not loaded.
+    pub error: Option<RaycastError>,
+}
+
+impl RaycastabilityReport {
+    /// Returns `true` if the entity meets every requirement for being raycast.
+    pub fn is_raycastable(&self) -> bool {
+        self.exists
+            && self.has_geometry
+            && self.has_aabb
+            && self.has_transform
+            && self.has_visibility
+            && self.is_visible
+            && self.in_layers
+            && self.passes_filter
+            && self.error.is_none()
+    }
+
+    /// Describes each requirement the entity fails.
+    pub fn problems(&self) -> Vec<String> {
+        if !self.exists {
+            return vec!["the entity does not exist".into()];
+        }
+        let mut problems = Vec::new();
+        if !self.has_geometry {
+            problems.push(
+                "it has no mesh, `DynamicRaycastMesh`, or `RaycastAabbTarget` to raycast".into(),
+            );
+        }
+        if !self.has_aabb {
+            problems.push("it has no `Aabb`, or its `DynamicRaycastMesh` is empty".into());
+        }
+        if !self.has_transform {
+            problems.push("it has no `GlobalTransform`".into());
+        }
+        if !self.has_visibility {
+            problems.push("it has no `InheritedVisibility` or `ViewVisibility`".into());
+        } else if !self.is_visible {
+            problems.push("it is not visible as required by `RaycastSettings::visibility`".into());
+        }
+        if !self.in_layers {
+            problems.push("it is not on any of the `RaycastSettings::layers`".into());
+        }
+        if !self.passes_filter {
+            problems.push("it is rejected by `RaycastSettings::filter`".into());
+        }
+        if let Some(error) = self.error {
+            problems.push(error.to_string());
+        }
+        problems
+    }
+}
+
+impl fmt::Display for RaycastabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.problems().as_slice() {
+            [] => write!(f, "the entity is raycastable"),
+            problems => write!(f, "{}", problems.join("; ")),
+        }
+    }
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Checks which requirements for being raycast with the `settings` the `entity` meets. See
+    /// the [module docs](crate::raycastability).
+    pub fn explain(&self, entity: Entity, settings: &RaycastSettings) -> RaycastabilityReport {
+        let Ok(data) = self.explain_query.get(entity) else {
+            return RaycastabilityReport::default();
+        };
+        let (
+            mesh,
+            mesh2d,
+            simplified_mesh,
+            dynamic_mesh,
+            aabb_target,
+            aabb,
+            inherited,
+            view,
+            transform,
+        ) = data;
+        let mesh = mesh.map(MeshHandleComponent::mesh_handle);
+        #[cfg(feature = "2d")]
+        let mesh = mesh.or(mesh2d.map(MeshHandleComponent::mesh_handle));
+        #[cfg(not(feature = "2d"))]
+        let () = mesh2d;
+
+        let visibility = inherited.zip(view);
+        // Mirrors the narrowphase, where AABB targets are raycast instead of their mesh.
+        let mesh_error = mesh.filter(|_| !aabb_target).and_then(|mesh| {
+            let mesh = simplified_mesh.map_or(mesh, |simplified| &simplified.mesh);
+            match self.meshes.get(mesh) {
+                Some(mesh) => validate_raycast_mesh(mesh).err(),
+                None => Some(RaycastError::MissingMesh),
+            }
+        });
+        let transform_error = transform
+            .map(|transform| {
+                model_matrix(
+                    entity,
+                    transform,
+                    &self.transform_history,
+                    settings.transform_blend,
+                )
+            })
+            .filter(is_degenerate_transform)
+            .map(|_| RaycastError::DegenerateTransform);
+        RaycastabilityReport {
+            exists: true,
+            has_geometry: mesh.is_some() || dynamic_mesh.is_some() || aabb_target,
+            has_aabb: culling_aabb((aabb, dynamic_mesh)).is_some(),
+            has_transform: transform.is_some(),
+            has_visibility: visibility.is_some(),
+            is_visible: visibility.is_some_and(|(inherited, view)| {
+                is_visible(settings.visibility, inherited, view, aabb_target)
+            }),
+            in_layers: is_in_layers(settings.layers, &self.layers_query, entity),
+            passes_filter: (settings.filter)(entity),
+            error: mesh_error.or(transform_error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, render::primitives::Aabb};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn reports_failed_requirements() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -2.0, -3.0, -4.0]);
+        world.entity_mut(planes[1]).remove::<Aabb>();
+        world
+            .entity_mut(planes[2])
+            .insert(InheritedVisibility::HIDDEN);
+        world
+            .entity_mut(planes[3])
+            .insert(Handle::<Mesh>::default())
+            .insert(GlobalTransform::from_scale(Vec3::ZERO));
+        let missing = world.spawn_empty().id();
+        world.despawn(missing);
+
+        let entities: Vec<_> = planes.iter().copied().chain([missing]).collect();
+        let reports = world.run_system_once(move |raycast: Raycast| {
+            let filter = |entity| entity != entities[0];
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::MustBeVisible)
+                .with_filter(&filter);
+            entities
+                .iter()
+                .map(|entity| raycast.explain(*entity, &settings))
+                .collect::<Vec<_>>()
+        });
+        let raycastable = RaycastabilityReport {
+            exists: true,
+            has_geometry: true,
+            has_aabb: true,
+            has_transform: true,
+            has_visibility: true,
+            is_visible: true,
+            in_layers: true,
+            passes_filter: true,
+            error: None,
+        };
+        assert!(raycastable.is_raycastable());
+        assert_eq!(
+            reports[0],
+            RaycastabilityReport {
+                passes_filter: false,
+                ..raycastable
+            }
+        );
+        assert_eq!(
+            reports[1],
+            RaycastabilityReport {
+                has_aabb: false,
+                ..raycastable
+            }
+        );
+        assert_eq!(
+            reports[2],
+            RaycastabilityReport {
+                is_visible: false,
+                ..raycastable
+            }
+        );
+        assert_eq!(
+            reports[3],
+            RaycastabilityReport {
+                error: Some(RaycastError::MissingMesh),
+                ..raycastable
+            }
+        );
+        assert_eq!(reports[4], RaycastabilityReport::default());
+        assert_eq!(
+            reports[2].to_string(),
+            "it is not visible as required by `RaycastSettings::visibility`"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! # Reflection
+//!
+//! Rays, hits, and the settings used to cast them implement [`Reflect`](bevy_reflect::Reflect),
+//! so they can be inspected in reflection-based editors and stored in scenes. The
+//! [`RaycastReflectionPlugin`] registers them in the [`AppTypeRegistry`]. It is added by the
+//! [`CursorRayPlugin`] and the deferred raycasting plugins, and can be added on its own when only
+//! the [`Raycast`] system param is used.
+//!
+//! With the `serialize` feature, these types also implement `serde`'s `Serialize` and
+//! `Deserialize`, and register them as type data, so rays can be authored in scene files, e.g. to
+//! give a fixed security camera a preset [`RaycastSource::ray`].
+
+use bevy_app::prelude::*;
+use bevy_math::Ray3d;
+
+#[allow(unused_imports)] // Needed for docs
+use bevy_ecs::reflect::AppTypeRegistry;
+
+use crate::prelude::*;
+
+/// Registers the reflected types of this crate. See the [module docs](self).
+#[derive(Default)]
+pub struct RaycastReflectionPlugin;
+impl Plugin for RaycastReflectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Ray3d>()
+            .register_type::<Option<Ray3d>>()
+            .register_type::<IntersectionData>()
+            .register_type::<HitData>()
+            .register_type::<PrimitiveIntersection>()
+            .register_type::<CoordinateSystem>()
+            .register_type::<RaycastVisibility>()
+            .register_type::<SpreadPattern>()
+            .register_type::<ConeSpread>()
+            .register_type::<RaycastLayers>();
+
+        #[cfg(feature = "deferred")]
+        app.register_type::<RaycastMethod>()
+            .register_type::<RaycastSourceStats>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{prelude::*, reflect::FromReflect};
+
+    use super::*;
+
+    #[test]
+    fn types_are_registered() {
+        let mut app = App::new();
+        app.add_plugins(RaycastReflectionPlugin);
+        let registry = app.world().resource::<AppTypeRegistry>().read();
+        assert!(registry.contains(std::any::TypeId::of::<Ray3d>()));
+        assert!(registry.contains(std::any::TypeId::of::<IntersectionData>()));
+
+        let hit = IntersectionData::new(Vec3::X, Vec3::Y, Vec3::ZERO, 1.0, None, Some(2))
+            .with_triangle_indices([0, 1, 2]);
+        let reflected = IntersectionData::from_reflect(&*hit.clone_value()).unwrap();
+        assert_eq!(reflected.position(), hit.position());
+        assert_eq!(reflected.triangle_indices(), hit.triangle_indices());
+    }
+}
@@ -0,0 +1,207 @@
+//! # Mesh Surface Sampling
+//!
+//! Utilities for scattering points across the surface of a mesh, such as raycast origins for
+//! checking how much of a surface is visible from a camera.
+//!
+//! [`MeshSurfaceSampler`] weights each triangle by its world-space area, so samples are uniformly
+//! distributed over the surface no matter how the mesh is tessellated. Samples are driven by
+//! caller-provided numbers in `[0, 1)`, so any random number generator can be used, or
+//! [`MeshSurfaceSampler::sample_evenly`] can be used for a deterministic, evenly spread set.
+
+use bevy_math::{Mat4, Vec3, Vec3A};
+use bevy_reflect::Reflect;
+use bevy_render::{
+    mesh::{Mesh, VertexAttributeValues},
+    render_resource::PrimitiveTopology,
+};
+
+use crate::raycast::{mesh_triangle_count, triangle_vertex_indices};
+
+/// A point sampled on the surface of a mesh.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct SurfaceSample {
+    /// The world-space position of the sample.
+    pub position: Vec3,
+    /// The world-space surface normal at the sample, interpolated from the mesh's vertex normals
+    /// when present.
+    pub normal: Vec3,
+    /// The index of the triangle the sample lies on, matching
+    /// [`IntersectionData::triangle_index`](crate::primitives::IntersectionData::triangle_index).
+    pub triangle_index: usize,
+}
+
+/// Samples points uniformly distributed over the world-space surface of a mesh. See the
+/// [module docs](self).
+#[derive(Clone, Debug, Default)]
+pub struct MeshSurfaceSampler {
+    triangles: Vec<[Vec3A; 3]>,
+    /// The index of each sampled triangle in the mesh, which differs from its index in
+    /// `triangles` when triangles with out of range vertices were skipped.
+    triangle_indices: Vec<usize>,
+    normals: Option<Vec<[Vec3A; 3]>>,
+    /// The running total of triangle areas, used to pick triangles weighted by area.
+    cumulative_areas: Vec<f32>,
+}
+
+impl MeshSurfaceSampler {
+    /// Builds a sampler for `mesh`, transformed into world space by `mesh_transform`. Returns
+    /// `None` if the mesh is not a [`PrimitiveTopology::TriangleList`] or has no `Float32x3` vertex
+    /// positions.
+    pub fn new(mesh: &Mesh, mesh_transform: &Mat4) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+        let vertex_normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => Some(normals),
+            _ => None,
+        };
+        let normal_matrix = mesh_transform.inverse().transpose();
+
+        let mut sampler = Self {
+            normals: vertex_normals.map(|_| Vec::new()),
+            ..Self::default()
+        };
+        let mut total_area = 0.0;
+        for triangle in 0..mesh_triangle_count(mesh) {
+            let Some(vertices) = triangle_vertex_indices(mesh, triangle) else {
+                continue;
+            };
+            let [Some(p0), Some(p1), Some(p2)] = vertices.map(|i| positions.get(i)) else {
+                continue;
+            };
+            let world = [p0, p1, p2].map(|p| mesh_transform.transform_point3a(Vec3A::from(*p)));
+            total_area += (world[1] - world[0]).cross(world[2] - world[0]).length() / 2.0;
+            sampler.triangles.push(world);
+            sampler.triangle_indices.push(triangle);
+            sampler.cumulative_areas.push(total_area);
+            if let (Some(normals), Some(vertex_normals)) = (&mut sampler.normals, vertex_normals) {
+                normals.push(vertices.map(|i| {
+                    let normal = vertex_normals.get(i).copied().unwrap_or_default();
+                    normal_matrix.transform_vector3a(Vec3A::from(normal))
+                }));
+            }
+        }
+        Some(sampler)
+    }
+
+    /// The world-space surface area of the mesh.
+    pub fn area(&self) -> f32 {
+        self.cumulative_areas.last().copied().unwrap_or(0.0)
+    }
+
+    /// Maps three numbers in `[0, 1)` to a point on the surface. Uniformly distributed inputs give
+    /// uniformly distributed samples. Returns `None` if the mesh has no area.
+    pub fn sample(&self, [u, r1, r2]: [f32; 3]) -> Option<SurfaceSample> {
+        let area = self.area();
+        if area <= 0.0 {
+            return None;
+        }
+        let target = u.clamp(0.0, 1.0) * area;
+        let sampled = self
+            .cumulative_areas
+            .partition_point(|cumulative| *cumulative <= target)
+            .min(self.triangles.len() - 1);
+
+        // Folding the unit square onto the triangle with a square root keeps the density uniform.
+        let s = r1.clamp(0.0, 1.0).sqrt();
+        let r2 = r2.clamp(0.0, 1.0);
+        let weights = [1.0 - s, s * (1.0 - r2), s * r2];
+        let [a, b, c] = self.triangles[sampled];
+        let position = a * weights[0] + b * weights[1] + c * weights[2];
+        let face_normal = (b - a).cross(c - a).normalize_or_zero();
+        let normal = self
+            .normals
+            .as_ref()
+            .map(|normals| {
+                let [na, nb, nc] = normals[sampled];
+                (na * weights[0] + nb * weights[1] + nc * weights[2]).normalize_or_zero()
+            })
+            .filter(|normal| *normal != Vec3A::ZERO)
+            .unwrap_or(face_normal);
+
+        Some(SurfaceSample {
+            position: position.into(),
+            normal: normal.into(),
+            triangle_index: self.triangle_indices[sampled],
+        })
+    }
+
+    /// Returns `count` samples spread evenly over the surface, using a low-discrepancy sequence.
+    /// The same mesh and count always give the same samples, which avoids noise when the samples
+    /// are used for scoring from frame to frame.
+    pub fn sample_evenly(&self, count: usize) -> impl Iterator<Item = SurfaceSample> + '_ {
+        // The R3 sequence, based on the generalized golden ratio for three dimensions.
+        const PHI: f64 = 1.220_744_084_605_759_5;
+        let alpha = [1.0 / PHI, 1.0 / (PHI * PHI), 1.0 / (PHI * PHI * PHI)];
+        (0..count).filter_map(move |n| {
+            let point = alpha.map(|a| (0.5 + a * n as f64).fract() as f32);
+            self.sample(point)
+        })
+    }
+}
+
+/// The world-space surface area of `mesh`, transformed by `mesh_transform`. Returns `0.0` for
+/// meshes that are not supported by [`MeshSurfaceSampler::new`].
+pub fn mesh_surface_area(mesh: &Mesh, mesh_transform: &Mat4) -> f32 {
+    MeshSurfaceSampler::new(mesh, mesh_transform).map_or(0.0, |sampler| sampler.area())
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::{mesh::Indices, render_asset::RenderAssetUsages};
+
+    use super::*;
+
+    #[test]
+    fn samples_lie_on_scaled_quad() {
+        let quad = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+        )
+        .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+        let transform = Mat4::from_scale(Vec3::new(2.0, 3.0, 1.0));
+        assert_eq!(mesh_surface_area(&quad, &transform), 6.0);
+
+        let sampler = MeshSurfaceSampler::new(&quad, &transform).unwrap();
+        let samples: Vec<_> = sampler.sample_evenly(64).collect();
+        assert_eq!(samples.len(), 64);
+        for sample in &samples {
+            assert!(sample.position.cmpge(Vec3::ZERO).all());
+            assert!(sample.position.cmple(Vec3::new(2.0, 3.0, 0.0)).all());
+            assert_eq!(sample.normal, Vec3::Z);
+        }
+        let on_first = samples.iter().filter(|s| s.triangle_index == 0).count();
+        assert!((24..=40).contains(&on_first));
+    }
+
+    #[test]
+    fn samples_report_mesh_triangle_indices() {
+        // The first triangle refers to a vertex that doesn't exist, so it is skipped.
+        let mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        )
+        .with_inserted_indices(Indices::U32(vec![0, 1, 9, 0, 1, 2]));
+        let sampler = MeshSurfaceSampler::new(&mesh, &Mat4::IDENTITY).unwrap();
+        assert_eq!(sampler.area(), 0.5);
+        assert!(sampler.sample_evenly(8).all(|s| s.triangle_index == 1));
+    }
+}
@@ -0,0 +1,379 @@
+//! # Selection Volumes
+//!
+//! Editors select everything inside a rectangle dragged on screen, or inside a brush placed in the
+//! world. [`Raycast::cast_volume`] finds the raycastable entities that touch a [`ConvexVolume`],
+//! which is bounded by any number of planes, such as the [`Frustum`] of a selection rectangle or a
+//! box from [`ConvexVolume::from_obb`].
+//!
+//! Like rays, volumes are first tested against the AABB of each entity. Entities whose AABB is
+//! entirely inside the volume are contained without looking at their meshes. The triangles of the
+//! remaining entities are clipped against the volume to find out if the mesh touches it.
+
+use bevy_asset::Handle;
+use bevy_ecs::entity::Entity;
+use bevy_math::{Mat3A, Mat4, Vec3, Vec3A};
+use bevy_reflect::Reflect;
+use bevy_render::{
+    mesh::{Mesh, VertexAttributeValues},
+    primitives::{Aabb, Frustum, HalfSpace},
+};
+use bevy_transform::components::GlobalTransform;
+
+use crate::{
+    dynamic_mesh::culling_aabb,
+    immediate::{culling_transform, model_matrix},
+    prelude::*,
+    raycast::triangle_vertex_indices,
+};
+
+/// A convex volume, bounded by planes. See the [module docs](self).
+#[derive(Clone, Debug, Default)]
+pub struct ConvexVolume {
+    half_spaces: Vec<HalfSpace>,
+}
+
+/// How much of an entity is inside a [`ConvexVolume`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum VolumeContainment {
+    /// The entity is entirely inside the volume.
+    Contained,
+    /// Part of the entity is inside the volume.
+    Intersecting,
+}
+
+impl ConvexVolume {
+    /// The volume inside all of the `half_spaces`, whose normals point into the volume.
+    pub fn new(half_spaces: impl IntoIterator<Item = HalfSpace>) -> Self {
+        Self {
+            half_spaces: half_spaces.into_iter().collect(),
+        }
+    }
+
+    /// The box bounded by the `aabb`, transformed into world space by `world_from_local`. Rotating
+    /// the transform gives an oriented box.
+    pub fn from_obb(aabb: &Aabb, world_from_local: &Mat4) -> Self {
+        // Planes are transformed by the inverse transpose of the transform applied to points.
+        let local_from_world_transpose = world_from_local.inverse().transpose();
+        let (min, max) = (aabb.min(), aabb.max());
+        let faces = [Vec3::X, Vec3::Y, Vec3::Z].into_iter().flat_map(|axis| {
+            let axis = Vec3A::from(axis);
+            [(-axis).extend(axis.dot(max)), axis.extend(-axis.dot(min))]
+        });
+        Self::new(faces.map(|plane| HalfSpace::new(local_from_world_transpose * plane)))
+    }
+
+    /// The planes bounding the volume.
+    pub fn half_spaces(&self) -> &[HalfSpace] {
+        &self.half_spaces
+    }
+
+    /// Returns `true` if the `point` is inside the volume, or on its surface.
+    pub fn contains_point(&self, point: Vec3A) -> bool {
+        self.half_spaces
+            .iter()
+            .all(|half_space| signed_distance(half_space, point) >= 0.0)
+    }
+
+    /// Returns `true` if any part of the `triangle` is inside the volume, by clipping the triangle
+    /// against each plane in turn.
+    pub fn intersects_triangle(&self, triangle: [Vec3A; 3]) -> bool {
+        let mut polygon = triangle.to_vec();
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for half_space in &self.half_spaces {
+            clipped.clear();
+            for (i, &a) in polygon.iter().enumerate() {
+                let b = polygon[(i + 1) % polygon.len()];
+                let (a_distance, b_distance) = (
+                    signed_distance(half_space, a),
+                    signed_distance(half_space, b),
+                );
+                if a_distance >= 0.0 {
+                    clipped.push(a);
+                }
+                if (a_distance >= 0.0) != (b_distance >= 0.0) {
+                    clipped.push(a.lerp(b, a_distance / (a_distance - b_distance)));
+                }
+            }
+            if clipped.is_empty() {
+                return false;
+            }
+            std::mem::swap(&mut polygon, &mut clipped);
+        }
+        true
+    }
+
+    /// Tests the `aabb`, transformed by `world_from_local`, against the volume. An AABB that
+    /// crosses the planes may still be outside the volume near its corners, so an `Intersecting`
+    /// AABB might not touch the volume at all.
+    fn aabb_containment(&self, aabb: &Aabb, world_from_local: &Mat4) -> Option<VolumeContainment> {
+        let center = world_from_local.transform_point3a(aabb.center);
+        let world_from_local_axes = Mat3A::from_mat4(*world_from_local);
+        let mut containment = VolumeContainment::Contained;
+        for half_space in &self.half_spaces {
+            let distance = signed_distance(half_space, center);
+            let radius = aabb.relative_radius(&half_space.normal(), &world_from_local_axes);
+            if distance + radius < 0.0 {
+                return None;
+            }
+            if distance - radius < 0.0 {
+                containment = VolumeContainment::Intersecting;
+            }
+        }
+        Some(containment)
+    }
+
+    /// Tests world-space triangles against the volume. Returns `None` if none of them touch it.
+    fn triangles_containment(&self, triangles: &[[Vec3A; 3]]) -> Option<VolumeContainment> {
+        let (mut inside, mut outside) = (false, false);
+        for triangle in triangles {
+            if triangle.iter().all(|vertex| self.contains_point(*vertex)) {
+                inside = true;
+            } else if self.intersects_triangle(*triangle) {
+                return Some(VolumeContainment::Intersecting);
+            } else {
+                outside = true;
+            }
+            if inside && outside {
+                return Some(VolumeContainment::Intersecting);
+            }
+        }
+        inside.then_some(VolumeContainment::Contained)
+    }
+}
+
+impl From<&Frustum> for ConvexVolume {
+    fn from(frustum: &Frustum) -> Self {
+        Self::new(frustum.half_spaces)
+    }
+}
+
+fn signed_distance(half_space: &HalfSpace, point: Vec3A) -> f32 {
+    half_space.normal().dot(point) + half_space.d()
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Finds the raycastable entities that touch the `volume`, and whether they are entirely inside
+    /// it. See the [module docs](self).
+    ///
+    /// Of the `settings`, only these are used, as in [`Raycast::cast_ray`]:
+    /// [`visibility`](RaycastSettings::visibility), [`filter`](RaycastSettings::filter),
+    /// [`layers`](RaycastSettings::layers), [`lod_viewpoint`](RaycastSettings::lod_viewpoint), and
+    /// [`transform_blend`](RaycastSettings::transform_blend).
+    ///
+    /// Every other setting only applies to rays and is ignored: the early exit and candidate tests,
+    /// `max_distance`, `sorting`, `include_behind_origin`, `all_hits_per_entity`, `max_hits`,
+    /// `max_triangles_per_cast`, `geometric_normals`, `warm_start`,
+    /// `parallel_narrowphase_threshold`, `hit_data`, and `priority_class`. Every triangle of an
+    /// entity that the volume partly overlaps is tested, and the [`RaycastBudget`] is not charged.
+    pub fn cast_volume(
+        &mut self,
+        volume: &ConvexVolume,
+        settings: &RaycastSettings,
+    ) -> Vec<(Entity, VolumeContainment)> {
+        self.cull_with(settings, None, |bounds| {
+            let model_to_world = bounds.transform.model_to_world();
+            volume
+                .aabb_containment(&bounds.aabb, &model_to_world)
+                .map(|_| [0.0; 2])
+        });
+
+        let transform_blend = settings.transform_blend;
+        let transform_cache = self.transform_cache.as_deref();
+        let mut triangles = Vec::new();
+        let mut entities = Vec::new();
+        for (_, entity) in self.culled_list.iter() {
+            let Ok((_, _, bounds, transform, ..)) = self.culling_query.get(*entity) else {
+                continue;
+            };
+            let (Some(aabb), Ok(transform)) = (
+                culling_aabb(bounds),
+                culling_transform(
+                    transform_cache,
+                    *entity,
+                    transform,
+                    &self.transform_history,
+                    transform_blend,
+                ),
+            ) else {
+                continue;
+            };
+            let containment = match volume.aabb_containment(&aabb, &transform.model_to_world()) {
+                Some(VolumeContainment::Intersecting) => {
+                    triangles.clear();
+                    self.world_triangles(*entity, transform_blend, &mut triangles);
+                    volume.triangles_containment(&triangles)
+                }
+                containment => containment,
+            };
+            if let Some(containment) = containment {
+                entities.push((*entity, containment));
+            }
+        }
+        entities
+    }
+
+    /// Collects the world-space triangles of every geometry of the `entity` that a ray could hit.
+    pub(crate) fn world_triangles(
+        &self,
+        entity: Entity,
+        transform_blend: Option<f32>,
+        triangles: &mut Vec<[Vec3A; 3]>,
+    ) {
+        let world_from_local = |transform: &GlobalTransform| {
+            model_matrix(entity, transform, &self.transform_history, transform_blend)
+        };
+        if let Ok((aabb, _, aabb_transform)) = self.aabb_target_query.get(entity) {
+            triangles.extend(aabb_triangles(aabb, &world_from_local(aabb_transform)));
+            return;
+        }
+        let mut mesh_triangles = |mesh_handle: &Handle<Mesh>,
+                                  simplified_mesh: Option<&SimplifiedMesh>,
+                                  transform: Mat4| {
+            let mesh_handle = simplified_mesh.map(|m| &m.mesh).unwrap_or(mesh_handle);
+            let Some(mesh) = self.meshes.get(mesh_handle) else {
+                return;
+            };
+            if validate_raycast_mesh(mesh).is_err() {
+                return;
+            }
+            let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+            else {
+                return;
+            };
+            let vertices = simplified_mesh
+                .and_then(|simplified| simplified.triangle_range(mesh))
+                .unwrap_or(0..mesh_triangle_count(mesh))
+                .filter_map(|triangle| triangle_vertex_indices(mesh, triangle));
+            triangles.extend(world_positions(vertices, positions, &transform));
+        };
+        if let Ok((mesh, simplified_mesh, _, mesh_transform)) = self.mesh_query.get(entity) {
+            mesh_triangles(
+                mesh.mesh_handle(),
+                simplified_mesh,
+                world_from_local(mesh_transform),
+            );
+        }
+        #[cfg(feature = "2d")]
+        if let Ok((mesh, simplified_mesh, mesh_transform)) = self.mesh2d_query.get(entity) {
+            mesh_triangles(
+                mesh.mesh_handle(),
+                simplified_mesh,
+                world_from_local(mesh_transform),
+            );
+        }
+        if let Ok((mesh, _, mesh_transform)) = self.dynamic_mesh_query.get(entity) {
+            let vertices = (0..mesh.triangle_count()).map(|triangle| match mesh.indices() {
+                Some(indices) => {
+                    let first = triangle * 3;
+                    [0, 1, 2].map(|i| indices[first + i] as usize)
+                }
+                None => [0, 1, 2].map(|i| triangle * 3 + i),
+            });
+            let transform = world_from_local(mesh_transform);
+            triangles.extend(world_positions(vertices, mesh.positions(), &transform));
+        }
+    }
+}
+
+/// The world-space triangles with the `vertices`, skipping any with out of bounds indices.
+fn world_positions<'a>(
+    vertices: impl Iterator<Item = [usize; 3]> + 'a,
+    positions: &'a [[f32; 3]],
+    transform: &'a Mat4,
+) -> impl Iterator<Item = [Vec3A; 3]> + 'a {
+    vertices.filter_map(move |[a, b, c]| {
+        let triangle = [positions.get(a)?, positions.get(b)?, positions.get(c)?];
+        Some(triangle.map(|p| transform.transform_point3a(Vec3A::from(*p))))
+    })
+}
+
+/// The twelve world-space triangles of the faces of the `aabb`.
+fn aabb_triangles(aabb: &Aabb, transform: &Mat4) -> [[Vec3A; 3]; 12] {
+    let (min, max) = (aabb.min(), aabb.max());
+    let corner = |i: usize| {
+        let pick = |bit: usize, min: f32, max: f32| if i & bit == 0 { min } else { max };
+        let local = Vec3A::new(
+            pick(1, min.x, max.x),
+            pick(2, min.y, max.y),
+            pick(4, min.z, max.z),
+        );
+        transform.transform_point3a(local)
+    };
+    // Two triangles per face, as pairs of corner indices whose bits are x, y, and z.
+    const FACES: [[usize; 4]; 6] = [
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+    ];
+    let mut triangles = [[Vec3A::ZERO; 3]; 12];
+    for (face, [a, b, c, d]) in FACES.into_iter().enumerate() {
+        triangles[face * 2] = [corner(a), corner(b), corner(c)];
+        triangles[face * 2 + 1] = [corner(a), corner(c), corner(d)];
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn volumes_find_contained_and_intersecting_entities() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0, -5.0]);
+        // The box reaches up to the first plane, and its tilted top only cuts through part of it.
+        let volume = ConvexVolume::new(
+            [
+                Vec4::new(1.0, 0.0, 0.0, 2.0),
+                Vec4::new(-1.0, 0.0, 0.0, 2.0),
+                Vec4::new(0.0, 1.0, 0.0, 2.0),
+                Vec4::new(0.0, -1.0, 0.0, 2.0),
+                Vec4::new(0.0, 0.0, 1.0, 4.0),
+                Vec4::new(-1.0, -1.0, -1.0, -0.5),
+            ]
+            .map(HalfSpace::new),
+        );
+
+        let mut hits = world.run_system_once(move |mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast.cast_volume(&volume, &settings)
+        });
+        hits.sort_by_key(|(entity, _)| *entity);
+        assert_eq!(
+            hits,
+            vec![
+                (planes[0], VolumeContainment::Intersecting),
+                (planes[1], VolumeContainment::Contained),
+            ]
+        );
+    }
+
+    #[test]
+    fn clipped_triangles() {
+        let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::ONE);
+        let volume = ConvexVolume::from_obb(&aabb, &Mat4::from_rotation_z(0.5));
+        let inside = [
+            Vec3A::splat(0.1),
+            Vec3A::new(0.2, 0.6, 0.1),
+            Vec3A::splat(0.4),
+        ];
+        assert!(inside.iter().all(|vertex| volume.contains_point(*vertex)));
+        assert!(volume.intersects_triangle(inside));
+        // Every vertex is outside, but the triangle passes through the box.
+        let crossing = [
+            Vec3A::new(-5.0, 0.5, 0.5),
+            Vec3A::new(5.0, 0.5, 0.5),
+            Vec3A::new(0.0, 5.0, 0.5),
+        ];
+        assert!(volume.intersects_triangle(crossing));
+        let beside = crossing.map(|vertex| vertex + Vec3A::Z * 2.0);
+        assert!(!volume.intersects_triangle(beside));
+    }
+}
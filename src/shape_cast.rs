@@ -0,0 +1,238 @@
+//! # Shape Casting
+//!
+//! A raycast finds what a point moving along a ray would hit. A character or projectile has a
+//! size, and can collide with geometry that a ray through its middle would miss, such as a ledge
+//! at knee height, or the edge of a doorway. [`Raycast::cast_sphere`] sweeps a sphere along a
+//! ray instead, and returns where it first touches the scene:
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! # #[derive(Component)]
+//! # struct Player;
+//! fn move_player(mut raycast: Raycast, mut player: Query<&mut Transform, With<Player>>) {
+//!     let Ok(mut transform) = player.get_single_mut() else {
+//!         return;
+//!     };
+//!     let step = Vec3::new(0.0, 0.0, -0.1);
+//!     let Ok(direction) = Dir3::new(step) else {
+//!         return;
+//!     };
+//!     let ray = Ray3d::new(transform.translation, *direction);
+//!     let settings = RaycastSettings::default().with_max_distance(step.length());
+//!     // Stop just short of the contact.
+//!     let travel = match raycast.cast_sphere(ray, 0.5, &settings) {
+//!         Some((_, hit)) => (hit.distance() - 1e-3).max(0.0),
+//!         None => step.length(),
+//!     };
+//!     transform.translation += *direction * travel;
+//! }
+//! ```
+//!
+//! The sweep is exact for every triangle it is tested against, but it tests every triangle of
+//! each entity whose bounds the sphere passes through, without using [`MeshBvhCache`].
+
+use bevy_asset::Handle;
+use bevy_ecs::entity::Entity;
+use bevy_math::Ray3d;
+use bevy_render::{mesh::Mesh, primitives::Aabb};
+use bevy_transform::components::GlobalTransform;
+
+use crate::{
+    immediate::{model_matrix, world_aabb},
+    prelude::*,
+    raycast::{sphere_mesh_sweep_over, validate_raycast_mesh},
+};
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Sweeps a sphere with `radius` from the origin of the ray along its direction, and returns
+    /// the first entity it touches, with the contact. The distance of the hit is how far the
+    /// center of the sphere traveled before touching the entity, its position is the contact
+    /// point, and its normal points from the contact point to the center of the sphere. See the
+    /// [module docs](self).
+    ///
+    /// Entities are found with the visibility, filter, transform blend, and maximum distance of
+    /// the `settings`, like [`Raycast::cast_ray`]. Triangles are touched from both sides, and a
+    /// sphere that starts out touching an entity hits it at a distance of zero.
+    pub fn cast_sphere(
+        &mut self,
+        ray: Ray3d,
+        radius: f32,
+        settings: &RaycastSettings,
+    ) -> Option<(Entity, IntersectionData)> {
+        if !self.spend_budget(settings.priority_class) {
+            self.culled_list.clear();
+            return None;
+        }
+
+        let transform_blend = settings.transform_blend;
+        let max_distance = settings.max_distance;
+        let slab_ray = SlabRay::new(ray);
+        self.cull_with(settings, None, |bounds| {
+            // The center of the sphere only passes within `radius` of the bounds if it passes
+            // through the bounds grown by `radius`.
+            let world_bounds = world_aabb(&bounds.aabb, &bounds.transform.model_to_world());
+            let grown = Aabb {
+                center: world_bounds.center,
+                half_extents: world_bounds.half_extents + radius,
+            };
+            let [near, far] = slab_ray.intersects_aabb(&grown)?;
+            (far >= 0.0 && near <= max_distance).then_some([near.max(0.0), far])
+        });
+
+        let mut max_distance = settings.max_distance;
+        let mut nearest = None;
+        for ([near, _], entity) in self.culled_list.iter() {
+            if *near > max_distance {
+                break;
+            }
+            let hit = self.sphere_sweep(*entity, ray, radius, transform_blend, max_distance);
+            if let Some(hit) = hit {
+                max_distance = hit.distance();
+                nearest = Some((*entity, hit));
+            }
+        }
+        nearest
+    }
+
+    /// Sweeps the sphere against the geometry of the entity, which is chosen as in the narrowphase
+    /// of a raycast.
+    fn sphere_sweep(
+        &self,
+        entity: Entity,
+        ray: Ray3d,
+        radius: f32,
+        transform_blend: Option<f32>,
+        max_distance: f32,
+    ) -> Option<IntersectionData> {
+        let model_matrix = |transform: &GlobalTransform| {
+            model_matrix(entity, transform, &self.transform_history, transform_blend)
+        };
+        let sweep_mesh = |mesh_handle: &Handle<Mesh>,
+                          simplified_mesh: Option<&SimplifiedMesh>,
+                          transform: &GlobalTransform| {
+            let mesh_handle = simplified_mesh.map_or(mesh_handle, |simplified| &simplified.mesh);
+            let mesh = self.meshes.get(mesh_handle)?;
+            validate_raycast_mesh(mesh).ok()?;
+            let triangles = simplified_mesh
+                .and_then(|simplified| simplified.triangle_range(mesh))
+                .unwrap_or(0..mesh_triangle_count(mesh));
+            let transform = model_matrix(transform);
+            sphere_mesh_sweep_over(mesh, triangles, &transform, ray, radius, max_distance)
+                .map(|hit| hit.with_source_mesh(mesh_handle.id(), simplified_mesh.is_some()))
+        };
+        if !self.aabb_target_query.contains(entity) {
+            if let Ok((mesh, simplified_mesh, _, transform)) = self.mesh_query.get(entity) {
+                return sweep_mesh(mesh.mesh_handle(), simplified_mesh, transform);
+            }
+            #[cfg(feature = "2d")]
+            if let Ok((mesh, simplified_mesh, transform)) = self.mesh2d_query.get(entity) {
+                return sweep_mesh(mesh.mesh_handle(), simplified_mesh, transform);
+            }
+        }
+
+        // AABB targets and dynamic meshes have no mesh asset, so their world-space triangles are
+        // swept instead.
+        let mut triangles = Vec::new();
+        self.world_triangles(entity, transform_blend, &mut triangles);
+        let mut max_distance = max_distance;
+        let mut nearest = None;
+        for triangle in triangles {
+            if let Some(hit) = sphere_triangle_sweep(ray, radius, triangle, max_distance) {
+                max_distance = hit.distance();
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, math::Vec3A, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn spheres_hit_what_rays_miss() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-2.0, -4.0]);
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            // Passes beside the planes, which span -1 to 1 on X and Y.
+            let ray = Ray3d::new(Vec3::new(1.25, 0.0, 0.0), Vec3::NEG_Z);
+            let missed = raycast.cast_ray(ray, &settings).is_empty();
+            (
+                missed,
+                raycast.cast_sphere(ray, 0.5, &settings),
+                raycast.cast_sphere(ray, 0.1, &settings),
+            )
+        });
+        let (missed, hit, too_small) = hits;
+        assert!(missed);
+        assert!(too_small.is_none());
+        let (entity, hit) = hit.unwrap();
+        assert_eq!(entity, planes[0]);
+        // The sphere touches the edge of the plane at X = 1, 0.25 from its path.
+        let travel = 2.0 - (0.5f32.powi(2) - 0.25f32.powi(2)).sqrt();
+        assert!((hit.distance() - travel).abs() < 1e-4);
+        assert!(hit.position().abs_diff_eq(Vec3::new(1.0, 0.0, -2.0), 1e-4));
+        let center = Vec3::new(1.25, 0.0, -travel);
+        let normal = (center - hit.position()).normalize();
+        assert!(hit.normal().abs_diff_eq(normal, 1e-4));
+    }
+
+    #[test]
+    fn spheres_touch_aabb_targets() {
+        let mut world = World::new();
+        spawn_planes(&mut world, &[]);
+        let target = world
+            .spawn((
+                RaycastAabbTarget,
+                Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+                GlobalTransform::from_xyz(0.0, 0.0, -5.0),
+                InheritedVisibility::VISIBLE,
+                ViewVisibility::default(),
+            ))
+            .id();
+        let hit = world.run_system_once(|mut raycast: Raycast| {
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            let ray = Ray3d::new(Vec3::new(0.5, 0.0, 0.0), Vec3::NEG_Z);
+            raycast.cast_sphere(ray, 0.5, &settings)
+        });
+        let (entity, hit) = hit.unwrap();
+        assert_eq!(entity, target);
+        // The sphere touches the front face of the box, at Z = -4.
+        assert!((hit.distance() - 3.5).abs() < 1e-4);
+        assert!(hit.position().abs_diff_eq(Vec3::new(0.5, 0.0, -4.0), 1e-4));
+    }
+
+    #[test]
+    fn sphere_triangle_sweeps() {
+        let triangle = [Vec3A::new(-1.0, -1.0, 0.0), Vec3A::X, Vec3A::Y];
+        let ray = Ray3d::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+
+        // Face contacts stop the sphere `radius` short of the plane.
+        let hit = sphere_triangle_sweep(ray, 1.0, triangle, f32::MAX).unwrap();
+        assert!((hit.distance() - 4.0).abs() < 1e-5);
+        assert!(hit.position().abs_diff_eq(Vec3::ZERO, 1e-5));
+        assert!(hit.normal().abs_diff_eq(Vec3::Z, 1e-5));
+        assert!(sphere_triangle_sweep(ray, 1.0, triangle, 3.0).is_none());
+
+        // Spheres moving away from the triangle, or past it, miss it.
+        let away = Ray3d::new(Vec3::new(0.0, 0.0, 5.0), Vec3::Z);
+        assert!(sphere_triangle_sweep(away, 1.0, triangle, f32::MAX).is_none());
+        let past = Ray3d::new(Vec3::new(3.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert!(sphere_triangle_sweep(past, 1.0, triangle, f32::MAX).is_none());
+
+        // Vertex contacts, and spheres that start out touching.
+        let corner = Ray3d::new(Vec3::new(1.5, 0.0, 5.0), Vec3::NEG_Z);
+        let hit = sphere_triangle_sweep(corner, 1.0, triangle, f32::MAX).unwrap();
+        assert!(hit.position().abs_diff_eq(Vec3::X, 1e-5));
+        assert!((hit.distance() - (5.0 - 0.75f32.sqrt())).abs() < 1e-4);
+        let touching = Ray3d::new(Vec3::new(0.0, 0.0, 0.5), Vec3::NEG_Z);
+        let hit = sphere_triangle_sweep(touching, 1.0, triangle, f32::MAX).unwrap();
+        assert_eq!(hit.distance(), 0.0);
+    }
+}
@@ -0,0 +1,286 @@
+//! # Skinned Meshes
+//!
+//! The [`Mesh`] asset of a skinned mesh holds its bind pose, so raycasting it directly hits the
+//! mesh where it would be before animation. Add a [`RaycastSkinnedMesh`] to an entity with a
+//! [`SkinnedMesh`] to raycast its posed geometry instead, and add the [`SkinnedRaycastPlugin`].
+//!
+//! Each frame, after transforms are propagated, the plugin skins the vertices of every
+//! [`RaycastSkinnedMesh`] on the CPU, using the joint index and weight attributes of its mesh, and
+//! stores the result in a [`DynamicRaycastMesh`] on the same entity. The bind pose mesh of these
+//! entities is ignored by raycasts. Skinning is done for every vertex, every frame, so only opt in
+//! the meshes that need to be raycast, and prefer low-poly meshes.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! fn make_characters_raycastable(
+//!     mut commands: Commands,
+//!     skinned: Query<Entity, Added<bevy::render::mesh::skinning::SkinnedMesh>>,
+//! ) {
+//!     for entity in &skinned {
+//!         commands.entity(entity).insert(RaycastSkinnedMesh);
+//!     }
+//! }
+//!
+//! App::new()
+//!     .add_plugins(SkinnedRaycastPlugin)
+//!     .add_systems(Update, make_characters_raycastable);
+//! ```
+
+use bevy_app::prelude::*;
+use bevy_asset::Assets;
+use bevy_ecs::prelude::*;
+use bevy_math::{Mat3, Mat4, Vec3};
+use bevy_reflect::Reflect;
+use bevy_render::mesh::{
+    skinning::{SkinnedMesh, SkinnedMeshInverseBindposes},
+    Indices, Mesh, VertexAttributeValues,
+};
+use bevy_transform::{components::GlobalTransform, TransformSystem};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+use crate::{
+    bounds::MeshHandleComponent, dynamic_mesh::DynamicRaycastMesh, immediate::Mesh3dComponent,
+    raycast::validate_raycast_mesh,
+};
+
+/// Keeps the [`DynamicRaycastMesh`] of every [`RaycastSkinnedMesh`] up to date with its pose. See
+/// the [module docs](self).
+#[derive(Default)]
+pub struct SkinnedRaycastPlugin;
+impl Plugin for SkinnedRaycastPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RaycastSkinnedMesh>().add_systems(
+            PostUpdate,
+            update_skinned_raycast_meshes.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Marks a [`SkinnedMesh`] to be raycast in its posed geometry, instead of its bind pose. See the
+/// [module docs](self).
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+pub struct RaycastSkinnedMesh;
+
+/// Skins the mesh of every [`RaycastSkinnedMesh`] into its [`DynamicRaycastMesh`], in model space.
+///
+/// Entities with a mesh that is not loaded, or that has no joint attributes, are left unchanged.
+pub fn update_skinned_raycast_meshes(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    inverse_bindposes: Option<Res<Assets<SkinnedMeshInverseBindposes>>>,
+    mut skinned: Query<
+        (
+            Entity,
+            &Mesh3dComponent,
+            &SkinnedMesh,
+            &GlobalTransform,
+            Option<&mut DynamicRaycastMesh>,
+        ),
+        With<RaycastSkinnedMesh>,
+    >,
+    joints: Query<&GlobalTransform>,
+) {
+    let Some(inverse_bindposes) = inverse_bindposes else {
+        return;
+    };
+    let mut joint_matrices = Vec::new();
+    for (entity, handle, skinned_mesh, transform, dynamic_mesh) in &mut skinned {
+        let Some(mesh) = meshes.get(handle.mesh_handle()) else {
+            continue;
+        };
+        let Some(bindposes) = inverse_bindposes.get(&skinned_mesh.inverse_bindposes) else {
+            continue;
+        };
+        // Skinned vertices are in world space, so they are moved back into the model space of the
+        // entity, which is where raycasts expect the dynamic mesh to be.
+        let world_to_model = transform.compute_matrix().inverse();
+        joint_matrices.clear();
+        joint_matrices.extend(skinned_mesh.joints.iter().zip(bindposes.iter()).map(
+            |(joint, bindpose)| {
+                let joint = joints.get(*joint).copied().unwrap_or_default();
+                world_to_model * joint.compute_matrix() * *bindpose
+            },
+        ));
+        let Some((positions, normals)) = skin_mesh(mesh, &joint_matrices) else {
+            continue;
+        };
+        let indices = mesh.indices().map(|indices| match indices {
+            Indices::U16(indices) => indices.iter().map(|i| *i as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        });
+
+        match dynamic_mesh {
+            Some(mut dynamic_mesh) => {
+                dynamic_mesh.set_geometry(positions, indices);
+                dynamic_mesh.set_normals(normals);
+            }
+            None => {
+                let mut dynamic_mesh = DynamicRaycastMesh::new(positions, indices);
+                dynamic_mesh.set_normals(normals);
+                commands.entity(entity).insert(dynamic_mesh);
+            }
+        }
+    }
+}
+
+/// Skins the positions and normals of `mesh` with the model-space `joint_matrices`. Returns `None`
+/// if the mesh can't be raycast, or has no joint attributes.
+fn skin_mesh(
+    mesh: &Mesh,
+    joint_matrices: &[Mat4],
+) -> Option<(Vec<[f32; 3]>, Option<Vec<[f32; 3]>>)> {
+    validate_raycast_mesh(mesh).ok()?;
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let Some(VertexAttributeValues::Uint16x4(joint_indices)) =
+        mesh.attribute(Mesh::ATTRIBUTE_JOINT_INDEX)
+    else {
+        return None;
+    };
+    let Some(VertexAttributeValues::Float32x4(joint_weights)) =
+        mesh.attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT)
+    else {
+        return None;
+    };
+    if joint_indices.len() != positions.len() || joint_weights.len() != positions.len() {
+        return None;
+    }
+
+    let skin = |vertex: usize| {
+        joint_indices[vertex]
+            .iter()
+            .zip(joint_weights[vertex])
+            .filter_map(|(joint, weight)| Some(*joint_matrices.get(*joint as usize)? * weight))
+            .fold(Mat4::ZERO, |skin, weighted| skin + weighted)
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float32x3(normals)) if normals.len() == positions.len() => {
+            Some(normals)
+        }
+        _ => None,
+    };
+
+    let mut skinned_positions = Vec::with_capacity(positions.len());
+    let mut skinned_normals = normals.map(|normals| Vec::with_capacity(normals.len()));
+    for (vertex, position) in positions.iter().enumerate() {
+        let skin = skin(vertex);
+        skinned_positions.push(skin.transform_point3(Vec3::from(*position)).to_array());
+        if let (Some(normals), Some(skinned_normals)) = (normals, &mut skinned_normals) {
+            // Normals are transformed by the inverse transpose, to stay perpendicular to the
+            // surface under non-uniform scale.
+            let normal_matrix = Mat3::from_mat4(skin).inverse().transpose();
+            let normal = (normal_matrix * Vec3::from(normals[vertex])).normalize_or_zero();
+            skinned_normals.push(normal.to_array());
+        }
+    }
+    Some((skinned_positions, skinned_normals))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn skinned_meshes_are_raycast_in_their_pose() {
+        let mut world = World::new();
+        let plane = spawn_planes(&mut world, &[0.0])[0];
+        let handle = world.get::<Handle<Mesh>>(plane).unwrap().clone();
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let mesh = meshes.get_mut(&handle).unwrap();
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_JOINT_INDEX,
+            VertexAttributeValues::Uint16x4(vec![[0; 4]; 4]),
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, vec![[1.0, 0.0, 0.0, 0.0]; 4]);
+
+        let mut bindposes = Assets::<SkinnedMeshInverseBindposes>::default();
+        let inverse_bindposes = bindposes.add(vec![Mat4::IDENTITY]);
+        world.insert_resource(bindposes);
+        // The joint moves the whole plane down to `z = -3`.
+        let joint = world
+            .spawn(GlobalTransform::from_translation(Vec3::Z * -3.0))
+            .id();
+        world.entity_mut(plane).insert((
+            SkinnedMesh {
+                inverse_bindposes,
+                joints: vec![joint],
+            },
+            RaycastSkinnedMesh,
+        ));
+        world.run_system_once(update_skinned_raycast_meshes);
+
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::Z, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast
+                .cast_ray(ray, &settings)
+                .iter()
+                .map(|(_, hit)| hit.distance())
+                .collect::<Vec<_>>()
+        });
+        // Only the posed plane is hit, not the bind pose at `z = 0`.
+        assert_eq!(hits, vec![4.0]);
+    }
+
+    #[test]
+    fn bounding_spheres_of_skinned_meshes_are_ignored() {
+        let mut world = World::new();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        let plane = spawn_planes(&mut world, &[0.0])[0];
+        let handle = world.get::<Handle<Mesh>>(plane).unwrap().clone();
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let mesh = meshes.get_mut(&handle).unwrap();
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_JOINT_INDEX,
+            VertexAttributeValues::Uint16x4(vec![[0; 4]; 4]),
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, vec![[1.0, 0.0, 0.0, 0.0]; 4]);
+
+        let mut bindposes = Assets::<SkinnedMeshInverseBindposes>::default();
+        let inverse_bindposes = bindposes.add(vec![Mat4::IDENTITY]);
+        world.insert_resource(bindposes);
+        // The joint moves the plane far outside the sphere around its bind pose.
+        let joint = world
+            .spawn(GlobalTransform::from_translation(Vec3::new(
+                10.0, 0.0, -3.0,
+            )))
+            .id();
+        world.entity_mut(plane).insert((
+            SkinnedMesh {
+                inverse_bindposes,
+                joints: vec![joint],
+            },
+            RaycastSkinnedMesh,
+        ));
+        world.run_system_once(update_bounding_spheres::<Handle<Mesh>>);
+        assert!(world.get::<BoundingSphere>(plane).is_some());
+        world.run_system_once(update_skinned_raycast_meshes);
+
+        let cast = |world: &mut World| {
+            world.run_system_once(|mut raycast: Raycast| {
+                let ray = Ray3d::new(Vec3::new(10.0, 0.0, 1.0), Vec3::NEG_Z);
+                let settings =
+                    RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+                raycast
+                    .cast_ray(ray, &settings)
+                    .iter()
+                    .map(|(_, hit)| hit.distance())
+                    .collect::<Vec<_>>()
+            })
+        };
+        // The stale sphere around the bind pose doesn't reject the posed plane.
+        assert_eq!(cast(&mut world), vec![4.0]);
+
+        world.run_system_once(update_bounding_spheres::<Handle<Mesh>>);
+        assert!(world.get::<BoundingSphere>(plane).is_none());
+        assert_eq!(cast(&mut world), vec![4.0]);
+    }
+}
@@ -0,0 +1,226 @@
+//! # Hit Smoothing
+//!
+//! Input devices such as webcam trackers and touch screens report a slightly different position
+//! every frame even when held still, which makes anything placed at the hit under the cursor shake.
+//!
+//! A [`HitSmoother`] averages the position and normal of the last few hits it is given, and snaps
+//! to a new hit when it jumps further than [`HitSmoother::snap_distance`], so moving onto another
+//! surface isn't smeared across frames. The [`CursorHitSmoothingPlugin`] uses one to smooth the
+//! [`CursorHit`] into the [`SmoothedCursorHit`] resource, without raycasting the cursor again.
+
+use std::collections::VecDeque;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_math::Vec3;
+
+use crate::prelude::*;
+
+/// Smooths the [`CursorHit`] into the [`SmoothedCursorHit`] every frame.
+///
+/// Requires the [`CursorRayPlugin`]. Adds the [`CursorHitPlugin`] if it hasn't been added yet, and
+/// the hit under the cursor is found with its [`CursorHitSettings`].
+#[derive(Default)]
+pub struct CursorHitSmoothingPlugin;
+impl Plugin for CursorHitSmoothingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorHitSmoothingSettings>()
+            .init_resource::<SmoothedCursorHit>()
+            .add_systems(First, update_smoothed_cursor_hit.after(update_cursor_hit));
+
+        if !app.is_plugin_added::<CursorHitPlugin>() {
+            app.add_plugins(CursorHitPlugin);
+        }
+    }
+}
+
+/// Configures the [`CursorHitSmoothingPlugin`].
+#[derive(Resource, Clone, Debug)]
+pub struct CursorHitSmoothingSettings {
+    /// How many frames of hits are averaged. Higher values are smoother, but lag further behind
+    /// the cursor.
+    pub frames: usize,
+    /// Hits further than this from the smoothed position reset the smoothing. See
+    /// [`HitSmoother::snap_distance`].
+    pub snap_distance: f32,
+}
+
+impl Default for CursorHitSmoothingSettings {
+    fn default() -> Self {
+        Self {
+            frames: 4,
+            snap_distance: f32::INFINITY,
+        }
+    }
+}
+
+/// A moving average of the positions and normals of recent hits. See the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct HitSmoother {
+    /// How many of the most recent hits are averaged.
+    pub frames: usize,
+    /// When a hit is further than this from the smoothed position, the older hits are discarded,
+    /// so the smoothed hit snaps to it.
+    pub snap_distance: f32,
+    samples: VecDeque<(Vec3, Vec3)>,
+}
+
+impl Default for HitSmoother {
+    fn default() -> Self {
+        Self::new(CursorHitSmoothingSettings::default().frames)
+    }
+}
+
+impl HitSmoother {
+    /// Creates a smoother that averages the last `frames` hits, and never snaps.
+    pub fn new(frames: usize) -> Self {
+        Self {
+            frames,
+            snap_distance: f32::INFINITY,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Set the `snap_distance` field of this smoother.
+    pub fn with_snap_distance(self, snap_distance: f32) -> Self {
+        Self {
+            snap_distance,
+            ..self
+        }
+    }
+
+    /// Adds the next hit, and returns the smoothed position and normal.
+    pub fn push(&mut self, position: Vec3, normal: Vec3) -> (Vec3, Vec3) {
+        if self
+            .smoothed()
+            .is_some_and(|(smoothed, _)| smoothed.distance(position) > self.snap_distance)
+        {
+            self.reset();
+        }
+        self.samples.push_back((position, normal));
+        while self.samples.len() > self.frames.max(1) {
+            self.samples.pop_front();
+        }
+        self.smoothed().unwrap_or((position, normal))
+    }
+
+    /// The smoothed position and normal, or `None` if there are no hits to smooth.
+    pub fn smoothed(&self) -> Option<(Vec3, Vec3)> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let (position, normal) = self
+            .samples
+            .iter()
+            .fold((Vec3::ZERO, Vec3::ZERO), |(p, n), (position, normal)| {
+                (p + *position, n + normal.normalize_or_zero())
+            });
+        let latest_normal = self.samples.back().map(|(_, normal)| *normal);
+        Some((
+            position / self.samples.len() as f32,
+            normal.try_normalize().or(latest_normal).unwrap_or_default(),
+        ))
+    }
+
+    /// Discards all hits, so the next hit is not smoothed.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// The smoothed [`CursorHit`], updated by the [`CursorHitSmoothingPlugin`].
+#[derive(Resource, Default, Debug)]
+pub struct SmoothedCursorHit {
+    hit: Option<SmoothedHit>,
+    smoother: HitSmoother,
+}
+
+/// A hit smoothed over several frames.
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothedHit {
+    /// The entity of the latest hit.
+    pub entity: Entity,
+    /// The smoothed position.
+    pub position: Vec3,
+    /// The smoothed normal.
+    pub normal: Vec3,
+    /// The latest hit, before smoothing.
+    pub raw: IntersectionData,
+}
+
+impl SmoothedCursorHit {
+    /// The smoothed hit, or `None` if nothing is under the cursor.
+    pub fn hit(&self) -> Option<&SmoothedHit> {
+        self.hit.as_ref()
+    }
+}
+
+/// Smooths the [`CursorHit`] into the [`SmoothedCursorHit`].
+pub fn update_smoothed_cursor_hit(
+    settings: Res<CursorHitSmoothingSettings>,
+    cursor_hit: Res<CursorHit>,
+    mut smoothed: ResMut<SmoothedCursorHit>,
+) {
+    let SmoothedCursorHit { hit, smoother } = smoothed.as_mut();
+    smoother.frames = settings.frames;
+    smoother.snap_distance = settings.snap_distance;
+    *hit = match **cursor_hit {
+        Some((entity, raw)) => {
+            let (position, normal) = smoother.push(raw.position(), raw.normal());
+            Some(SmoothedHit {
+                entity,
+                position,
+                normal,
+                raw,
+            })
+        }
+        None => {
+            smoother.reset();
+            None
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+
+    #[test]
+    fn averages_recent_hits_and_snaps() {
+        let mut smoother = HitSmoother::new(2).with_snap_distance(1.0);
+        let jitter = [Vec3::X * 0.1, Vec3::X * 0.3, Vec3::X * 0.5];
+        let smoothed = jitter.map(|position| smoother.push(position, Vec3::Y).0);
+        assert!(smoothed[1].abs_diff_eq(Vec3::X * 0.2, 1e-6));
+        assert!(smoothed[2].abs_diff_eq(Vec3::X * 0.4, 1e-6));
+
+        // A jump onto another surface snaps instead of being averaged with the old hits.
+        let (position, normal) = smoother.push(Vec3::Z * 5.0, Vec3::X);
+        assert_eq!((position, normal), (Vec3::Z * 5.0, Vec3::X));
+    }
+
+    #[test]
+    fn smooths_the_cursor_hit_without_raycasting_again() {
+        let mut app = App::new();
+        app.add_plugins(CursorHitSmoothingPlugin);
+        assert!(app.is_plugin_added::<CursorHitPlugin>());
+
+        let mut world = World::new();
+        world.init_resource::<CursorHitSmoothingSettings>();
+        world.init_resource::<SmoothedCursorHit>();
+        let entity = world.spawn_empty().id();
+        for x in [0.0, 1.0] {
+            let hit = IntersectionData::new(Vec3::X * x, Vec3::Z, Vec3::ZERO, 1.0, None, None);
+            world.insert_resource(CursorHit(Some((entity, hit))));
+            world.run_system_once(update_smoothed_cursor_hit);
+        }
+        let smoothed = *world.resource::<SmoothedCursorHit>().hit().unwrap();
+        assert_eq!(smoothed.entity, entity);
+        assert!(smoothed.position.abs_diff_eq(Vec3::X * 0.5, 1e-6));
+
+        world.insert_resource(CursorHit(None));
+        world.run_system_once(update_smoothed_cursor_hit);
+        assert!(world.resource::<SmoothedCursorHit>().hit().is_none());
+    }
+}
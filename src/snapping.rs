@@ -0,0 +1,162 @@
+//! # Hit Snapping
+//!
+//! Editor placement tools usually want a hit position to stick to nearby features of the hit
+//! triangle. [`snap_hit`] post-processes an [`IntersectionData`] using the world-space triangle the
+//! raycast returns when [`HitData::triangle`] is requested, snapping to the nearest vertex, the
+//! nearest point on an edge, or the center of the face, whichever is enabled and in range first.
+//! For hits cast without it, look up the triangle with [`Raycast::hit_triangle`] and set it with
+//! [`IntersectionData::with_triangle`] before snapping.
+
+use bevy_math::{Vec3, Vec3A};
+use bevy_reflect::Reflect;
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+use crate::primitives::IntersectionData;
+
+/// Which features of the hit triangle a position may snap to, and within what world-space radius.
+/// Features are tried in order: vertices, then edges, then the face center.
+#[derive(Clone, Copy, Debug, Default, Reflect)]
+pub struct SnapSettings {
+    /// Snap to a triangle vertex within this distance of the hit.
+    pub vertex_radius: Option<f32>,
+    /// Snap to the nearest point on a triangle edge within this distance of the hit.
+    pub edge_radius: Option<f32>,
+    /// Snap to the triangle's centroid within this distance of the hit.
+    pub face_center_radius: Option<f32>,
+}
+
+impl SnapSettings {
+    /// Set the vertex snapping radius.
+    pub fn with_vertex_radius(self, radius: f32) -> Self {
+        Self {
+            vertex_radius: Some(radius),
+            ..self
+        }
+    }
+
+    /// Set the edge snapping radius.
+    pub fn with_edge_radius(self, radius: f32) -> Self {
+        Self {
+            edge_radius: Some(radius),
+            ..self
+        }
+    }
+
+    /// Set the face center snapping radius.
+    pub fn with_face_center_radius(self, radius: f32) -> Self {
+        Self {
+            face_center_radius: Some(radius),
+            ..self
+        }
+    }
+}
+
+/// The feature of the hit triangle a position was snapped to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum SnapTarget {
+    /// Snapped to vertex `i` of [`IntersectionData::triangle`].
+    Vertex(usize),
+    /// Snapped to edge `i`, connecting vertices `i` and `(i + 1) % 3`.
+    Edge(usize),
+    /// Snapped to the centroid of the triangle.
+    FaceCenter,
+    /// No feature was in range, the position is the unmodified hit position.
+    None,
+}
+
+/// A hit position after snapping.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct SnappedPosition {
+    pub position: Vec3,
+    pub target: SnapTarget,
+}
+
+/// Snaps the hit position to a feature of the hit triangle according to `settings`. Hits without
+/// triangle data are returned unsnapped.
+pub fn snap_hit(hit: &IntersectionData, settings: &SnapSettings) -> SnappedPosition {
+    let unsnapped = SnappedPosition {
+        position: hit.position(),
+        target: SnapTarget::None,
+    };
+    let Some(triangle) = hit.triangle() else {
+        return unsnapped;
+    };
+    let position = Vec3A::from(hit.position());
+    let nearest = |candidates: [Vec3A; 3]| {
+        candidates
+            .into_iter()
+            .map(|candidate| candidate.distance(position))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, distance)| (i, candidates[i], distance))
+    };
+
+    if let Some(radius) = settings.vertex_radius {
+        if let Some((i, vertex, _)) = nearest(triangle).filter(|(.., d)| *d <= radius) {
+            return SnappedPosition {
+                position: vertex.into(),
+                target: SnapTarget::Vertex(i),
+            };
+        }
+    }
+
+    if let Some(radius) = settings.edge_radius {
+        let edge_points = [0, 1, 2].map(|i| {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            let t = (position - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON);
+            a.lerp(b, t.clamp(0.0, 1.0))
+        });
+        if let Some((i, point, _)) = nearest(edge_points).filter(|(.., d)| *d <= radius) {
+            return SnappedPosition {
+                position: point.into(),
+                target: SnapTarget::Edge(i),
+            };
+        }
+    }
+
+    if let Some(radius) = settings.face_center_radius {
+        let center = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+        if center.distance(position) <= radius {
+            return SnappedPosition {
+                position: center.into(),
+                target: SnapTarget::FaceCenter,
+            };
+        }
+    }
+
+    unsnapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit_at(position: Vec3) -> IntersectionData {
+        let triangle = [Vec3A::ZERO, Vec3A::X, Vec3A::Y];
+        IntersectionData::new(position, Vec3::Z, Vec3::ZERO, 1.0, Some(triangle), Some(0))
+    }
+
+    #[test]
+    fn snaps_in_priority_order() {
+        let settings = SnapSettings::default()
+            .with_vertex_radius(0.1)
+            .with_edge_radius(0.1)
+            .with_face_center_radius(0.1);
+
+        let snapped = snap_hit(&hit_at(Vec3::new(0.95, 0.02, 0.0)), &settings);
+        assert_eq!(snapped.target, SnapTarget::Vertex(1));
+        assert_eq!(snapped.position, Vec3::X);
+
+        let snapped = snap_hit(&hit_at(Vec3::new(0.5, 0.05, 0.0)), &settings);
+        assert_eq!(snapped.target, SnapTarget::Edge(0));
+        assert_eq!(snapped.position, Vec3::new(0.5, 0.0, 0.0));
+
+        let snapped = snap_hit(&hit_at(Vec3::new(0.3, 0.3, 0.0)), &settings);
+        assert_eq!(snapped.target, SnapTarget::FaceCenter);
+
+        let snapped = snap_hit(&hit_at(Vec3::new(0.2, 0.5, 0.0)), &settings);
+        assert_eq!(snapped.target, SnapTarget::None);
+    }
+}
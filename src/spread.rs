@@ -0,0 +1,265 @@
+//! # Ray Spreads
+//!
+//! Shotguns, sprays, and area sensors cast many rays in a cone around an aim direction.
+//! [`ConeSpread`] generates these rays in a [`SpreadPattern`], and
+//! [`Raycast::cast_cone_rays`] casts all of them at once, culling the world against the cone a
+//! single time instead of once per ray:
+//!
+//! ```
+//! # use bevy_mod_raycast::prelude::*;
+//! # use bevy::prelude::*;
+//! fn fire_shotgun(mut raycast: Raycast, time: Res<Time>) {
+//!     let aim = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+//!     let spread = ConeSpread::new(8, 0.1).with_pattern(SpreadPattern::Gaussian { std_dev: 0.5 });
+//!     // Rotate the pattern every shot, so pellets don't always land in the same places.
+//!     let rays = spread.rays(aim, time.elapsed_seconds() * 7.0);
+//!     for pellet_hits in raycast.cast_cone_rays(aim, spread.half_angle, rays, &default()) {
+//!         if let Some((entity, hit)) = pellet_hits.first() {
+//!             info!("pellet hit {entity:?} at {}", hit.position());
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::f32::consts::{PI, TAU};
+
+use bevy_ecs::entity::Entity;
+use bevy_math::{Dir3, Ray3d, Vec2, Vec3A};
+use bevy_reflect::Reflect;
+#[cfg(feature = "serialize")]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize};
+
+use crate::{immediate::world_aabb, prelude::*};
+
+/// How the rays of a [`ConeSpread`] are distributed across the cone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub enum SpreadPattern {
+    /// Rays are spread evenly over the cone.
+    #[default]
+    Uniform,
+    /// Rays are evenly spaced around the edge of the cone.
+    Ring,
+    /// Rays are clustered around the middle of the cone. Their offsets from the middle are normally
+    /// distributed, with a standard deviation given as a fraction of the width of the cone. Rays
+    /// that would fall outside the cone are placed on its edge.
+    Gaussian {
+        /// The standard deviation, as a fraction of the width of the cone.
+        std_dev: f32,
+    },
+}
+
+/// Generates rays in a cone around a base ray. See the [module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    reflect(Serialize, Deserialize)
+)]
+pub struct ConeSpread {
+    /// The number of rays.
+    pub count: usize,
+    /// The angle between the base ray and the edge of the cone, in radians.
+    pub half_angle: f32,
+    /// How the rays are distributed across the cone.
+    pub pattern: SpreadPattern,
+}
+
+impl ConeSpread {
+    /// A spread of `count` rays in a cone that opens `half_angle` radians away from the base ray,
+    /// spread evenly.
+    pub fn new(count: usize, half_angle: f32) -> Self {
+        Self {
+            count,
+            half_angle,
+            pattern: SpreadPattern::Uniform,
+        }
+    }
+
+    /// Set the pattern the rays are distributed in.
+    pub fn with_pattern(mut self, pattern: SpreadPattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Maps two numbers in `[0, 1)` to a ray in the cone around `base`, starting at its origin.
+    /// Uniformly distributed inputs give rays distributed in the [`SpreadPattern`].
+    pub fn sample(&self, base: Ray3d, [u, v]: [f32; 2]) -> Ray3d {
+        let offset = match self.pattern {
+            SpreadPattern::Uniform => Vec2::from_angle(v * TAU) * u.sqrt(),
+            SpreadPattern::Ring => Vec2::from_angle(u * TAU),
+            SpreadPattern::Gaussian { std_dev } => {
+                // The Box-Muller transform, with `1 - u` to avoid the logarithm of zero.
+                let radius = (-2.0 * (1.0 - u).ln()).sqrt() * std_dev;
+                Vec2::from_angle(v * TAU) * radius.min(1.0)
+            }
+        };
+        self.ray_through(base, offset)
+    }
+
+    /// Returns the [`count`](Self::count) rays, spread evenly in the [`SpreadPattern`]. The same
+    /// spread always gives the same rays, rotated by `rotation` radians around the base ray.
+    pub fn rays(&self, base: Ray3d, rotation: f32) -> impl Iterator<Item = Ray3d> + '_ {
+        // The golden angle places each point of a sunflower spiral far from the previous ones.
+        let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+        let count = self.count as f32;
+        (0..self.count).map(move |i| {
+            let i = i as f32;
+            let offset = match self.pattern {
+                SpreadPattern::Uniform => {
+                    Vec2::from_angle(i * golden_angle + rotation) * ((i + 0.5) / count).sqrt()
+                }
+                SpreadPattern::Ring => Vec2::from_angle(i / count * TAU + rotation),
+                SpreadPattern::Gaussian { .. } => {
+                    return self.sample(
+                        base,
+                        [(i + 0.5) / count, (i * golden_angle + rotation) / TAU],
+                    )
+                }
+            };
+            self.ray_through(base, offset)
+        })
+    }
+
+    /// The ray through the point `offset` on the unit disk, which is mapped onto the cap of the
+    /// cone so equal areas of the disk cover equal solid angles.
+    fn ray_through(&self, base: Ray3d, offset: Vec2) -> Ray3d {
+        let cos_angle = 1.0 - offset.length_squared() * (1.0 - self.half_angle.cos());
+        let sin_angle = (1.0 - cos_angle * cos_angle).max(0.0).sqrt();
+        let (x, y) = base.direction.any_orthonormal_pair();
+        let sideways = (x * offset.x + y * offset.y).normalize_or_zero();
+        let direction = *base.direction * cos_angle + sideways * sin_angle;
+        Ray3d {
+            origin: base.origin,
+            direction: Dir3::new(direction).unwrap_or(base.direction),
+        }
+    }
+}
+
+/// Returns `true` if the sphere at `center` with `radius` touches the cone with its apex at the
+/// origin of `axis`, opening `half_angle` radians around it.
+fn sphere_intersects_cone(center: Vec3A, radius: f32, axis: Ray3d, half_angle: f32) -> bool {
+    let offset = center - Vec3A::from(axis.origin);
+    let along = offset.dot(Vec3A::from(*axis.direction));
+    let across = (offset.length_squared() - along * along).max(0.0).sqrt();
+    let (sin, cos) = half_angle.sin_cos();
+    // In the plane through the axis and the center, the nearest point of the cone is the apex
+    // for centers behind it, and otherwise on the edge of the cone.
+    let distance = if along * cos + across * sin < 0.0 {
+        offset.length()
+    } else {
+        across * cos - along * sin
+    };
+    distance <= radius
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Casts each of the `rays`, which must start at the origin of `base` and lie within
+    /// `half_angle` radians of it, such as the rays of a [`ConeSpread`]. Returns the hits of each
+    /// ray, in the order of the rays, as [`Raycast::cast_ray`] would.
+    ///
+    /// The world is culled against the cone once, and each ray is only cast against the entities
+    /// whose bounds touch the cone. See the [module docs](self).
+    pub fn cast_cone_rays(
+        &mut self,
+        base: Ray3d,
+        half_angle: f32,
+        rays: impl IntoIterator<Item = Ray3d>,
+        settings: &RaycastSettings,
+    ) -> Vec<Vec<(Entity, IntersectionData)>> {
+        let max_distance = settings.max_distance;
+        self.cull_with(settings, None, |bounds| {
+            let bounds = world_aabb(&bounds.aabb, &bounds.transform.model_to_world());
+            let radius = bounds.half_extents.length();
+            let in_range =
+                Vec3A::from(base.origin).distance(bounds.center) - radius <= max_distance;
+            (in_range && sphere_intersects_cone(bounds.center, radius, base, half_angle))
+                .then_some([0.0; 2])
+        });
+        self.contain_culled();
+
+        let candidates = std::mem::take(&mut *self.contained);
+        let hits = rays
+            .into_iter()
+            .map(|ray| {
+                self.cast_ray_on_candidates(ray, settings, candidates.iter().copied())
+                    .to_vec()
+            })
+            .collect();
+        *self.contained = candidates;
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn spread_rays_stay_in_the_cone() {
+        let base = Ray3d::new(Vec3::ONE, Vec3::new(1.0, 2.0, 3.0));
+        let angle = |ray: Ray3d| ray.direction.angle_between(*base.direction);
+        let patterns = [
+            SpreadPattern::Uniform,
+            SpreadPattern::Ring,
+            SpreadPattern::Gaussian { std_dev: 0.5 },
+        ];
+        for pattern in patterns {
+            let spread = ConeSpread::new(32, 0.2).with_pattern(pattern);
+            let rays: Vec<_> = spread.rays(base, 1.0).collect();
+            assert_eq!(rays.len(), 32);
+            assert!(rays.iter().all(|ray| ray.origin == base.origin));
+            assert!(rays.iter().all(|ray| angle(*ray) <= 0.2 + 1e-3));
+        }
+        let ring = ConeSpread::new(8, 0.2).with_pattern(SpreadPattern::Ring);
+        assert!(ring
+            .rays(base, 0.0)
+            .all(|ray| (angle(ray) - 0.2).abs() < 1e-3));
+        assert!(
+            ring.sample(base, [0.3, 0.0])
+                .direction
+                .angle_between(*base.direction)
+                > 0.19
+        );
+    }
+
+    #[test]
+    fn cone_rays_only_hit_entities_in_the_cone() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-2.0, -4.0]);
+        // Beside the cone, but in front of the second plane along the base ray.
+        world
+            .entity_mut(planes[1])
+            .insert(GlobalTransform::from_xyz(10.0, 0.0, -4.0));
+        let hits = world.run_system_once(|mut raycast: Raycast| {
+            let base = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let spread = ConeSpread::new(6, 0.2);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast.cast_cone_rays(base, spread.half_angle, spread.rays(base, 0.0), &settings)
+        });
+        assert_eq!(hits.len(), 6);
+        for pellet_hits in hits {
+            assert_eq!(pellet_hits.len(), 1);
+            assert_eq!(pellet_hits[0].0, planes[0]);
+        }
+        assert!(sphere_intersects_cone(
+            Vec3A::new(10.0, 0.0, -4.0),
+            9.5,
+            Ray3d::new(Vec3::ZERO, Vec3::NEG_Z),
+            0.2
+        ));
+        assert!(!sphere_intersects_cone(
+            Vec3A::new(0.0, 0.0, 4.0),
+            1.0,
+            Ray3d::new(Vec3::ZERO, Vec3::NEG_Z),
+            0.2
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+//! # Static Geometry
+//!
+//! Level geometry such as terrain, walls, and buildings never moves, but a regular raycast still
+//! culls and tests each of its entities separately on every cast. Games that mostly cast against
+//! this geometry, such as for line-of-sight checks, can instead mark it with
+//! [`StaticRaycastGeometry`], and add the [`StaticGeometryPlugin`].
+//!
+//! The plugin gathers the triangles of all static entities in world space, and builds a single
+//! [`TriangleBvh`] over them in the [`StaticGeometry`] resource. Rays cast with
+//! [`StaticGeometry::cast_ray`] only consider static geometry, and only test the triangles near
+//! the ray, no matter how many entities the level is made of:
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_mod_raycast::prelude::*;
+//! # #[derive(Component)]
+//! # struct Enemy;
+//! fn line_of_sight(
+//!     level: Res<StaticGeometry>,
+//!     player: Query<&GlobalTransform, With<Camera>>,
+//!     enemies: Query<(Entity, &GlobalTransform), With<Enemy>>,
+//! ) {
+//!     let Ok(player) = player.get_single() else { return };
+//!     for (enemy, transform) in &enemies {
+//!         let to_enemy = transform.translation() - player.translation();
+//!         let ray = Ray3d::new(player.translation(), to_enemy);
+//!         let settings = RaycastSettings::default().with_max_distance(to_enemy.length());
+//!         if level.cast_ray(ray, &settings).is_none() {
+//!             info!("{enemy:?} is in sight");
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The structure is rebuilt in [`First`] whenever a static entity is added, removed, moved, or
+//! changes its mesh, so static geometry should change rarely. Static entities are still raycast
+//! as usual by the [`Raycast`] system param.
+//!
+//! To find the nearest hit on static and dynamic entities alike, use
+//! [`Raycast::cast_ray_with_static_geometry`]. It casts against the static geometry first, and
+//! only raycasts the other entities in front of the static hit, so dynamic entities behind walls
+//! are culled without testing their meshes.
+
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, Assets};
+use bevy_ecs::prelude::*;
+use bevy_math::{Ray3d, Vec3, Vec3A};
+use bevy_render::mesh::{Mesh, VertexAttributeValues};
+use bevy_transform::components::GlobalTransform;
+use bevy_utils::{tracing::*, HashSet};
+
+use crate::{
+    bounds::MeshHandleComponent,
+    bvh::TriangleBvh,
+    immediate::{backfaces, Mesh3dComponent},
+    markers::{NoBackfaceCulling, SimplifiedMesh},
+    primitives::IntersectionData,
+    raycast::{
+        mesh_triangle_count, ray_triangle_intersection, triangle_vertex_indices,
+        validate_raycast_mesh, Backfaces,
+    },
+};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Maintains the [`StaticGeometry`] of entities marked with [`StaticRaycastGeometry`].
+#[derive(Default)]
+pub struct StaticGeometryPlugin;
+impl Plugin for StaticGeometryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StaticGeometry>()
+            .add_systems(First, update_static_geometry);
+    }
+}
+
+/// Marks a mesh entity as part of the level geometry that never moves. See the
+/// [module docs](self).
+#[derive(Component, Default)]
+pub struct StaticRaycastGeometry;
+
+/// The world-space triangles of every [`StaticRaycastGeometry`] entity, and a [`TriangleBvh`] over
+/// them. See the [module docs](self).
+#[derive(Resource, Default)]
+pub struct StaticGeometry {
+    triangles: Vec<[Vec3A; 3]>,
+    /// The index of the owner in `entities`, and the index of the triangle in its mesh, of each
+    /// triangle.
+    sources: Vec<(u32, u32)>,
+    /// Each entity, how it culls backfaces, and whether the winding of its triangles was reversed.
+    entities: Vec<(Entity, Backfaces, bool)>,
+    /// The entities whose triangles are in the structure.
+    included: HashSet<Entity>,
+    bvh: TriangleBvh,
+}
+
+impl StaticGeometry {
+    /// Casts the `ray` against the static geometry, and returns the nearest hit. Only the
+    /// [`filter`](RaycastSettings::filter) and [`max_distance`](RaycastSettings::max_distance) of
+    /// the `settings` are used. Visibility is ignored, since level geometry is usually visible.
+    ///
+    /// Hits report the normal of the hit triangle, as vertex normals are not kept.
+    pub fn cast_ray(
+        &self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+    ) -> Option<(Entity, IntersectionData)> {
+        let mut nearest = None;
+        self.bvh
+            .cast_ray(ray, settings.max_distance, |triangle, max_distance| {
+                let (owner, _) = self.sources[triangle];
+                let (entity, backfaces, _) = self.entities[owner as usize];
+                if !(settings.filter)(entity) {
+                    return None;
+                }
+                let hit = ray_triangle_intersection(&ray, &self.triangles[triangle], backfaces)?;
+                let distance = *hit.distance();
+                if !(0.0..=max_distance).contains(&distance) {
+                    return None;
+                }
+                nearest = Some((triangle, hit));
+                Some(distance)
+            });
+        let (triangle, hit) = nearest?;
+        let (owner, mesh_triangle) = self.sources[triangle];
+        let (entity, _, mirrored) = self.entities[owner as usize];
+        let [a, b, c] = self.triangles[triangle];
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        // Report the triangle in the winding of the mesh, so the barycentric coordinates match it.
+        let ([a, b, c], (u, v)) = match (mirrored, *hit.uv_coords()) {
+            (true, (u, v)) => ([a, c, b], (v, u)),
+            (false, uv) => ([a, b, c], uv),
+        };
+        let intersection = IntersectionData::new(
+            ray.get_point(*hit.distance()),
+            normal,
+            Vec3::new(u, v, 1.0 - u - v),
+            *hit.distance(),
+            Some([a, b, c]),
+            Some(mesh_triangle as usize),
+        );
+        Some((entity, intersection))
+    }
+
+    /// Returns `true` if the triangles of the `entity` are part of the static geometry. Static
+    /// entities whose mesh is not loaded yet are not.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.included.contains(&entity)
+    }
+
+    /// The number of triangles in the static geometry.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Rebuilds the structure from the static entities.
+    fn rebuild<'a>(
+        &mut self,
+        entities: impl Iterator<Item = StaticEntity<'a>>,
+        meshes: &Assets<Mesh>,
+    ) {
+        self.triangles.clear();
+        self.sources.clear();
+        self.entities.clear();
+        self.included.clear();
+        for (entity, mesh, simplified_mesh, culling, transform) in entities {
+            let handle = simplified_mesh.map_or(mesh.mesh_handle(), |simplified| &simplified.mesh);
+            let Some(mesh) = meshes.get(handle) else {
+                continue;
+            };
+            if let Err(error) = validate_raycast_mesh(mesh) {
+                warn!("Static geometry {entity} cannot be raycast: {error:?}");
+                continue;
+            }
+            let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+            else {
+                continue;
+            };
+            let backfaces = backfaces(culling);
+            let transform = transform.compute_matrix();
+            // Mirroring reverses the winding of triangles in world space, which would make backface
+            // culling cull front faces, so the winding is reversed back.
+            let mirrored = transform.determinant() < 0.0;
+            let owner = self.entities.len() as u32;
+            self.entities.push((entity, backfaces, mirrored));
+            self.included.insert(entity);
+            let triangles = simplified_mesh
+                .and_then(|simplified| simplified.triangle_range(mesh))
+                .unwrap_or(0..mesh_triangle_count(mesh));
+            for triangle in triangles {
+                let Some(vertices) =
+                    triangle_vertex_indices(mesh, triangle).and_then(|[a, b, c]| {
+                        Some([positions.get(a)?, positions.get(b)?, positions.get(c)?])
+                    })
+                else {
+                    continue;
+                };
+                let [a, b, c] =
+                    vertices.map(|position| transform.transform_point3a(Vec3A::from(*position)));
+                self.triangles
+                    .push(if mirrored { [a, c, b] } else { [a, b, c] });
+                self.sources.push((owner, triangle as u32));
+            }
+        }
+        self.bvh = TriangleBvh::new(&self.triangles);
+    }
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Casts the `ray` against the `static_geometry` and every other raycastable entity, and returns
+    /// the nearest hit across both. See the [module docs](self).
+    ///
+    /// The static geometry is raycast first, and its hit limits the
+    /// [`max_distance`](RaycastSettings::max_distance) of the raycast against the other entities.
+    /// Entities in the static geometry are skipped by that raycast, since they were already tested.
+    pub fn cast_ray_with_static_geometry(
+        &mut self,
+        ray: Ray3d,
+        static_geometry: &StaticGeometry,
+        settings: &RaycastSettings,
+    ) -> Option<(Entity, IntersectionData)> {
+        let static_hit = static_geometry.cast_ray(ray, settings);
+        let max_distance = static_hit.map_or(settings.max_distance, |(_, hit)| hit.distance());
+        let filter = |entity| !static_geometry.contains(entity) && (settings.filter)(entity);
+        let dynamic_settings = RaycastSettings {
+            filter: &filter,
+            max_distance,
+            ..settings.clone()
+        };
+        let dynamic_hit = self.cast_ray(ray, &dynamic_settings).first().copied();
+        dynamic_hit.or(static_hit)
+    }
+}
+
+type StaticEntity<'a> = (
+    Entity,
+    &'a Mesh3dComponent,
+    Option<&'a SimplifiedMesh>,
+    Option<&'a NoBackfaceCulling>,
+    &'a GlobalTransform,
+);
+
+/// Rebuilds the [`StaticGeometry`] when a static entity was added, removed, moved, or changed its
+/// mesh, or when one of their meshes was modified or removed.
+pub fn update_static_geometry(
+    mut geometry: ResMut<StaticGeometry>,
+    entities: Query<StaticEntity, With<StaticRaycastGeometry>>,
+    changed: Query<
+        (),
+        (
+            With<StaticRaycastGeometry>,
+            Or<(
+                Added<StaticRaycastGeometry>,
+                Changed<GlobalTransform>,
+                Changed<Mesh3dComponent>,
+                Changed<SimplifiedMesh>,
+            )>,
+        ),
+    >,
+    mut removed: RemovedComponents<StaticRaycastGeometry>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    let mut needs_rebuild = !changed.is_empty() || removed.read().count() > 0;
+    for event in mesh_events.read() {
+        if let AssetEvent::Added { id } | AssetEvent::Modified { id } | AssetEvent::Removed { id } =
+            event
+        {
+            needs_rebuild |= entities.iter().any(|(_, mesh, simplified_mesh, ..)| {
+                mesh.mesh_id() == *id || simplified_mesh.is_some_and(|s| s.mesh.id() == *id)
+            });
+        }
+    }
+    if needs_rebuild {
+        geometry.rebuild(entities.iter(), &meshes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn static_geometry_is_raycast_in_world_space() {
+        let mut world = World::new();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.init_resource::<StaticGeometry>();
+        let planes = spawn_planes(&mut world, &[-1.0, -3.0, -5.0]);
+        for plane in &planes[1..] {
+            world.entity_mut(*plane).insert(StaticRaycastGeometry);
+        }
+        world.run_system_once(update_static_geometry);
+
+        let geometry = world.resource::<StaticGeometry>();
+        assert_eq!(geometry.triangle_count(), 4);
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+        let (entity, hit) = geometry.cast_ray(ray, &default()).unwrap();
+        // The nearest plane is not static.
+        assert_eq!(
+            (entity, hit.distance(), hit.normal()),
+            (planes[1], 3.0, Vec3::Z)
+        );
+        let settings = RaycastSettings::default().with_max_distance(2.0);
+        assert!(geometry.cast_ray(ray, &settings).is_none());
+
+        // The nearest plane is dynamic and in front of the static geometry. When it is filtered
+        // out, the static hit is nearest.
+        let nearest = planes[0];
+        let hits =
+            world.run_system_once(move |mut raycast: Raycast, level: Res<StaticGeometry>| {
+                let settings =
+                    RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+                let filter = |entity| entity != nearest;
+                [settings.clone(), settings.with_filter(&filter)]
+                    .map(|settings| raycast.cast_ray_with_static_geometry(ray, &level, &settings))
+            });
+        let hits = hits.map(|hit| hit.map(|(entity, _)| entity));
+        assert_eq!(hits, [Some(planes[0]), Some(planes[1])]);
+
+        world
+            .entity_mut(planes[1])
+            .remove::<StaticRaycastGeometry>();
+        world.run_system_once(update_static_geometry);
+        let geometry = world.resource::<StaticGeometry>();
+        assert_eq!(geometry.cast_ray(ray, &default()).unwrap().0, planes[2]);
+    }
+
+    #[test]
+    fn removed_meshes_leave_the_static_geometry() {
+        let mut world = World::new();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.init_resource::<StaticGeometry>();
+        let plane = spawn_planes(&mut world, &[-1.0])[0];
+        world.entity_mut(plane).insert(StaticRaycastGeometry);
+        // The system is kept between runs, so only the removal of the mesh triggers a rebuild.
+        let update = world.register_system(update_static_geometry);
+        world.run_system(update).unwrap();
+        let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+        let geometry = world.resource::<StaticGeometry>();
+        assert_eq!(geometry.cast_ray(ray, &default()).unwrap().0, plane);
+
+        let id = world.get::<Handle<Mesh>>(plane).unwrap().id();
+        world.resource_mut::<Assets<Mesh>>().remove(id);
+        world.send_event(AssetEvent::<Mesh>::Removed { id });
+        world.run_system(update).unwrap();
+        let geometry = world.resource::<StaticGeometry>();
+        assert_eq!(geometry.triangle_count(), 0);
+        assert!(!geometry.contains(plane));
+        assert!(geometry.cast_ray(ray, &default()).is_none());
+    }
+}
@@ -0,0 +1,214 @@
+//! # Text Glyph Picking
+//!
+//! Raycasting against [`Text`] laid out in the world with a `Text2dBundle`, down to the individual
+//! glyph. Requires the `text` feature.
+//!
+//! Text is not rendered with a [`Mesh`](bevy_render::mesh::Mesh), so it is invisible to a
+//! [`Raycast`]. Instead, [`ray_glyph_intersection`] uses the [`TextLayoutInfo`] computed by
+//! `bevy_text` to place each glyph quad the same way the renderer does, and reports which glyph
+//! was hit along with its character index in the text. The text entity may be transformed freely,
+//! so this also works for text placed in a 3D scene.
+//!
+//! Use the [`TextRaycast`] system param to cast against every text entity at once.
+
+use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_math::{FloatOrd, Ray3d, Vec2, Vec3};
+use bevy_render::view::{InheritedVisibility, ViewVisibility};
+use bevy_sprite::Anchor;
+use bevy_text::{Text, TextLayoutInfo};
+use bevy_transform::components::GlobalTransform;
+use bevy_window::{PrimaryWindow, Window};
+
+use crate::{immediate::is_visible, prelude::*};
+
+/// The glyph of a text entity that was hit by a ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphHit {
+    /// The index of the glyph in [`TextLayoutInfo::glyphs`].
+    pub glyph_index: usize,
+    /// The index of the [`TextSection`](bevy_text::TextSection) the glyph belongs to.
+    pub section_index: usize,
+    /// The byte index of the glyph's character in the section's value.
+    pub byte_index: usize,
+    /// The character index of the glyph's character in the section's value.
+    pub char_index: usize,
+    /// The world-space position of the hit.
+    pub position: Vec3,
+    /// The distance from the ray origin to the hit.
+    pub distance: f32,
+}
+
+/// Checks if the ray hits a glyph of the text, returning the hit glyph. Glyph quads are placed the
+/// same way `bevy_text` renders a `Text2dBundle`, relative to the text's `transform` and
+/// `anchor`. `scale_factor` is the scale factor of the window the text was laid out for.
+///
+/// Glyph quads are tight around the glyph's ink, so rays passing between letters or through the
+/// space character do not hit anything.
+pub fn ray_glyph_intersection(
+    ray: Ray3d,
+    text: &Text,
+    layout: &TextLayoutInfo,
+    anchor: &Anchor,
+    transform: &GlobalTransform,
+    scale_factor: f32,
+) -> Option<GlyphHit> {
+    let alignment_translation = layout.logical_size * -(anchor.as_vec() + 0.5);
+    let text_to_world = transform.compute_matrix()
+        * GlobalTransform::from_translation(alignment_translation.extend(0.0)).compute_matrix()
+        * GlobalTransform::from_scale(Vec2::splat(scale_factor.recip()).extend(1.0))
+            .compute_matrix();
+    if is_degenerate_transform(&text_to_world) {
+        return None;
+    }
+    let world_to_text = text_to_world.inverse();
+
+    // The direction is not normalized, so distances along the local ray match world distances.
+    let origin = world_to_text.transform_point3(ray.origin);
+    let direction = world_to_text.transform_vector3(*ray.direction);
+    if direction.z.abs() <= f32::EPSILON {
+        return None;
+    }
+    let distance = -origin.z / direction.z;
+    if distance < 0.0 || !distance.is_finite() {
+        return None;
+    }
+    let local_hit = (origin + direction * distance).truncate();
+
+    let (glyph_index, glyph) = layout.glyphs.iter().enumerate().find(|(_, glyph)| {
+        let offset = (local_hit - glyph.position).abs();
+        offset.x <= glyph.size.x / 2.0 && offset.y <= glyph.size.y / 2.0
+    })?;
+    let char_index = text
+        .sections
+        .get(glyph.section_index)
+        .and_then(|section| section.value.get(..glyph.byte_index))
+        .map_or(0, |preceding| preceding.chars().count());
+
+    Some(GlyphHit {
+        glyph_index,
+        section_index: glyph.section_index,
+        byte_index: glyph.byte_index,
+        char_index,
+        position: ray.get_point(distance),
+        distance,
+    })
+}
+
+/// A [`SystemParam`] for raycasting against the glyphs of every text entity, similar to
+/// [`Raycast`]. See the [module docs](self).
+#[derive(SystemParam)]
+pub struct TextRaycast<'w, 's> {
+    #[doc(hidden)]
+    pub texts: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Text,
+            &'static TextLayoutInfo,
+            &'static Anchor,
+            &'static GlobalTransform,
+            &'static InheritedVisibility,
+            &'static ViewVisibility,
+        ),
+    >,
+    #[doc(hidden)]
+    pub windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    #[doc(hidden)]
+    pub hits: Local<'s, Vec<(Entity, GlyphHit)>>,
+}
+
+impl<'w, 's> TextRaycast<'w, 's> {
+    /// Casts the `ray` into the world and returns a sorted list of glyph hits, nearest first.
+    ///
+    /// The [`RaycastSettings`] are interpreted the same way as in [`Raycast::cast_ray`]. Settings
+    /// that only apply to meshes, such as [`RaycastSettings::max_triangles_per_cast`], are ignored.
+    pub fn cast_ray(&mut self, ray: Ray3d, settings: &RaycastSettings) -> &[(Entity, GlyphHit)] {
+        let scale_factor = self
+            .windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0);
+
+        self.hits.clear();
+        for (entity, text, layout, anchor, transform, inherited, view) in &self.texts {
+            if !is_visible(settings.visibility, inherited, view, false)
+                || !(settings.filter)(entity)
+            {
+                continue;
+            }
+            let Some(hit) =
+                ray_glyph_intersection(ray, text, layout, anchor, transform, scale_factor)
+            else {
+                continue;
+            };
+            if hit.distance <= settings.max_distance {
+                self.hits.push((entity, hit));
+            }
+        }
+        self.hits
+            .sort_by_key(|(_, hit)| FloatOrd(settings.sorting.key(ray, hit.position)));
+
+        if let Some(exit) = self
+            .hits
+            .iter()
+            .position(|(entity, _)| (settings.early_exit_test)(*entity))
+        {
+            self.hits.truncate(exit + 1);
+        }
+        self.hits.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_text::{GlyphAtlasInfo, PositionedGlyph};
+
+    use super::*;
+
+    #[test]
+    fn reports_hit_glyph_and_char_index() {
+        let text = Text::from_section("añb", Default::default());
+        let glyph = |x: f32, byte_index| PositionedGlyph {
+            position: Vec2::new(x, 5.0),
+            size: Vec2::splat(8.0),
+            atlas_info: GlyphAtlasInfo {
+                texture_atlas: Default::default(),
+                texture: Default::default(),
+                glyph_index: 0,
+            },
+            section_index: 0,
+            byte_index,
+        };
+        let layout = TextLayoutInfo {
+            glyphs: vec![glyph(5.0, 0), glyph(15.0, 1), glyph(25.0, 3)],
+            logical_size: Vec2::new(30.0, 10.0),
+        };
+        // Centered text spans -15..15 in x, so the third glyph is centered at x = 10.
+        let transform = GlobalTransform::from_translation(Vec3::new(0.0, 0.0, -5.0));
+        let ray = Ray3d::new(Vec3::new(10.0, 0.0, 5.0), Vec3::NEG_Z);
+
+        let hit =
+            ray_glyph_intersection(ray, &text, &layout, &Anchor::Center, &transform, 1.0).unwrap();
+        assert_eq!(hit.glyph_index, 2);
+        assert_eq!(hit.byte_index, 3);
+        assert_eq!(hit.char_index, 2);
+        assert_eq!(hit.distance, 10.0);
+
+        let between_glyphs = Ray3d::new(Vec3::new(-5.0, 0.0, 5.0), Vec3::NEG_Z);
+        let hit = ray_glyph_intersection(
+            between_glyphs,
+            &text,
+            &layout,
+            &Anchor::Center,
+            &transform,
+            1.0,
+        );
+        assert!(hit.is_none());
+
+        // Glyph positions are in physical pixels, so a scale factor of 2 halves their world size.
+        let ray = Ray3d::new(Vec3::new(-2.5, -2.5, 5.0), Vec3::NEG_Z);
+        let hit = ray_glyph_intersection(ray, &text, &layout, &Anchor::Center, &transform, 2.0);
+        assert_eq!(hit.map(|hit| hit.glyph_index), Some(2));
+    }
+}
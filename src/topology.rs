@@ -0,0 +1,381 @@
+//! # Mesh Topology
+//!
+//! Connectivity information for a [`Mesh`] that the raycast itself does not need, but that is
+//! useful when interpreting a hit, such as whether the hit triangle borders a hole in the mesh.
+//!
+//! Vertices are welded by position when building a [`MeshTopology`], so edges split only by UV or
+//! normal seams are still treated as shared.
+//!
+//! Building a topology walks the whole mesh, so it should not be done per query. Add the
+//! [`MeshTopologyPlugin`] and use the [`MeshTopologyCache`] resource to build topologies lazily and
+//! reuse them until the mesh asset changes. Meshes marked in [`VolatileMeshes`] are not cached.
+
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, AssetId, Assets};
+use bevy_ecs::prelude::*;
+use bevy_math::Vec3A;
+use bevy_render::{
+    mesh::{Mesh, VertexAttributeValues},
+    render_resource::PrimitiveTopology,
+};
+use bevy_utils::{HashMap, HashSet};
+
+use crate::{primitives::IntersectionData, volatility::VolatileMeshes};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Triangle adjacency for a [`Mesh`].
+///
+/// Triangles are identified by their [`IntersectionData::triangle_index`], and the edges of a
+/// triangle are numbered so that edge `i` connects its vertices `i` and `(i + 1) % 3`.
+#[derive(Clone, Debug, Default)]
+pub struct MeshTopology {
+    /// For each triangle, the indices of its vertices in the mesh's vertex buffer.
+    triangles: Vec<[u32; 3]>,
+    /// For each triangle, the triangle on the other side of each of its edges, if there is one.
+    neighbors: Vec<[Option<u32>; 3]>,
+    /// For each vertex, its welded vertex id.
+    welded_ids: Vec<u32>,
+    /// For each welded vertex id, the range of [`Self::vertex_triangle_list`] holding the triangles
+    /// that touch it.
+    vertex_triangle_ranges: Vec<std::ops::Range<u32>>,
+    vertex_triangle_list: Vec<u32>,
+}
+
+impl MeshTopology {
+    /// Builds the topology of `mesh`. Returns `None` if the mesh is not a
+    /// [`PrimitiveTopology::TriangleList`] or has no `Float32x3` vertex positions.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+
+        // Weld vertices that share a position, so seams in other attributes don't become holes.
+        let mut welded = HashMap::<[u32; 3], u32>::default();
+        let welded_ids: Vec<u32> = positions
+            .iter()
+            .map(|p| {
+                let next_id = welded.len() as u32;
+                *welded.entry(p.map(f32::to_bits)).or_insert(next_id)
+            })
+            .collect();
+
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [t[0] as u32, t[1] as u32, t[2] as u32])
+            .collect();
+        let welded_triangle = |t: &[u32; 3]| t.map(|v| welded_ids[v as usize]);
+
+        let mut edges = HashMap::<(u32, u32), Vec<(u32, usize)>>::default();
+        for (triangle, vertices) in triangles.iter().map(welded_triangle).enumerate() {
+            for edge in 0..3 {
+                let (a, b) = (vertices[edge], vertices[(edge + 1) % 3]);
+                edges
+                    .entry((a.min(b), a.max(b)))
+                    .or_default()
+                    .push((triangle as u32, edge));
+            }
+        }
+
+        let mut neighbors = vec![[None; 3]; triangles.len()];
+        for sharing in edges.values() {
+            // Edges shared by more than two triangles are non-manifold; link only the first pair.
+            if let [(tri_a, edge_a), (tri_b, edge_b), ..] = sharing.as_slice() {
+                neighbors[*tri_a as usize][*edge_a] = Some(*tri_b);
+                neighbors[*tri_b as usize][*edge_b] = Some(*tri_a);
+            }
+        }
+
+        let mut vertex_triangles = vec![Vec::new(); welded.len()];
+        for (triangle, vertices) in triangles.iter().map(welded_triangle).enumerate() {
+            for vertex in vertices {
+                vertex_triangles[vertex as usize].push(triangle as u32);
+            }
+        }
+        let mut vertex_triangle_ranges = Vec::with_capacity(vertex_triangles.len());
+        let mut vertex_triangle_list = Vec::with_capacity(triangles.len() * 3);
+        for mut adjacent in vertex_triangles {
+            adjacent.dedup();
+            let start = vertex_triangle_list.len() as u32;
+            vertex_triangle_list.extend(adjacent);
+            vertex_triangle_ranges.push(start..vertex_triangle_list.len() as u32);
+        }
+
+        Some(Self {
+            triangles,
+            neighbors,
+            welded_ids,
+            vertex_triangle_ranges,
+            vertex_triangle_list,
+        })
+    }
+
+    /// The number of triangles in the mesh.
+    pub fn triangle_count(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    /// The indices of the vertices of `triangle` in the mesh's vertex buffer.
+    pub fn triangle_vertices(&self, triangle: usize) -> Option<[u32; 3]> {
+        self.triangles.get(triangle).copied()
+    }
+
+    /// The triangles that touch `vertex`, including triangles that only share its position.
+    pub fn vertex_triangles(&self, vertex: usize) -> &[u32] {
+        self.welded_ids
+            .get(vertex)
+            .and_then(|id| self.vertex_triangle_ranges.get(*id as usize))
+            .map(|range| &self.vertex_triangle_list[range.start as usize..range.end as usize])
+            .unwrap_or_default()
+    }
+
+    /// The triangles on the other side of each edge of `triangle`, or `None` for boundary edges.
+    pub fn neighbors(&self, triangle: usize) -> Option<[Option<u32>; 3]> {
+        self.neighbors.get(triangle).copied()
+    }
+
+    /// Which edges of `triangle` are boundary edges, i.e. have no adjacent triangle.
+    pub fn boundary_edges(&self, triangle: usize) -> [bool; 3] {
+        self.neighbors(triangle)
+            .map(|n| n.map(|n| n.is_none()))
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if any edge of `triangle` is a boundary edge.
+    pub fn is_boundary_triangle(&self, triangle: usize) -> bool {
+        self.boundary_edges(triangle).contains(&true)
+    }
+
+    /// Finds the boundary edge of the hit triangle nearest to the hit position, returning the edge
+    /// number and its world-space distance from the hit. Returns `None` if the hit triangle has no
+    /// boundary edges, or if the hit is missing triangle data.
+    pub fn nearest_boundary_edge(&self, hit: &IntersectionData) -> Option<(usize, f32)> {
+        let triangle = hit.triangle()?;
+        let position = Vec3A::from(hit.position());
+        self.boundary_edges(hit.triangle_index()?)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, is_boundary)| *is_boundary)
+            .map(|(edge, _)| {
+                let (a, b) = (triangle[edge], triangle[(edge + 1) % 3]);
+                let t = (position - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON);
+                (edge, position.distance(a.lerp(b, t.clamp(0.0, 1.0))))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    /// Finds the triangles whose winding is reversed compared to the triangles around them, and
+    /// returns their indices. Neighboring triangles are wound consistently when they traverse their
+    /// shared edge in opposite directions.
+    ///
+    /// Raycasts cull the backfaces of triangles by their winding, so rays pass through reversed
+    /// triangles from the outside, and hit them from the inside. In each connected part of the mesh,
+    /// the winding shared by most triangles is assumed to be correct, and the others are reported.
+    /// Check meshes when they are loaded, and fix them with
+    /// [`consistently_wound_indices`](Self::consistently_wound_indices), or add
+    /// [`NoBackfaceCulling`] to entities using them.
+    pub fn find_inconsistent_winding(&self) -> Vec<usize> {
+        let mut flipped: Vec<Option<bool>> = vec![None; self.triangles.len()];
+        let mut inconsistent = Vec::new();
+        let mut part = Vec::new();
+        for seed in 0..self.triangles.len() {
+            if flipped[seed].is_some() {
+                continue;
+            }
+            flipped[seed] = Some(false);
+            part.clear();
+            part.push(seed);
+            let mut next = 0;
+            while let Some(&triangle) = part.get(next) {
+                next += 1;
+                let triangle_flipped = flipped[triangle] == Some(true);
+                for (edge, neighbor) in self.neighbors[triangle].iter().enumerate() {
+                    let Some(neighbor) = neighbor.map(|n| n as usize) else {
+                        continue;
+                    };
+                    if flipped[neighbor].is_some() {
+                        continue;
+                    }
+                    let same_direction = self.shares_edge_direction(triangle, edge, neighbor);
+                    flipped[neighbor] = Some(triangle_flipped != same_direction);
+                    part.push(neighbor);
+                }
+            }
+            let flipped_count = part.iter().filter(|t| flipped[**t] == Some(true)).count();
+            // Report the smaller set of triangles, keeping the winding of the seed on ties.
+            let report_flipped = flipped_count * 2 <= part.len();
+            inconsistent.extend(
+                part.iter()
+                    .filter(|t| (flipped[**t] == Some(true)) == report_flipped),
+            );
+        }
+        inconsistent.sort_unstable();
+        inconsistent
+    }
+
+    /// Builds an index buffer for the mesh in which the triangles found by
+    /// [`find_inconsistent_winding`](Self::find_inconsistent_winding) are reversed, so all
+    /// triangles are wound consistently. Insert it into the mesh with
+    /// [`Mesh::insert_indices`] and [`Indices::U32`](bevy_render::mesh::Indices::U32).
+    pub fn consistently_wound_indices(&self) -> Vec<u32> {
+        let mut triangles = self.triangles.clone();
+        for triangle in self.find_inconsistent_winding() {
+            triangles[triangle].swap(1, 2);
+        }
+        triangles.into_iter().flatten().collect()
+    }
+
+    /// Returns `true` if the `neighbor` traverses the `edge` of the `triangle` in the same direction,
+    /// meaning one of them is wound the other way.
+    fn shares_edge_direction(&self, triangle: usize, edge: usize, neighbor: usize) -> bool {
+        let welded = |t: usize| self.triangles[t].map(|v| self.welded_ids[v as usize]);
+        let (vertices, neighbor_vertices) = (welded(triangle), welded(neighbor));
+        let (a, b) = (vertices[edge], vertices[(edge + 1) % 3]);
+        (0..3).any(|e| neighbor_vertices[e] == a && neighbor_vertices[(e + 1) % 3] == b)
+    }
+}
+
+/// Maintains the [`MeshTopologyCache`], discarding topologies of meshes that are modified or
+/// removed.
+#[derive(Default)]
+pub struct MeshTopologyPlugin;
+impl Plugin for MeshTopologyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeshTopologyCache>().add_systems(
+            First,
+            invalidate_mesh_topology.after(crate::volatility::update_mesh_volatility),
+        );
+    }
+}
+
+/// Lazily built [`MeshTopology`]s, cached by mesh asset id.
+///
+/// Requires the [`MeshTopologyPlugin`] to discard stale entries when meshes change. If the
+/// [`MeshVolatilityPlugin`] is also added, meshes that are currently volatile are never cached.
+#[derive(Resource, Default)]
+pub struct MeshTopologyCache {
+    topologies: HashMap<AssetId<Mesh>, Option<MeshTopology>>,
+    volatile: HashSet<AssetId<Mesh>>,
+}
+
+impl MeshTopologyCache {
+    /// Get the topology of the mesh, building and caching it if this is the first request since
+    /// the mesh last changed. Returns `None` if the mesh is not loaded, is volatile, or is not
+    /// supported by [`MeshTopology::from_mesh`]. Callers that still need the topology of a volatile
+    /// mesh can build it with [`MeshTopology::from_mesh`] directly.
+    pub fn get_or_build(
+        &mut self,
+        mesh: impl Into<AssetId<Mesh>>,
+        meshes: &Assets<Mesh>,
+    ) -> Option<&MeshTopology> {
+        let id = mesh.into();
+        if self.volatile.contains(&id) {
+            return None;
+        }
+        if !self.topologies.contains_key(&id) {
+            let mesh = meshes.get(id)?;
+            self.topologies.insert(id, MeshTopology::from_mesh(mesh));
+        }
+        self.topologies.get(&id)?.as_ref()
+    }
+
+    /// Get the cached topology of the mesh, without building it.
+    pub fn get(&self, mesh: impl Into<AssetId<Mesh>>) -> Option<&MeshTopology> {
+        self.topologies.get(&mesh.into())?.as_ref()
+    }
+
+    /// Discard the cached topology of the mesh.
+    pub fn invalidate(&mut self, mesh: impl Into<AssetId<Mesh>>) {
+        self.topologies.remove(&mesh.into());
+    }
+}
+
+/// Discards cached [`MeshTopology`]s of meshes that were modified or removed, and stops caching
+/// meshes that are volatile. Meshes that became stable are cached again, and their topology is
+/// rebuilt the next time it is requested.
+pub fn invalidate_mesh_topology(
+    mut cache: ResMut<MeshTopologyCache>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    volatile_meshes: Option<Res<VolatileMeshes>>,
+) {
+    if let Some(volatile_meshes) = volatile_meshes {
+        cache.volatile.clear();
+        cache.volatile.extend(volatile_meshes.iter_volatile());
+        for id in volatile_meshes
+            .iter_volatile()
+            .chain(volatile_meshes.iter_stabilized())
+        {
+            cache.topologies.remove(&id);
+        }
+    }
+    for event in mesh_events.read() {
+        match event {
+            AssetEvent::Modified { id }
+            | AssetEvent::Removed { id }
+            | AssetEvent::Unused { id } => {
+                cache.invalidate(*id);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::{mesh::Indices, render_asset::RenderAssetUsages};
+
+    use super::*;
+
+    fn quad() -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+        )
+        .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]))
+    }
+
+    #[test]
+    fn shared_edge_is_not_boundary() {
+        let topology = MeshTopology::from_mesh(&quad()).unwrap();
+        assert_eq!(topology.triangle_count(), 2);
+        assert_eq!(topology.neighbors(0), Some([None, None, Some(1)]));
+        assert_eq!(topology.boundary_edges(1), [false, true, true]);
+        assert!(topology.is_boundary_triangle(0));
+        assert_eq!(topology.vertex_triangles(2), &[0, 1]);
+        assert_eq!(topology.vertex_triangles(1), &[0]);
+    }
+
+    #[test]
+    fn reversed_triangles_are_found_and_fixed() {
+        let mut mesh = quad();
+        let topology = MeshTopology::from_mesh(&mesh).unwrap();
+        assert!(topology.find_inconsistent_winding().is_empty());
+
+        mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 3, 2]));
+        let topology = MeshTopology::from_mesh(&mesh).unwrap();
+        assert_eq!(topology.find_inconsistent_winding(), vec![1]);
+        assert_eq!(
+            topology.consistently_wound_indices(),
+            vec![0, 1, 2, 0, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! # Transform Cache
+//!
+//! Every raycast transforms the ray into the model space of each entity it culls and tests, which
+//! takes the inverse of the entity's transform. Workloads casting many rays a frame, such as cone
+//! casts or many deferred sources, invert the same transforms over and over. The
+//! [`TransformCachePlugin`] keeps the inverse transform of every raycastable entity in the
+//! [`TransformCache`], and the [`Raycast`] system param reads it instead of inverting the
+//! transform again.
+//!
+//! The cache is updated in [`PostUpdate`], after transforms are propagated, for entities whose
+//! [`GlobalTransform`] changed, and entities that are despawned or no longer raycastable are
+//! discarded. Entries that no longer match the entity's [`GlobalTransform`], such as when it was
+//! set directly later in the frame, are ignored, so raycasts are never done with stale transforms.
+//! Raycasts with a [`transform_blend`](crate::immediate::RaycastSettings::transform_blend) don't
+//! use the cache.
+
+use bevy_app::prelude::*;
+use bevy_ecs::{entity::EntityHashMap, prelude::*};
+use bevy_math::{Mat3A, Mat4};
+use bevy_transform::{components::GlobalTransform, TransformSystem};
+
+#[cfg(feature = "2d")]
+use crate::immediate::Mesh2dComponent;
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+use crate::{
+    immediate::{Mesh3dComponent, MeshFilter},
+    raycast::is_degenerate_transform,
+};
+
+/// Maintains the [`TransformCache`]. See the [module docs](self).
+#[derive(Default)]
+pub struct TransformCachePlugin;
+impl Plugin for TransformCachePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TransformCache>().add_systems(
+            PostUpdate,
+            update_transform_cache.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// The inverse transforms of raycastable entities, by entity. See the [module docs](self).
+#[derive(Resource, Default)]
+pub struct TransformCache {
+    transforms: EntityHashMap<CachedTransform>,
+}
+
+impl TransformCache {
+    /// The cached transform of the `entity`, or `None` if it isn't cached, or was cached for a
+    /// different `transform`.
+    pub fn get(&self, entity: Entity, transform: &GlobalTransform) -> Option<&CachedTransform> {
+        self.transforms
+            .get(&entity)
+            .filter(|cached| cached.transform == *transform)
+    }
+
+    /// Caches the `transform` of the `entity`, replacing any cached transform.
+    pub fn insert(&mut self, entity: Entity, transform: &GlobalTransform) {
+        self.transforms
+            .insert(entity, CachedTransform::new(transform));
+    }
+
+    /// Discards the cached transform of the `entity`.
+    pub fn remove(&mut self, entity: Entity) {
+        self.transforms.remove(&entity);
+    }
+
+    /// The number of cached transforms.
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Returns `true` if no transforms are cached.
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+/// A [`GlobalTransform`], with the matrices raycasts need precomputed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CachedTransform {
+    transform: GlobalTransform,
+    model_to_world: Mat4,
+    world_to_model: Option<Mat4>,
+}
+
+impl CachedTransform {
+    /// Precomputes the matrices of the `transform`.
+    pub fn new(transform: &GlobalTransform) -> Self {
+        let model_to_world = transform.compute_matrix();
+        let world_to_model = (!is_degenerate_transform(&model_to_world)).then(|| {
+            // Only the 3x3 part needs a general inverse, since the transform is affine.
+            let linear = Mat3A::from_mat4(model_to_world).inverse();
+            let translation = -(linear * transform.translation_vec3a());
+            Mat4::from_cols(
+                linear.x_axis.extend(0.0),
+                linear.y_axis.extend(0.0),
+                linear.z_axis.extend(0.0),
+                translation.extend(1.0),
+            )
+        });
+        Self {
+            transform: *transform,
+            model_to_world,
+            world_to_model,
+        }
+    }
+
+    /// The transform from model space to world space.
+    pub fn model_to_world(&self) -> &Mat4 {
+        &self.model_to_world
+    }
+
+    /// The transform from world space to model space, or `None` if the transform is
+    /// [degenerate](is_degenerate_transform).
+    pub fn world_to_model(&self) -> Option<&Mat4> {
+        self.world_to_model.as_ref()
+    }
+}
+
+/// Caches the transforms of raycastable entities that changed, and discards those of entities that
+/// lost their transform, or are no longer raycastable.
+pub fn update_transform_cache(
+    mut cache: ResMut<TransformCache>,
+    changed: Query<(Entity, &GlobalTransform), (Changed<GlobalTransform>, MeshFilter)>,
+    raycastable: Query<(), MeshFilter>,
+    (mut removed, mut removed_meshes, mut removed_dynamic_meshes, mut removed_aabb_targets): (
+        RemovedComponents<GlobalTransform>,
+        RemovedComponents<Mesh3dComponent>,
+        RemovedComponents<DynamicRaycastMesh>,
+        RemovedComponents<RaycastAabbTarget>,
+    ),
+    #[cfg(feature = "2d")] mut removed_2d_meshes: RemovedComponents<Mesh2dComponent>,
+) {
+    for entity in removed.read() {
+        cache.remove(entity);
+    }
+    let unraycastable = removed_meshes
+        .read()
+        .chain(removed_dynamic_meshes.read())
+        .chain(removed_aabb_targets.read());
+    #[cfg(feature = "2d")]
+    let unraycastable = unraycastable.chain(removed_2d_meshes.read());
+    for entity in unraycastable {
+        if !raycastable.contains(entity) {
+            cache.remove(entity);
+        }
+    }
+    for (entity, transform) in &changed {
+        cache.insert(entity, transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::immediate::tests::spawn_planes;
+
+    #[test]
+    fn cached_transforms_match_inverted_transforms() {
+        let transform = GlobalTransform::from(
+            Transform::from_xyz(1.0, -2.0, 3.0)
+                .with_rotation(Quat::from_rotation_y(0.8))
+                .with_scale(Vec3::new(2.0, 0.5, 1.5)),
+        );
+        let cached = CachedTransform::new(&transform);
+        let expected = transform.compute_matrix().inverse();
+        assert!(cached.world_to_model().unwrap().abs_diff_eq(expected, 1e-5));
+
+        let degenerate = GlobalTransform::from(Transform::from_scale(Vec3::ZERO));
+        assert_eq!(CachedTransform::new(&degenerate).world_to_model(), None);
+    }
+
+    #[test]
+    fn transforms_are_discarded_with_the_mesh() {
+        let mut world = World::new();
+        let plane = spawn_planes(&mut world, &[-1.0])[0];
+        world.init_resource::<TransformCache>();
+        world.run_system_once(update_transform_cache);
+        assert_eq!(world.resource::<TransformCache>().len(), 1);
+
+        // The entity is still raycast by its AABB.
+        world.entity_mut(plane).insert(RaycastAabbTarget);
+        world.entity_mut(plane).remove::<Handle<Mesh>>();
+        world.run_system_once(update_transform_cache);
+        assert_eq!(world.resource::<TransformCache>().len(), 1);
+
+        world.entity_mut(plane).remove::<RaycastAabbTarget>();
+        world.run_system_once(update_transform_cache);
+        assert!(world.resource::<TransformCache>().is_empty());
+    }
+
+    #[test]
+    fn stale_transforms_are_ignored() {
+        let mut world = World::new();
+        let plane = spawn_planes(&mut world, &[-1.0])[0];
+        world.init_resource::<TransformCache>();
+        world.run_system_once(update_transform_cache);
+        assert_eq!(world.resource::<TransformCache>().len(), 1);
+
+        // The transform is set after the cache was updated, so the raycast must not use it.
+        *world.get_mut::<GlobalTransform>(plane).unwrap() =
+            GlobalTransform::from_translation(Vec3::NEG_Z * 3.0);
+        let transform = *world.get::<GlobalTransform>(plane).unwrap();
+        assert!(world
+            .resource::<TransformCache>()
+            .get(plane, &transform)
+            .is_none());
+        let distance = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast
+                .cast_ray(ray, &settings)
+                .first()
+                .map(|(_, hit)| hit.distance())
+        });
+        assert_eq!(distance, Some(3.0));
+
+        world.run_system_once(update_transform_cache);
+        let cache = world.resource::<TransformCache>();
+        assert!(cache.get(plane, &transform).is_some());
+        let distance = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::ZERO, Vec3::NEG_Z);
+            let settings = RaycastSettings::default().with_visibility(RaycastVisibility::Ignore);
+            raycast
+                .cast_ray(ray, &settings)
+                .first()
+                .map(|(_, hit)| hit.distance())
+        });
+        assert_eq!(distance, Some(3.0));
+    }
+}
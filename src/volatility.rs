@@ -0,0 +1,256 @@
+//! # Volatile Meshes
+//!
+//! Meshes that are regenerated at runtime every frame, such as procedural terrain chunks, change
+//! faster than any cached data derived from them can be reused. Rebuilding that data each time the
+//! mesh changes is slower than not caching at all.
+//!
+//! Mark these meshes with [`VolatileMeshes::mark`], and caching layers such as the
+//! [`MeshTopologyCache`] will skip them, leaving the raycast to brute force the mesh every time.
+//! Raycasting itself always reads the latest mesh data, so nothing else needs to change.
+//!
+//! A marked mesh that has not been modified for [`VolatileMeshes::stable_frames`] frames is
+//! considered stable again and becomes eligible for caching, until it is next modified. A
+//! [`MeshVolatilityChanged`] event is sent each time this flips. Caching layers rebuild their data
+//! for the meshes in [`VolatileMeshes::iter_stabilized`], since these meshes may not be modified
+//! again for a long time.
+
+use bevy_app::prelude::*;
+use bevy_asset::{AssetEvent, AssetId};
+use bevy_ecs::prelude::*;
+use bevy_render::mesh::Mesh;
+use bevy_utils::{HashMap, HashSet};
+
+#[allow(unused_imports)] // Needed for docs
+use crate::prelude::*;
+
+/// Tracks the meshes marked in [`VolatileMeshes`] and sends [`MeshVolatilityChanged`] events.
+#[derive(Default)]
+pub struct MeshVolatilityPlugin;
+impl Plugin for MeshVolatilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VolatileMeshes>()
+            .add_event::<MeshVolatilityChanged>()
+            .add_systems(First, update_mesh_volatility);
+    }
+}
+
+/// The set of meshes that are expected to change often. See the [module docs](self).
+#[derive(Resource, Debug)]
+pub struct VolatileMeshes {
+    /// How many consecutive frames a marked mesh must go unmodified to be considered stable.
+    pub stable_frames: u32,
+    /// Frames since each marked mesh was last modified.
+    frames_unmodified: HashMap<AssetId<Mesh>, u32>,
+    /// Meshes that became stable in the latest [`update_mesh_volatility`].
+    stabilized: Vec<AssetId<Mesh>>,
+}
+
+impl Default for VolatileMeshes {
+    fn default() -> Self {
+        Self {
+            stable_frames: 60,
+            frames_unmodified: HashMap::default(),
+            stabilized: Vec::new(),
+        }
+    }
+}
+
+impl VolatileMeshes {
+    /// Mark the mesh as volatile. It stays tracked until [`VolatileMeshes::unmark`] is called or
+    /// the mesh is removed.
+    pub fn mark(&mut self, mesh: impl Into<AssetId<Mesh>>) {
+        self.frames_unmodified.insert(mesh.into(), 0);
+    }
+
+    /// Stop tracking the mesh, making it always eligible for caching.
+    pub fn unmark(&mut self, mesh: impl Into<AssetId<Mesh>>) {
+        self.frames_unmodified.remove(&mesh.into());
+    }
+
+    /// Returns `true` if the mesh is marked and has been modified within the last
+    /// [`VolatileMeshes::stable_frames`] frames. Caching layers should skip volatile meshes.
+    pub fn is_volatile(&self, mesh: impl Into<AssetId<Mesh>>) -> bool {
+        self.frames_unmodified
+            .get(&mesh.into())
+            .is_some_and(|frames| *frames < self.stable_frames)
+    }
+
+    /// Iterate over the meshes that are currently volatile.
+    pub fn iter_volatile(&self) -> impl Iterator<Item = AssetId<Mesh>> + '_ {
+        self.frames_unmodified
+            .iter()
+            .filter(|(_, frames)| **frames < self.stable_frames)
+            .map(|(id, _)| *id)
+    }
+
+    /// Iterate over the meshes that became stable in the latest [`update_mesh_volatility`], the
+    /// same meshes a [`MeshVolatilityChanged`] event with `volatile: false` was sent for. Caching
+    /// layers skipped these meshes while they were volatile, so they should build their data now.
+    pub fn iter_stabilized(&self) -> impl Iterator<Item = AssetId<Mesh>> + '_ {
+        self.stabilized.iter().copied()
+    }
+}
+
+/// Sent when a mesh marked in [`VolatileMeshes`] flips between volatile and stable.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MeshVolatilityChanged {
+    /// The mesh marked in [`VolatileMeshes`] that flipped.
+    pub mesh: AssetId<Mesh>,
+    /// `true` if the mesh was modified after being stable, `false` if it has now gone unmodified
+    /// for [`VolatileMeshes::stable_frames`] frames.
+    pub volatile: bool,
+}
+
+/// Counts frames since each marked mesh was modified, and sends [`MeshVolatilityChanged`] events.
+pub fn update_mesh_volatility(
+    mut volatile_meshes: ResMut<VolatileMeshes>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    mut changed: EventWriter<MeshVolatilityChanged>,
+) {
+    let volatile_meshes = volatile_meshes.as_mut();
+    volatile_meshes.stabilized.clear();
+    let stable_frames = volatile_meshes.stable_frames;
+    let mut modified = HashSet::new();
+    for event in mesh_events.read() {
+        match event {
+            AssetEvent::Modified { id } => {
+                modified.insert(*id);
+            }
+            AssetEvent::Removed { id } => {
+                volatile_meshes.frames_unmodified.remove(id);
+            }
+            _ => (),
+        }
+    }
+    for (id, frames) in volatile_meshes.frames_unmodified.iter_mut() {
+        if modified.contains(id) {
+            if *frames >= stable_frames {
+                changed.send(MeshVolatilityChanged {
+                    mesh: *id,
+                    volatile: true,
+                });
+            }
+            *frames = 0;
+        } else {
+            *frames = frames.saturating_add(1);
+            if *frames == stable_frames {
+                changed.send(MeshVolatilityChanged {
+                    mesh: *id,
+                    volatile: false,
+                });
+                volatile_meshes.stabilized.push(*id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::*;
+    use crate::{
+        flat_normals::{update_flat_normal_cache, FlatNormalCache},
+        immediate::tests::spawn_planes,
+        mesh_bvh::{update_mesh_bvh_cache, MeshBvhCache},
+        topology::{invalidate_mesh_topology, MeshTopologyCache},
+    };
+
+    /// Runs a frame of the volatility and cache systems, optionally modifying the mesh first, and
+    /// returns the volatility events sent.
+    fn run_frame(world: &mut World, modified: Option<AssetId<Mesh>>) -> Vec<MeshVolatilityChanged> {
+        if let Some(id) = modified {
+            world.send_event(AssetEvent::Modified { id });
+        }
+        world.run_system_once(update_mesh_volatility);
+        world.run_system_once(update_flat_normal_cache);
+        world.run_system_once(update_mesh_bvh_cache);
+        world.run_system_once(invalidate_mesh_topology);
+        // Each `run_system_once` reads every event, so they are cleared between frames.
+        world.resource_mut::<Events<AssetEvent<Mesh>>>().clear();
+        world
+            .resource_mut::<Events<MeshVolatilityChanged>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn volatility_flips_both_ways() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0]);
+        let id = world.get::<Handle<Mesh>>(planes[0]).unwrap().id();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.init_resource::<Events<MeshVolatilityChanged>>();
+        world.init_resource::<FlatNormalCache>();
+        world.init_resource::<MeshTopologyCache>();
+        world.insert_resource(MeshBvhCache::default());
+        let mut volatile_meshes = VolatileMeshes {
+            stable_frames: 2,
+            ..default()
+        };
+        volatile_meshes.mark(id);
+        world.insert_resource(volatile_meshes);
+        let changed = |volatile| vec![MeshVolatilityChanged { mesh: id, volatile }];
+
+        assert!(run_frame(&mut world, Some(id)).is_empty());
+        assert!(world.resource::<VolatileMeshes>().is_volatile(id));
+        assert!(run_frame(&mut world, None).is_empty());
+        assert_eq!(run_frame(&mut world, None), changed(false));
+        assert!(!world.resource::<VolatileMeshes>().is_volatile(id));
+        assert_eq!(
+            world
+                .resource::<VolatileMeshes>()
+                .iter_stabilized()
+                .collect::<Vec<_>>(),
+            vec![id]
+        );
+        assert!(run_frame(&mut world, None).is_empty());
+        assert_eq!(
+            world.resource::<VolatileMeshes>().iter_stabilized().count(),
+            0
+        );
+        assert_eq!(run_frame(&mut world, Some(id)), changed(true));
+        assert!(world.resource::<VolatileMeshes>().is_volatile(id));
+    }
+
+    #[test]
+    fn caches_are_rebuilt_when_meshes_become_stable() {
+        let mut world = World::new();
+        let planes = spawn_planes(&mut world, &[-1.0]);
+        let id = world.get::<Handle<Mesh>>(planes[0]).unwrap().id();
+        world.init_resource::<Events<AssetEvent<Mesh>>>();
+        world.init_resource::<Events<MeshVolatilityChanged>>();
+        world.init_resource::<FlatNormalCache>();
+        world.init_resource::<MeshTopologyCache>();
+        let mut bvh_cache = MeshBvhCache::default();
+        bvh_cache.min_triangles = 0;
+        bvh_cache.build_async = false;
+        world.insert_resource(bvh_cache);
+        let mut volatile_meshes = VolatileMeshes {
+            stable_frames: 1,
+            ..default()
+        };
+        volatile_meshes.mark(id);
+        world.insert_resource(volatile_meshes);
+        let cached = |world: &mut World| {
+            let topology = world.resource_scope(|world, mut cache: Mut<MeshTopologyCache>| {
+                cache
+                    .get_or_build(id, world.resource::<Assets<Mesh>>())
+                    .is_some()
+            });
+            [
+                world.resource::<FlatNormalCache>().get(id).is_some(),
+                world.resource::<MeshBvhCache>().get(id).is_some(),
+                topology,
+            ]
+        };
+
+        run_frame(&mut world, Some(id));
+        assert_eq!(cached(&mut world), [false; 3]);
+        // The mesh is not modified again, but becomes stable, so every cache is rebuilt.
+        run_frame(&mut world, None);
+        assert_eq!(cached(&mut world), [true; 3]);
+        run_frame(&mut world, Some(id));
+        assert_eq!(cached(&mut world), [false; 3]);
+    }
+}
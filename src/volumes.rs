@@ -0,0 +1,166 @@
+//! # Volumes
+//!
+//! Closed meshes can be used as volumes, such as fog, water, or damage zones, where gameplay needs
+//! to know how much of a ray passes through them rather than where the ray first hits them.
+//!
+//! [`Raycast::cast_ray_volume_spans`] finds every hit on each entity, then pairs the hits where the
+//! ray enters a mesh with the hits where it exits, to report the [`VolumeSpan`]s of the ray inside
+//! each volume. The ray exits a volume through the inside of its faces, so volume entities need
+//! [`NoBackfaceCulling`].
+
+use bevy_ecs::entity::Entity;
+use bevy_math::{FloatOrd, Ray3d, Vec3};
+use bevy_reflect::Reflect;
+use bevy_utils::HashMap;
+
+use crate::prelude::*;
+
+/// A section of a ray that is inside the volume of an entity.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct VolumeSpan {
+    /// The volume entity.
+    pub entity: Entity,
+    /// The distance along the ray where it enters the volume. This is `0.0` when the ray starts
+    /// inside the volume.
+    pub enter: f32,
+    /// The distance along the ray where it exits the volume.
+    pub exit: f32,
+}
+
+impl VolumeSpan {
+    /// The length of the ray inside the volume.
+    pub fn length(&self) -> f32 {
+        self.exit - self.enter
+    }
+}
+
+/// Pairs the entry and exit hits of each entity into [`VolumeSpan`]s, sorted by where they are
+/// entered. The hits must be sorted nearest first, as returned by [`Raycast::cast_ray`].
+///
+/// A hit enters the volume when the ray passes through the front of the hit triangle, and exits it
+/// otherwise. When the ray ends inside a volume, such as when the mesh is not closed or the ray was
+/// limited by a maximum distance, its span exits at `end`.
+pub fn pair_volume_spans(
+    ray: Ray3d,
+    hits: &[(Entity, IntersectionData)],
+    end: f32,
+) -> Vec<VolumeSpan> {
+    let mut spans = Vec::new();
+    let mut entered: HashMap<Entity, Option<f32>> = HashMap::default();
+    for (entity, hit) in hits {
+        // The winding of the triangle is reversed on mirrored meshes, so the face normal is
+        // flipped to agree with the hit normal, which always points out of the mesh.
+        let normal = hit
+            .triangle()
+            .map(|[a, b, c]| Vec3::from((b - a).cross(c - a)))
+            .map(|face| face * face.dot(hit.normal()).signum())
+            .unwrap_or(hit.normal());
+        let is_entering = normal.dot(*ray.direction) < 0.0;
+        let enter = entered.entry(*entity).or_default();
+        match (is_entering, *enter) {
+            (true, None) => *enter = Some(hit.distance()),
+            (false, Some(distance)) => {
+                spans.push(VolumeSpan {
+                    entity: *entity,
+                    enter: distance,
+                    exit: hit.distance(),
+                });
+                *enter = None;
+            }
+            // The first hit exits the volume, so the ray started inside it.
+            (false, None) if !spans.iter().any(|span| span.entity == *entity) => {
+                spans.push(VolumeSpan {
+                    entity: *entity,
+                    enter: hit.distance().min(0.0),
+                    exit: hit.distance(),
+                });
+            }
+            // Overlapping faces, or a mesh that is not closed.
+            _ => {}
+        }
+    }
+    spans.extend(entered.into_iter().filter_map(|(entity, enter)| {
+        Some(VolumeSpan {
+            entity,
+            enter: enter?,
+            exit: end,
+        })
+    }));
+    spans.sort_by_key(|span| FloatOrd(span.enter));
+    spans
+}
+
+impl<'w, 's> Raycast<'w, 's> {
+    /// Casts the `ray`, and returns the spans of the ray inside each entity it hits, sorted by
+    /// where they are entered. See the [module docs](self).
+    ///
+    /// [`RaycastSettings::all_hits_per_entity`] and [`HitData::triangle`] are always enabled. Use
+    /// [`RaycastSettings::never_early_exit`] to find the volumes behind the first one, and
+    /// [`RaycastSettings::max_distance`] to end the spans of volumes the ray ends inside of.
+    pub fn cast_ray_volume_spans(
+        &mut self,
+        ray: Ray3d,
+        settings: &RaycastSettings,
+    ) -> Vec<VolumeSpan> {
+        let settings = settings
+            .clone()
+            .with_all_hits_per_entity(true)
+            .with_hit_data(settings.hit_data.with_triangle(true));
+        let hits = self.cast_ray(ray, &settings);
+        pair_volume_spans(ray, hits, settings.max_distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, render::primitives::Aabb};
+
+    use super::*;
+
+    #[test]
+    fn spans_through_cubes() {
+        bevy::tasks::ComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut world = World::new();
+        let mut meshes = Assets::<Mesh>::default();
+        let cube = meshes.add(Cuboid::new(2.0, 2.0, 2.0));
+        world.insert_resource(meshes);
+        let mut spawn_cube = |z: f32| {
+            world
+                .spawn((
+                    cube.clone(),
+                    Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0)),
+                    GlobalTransform::from_xyz(0.0, 0.0, z),
+                    InheritedVisibility::VISIBLE,
+                    ViewVisibility::default(),
+                    NoBackfaceCulling,
+                ))
+                .id()
+        };
+        let around_origin = spawn_cube(0.0);
+        let ahead = spawn_cube(-5.0);
+        let far = spawn_cube(-20.0);
+
+        let spans = world.run_system_once(|mut raycast: Raycast| {
+            let ray = Ray3d::new(Vec3::new(0.1, 0.2, 0.0), Vec3::NEG_Z);
+            let settings = RaycastSettings::default()
+                .with_visibility(RaycastVisibility::Ignore)
+                .with_max_distance(20.0)
+                .never_early_exit();
+            raycast.cast_ray_volume_spans(ray, &settings)
+        });
+        let span = |entity, enter, exit| VolumeSpan {
+            entity,
+            enter,
+            exit,
+        };
+        assert_eq!(
+            spans,
+            vec![
+                span(around_origin, 0.0, 1.0),
+                span(ahead, 4.0, 6.0),
+                span(far, 19.0, 20.0),
+            ]
+        );
+        assert_eq!(spans.iter().map(VolumeSpan::length).sum::<f32>(), 4.0);
+    }
+}